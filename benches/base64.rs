@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fusio::remotes::encoding::{base64_decode, base64_encode};
+use rand::RngCore;
+
+fn base64(c: &mut Criterion) {
+    let mut group = c.benchmark_group("base64");
+
+    // digests of checksum headers up to request bodies
+    for size in [32, 4096, 1024 * 1024] {
+        let mut bytes = vec![0u8; size];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let encoded = base64_encode(&bytes);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("encode", size), &bytes, |b, bytes| {
+            b.iter(|| base64_encode(bytes))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", size), &encoded, |b, encoded| {
+            b.iter(|| base64_decode(encoded))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, base64);
+criterion_main!(benches);
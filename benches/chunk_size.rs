@@ -0,0 +1,81 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fusio::{
+    disk::MonoIoFs,
+    fs::{Fs, OpenOptions},
+    path::Path,
+    Read, Write,
+};
+use rand::Rng;
+use tempfile::NamedTempFile;
+
+const FILE_SIZE: usize = 8 * 1024 * 1024;
+
+const CHUNK_SIZES: [usize; 5] = [16 * 1024, 64 * 1024, 128 * 1024, 512 * 1024, 1024 * 1024];
+
+fn chunk_size(c: &mut Criterion) {
+    let mut runtime = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+        .build()
+        .unwrap();
+
+    let mut bytes = vec![0u8; FILE_SIZE];
+    rand::thread_rng().fill(&mut bytes[..]);
+
+    // the writes append to one file, the reads read the whole other one
+    let write_file = NamedTempFile::new().unwrap();
+    let write_path = Path::from_filesystem_path(write_file.path()).unwrap();
+    let read_file = NamedTempFile::new().unwrap();
+    std::fs::write(read_file.path(), &bytes).unwrap();
+    let read_path = Path::from_filesystem_path(read_file.path()).unwrap();
+
+    let mut group = c.benchmark_group("chunk size");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+
+    for chunk_size in CHUNK_SIZES {
+        let fs = MonoIoFs::default().chunk_size(chunk_size);
+        let (mut writer, mut reader) = runtime.block_on(async {
+            (
+                fs.open_options(&write_path, OpenOptions::default().write(true))
+                    .await
+                    .unwrap(),
+                fs.open(&read_path).await.unwrap(),
+            )
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("write 8M", chunk_size),
+            &chunk_size,
+            |b, _| {
+                b.iter(|| {
+                    // the buffer is handed to the runtime, and back, rather than borrowed
+                    let (result, buf) =
+                        runtime.block_on(writer.write_all(std::mem::take(&mut bytes)));
+                    result.unwrap();
+                    bytes = buf;
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("read 8M", chunk_size),
+            &chunk_size,
+            |b, _| {
+                b.iter(|| {
+                    runtime.block_on(async {
+                        let (result, buf) = reader.read_to_end_at(Vec::new(), 0).await;
+                        result.unwrap();
+                        assert_eq!(buf.len(), FILE_SIZE);
+                    })
+                })
+            },
+        );
+
+        runtime.block_on(async {
+            writer.close().await.unwrap();
+            reader.close().await.unwrap();
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, chunk_size);
+criterion_main!(benches);
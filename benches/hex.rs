@@ -0,0 +1,36 @@
+use std::fmt::Write;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fusio::remotes::aws::credential::hex_encode;
+use rand::RngCore;
+
+/// The encoder `hex_encode` replaced, formatting every byte on its own.
+fn hex_encode_formatted(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+fn hex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hex_encode");
+
+    for size in [32, 4096, 1024 * 1024] {
+        let mut bytes = vec![0u8; size];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("table", size), &bytes, |b, bytes| {
+            b.iter(|| hex_encode(bytes))
+        });
+        group.bench_with_input(BenchmarkId::new("write!", size), &bytes, |b, bytes| {
+            b.iter(|| hex_encode_formatted(bytes))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, hex);
+criterion_main!(benches);
@@ -4,7 +4,7 @@ use fusio::{disk::LocalFs, dynamic::DynFile, DynFs};
 
 #[allow(unused)]
 async fn use_fs() {
-    let fs: Arc<dyn DynFs> = Arc::new(LocalFs {});
+    let fs: Arc<dyn DynFs> = Arc::new(LocalFs::default());
 
     let mut file: Box<dyn DynFile> = Box::new(fs.open(&"foo.txt".into()).await.unwrap());
 
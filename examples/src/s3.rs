@@ -1,6 +1,7 @@
 use std::{env, sync::Arc};
 
 use fusio::{
+    fs::OpenOptions,
     remotes::aws::{fs::AmazonS3Builder, AwsCredential},
     DynFs,
 };
@@ -25,7 +26,13 @@ async fn use_fs() {
     );
 
     let _ = write_without_runtime_awareness(
-        &mut s3.open(&"foo.txt".into()).await.unwrap(),
+        &mut s3
+            .open_options(
+                &"foo.txt".into(),
+                OpenOptions::default().create(true).truncate(true),
+            )
+            .await
+            .unwrap(),
         "hello, world".as_bytes(),
         &mut [0; 12][..],
     )
@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
-use fusio::{DynFs, Error};
+use fusio::{path::Path, DynFs, Error};
 
 #[derive(Clone)]
 #[non_exhaustive]
@@ -21,7 +21,7 @@ impl FsOptions {
     pub fn parse(self) -> Result<Arc<dyn DynFs>, Error> {
         match self {
             #[cfg(any(feature = "tokio", feature = "monoio"))]
-            FsOptions::Local => Ok(Arc::new(fusio::disk::LocalFs {})),
+            FsOptions::Local => Ok(Arc::new(fusio::disk::LocalFs::default())),
             #[cfg(feature = "object_store")]
             FsOptions::S3 {
                 bucket,
@@ -86,3 +86,140 @@ impl FsOptions {
         }
     }
 }
+
+/// The backend of a location, as named by the scheme of its URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BackendKind {
+    /// The local file system, `file://` or no scheme at all.
+    Local,
+    /// A bucket of Amazon S3, `s3://`.
+    S3 { bucket: String },
+    /// A bucket of Google Cloud Storage, `gs://`.
+    Gcs { bucket: String },
+}
+
+/// Parses a location given as a single string, e.g. in a configuration, into its backend and its
+/// path there, the reverse of [`Fs::uri`](fusio::fs::Fs::uri):
+///
+/// - `file:///abs/path`, whose host may only be empty or `localhost`,
+/// - `s3://bucket/key` and `gs://bucket/key`,
+/// - a local path without a scheme, taken as it is rather than percent-decoded, and relative to the
+///   current directory unless absolute.
+///
+/// The paths of URIs are percent-decoded, `a%20b` is the file or key `a b`.
+pub fn parse_location(uri: &str) -> Result<(BackendKind, Path), Error> {
+    let invalid = |reason: &str| -> Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid location \"{}\": {}", uri, reason),
+        )
+        .into()
+    };
+
+    let (scheme, rest) = match uri.split_once("://") {
+        Some(parts) => parts,
+        None => {
+            let path = std::path::Path::new(uri);
+            let path = if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                std::env::current_dir()?.join(path)
+            };
+            return Ok((BackendKind::Local, Path::from_absolute_path(path)?));
+        }
+    };
+    if rest.contains(['?', '#']) {
+        return Err(invalid("it has a query or a fragment"));
+    }
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = Path::from_url_path(path)?;
+    let bucket = || {
+        if authority.is_empty() {
+            Err(invalid("the bucket is missing"))
+        } else {
+            Ok(authority.to_string())
+        }
+    };
+
+    let kind = match scheme.to_ascii_lowercase().as_str() {
+        "file" => {
+            if !matches!(authority, "" | "localhost") {
+                return Err(invalid(
+                    "the host of a local file must be empty or localhost",
+                ));
+            }
+            BackendKind::Local
+        }
+        "s3" | "s3a" => BackendKind::S3 { bucket: bucket()? },
+        "gs" => BackendKind::Gcs { bucket: bucket()? },
+        scheme => {
+            return Err(Error::Unsupported {
+                message: format!("the scheme \"{}\" of \"{}\"", scheme, uri),
+            })
+        }
+    };
+    Ok((kind, path))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_locations() {
+        use fusio::{path::Path, Error};
+
+        use crate::{parse_location, BackendKind};
+
+        let s3 = |bucket: &str| BackendKind::S3 {
+            bucket: bucket.into(),
+        };
+        assert_eq!(
+            parse_location("s3://bucket/data/part-0.parquet").unwrap(),
+            (s3("bucket"), Path::from("data/part-0.parquet"))
+        );
+        // encoded special characters are decoded
+        assert_eq!(
+            parse_location("s3://bucket/data/a%20b%2Bc%3D1/file").unwrap(),
+            (s3("bucket"), Path::from("data/a b+c=1/file"))
+        );
+        assert_eq!(
+            parse_location("gs://other-bucket/key").unwrap(),
+            (
+                BackendKind::Gcs {
+                    bucket: "other-bucket".into()
+                },
+                Path::from("key")
+            )
+        );
+        assert_eq!(
+            parse_location("file:///tmp/dir/a%20b").unwrap(),
+            (BackendKind::Local, Path::from("tmp/dir/a b"))
+        );
+        assert_eq!(
+            parse_location("file://localhost/tmp").unwrap(),
+            (BackendKind::Local, Path::from("tmp"))
+        );
+
+        // without a scheme, a path of the local file system
+        #[cfg(unix)]
+        assert_eq!(
+            parse_location("/tmp/dir/a b").unwrap(),
+            (BackendKind::Local, Path::from("tmp/dir/a b"))
+        );
+        let (kind, path) = parse_location("relative/file").unwrap();
+        assert_eq!(kind, BackendKind::Local);
+        assert_eq!(
+            path,
+            Path::from_absolute_path(std::env::current_dir().unwrap().join("relative/file"))
+                .unwrap()
+        );
+
+        assert!(parse_location("s3:///key").is_err());
+        assert!(parse_location("file://host/tmp").is_err());
+        assert!(parse_location("s3://bucket/key?versionId=1").is_err());
+        assert!(matches!(
+            parse_location("ftp://host/file"),
+            Err(Error::Unsupported { .. })
+        ));
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use async_stream::stream;
 use fusio::{
-    fs::{FileMeta, Fs, OpenOptions},
+    fs::{DelimitedListing, DirStats, FileMeta, FileType, Fs, OpenOptions},
     path::Path,
     Error,
 };
@@ -40,7 +40,7 @@ impl<O: ObjectStore> Fs for S3Store<O> {
         })
     }
 
-    async fn create_dir_all(_: &Path) -> Result<(), Error> {
+    async fn create_dir_all(&self, _: &Path) -> Result<(), Error> {
         Ok(())
     }
 
@@ -53,15 +53,129 @@ impl<O: ObjectStore> Fs for S3Store<O> {
 
         Ok(stream! {
             while let Some(meta) = stream.next().await.transpose().map_err(BoxedError::from)? {
-                yield Ok(FileMeta { path: meta.location.into(), size: meta.size as u64 });
+                yield Ok(FileMeta::new(meta.location.into(), meta.size as u64).with_etag(meta.e_tag));
             }
         })
     }
 
+    /// Object stores may list in any order, the whole listing after the cursor is fetched to
+    /// sort it.
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        let path = path.clone().into();
+        let mut stream = match cursor {
+            Some(cursor) => self
+                .inner
+                .list_with_offset(Some(&path), &object_store::path::Path::from(cursor)),
+            None => self.inner.list(Some(&path)),
+        };
+        let mut metas = vec![];
+        while let Some(meta) = stream.next().await.transpose().map_err(BoxedError::from)? {
+            metas.push(meta);
+        }
+        metas.sort_by(|a, b| a.location.cmp(&b.location));
+
+        let page_size = page_size.max(1);
+        let cursor = match metas.len() > page_size {
+            true => Some(metas[page_size - 1].location.to_string()),
+            false => None,
+        };
+        let entries = metas
+            .into_iter()
+            .take(page_size)
+            .map(|meta| FileMeta::new(meta.location.into(), meta.size as u64).with_etag(meta.e_tag))
+            .collect();
+        Ok((entries, cursor))
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        let path = path.clone().into();
+        let listing = self
+            .inner
+            .list_with_delimiter(Some(&path))
+            .await
+            .map_err(BoxedError::from)?;
+
+        Ok(DelimitedListing {
+            files: listing
+                .objects
+                .into_iter()
+                .map(|meta| {
+                    FileMeta::new(meta.location.into(), meta.size as u64).with_etag(meta.e_tag)
+                })
+                .collect(),
+            prefixes: listing
+                .common_prefixes
+                .into_iter()
+                .map(Path::from)
+                .collect(),
+        })
+    }
+
     async fn remove(&self, path: &Path) -> Result<(), Error> {
         let path = path.clone().into();
         self.inner.delete(&path).await.map_err(BoxedError::from)?;
 
         Ok(())
     }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        let meta = self
+            .inner
+            .head(&path.clone().into())
+            .await
+            .map_err(BoxedError::from)?;
+
+        Ok(FileMeta::new(meta.location.into(), meta.size as u64).with_etag(meta.e_tag))
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        let path = path.clone().into();
+        match self.inner.head(&path).await {
+            Ok(_) => return Ok(Some(FileType::File)),
+            Err(object_store::Error::NotFound { .. }) => {}
+            Err(e) => return Err(BoxedError::from(e).into()),
+        }
+        let mut stream = self.inner.list(Some(&path));
+        match stream.next().await.transpose().map_err(BoxedError::from)? {
+            Some(_) => Ok(Some(FileType::Dir)),
+            None => Ok(None),
+        }
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        let path = path.clone().into();
+        let mut stream = self.inner.list(Some(&path));
+        let mut stats = DirStats::default();
+        while let Some(meta) = stream.next().await.transpose().map_err(BoxedError::from)? {
+            stats.entry_count += 1;
+            stats.total_size += meta.size as u64;
+        }
+        Ok(stats)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.inner
+            .copy(&from.clone().into(), &to.clone().into())
+            .await
+            .map_err(BoxedError::from)?;
+
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.inner
+            .rename(&from.clone().into(), &to.clone().into())
+            .await
+            .map_err(BoxedError::from)?;
+
+        Ok(())
+    }
+    fn is_remote(&self) -> bool {
+        true
+    }
 }
@@ -1,11 +1,25 @@
-use std::pin::Pin;
+use std::{borrow::Cow, ffi::OsStr, ops::Range, path::PathBuf, pin::Pin, sync::Arc};
 
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
 use futures_core::Stream;
 
 use super::MaybeSendFuture;
+#[cfg(feature = "bytes")]
+use super::MaybeSendStream;
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+use crate::fs::WatchEvent;
 use crate::{
-    buf::IoBufMut,
-    fs::{FileMeta, Fs, OpenOptions},
+    buf::{IoBufMut, Slice},
+    fs::{
+        CopyProgress, DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs,
+        ListOptions, OpenOptions, PresignedFs, TempGuard,
+    },
+    io::SharedReader,
     path::Path,
     DynRead, DynWrite, Error, IoBuf, MaybeSend, MaybeSync, Read, Write,
 };
@@ -45,6 +59,14 @@ impl<'write> Write for Box<dyn DynFile + 'write> {
     async fn close(&mut self) -> Result<(), Error> {
         DynWrite::close(self.as_mut()).await
     }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        DynWrite::close_with_meta(self.as_mut()).await
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        DynWrite::allocate(self.as_mut(), len).await
+    }
 }
 
 pub trait DynFs: MaybeSend + MaybeSync {
@@ -61,6 +83,19 @@ pub trait DynFs: MaybeSend + MaybeSync {
         options: OpenOptions,
     ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>> + 's>>;
 
+    fn open_shared<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<
+        Box<dyn MaybeSendFuture<Output = Result<Arc<SharedReader<Box<dyn DynFile>>>, Error>> + 's>,
+    >;
+
+    fn open_with_len<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        len: u64,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>> + 's>>;
+
     fn create_dir_all<'s, 'path: 's>(
         &'s self,
         path: &'path Path,
@@ -80,10 +115,266 @@ pub trait DynFs: MaybeSend + MaybeSync {
         >,
     >;
 
+    fn list_with<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        options: ListOptions,
+    ) -> Pin<
+        Box<
+            dyn MaybeSendFuture<
+                    Output = Result<
+                        Pin<Box<dyn Stream<Item = Result<FileMeta, Error>> + 's>>,
+                        Error,
+                    >,
+                > + 's,
+        >,
+    >;
+
+    fn list_page<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        cursor: Option<&'path str>,
+        page_size: usize,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(Vec<FileMeta>, Option<String>), Error>> + 's>>;
+
+    fn list_delimited<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<DelimitedListing, Error>> + 's>>;
+
+    fn list_versions<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<FileVersion>, Error>> + 's>>;
+
     fn remove<'s, 'path: 's>(
         &'s self,
         path: &'path Path,
     ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn remove_if_exists<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>>;
+
+    fn remove_dir_all<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn metadata<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileMeta, Error>> + 's>>;
+
+    fn file_type<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Option<FileType>, Error>> + 's>>;
+
+    fn dir_stats<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<DirStats, Error>> + 's>>;
+
+    fn total_size<'s, 'path: 's>(
+        &'s self,
+        prefix: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<u64, Error>> + 's>>;
+
+    fn exists<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>>;
+
+    fn is_dir<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>>;
+
+    fn is_file<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>>;
+
+    fn read_if_none_match<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        etag: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Option<(FileMeta, Vec<u8>)>, Error>> + 's>>;
+
+    fn read_head<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        n: usize,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<u8>, Error>> + 's>>;
+
+    fn read_tail<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        n: usize,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<u8>, Error>> + 's>>;
+
+    fn write_file<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        buf: Slice,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = (Result<FileMeta, Error>, Slice)> + 's>>;
+
+    #[cfg(feature = "checksum")]
+    fn write_file_with_checksum<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        buf: Slice,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = (Result<(FileMeta, [u8; 32]), Error>, Slice)> + 's>>;
+
+    fn temp_file<'s, 'path: 's>(
+        &'s self,
+        dir: &'path Path,
+        prefix: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(Path, Box<dyn DynFile>), Error>> + 's>>;
+
+    fn write_file_atomic<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        buf: Slice,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = (Result<FileMeta, Error>, Slice)> + 's>>;
+    fn anon_temp_file<'s, 'path: 's>(
+        &'s self,
+        dir: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>> + 's>>;
+
+    fn copy<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn copy_if_source_matches<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+        expected_etag: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn copy_with_progress<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+        progress: &'path mut dyn CopyProgress,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn copy_range<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        range: Range<u64>,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn rename<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn rename_durable<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn rename_with_meta<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileMeta, Error>> + 's>>;
+
+    fn get_xattr<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        name: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Option<Vec<u8>>, Error>> + 's>>;
+
+    fn set_xattr<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        name: &'path str,
+        value: &'path [u8],
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>>;
+
+    fn list_xattr<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<String>, Error>> + 's>>;
+
+    fn lock_shared<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileLock, Error>> + 's>>;
+
+    fn lock_exclusive<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileLock, Error>> + 's>>;
+
+    #[cfg(feature = "mmap")]
+    fn mmap<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Mmap, Error>> + 's>>;
+
+    fn localize<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(PathBuf, TempGuard), Error>> + 's>>;
+
+    #[cfg(feature = "bytes")]
+    fn get_stream<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<
+        Box<
+            dyn MaybeSendFuture<
+                    Output = Result<Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + 's>>, Error>,
+                > + 's,
+        >,
+    >;
+
+    #[cfg(feature = "bytes")]
+    fn put_stream<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        stream: Pin<Box<dyn MaybeSendStream<Item = Result<Bytes, Error>> + 's>>,
+        size_hint: Option<u64>,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileMeta, Error>> + 's>>;
+
+    #[cfg(feature = "checksum")]
+    fn checksum<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        algorithm: ChecksumAlgorithm,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<u8>, Error>> + 's>>;
+
+    #[cfg(feature = "checksum")]
+    fn list_digest<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<[u8; 32], Error>> + 's>>;
+
+    #[cfg(any(feature = "tokio", feature = "monoio"))]
+    fn watch<'s, 'path: 's>(
+        &'s self,
+        prefix: &'path Path,
+        interval: std::time::Duration,
+    ) -> Pin<Box<dyn Stream<Item = Result<WatchEvent, Error>> + 's>>;
+
+    fn uri(&self, path: &Path) -> String;
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr>;
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs>;
+
+    fn is_remote(&self) -> bool;
 }
 
 impl<F: Fs> DynFs for F {
@@ -98,11 +389,38 @@ impl<F: Fs> DynFs for F {
         })
     }
 
+    // files are shared across tasks only where they are `Send`
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn open_shared<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<
+        Box<dyn MaybeSendFuture<Output = Result<Arc<SharedReader<Box<dyn DynFile>>>, Error>> + 's>,
+    > {
+        Box::pin(async move {
+            let file = F::open_options(self, path, OpenOptions::default()).await?;
+            Ok(Arc::new(SharedReader::new(
+                Box::new(file) as Box<dyn DynFile>
+            )))
+        })
+    }
+
+    fn open_with_len<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        len: u64,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>> + 's>> {
+        Box::pin(async move {
+            let file = F::open_with_len(self, path, len).await?;
+            Ok(Box::new(file) as Box<dyn DynFile>)
+        })
+    }
+
     fn create_dir_all<'s, 'path: 's>(
         &'s self,
         path: &'path Path,
     ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
-        Box::pin(F::create_dir_all(path))
+        Box::pin(F::create_dir_all(self, path))
     }
 
     fn list<'s, 'path: 's>(
@@ -124,12 +442,372 @@ impl<F: Fs> DynFs for F {
         })
     }
 
+    fn list_with<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        options: ListOptions,
+    ) -> Pin<
+        Box<
+            dyn MaybeSendFuture<
+                    Output = Result<
+                        Pin<Box<dyn Stream<Item = Result<FileMeta, Error>> + 's>>,
+                        Error,
+                    >,
+                > + 's,
+        >,
+    > {
+        Box::pin(async move {
+            let stream = F::list_with(self, path, options).await?;
+            Ok(Box::pin(stream) as Pin<Box<dyn Stream<Item = Result<FileMeta, Error>>>>)
+        })
+    }
+
+    fn list_page<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        cursor: Option<&'path str>,
+        page_size: usize,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(Vec<FileMeta>, Option<String>), Error>> + 's>>
+    {
+        Box::pin(F::list_page(self, path, cursor, page_size))
+    }
+
+    fn list_delimited<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<DelimitedListing, Error>> + 's>> {
+        Box::pin(F::list_delimited(self, path))
+    }
+
+    fn list_versions<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<FileVersion>, Error>> + 's>> {
+        Box::pin(F::list_versions(self, path))
+    }
+
     fn remove<'s, 'path: 's>(
         &'s self,
         path: &'path Path,
     ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
         Box::pin(F::remove(self, path))
     }
+
+    fn remove_if_exists<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>> {
+        Box::pin(F::remove_if_exists(self, path))
+    }
+
+    fn remove_dir_all<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::remove_dir_all(self, path))
+    }
+
+    fn metadata<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileMeta, Error>> + 's>> {
+        Box::pin(F::metadata(self, path))
+    }
+
+    fn file_type<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Option<FileType>, Error>> + 's>> {
+        Box::pin(F::file_type(self, path))
+    }
+
+    fn dir_stats<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<DirStats, Error>> + 's>> {
+        Box::pin(F::dir_stats(self, path))
+    }
+
+    fn total_size<'s, 'path: 's>(
+        &'s self,
+        prefix: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<u64, Error>> + 's>> {
+        Box::pin(F::total_size(self, prefix))
+    }
+
+    fn exists<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>> {
+        Box::pin(F::exists(self, path))
+    }
+
+    fn is_dir<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>> {
+        Box::pin(F::is_dir(self, path))
+    }
+
+    fn is_file<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<bool, Error>> + 's>> {
+        Box::pin(F::is_file(self, path))
+    }
+
+    fn read_if_none_match<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        etag: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Option<(FileMeta, Vec<u8>)>, Error>> + 's>>
+    {
+        Box::pin(F::read_if_none_match(self, path, etag))
+    }
+
+    fn read_head<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        n: usize,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<u8>, Error>> + 's>> {
+        Box::pin(F::read_head(self, path, n))
+    }
+
+    fn read_tail<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        n: usize,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<u8>, Error>> + 's>> {
+        Box::pin(F::read_tail(self, path, n))
+    }
+
+    fn write_file<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        buf: Slice,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = (Result<FileMeta, Error>, Slice)> + 's>> {
+        Box::pin(F::write_file(self, path, buf))
+    }
+
+    #[cfg(feature = "checksum")]
+    fn write_file_with_checksum<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        buf: Slice,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = (Result<(FileMeta, [u8; 32]), Error>, Slice)> + 's>>
+    {
+        Box::pin(F::write_file_with_checksum(self, path, buf))
+    }
+
+    fn temp_file<'s, 'path: 's>(
+        &'s self,
+        dir: &'path Path,
+        prefix: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(Path, Box<dyn DynFile>), Error>> + 's>> {
+        Box::pin(async move {
+            let (path, file) = F::temp_file(self, dir, prefix).await?;
+            Ok((path, Box::new(file) as Box<dyn DynFile>))
+        })
+    }
+
+    fn write_file_atomic<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        buf: Slice,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = (Result<FileMeta, Error>, Slice)> + 's>> {
+        Box::pin(F::write_file_atomic(self, path, buf))
+    }
+
+    fn anon_temp_file<'s, 'path: 's>(
+        &'s self,
+        dir: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Box<dyn DynFile>, Error>> + 's>> {
+        Box::pin(async move {
+            let file = F::anon_temp_file(self, dir).await?;
+            Ok(Box::new(file) as Box<dyn DynFile>)
+        })
+    }
+
+    fn copy<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::copy(self, from, to))
+    }
+
+    fn copy_if_source_matches<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+        expected_etag: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::copy_if_source_matches(self, from, to, expected_etag))
+    }
+
+    fn copy_with_progress<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+        progress: &'path mut dyn CopyProgress,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::copy_with_progress(self, from, to, progress))
+    }
+
+    fn copy_range<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        range: Range<u64>,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::copy_range(self, from, range, to))
+    }
+
+    fn rename<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::rename(self, from, to))
+    }
+
+    fn rename_durable<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::rename_durable(self, from, to))
+    }
+
+    fn rename_with_meta<'s, 'path: 's>(
+        &'s self,
+        from: &'path Path,
+        to: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileMeta, Error>> + 's>> {
+        Box::pin(F::rename_with_meta(self, from, to))
+    }
+
+    fn get_xattr<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        name: &'path str,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Option<Vec<u8>>, Error>> + 's>> {
+        Box::pin(F::get_xattr(self, path, name))
+    }
+
+    fn set_xattr<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        name: &'path str,
+        value: &'path [u8],
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + 's>> {
+        Box::pin(F::set_xattr(self, path, name, value))
+    }
+
+    fn list_xattr<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<String>, Error>> + 's>> {
+        Box::pin(F::list_xattr(self, path))
+    }
+
+    fn lock_shared<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileLock, Error>> + 's>> {
+        Box::pin(F::lock_shared(self, path))
+    }
+
+    fn lock_exclusive<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileLock, Error>> + 's>> {
+        Box::pin(F::lock_exclusive(self, path))
+    }
+
+    #[cfg(feature = "mmap")]
+    fn mmap<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Mmap, Error>> + 's>> {
+        Box::pin(F::mmap(self, path))
+    }
+
+    fn localize<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(PathBuf, TempGuard), Error>> + 's>> {
+        Box::pin(F::localize(self, path))
+    }
+
+    #[cfg(feature = "bytes")]
+    fn get_stream<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<
+        Box<
+            dyn MaybeSendFuture<
+                    Output = Result<Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + 's>>, Error>,
+                > + 's,
+        >,
+    > {
+        Box::pin(async move {
+            let stream = F::get_stream(self, path).await?;
+            Ok(Box::pin(stream) as Pin<Box<dyn Stream<Item = Result<Bytes, Error>>>>)
+        })
+    }
+
+    #[cfg(feature = "bytes")]
+    fn put_stream<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        stream: Pin<Box<dyn MaybeSendStream<Item = Result<Bytes, Error>> + 's>>,
+        size_hint: Option<u64>,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<FileMeta, Error>> + 's>> {
+        Box::pin(F::put_stream(self, path, stream, size_hint))
+    }
+
+    #[cfg(feature = "checksum")]
+    fn checksum<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+        algorithm: ChecksumAlgorithm,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<Vec<u8>, Error>> + 's>> {
+        Box::pin(F::checksum(self, path, algorithm))
+    }
+
+    #[cfg(feature = "checksum")]
+    fn list_digest<'s, 'path: 's>(
+        &'s self,
+        path: &'path Path,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<[u8; 32], Error>> + 's>> {
+        Box::pin(F::list_digest(self, path))
+    }
+
+    #[cfg(any(feature = "tokio", feature = "monoio"))]
+    fn watch<'s, 'path: 's>(
+        &'s self,
+        prefix: &'path Path,
+        interval: std::time::Duration,
+    ) -> Pin<Box<dyn Stream<Item = Result<WatchEvent, Error>> + 's>> {
+        Box::pin(F::watch(self, prefix, interval))
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        F::uri(self, path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        F::native_path(self, path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        F::as_presignable(self)
+    }
+
+    fn is_remote(&self) -> bool {
+        F::is_remote(self)
+    }
 }
 
 #[cfg(test)]
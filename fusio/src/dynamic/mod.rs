@@ -5,6 +5,7 @@ use std::{future::Future, pin::Pin};
 
 #[cfg(feature = "fs")]
 pub use fs::{DynFile, DynFs};
+use futures_core::Stream;
 
 use crate::{
     buf::{Slice, SliceMut},
@@ -15,6 +16,10 @@ pub trait MaybeSendFuture: Future + MaybeSend {}
 
 impl<F> MaybeSendFuture for F where F: Future + MaybeSend {}
 
+pub trait MaybeSendStream: Stream + MaybeSend {}
+
+impl<S> MaybeSendStream for S where S: Stream + MaybeSend {}
+
 pub trait DynWrite: MaybeSend {
     fn write_all(
         &mut self,
@@ -24,6 +29,16 @@ pub trait DynWrite: MaybeSend {
     fn flush(&mut self) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + '_>>;
 
     fn close(&mut self) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + '_>>;
+
+    #[cfg(feature = "fs")]
+    fn close_with_meta(
+        &mut self,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<crate::fs::FileMeta, Error>> + '_>>;
+
+    fn allocate(
+        &mut self,
+        len: u64,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + '_>>;
 }
 
 impl<W: Write> DynWrite for W {
@@ -41,6 +56,20 @@ impl<W: Write> DynWrite for W {
     fn close(&mut self) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + '_>> {
         Box::pin(W::close(self))
     }
+
+    #[cfg(feature = "fs")]
+    fn close_with_meta(
+        &mut self,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<crate::fs::FileMeta, Error>> + '_>> {
+        Box::pin(W::close_with_meta(self))
+    }
+
+    fn allocate(
+        &mut self,
+        len: u64,
+    ) -> Pin<Box<dyn MaybeSendFuture<Output = Result<(), Error>> + '_>> {
+        Box::pin(W::allocate(self, len))
+    }
 }
 
 pub trait DynRead: MaybeSend + MaybeSync {
@@ -16,6 +16,15 @@ pub enum Error {
     Unsupported {
         message: String,
     },
+    /// A directory was opened as a file, which local file systems would only refuse on its first
+    /// read.
+    #[error("\"{path}\" is a directory, it can't be opened as a file")]
+    IsADirectory {
+        path: String,
+    },
+    /// The operation was aborted by the `CancellationToken` of a `CancellableFs`.
+    #[error("operation cancelled")]
+    Cancelled,
     #[error(transparent)]
     Other(#[from] BoxedError),
 }
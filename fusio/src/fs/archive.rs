@@ -0,0 +1,1056 @@
+use std::io;
+
+use crate::{
+    fs::{parent, FileMeta, Fs, OpenOptions},
+    path::Path,
+    Error, Read, Write,
+};
+
+/// Files are streamed into archives in blocks of this size.
+const BLOCK_SIZE: u64 = 1024 * 1024;
+
+const TAR_BLOCK_SIZE: u64 = 512;
+const USTAR_MAGIC: &[u8; 8] = b"ustar\x0000";
+/// The magic of GNU tar, whose long name entries hold the paths too long for `ustar` headers.
+const GNU_MAGIC: &[u8; 8] = b"ustar  \x00";
+/// The largest size the octal size field of a tar header holds, larger ones are written in the
+/// base-256 encoding of GNU tar.
+const TAR_MAX_OCTAL_SIZE: u64 = 0o77777777777;
+/// The largest GNU long name or pax extended header read into memory, the paths and records
+/// they hold being far shorter.
+const TAR_MAX_EXTENDED_HEADER: u64 = 1024 * 1024;
+
+/// Sizes, offsets and counts from this value on are written in the zip64 fields of a zip
+/// archive, the value standing for them in the 32-bit fields.
+const ZIP64_LIMIT: u64 = u32::MAX as u64;
+/// The version of the zip specification needed to read zip64 fields, 4.5.
+const ZIP64_VERSION: u16 = 45;
+/// The version needed to read the other entries, 2.0.
+const ZIP_VERSION: u16 = 20;
+/// The content is followed by a data descriptor holding its CRC-32, and the names are UTF-8.
+const ZIP_FLAGS: u16 = 0x0808;
+/// 1980-01-01, the earliest date of the MS-DOS format, as the files are written without one.
+const ZIP_DATE: u16 = 0x21;
+
+/// The format of an archive written by [`archive`]. [`extract`] tells the format of the archives
+/// it reads by their content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// A POSIX `ustar` archive, with the GNU extensions for longer paths and larger files.
+    Tar,
+    /// A zip archive, its files stored uncompressed, with the zip64 extensions for larger files
+    /// and archives.
+    Zip,
+}
+
+/// Writes the files under `prefix` of `fs`, at any depth, into an archive of `format` written
+/// to `writer`, e.g. to export or back up a directory or a prefix of S3. The files are named by
+/// their paths relative to `prefix` and streamed block by block: only a block and the listing of
+/// a directory are held in memory at once, along with the names of the files for zip archives,
+/// which list them at their end. The writer is flushed, not closed. Returns the number of files
+/// archived.
+pub async fn archive<F, W>(
+    fs: &F,
+    prefix: &Path,
+    format: ArchiveFormat,
+    writer: &mut W,
+) -> Result<u64, Error>
+where
+    F: Fs + ?Sized,
+    W: Write,
+{
+    let mut out = CountingWriter { writer, written: 0 };
+    let mut zip_entries = vec![];
+    let mut count = 0;
+    let mut dirs = vec![prefix.clone()];
+    while let Some(dir) = dirs.pop() {
+        let listing = fs.list_delimited(&dir).await?;
+        // the directories below are archived after the files of this one, in order
+        dirs.extend(listing.prefixes.into_iter().rev());
+        for meta in listing.files {
+            let name = meta
+                .path
+                .prefix_match(prefix)
+                .into_iter()
+                .flatten()
+                .map(|part| part.as_ref().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+            if name.is_empty() {
+                continue;
+            }
+            match format {
+                ArchiveFormat::Tar => append_tar(fs, &mut out, &meta, &name).await?,
+                ArchiveFormat::Zip => {
+                    zip_entries.push(append_zip(fs, &mut out, &meta, name).await?)
+                }
+            }
+            count += 1;
+        }
+    }
+
+    match format {
+        // the end of a tar archive is marked by two empty blocks
+        ArchiveFormat::Tar => {
+            out.write(vec![0; 2 * TAR_BLOCK_SIZE as usize]).await?;
+        }
+        ArchiveFormat::Zip => finish_zip(&mut out, &zip_entries).await?,
+    }
+    out.writer.flush().await?;
+    Ok(count)
+}
+
+/// Writes to an archive, counting the bytes written for the offsets of zip archives.
+struct CountingWriter<'a, W> {
+    writer: &'a mut W,
+    written: u64,
+}
+
+impl<W: Write> CountingWriter<'_, W> {
+    async fn write(&mut self, buf: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let (result, buf) = self.writer.write_all(buf).await;
+        result?;
+        self.written += buf.len() as u64;
+        Ok(buf)
+    }
+}
+
+/// Streams the content of the file `meta` of `fs` to `out`, returning its CRC-32.
+async fn copy_content<F, W>(
+    fs: &F,
+    out: &mut CountingWriter<'_, W>,
+    meta: &FileMeta,
+) -> Result<u32, Error>
+where
+    F: Fs + ?Sized,
+    W: Write,
+{
+    let mut file = fs.open_with_len(&meta.path, meta.size).await?;
+    let mut crc = 0;
+    let mut buf = vec![];
+    let mut copied = 0;
+    while copied < meta.size {
+        buf.resize(BLOCK_SIZE.min(meta.size - copied) as usize, 0);
+        let (result, read) = file.read_exact_at(buf, copied).await;
+        result?;
+        crc = crc32(crc, &read);
+        buf = out.write(read).await?;
+        copied += buf.len() as u64;
+    }
+    Ok(crc)
+}
+
+async fn append_tar<F, W>(
+    fs: &F,
+    out: &mut CountingWriter<'_, W>,
+    meta: &FileMeta,
+    name: &str,
+) -> Result<(), Error>
+where
+    F: Fs + ?Sized,
+    W: Write,
+{
+    let header = match split_tar_name(name) {
+        Some((prefix, name)) => tar_header(
+            USTAR_MAGIC,
+            prefix.as_bytes(),
+            name.as_bytes(),
+            meta.size,
+            b'0',
+        ),
+        None => {
+            // a GNU long name entry holds the whole name, the header of the file a truncated one
+            let mut long_name = name.as_bytes().to_vec();
+            long_name.push(0);
+            let len = long_name.len() as u64;
+            out.write(tar_header(GNU_MAGIC, b"", b"././@LongLink", len, b'L'))
+                .await?;
+            long_name.resize(tar_padded(len) as usize, 0);
+            out.write(long_name).await?;
+            tar_header(GNU_MAGIC, b"", &name.as_bytes()[..100], meta.size, b'0')
+        }
+    };
+    out.write(header).await?;
+    copy_content(fs, out, meta).await?;
+    out.write(vec![0; (tar_padded(meta.size) - meta.size) as usize])
+        .await?;
+    Ok(())
+}
+
+/// Rounds `len` up to whole tar blocks.
+fn tar_padded(len: u64) -> u64 {
+    len.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE
+}
+
+/// Splits `name` at a `/` into the prefix and name fields of a `ustar` header, of at most 155
+/// and 100 bytes, `None` if it can't be.
+fn split_tar_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    let split = name
+        .match_indices('/')
+        .map(|(i, _)| i)
+        .find(|&i| name.len() - i - 1 <= 100)?;
+    match split <= 155 {
+        true => Some((&name[..split], &name[split + 1..])),
+        false => None,
+    }
+}
+
+fn tar_header(magic: &[u8; 8], prefix: &[u8], name: &[u8], size: u64, kind: u8) -> Vec<u8> {
+    let mut header = vec![0; TAR_BLOCK_SIZE as usize];
+    header[..name.len()].copy_from_slice(name);
+    header[100..108].copy_from_slice(b"0000644\0");
+    // the owner and the modification time are left at zero
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    match size <= TAR_MAX_OCTAL_SIZE {
+        true => header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes()),
+        false => {
+            header[124] = 0x80;
+            header[128..136].copy_from_slice(&size.to_be_bytes());
+        }
+    }
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = kind;
+    header[257..265].copy_from_slice(magic);
+    header[345..345 + prefix.len()].copy_from_slice(prefix);
+    // the checksum is computed with its own field made of spaces
+    header[148..156].fill(b' ');
+    let checksum = header.iter().map(|&b| b as u32).sum::<u32>();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    header
+}
+
+/// What the central directory at the end of a zip archive records of a file.
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u64,
+    offset: u64,
+}
+
+impl ZipEntry {
+    fn is_zip64(&self) -> bool {
+        self.size >= ZIP64_LIMIT || self.offset >= ZIP64_LIMIT
+    }
+}
+
+async fn append_zip<F, W>(
+    fs: &F,
+    out: &mut CountingWriter<'_, W>,
+    meta: &FileMeta,
+    name: String,
+) -> Result<ZipEntry, Error>
+where
+    F: Fs + ?Sized,
+    W: Write,
+{
+    let mut entry = ZipEntry {
+        name,
+        crc: 0,
+        size: meta.size,
+        offset: out.written,
+    };
+    let zip64 = entry.is_zip64();
+
+    // the CRC-32 and the sizes are in the data descriptor following the content
+    let mut header = Vec::with_capacity(30 + entry.name.len() + 20);
+    put_u32(&mut header, 0x04034b50);
+    put_u16(&mut header, if zip64 { ZIP64_VERSION } else { ZIP_VERSION });
+    put_u16(&mut header, ZIP_FLAGS);
+    // stored, at midnight
+    put_u16(&mut header, 0);
+    put_u16(&mut header, 0);
+    put_u16(&mut header, ZIP_DATE);
+    put_u32(&mut header, 0);
+    let size = if zip64 { u32::MAX } else { 0 };
+    put_u32(&mut header, size);
+    put_u32(&mut header, size);
+    put_u16(&mut header, entry.name.len() as u16);
+    put_u16(&mut header, if zip64 { 20 } else { 0 });
+    header.extend_from_slice(entry.name.as_bytes());
+    if zip64 {
+        put_u16(&mut header, 0x0001);
+        put_u16(&mut header, 16);
+        header.extend_from_slice(&[0; 16]);
+    }
+    out.write(header).await?;
+
+    entry.crc = copy_content(fs, out, meta).await?;
+
+    let mut descriptor = Vec::with_capacity(24);
+    put_u32(&mut descriptor, 0x08074b50);
+    put_u32(&mut descriptor, entry.crc);
+    match zip64 {
+        true => {
+            put_u64(&mut descriptor, entry.size);
+            put_u64(&mut descriptor, entry.size);
+        }
+        false => {
+            put_u32(&mut descriptor, entry.size as u32);
+            put_u32(&mut descriptor, entry.size as u32);
+        }
+    }
+    out.write(descriptor).await?;
+    Ok(entry)
+}
+
+/// Writes the central directory of the zip archive holding `entries`, and its end records.
+async fn finish_zip<W: Write>(
+    out: &mut CountingWriter<'_, W>,
+    entries: &[ZipEntry],
+) -> Result<(), Error> {
+    let start = out.written;
+    let mut buf = vec![];
+    for entry in entries {
+        let zip64 = entry.is_zip64();
+        let version = if zip64 { ZIP64_VERSION } else { ZIP_VERSION };
+        put_u32(&mut buf, 0x02014b50);
+        // made by a Unix system, for the permissions
+        put_u16(&mut buf, (3 << 8) | version);
+        put_u16(&mut buf, version);
+        put_u16(&mut buf, ZIP_FLAGS);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, ZIP_DATE);
+        put_u32(&mut buf, entry.crc);
+        let (size, offset) = match zip64 {
+            true => (u32::MAX, u32::MAX),
+            false => (entry.size as u32, entry.offset as u32),
+        };
+        put_u32(&mut buf, size);
+        put_u32(&mut buf, size);
+        put_u16(&mut buf, entry.name.len() as u16);
+        put_u16(&mut buf, if zip64 { 28 } else { 0 });
+        // no comment, on the first disk, a binary regular file readable by all
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, 0);
+        put_u16(&mut buf, 0);
+        put_u32(&mut buf, 0o100644 << 16);
+        put_u32(&mut buf, offset);
+        buf.extend_from_slice(entry.name.as_bytes());
+        if zip64 {
+            put_u16(&mut buf, 0x0001);
+            put_u16(&mut buf, 24);
+            put_u64(&mut buf, entry.size);
+            put_u64(&mut buf, entry.size);
+            put_u64(&mut buf, entry.offset);
+        }
+        if buf.len() as u64 >= BLOCK_SIZE {
+            buf = out.write(buf).await?;
+            buf.clear();
+        }
+    }
+    let end = out.written + buf.len() as u64;
+    let (count, directory_size) = (entries.len() as u64, end - start);
+
+    if count >= u16::MAX as u64 || directory_size >= ZIP64_LIMIT || start >= ZIP64_LIMIT {
+        // the zip64 end of central directory record, then the locator pointing at it
+        put_u32(&mut buf, 0x06064b50);
+        put_u64(&mut buf, 44);
+        put_u16(&mut buf, (3 << 8) | ZIP64_VERSION);
+        put_u16(&mut buf, ZIP64_VERSION);
+        put_u32(&mut buf, 0);
+        put_u32(&mut buf, 0);
+        put_u64(&mut buf, count);
+        put_u64(&mut buf, count);
+        put_u64(&mut buf, directory_size);
+        put_u64(&mut buf, start);
+        put_u32(&mut buf, 0x07064b50);
+        put_u32(&mut buf, 0);
+        put_u64(&mut buf, end);
+        put_u32(&mut buf, 1);
+    }
+    put_u32(&mut buf, 0x06054b50);
+    put_u16(&mut buf, 0);
+    put_u16(&mut buf, 0);
+    put_u16(&mut buf, count.min(u16::MAX as u64) as u16);
+    put_u16(&mut buf, count.min(u16::MAX as u64) as u16);
+    put_u32(&mut buf, directory_size.min(ZIP64_LIMIT) as u32);
+    put_u32(&mut buf, start.min(ZIP64_LIMIT) as u32);
+    put_u16(&mut buf, 0);
+    out.write(buf).await?;
+    Ok(())
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// The CRC-32 of zip archives, of the reflected polynomial `0xedb88320`, by bytes.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb88320,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Updates the CRC-32 `crc` of the bytes before `bytes` with them, starting from 0.
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Writes the files of the archive read from `reader`, tar or zip as told by its content, under
+/// `prefix` of `fs`, e.g. to import a backup made by [`archive`], creating the directories they
+/// are in. Their content is streamed block by block, and zip archives are checked against their
+/// CRC-32. Entries named with `..` parts or absolute paths, which would be written outside of
+/// `prefix`, fail the extraction with [`io::ErrorKind::InvalidData`], as do malformed archives.
+/// Entries that are neither files nor directories, e.g. symbolic links, are skipped, and zip
+/// archives can only be extracted if their files are stored uncompressed. Returns the number of
+/// files extracted.
+pub async fn extract<F, R>(fs: &F, reader: &mut R, prefix: &Path) -> Result<u64, Error>
+where
+    F: Fs + ?Sized,
+    R: Read,
+{
+    let size = reader.size().await?;
+    let head = read_at(reader, 0, size.min(TAR_BLOCK_SIZE) as usize).await?;
+    if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+        return extract_zip(fs, reader, size, prefix).await;
+    }
+    if head.len() == TAR_BLOCK_SIZE as usize
+        && (&head[257..262] == b"ustar" || head.iter().all(|&b| b == 0))
+    {
+        return extract_tar(fs, reader, size, prefix).await;
+    }
+    Err(invalid_archive("neither a tar nor a zip archive"))
+}
+
+fn invalid_archive(message: impl Into<String>) -> Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into()).into()
+}
+
+async fn read_at<R: Read>(reader: &mut R, pos: u64, len: usize) -> Result<Vec<u8>, Error> {
+    let (result, buf) = reader.read_exact_at(vec![0; len], pos).await;
+    result?;
+    Ok(buf)
+}
+
+/// Returns the path of the entry `name` of an archive under `prefix`, `None` for the archive's
+/// root, refusing the names that would land outside of `prefix`.
+fn entry_path(prefix: &Path, name: &str) -> Result<Option<Path>, Error> {
+    if name.starts_with(['/', '\\']) {
+        return Err(invalid_archive(format!(
+            "refusing to extract \"{}\", an absolute path",
+            name
+        )));
+    }
+    let mut path = prefix.clone();
+    let mut empty = true;
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => continue,
+            ".." => {
+                return Err(invalid_archive(format!(
+                    "refusing to extract \"{}\" outside of \"{}\"",
+                    name, prefix
+                )))
+            }
+            part => {
+                path = path.child(part);
+                empty = false;
+            }
+        }
+    }
+    Ok((!empty).then_some(path))
+}
+
+/// Writes the `len` bytes of the archive from `pos` to the file at `path`, returning their
+/// CRC-32.
+async fn extract_file<F, R>(
+    fs: &F,
+    reader: &mut R,
+    pos: u64,
+    len: u64,
+    path: &Path,
+) -> Result<u32, Error>
+where
+    F: Fs + ?Sized,
+    R: Read,
+{
+    fs.create_dir_all(&parent(path)).await?;
+    let mut file = fs
+        .open_options(path, OpenOptions::default().create(true).truncate(true))
+        .await?;
+    let mut crc = 0;
+    let mut buf = vec![];
+    let mut copied = 0;
+    while copied < len {
+        buf.resize(BLOCK_SIZE.min(len - copied) as usize, 0);
+        let (result, read) = reader.read_exact_at(buf, pos + copied).await;
+        result?;
+        crc = crc32(crc, &read);
+        let (result, written) = file.write_all(read).await;
+        result?;
+        copied += written.len() as u64;
+        buf = written;
+    }
+    file.close().await?;
+    Ok(crc)
+}
+
+async fn extract_tar<F, R>(fs: &F, reader: &mut R, size: u64, prefix: &Path) -> Result<u64, Error>
+where
+    F: Fs + ?Sized,
+    R: Read,
+{
+    let mut count = 0;
+    // the name and size given to the next entry by a GNU long name or a pax header
+    let (mut next_name, mut next_size) = (None, None);
+    let mut pos = 0;
+    while pos + TAR_BLOCK_SIZE <= size {
+        let header = read_at(reader, pos, TAR_BLOCK_SIZE as usize).await?;
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let mut checked = header.clone();
+        checked[148..156].fill(b' ');
+        let checksum = checked.iter().map(|&b| b as u64).sum::<u64>();
+        if parse_octal(&header[148..156]) != Some(checksum) {
+            return Err(invalid_archive(format!(
+                "the tar header at {} doesn't match its checksum",
+                pos
+            )));
+        }
+
+        let len = match next_size.take() {
+            Some(len) => len,
+            None => parse_tar_size(&header[124..136]).ok_or_else(|| {
+                invalid_archive(format!("invalid size in the tar header at {}", pos))
+            })?,
+        };
+        let data = pos + TAR_BLOCK_SIZE;
+        if data.checked_add(len).is_none_or(|end| end > size) {
+            return Err(invalid_archive(format!(
+                "the tar entry at {} runs past the end of the archive",
+                pos
+            )));
+        }
+        if matches!(header[156], b'L' | b'x') && len > TAR_MAX_EXTENDED_HEADER {
+            return Err(invalid_archive(format!(
+                "the extended tar header at {} is larger than {} bytes",
+                pos, TAR_MAX_EXTENDED_HEADER
+            )));
+        }
+        // within the archive, the padded end can't overflow
+        pos = data + tar_padded(len);
+        let name = match next_name.take() {
+            Some(name) => name,
+            None => {
+                let name = until_nul(&header[..100]);
+                let prefix = until_nul(&header[345..500]);
+                match &header[257..263] == b"ustar\0" && !prefix.is_empty() {
+                    true => format!("{}/{}", prefix, name),
+                    false => name,
+                }
+            }
+        };
+
+        match header[156] {
+            b'L' => next_name = Some(until_nul(&read_at(reader, data, len as usize).await?)),
+            b'x' => {
+                let records = read_at(reader, data, len as usize).await?;
+                for (key, value) in pax_records(&records) {
+                    match key {
+                        "path" => next_name = Some(value.to_string()),
+                        "size" => next_size = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            b'0' | b'\0' | b'7' => {
+                if let Some(path) = entry_path(prefix, &name)? {
+                    extract_file(fs, reader, data, len, &path).await?;
+                    count += 1;
+                }
+            }
+            b'5' => {
+                if let Some(path) = entry_path(prefix, &name)? {
+                    fs.create_dir_all(&path).await?;
+                }
+            }
+            // links, devices and global pax headers
+            _ => {}
+        }
+    }
+    Ok(count)
+}
+
+/// Returns the text of `field` up to its first NUL.
+fn until_nul(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Parses an octal field of a tar header, padded with NULs and spaces.
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let digits = std::str::from_utf8(field).ok()?;
+    let digits = digits.trim_matches(|c| c == '\0' || c == ' ');
+    match digits.is_empty() {
+        true => Some(0),
+        false => u64::from_str_radix(digits, 8).ok(),
+    }
+}
+
+/// Parses the size field of a tar header, in octal or in the base-256 encoding of GNU tar.
+fn parse_tar_size(field: &[u8]) -> Option<u64> {
+    match field[0] & 0x80 != 0 {
+        true => Some(field[4..].iter().fold(0, |size, &b| (size << 8) | b as u64)),
+        false => parse_octal(field),
+    }
+}
+
+/// Parses the `<length> <key>=<value>\n` records of a pax extended header.
+fn pax_records(records: &[u8]) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = records;
+    std::iter::from_fn(move || {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let len = std::str::from_utf8(&rest[..space])
+            .ok()?
+            .parse::<usize>()
+            .ok()?;
+        if len <= space + 1 || len > rest.len() {
+            return None;
+        }
+        let record = std::str::from_utf8(&rest[space + 1..len]).ok();
+        rest = &rest[len..];
+        record?.trim_end_matches('\n').split_once('=')
+    })
+}
+
+/// Reads the little-endian integer of `len` bytes at `at` of a zip record.
+fn zip_field(record: &[u8], at: usize, len: usize) -> Result<u64, Error> {
+    let bytes = record
+        .get(at..at + len)
+        .ok_or_else(|| invalid_archive("truncated zip record"))?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &b| (value << 8) | b as u64))
+}
+
+async fn extract_zip<F, R>(fs: &F, reader: &mut R, size: u64, prefix: &Path) -> Result<u64, Error>
+where
+    F: Fs + ?Sized,
+    R: Read,
+{
+    // the end of central directory record ends the archive, but for a comment of up to 64 KiB
+    let tail_len = size.min(22 + u16::MAX as u64);
+    let tail_pos = size - tail_len;
+    let tail = read_at(reader, tail_pos, tail_len as usize).await?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| tail[i..i + 4] == 0x06054b50u32.to_le_bytes())
+        .ok_or_else(|| invalid_archive("no end of central directory in the zip archive"))?;
+    let record = &tail[end..];
+    let mut count = zip_field(record, 10, 2)?;
+    let mut directory_size = zip_field(record, 12, 4)?;
+    let mut directory_pos = zip_field(record, 16, 4)?;
+    if count == u16::MAX as u64 || directory_size == ZIP64_LIMIT || directory_pos == ZIP64_LIMIT {
+        // the zip64 locator is right before the end record, pointing at the zip64 one
+        let locator_pos = (tail_pos + end as u64)
+            .checked_sub(20)
+            .ok_or_else(|| invalid_archive("no zip64 locator in the zip archive"))?;
+        let locator = read_at(reader, locator_pos, 20).await?;
+        if zip_field(&locator, 0, 4)? != 0x07064b50 {
+            return Err(invalid_archive("no zip64 locator in the zip archive"));
+        }
+        let record = read_at(reader, zip_field(&locator, 8, 8)?, 56).await?;
+        if zip_field(&record, 0, 4)? != 0x06064b50 {
+            return Err(invalid_archive(
+                "no zip64 end of central directory in the zip archive",
+            ));
+        }
+        count = zip_field(&record, 32, 8)?;
+        directory_size = zip_field(&record, 40, 8)?;
+        directory_pos = zip_field(&record, 48, 8)?;
+    }
+    if directory_pos
+        .checked_add(directory_size)
+        .is_none_or(|end| end > size)
+    {
+        return Err(invalid_archive(
+            "the central directory is past the end of the zip archive",
+        ));
+    }
+
+    let directory = read_at(reader, directory_pos, directory_size as usize).await?;
+    let mut extracted = 0;
+    let mut at = 0;
+    for _ in 0..count {
+        if zip_field(&directory, at, 4)? != 0x02014b50 {
+            return Err(invalid_archive(
+                "invalid central directory in the zip archive",
+            ));
+        }
+        let flags = zip_field(&directory, at + 8, 2)?;
+        let method = zip_field(&directory, at + 10, 2)?;
+        let crc = zip_field(&directory, at + 16, 4)? as u32;
+        let mut compressed = zip_field(&directory, at + 20, 4)?;
+        let mut len = zip_field(&directory, at + 24, 4)?;
+        let name_len = zip_field(&directory, at + 28, 2)? as usize;
+        let extra_len = zip_field(&directory, at + 30, 2)? as usize;
+        let comment_len = zip_field(&directory, at + 32, 2)? as usize;
+        let mut offset = zip_field(&directory, at + 42, 4)?;
+        let name = directory
+            .get(at + 46..at + 46 + name_len)
+            .map(String::from_utf8_lossy)
+            .ok_or_else(|| invalid_archive("truncated zip record"))?
+            .into_owned();
+
+        // the zip64 extra field holds those of the sizes and the offset that don't fit
+        let mut extra = at + 46 + name_len;
+        let extra_end = extra + extra_len;
+        while extra + 4 <= extra_end {
+            let (id, field_len) = (
+                zip_field(&directory, extra, 2)?,
+                zip_field(&directory, extra + 2, 2)? as usize,
+            );
+            if id == 0x0001 {
+                let mut field = extra + 4;
+                for value in [&mut len, &mut compressed, &mut offset] {
+                    if *value == ZIP64_LIMIT {
+                        *value = zip_field(&directory, field, 8)?;
+                        field += 8;
+                    }
+                }
+            }
+            extra += 4 + field_len;
+        }
+        at = extra_end + comment_len;
+
+        let Some(path) = entry_path(prefix, &name)? else {
+            continue;
+        };
+        if name.ends_with('/') {
+            fs.create_dir_all(&path).await?;
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(Error::Unsupported {
+                message: format!("can't extract \"{}\", encrypted in the zip archive", name),
+            });
+        }
+        if method != 0 || compressed != len {
+            return Err(Error::Unsupported {
+                message: format!(
+                    "can't extract \"{}\", compressed with the method {} in the zip archive",
+                    name, method
+                ),
+            });
+        }
+
+        let local = read_at(reader, offset, 30).await?;
+        if zip_field(&local, 0, 4)? != 0x04034b50 {
+            return Err(invalid_archive(format!(
+                "no local header for \"{}\" in the zip archive",
+                name
+            )));
+        }
+        let data = (offset + 30)
+            .checked_add(zip_field(&local, 26, 2)? + zip_field(&local, 28, 2)?)
+            .filter(|data| data.checked_add(len).is_some_and(|end| end <= size))
+            .ok_or_else(|| {
+                invalid_archive(format!("\"{}\" runs past the end of the zip archive", name))
+            })?;
+        if extract_file(fs, reader, data, len, &path).await? != crc {
+            // the corrupt content isn't left behind as if it was extracted
+            fs.remove(&path).await?;
+            return Err(invalid_archive(format!(
+                "\"{}\" doesn't match its CRC-32 in the zip archive",
+                name
+            )));
+        }
+        extracted += 1;
+    }
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn archives_hold_the_files_under_the_prefix() {
+        use std::{
+            collections::BTreeMap,
+            io::{Cursor, Read as _},
+        };
+
+        use crate::{
+            fs::{archive, ArchiveFormat},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let long = format!("{}file", "nested/".repeat(30));
+        let mut files = BTreeMap::new();
+        files.insert("a".to_string(), b"hello".to_vec());
+        files.insert("empty".to_string(), vec![]);
+        files.insert(
+            "dir/large".to_string(),
+            (0..3 * 1024 * 1024 / 2).map(|i| i as u8).collect(),
+        );
+        files.insert(long.clone(), b"deep".to_vec());
+        for (name, content) in &files {
+            mock.put_object(&format!("backup/{}", name), content.clone());
+        }
+        mock.put_object("other", b"not archived".to_vec());
+
+        let mut tar = vec![];
+        let count = archive(
+            &mock.fs(),
+            &Path::from("backup"),
+            ArchiveFormat::Tar,
+            &mut Cursor::new(&mut tar),
+        )
+        .await
+        .unwrap();
+        assert_eq!(count, 4);
+        let mut extracted = BTreeMap::new();
+        for entry in tar::Archive::new(&tar[..]).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_str().unwrap().to_string();
+            let mut content = vec![];
+            entry.read_to_end(&mut content).unwrap();
+            extracted.insert(name, content);
+        }
+        assert_eq!(extracted, files);
+
+        let mut zip = vec![];
+        let count = archive(
+            &mock.fs(),
+            &Path::from("backup"),
+            ArchiveFormat::Zip,
+            &mut Cursor::new(&mut zip),
+        )
+        .await
+        .unwrap();
+        assert_eq!(count, 4);
+        let mut zip = zip::ZipArchive::new(Cursor::new(zip)).unwrap();
+        let mut extracted = BTreeMap::new();
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            let mut content = vec![];
+            file.read_to_end(&mut content).unwrap();
+            extracted.insert(file.name().to_string(), content);
+        }
+        assert_eq!(extracted, files);
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn extracted_archives_recreate_the_files() {
+        use std::io::Cursor;
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{archive, extract, ArchiveFormat},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let long = format!("{}file", "nested/".repeat(30));
+        let files = [
+            ("a", b"hello".to_vec()),
+            (
+                "dir/large",
+                (0..3 * 1024 * 1024 / 2).map(|i| i as u8).collect(),
+            ),
+            ("dir/sub/empty", vec![]),
+            (long.as_str(), b"deep".to_vec()),
+        ];
+        for (name, content) in &files {
+            mock.put_object(&format!("backup/{}", name), content.clone());
+        }
+
+        for format in [ArchiveFormat::Tar, ArchiveFormat::Zip] {
+            let mut bytes = vec![];
+            archive(
+                &mock.fs(),
+                &Path::from("backup"),
+                format,
+                &mut Cursor::new(&mut bytes),
+            )
+            .await
+            .unwrap();
+
+            let tmp_dir = TempDir::new().unwrap();
+            let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+            let count = extract(&TokioFs, &mut &mut bytes, &dir.child("restored"))
+                .await
+                .unwrap();
+            assert_eq!(count, 4);
+            for (name, content) in &files {
+                let local = tmp_dir.path().join("restored").join(name);
+                assert_eq!(&std::fs::read(local).unwrap(), content, "{:?}", format);
+            }
+        }
+
+        // archives made by other tools, with their own headers for long names
+        let mut builder = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        builder
+            .append_data(&mut header, &long, &b"other"[..])
+            .unwrap();
+        let mut bytes = builder.into_inner().unwrap();
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        assert_eq!(extract(&TokioFs, &mut &mut bytes, &dir).await.unwrap(), 1);
+        assert_eq!(std::fs::read(tmp_dir.path().join(&long)).unwrap(), b"other");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn entries_outside_of_the_prefix_are_rejected() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use super::{tar_header, USTAR_MAGIC};
+        use crate::{
+            disk::TokioFs,
+            fs::{extract, Fs},
+            path::Path,
+            Error,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let target = dir.child("target");
+        TokioFs.create_dir_all(&target).await.unwrap();
+
+        for name in ["../escape", "/escape", "inner/../../escape"] {
+            let mut bytes = tar_header(USTAR_MAGIC, b"", name.as_bytes(), 3, b'0');
+            bytes.extend_from_slice(b"bad");
+            bytes.resize(512 + 512 + 1024, 0);
+            assert!(matches!(
+                extract(&TokioFs, &mut &mut bytes, &target).await,
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+            ));
+            assert!(!tmp_dir.path().join("escape").exists());
+        }
+        // nothing was written under the prefix either
+        assert!(std::fs::read_dir(tmp_dir.path().join("target"))
+            .unwrap()
+            .next()
+            .is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn oversized_entries_are_rejected() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use super::{tar_header, GNU_MAGIC, TAR_MAX_OCTAL_SIZE, USTAR_MAGIC};
+        use crate::{disk::TokioFs, fs::extract, path::Path, Error};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+
+        // sizes read from the headers aren't trusted past the end of the archive
+        for (magic, kind) in [(GNU_MAGIC, b'L'), (USTAR_MAGIC, b'x'), (USTAR_MAGIC, b'0')] {
+            let mut bytes = tar_header(magic, b"", b"name", TAR_MAX_OCTAL_SIZE, kind);
+            bytes.resize(512 + 1024, 0);
+            assert!(matches!(
+                extract(&TokioFs, &mut &mut bytes, &dir).await,
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+            ));
+        }
+        let mut bytes = tar_header(USTAR_MAGIC, b"", b"name", u64::MAX, b'0');
+        bytes.resize(512 + 1024, 0);
+        assert!(matches!(
+            extract(&TokioFs, &mut &mut bytes, &dir).await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+        // nor are the extended headers read into memory past a limit
+        let len = 2 * 1024 * 1024;
+        let mut bytes = tar_header(GNU_MAGIC, b"", b"././@LongLink", len, b'L');
+        bytes.resize(512 + len as usize + 1024, b'a');
+        assert!(matches!(
+            extract(&TokioFs, &mut &mut bytes, &dir).await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+        assert!(std::fs::read_dir(tmp_dir.path()).unwrap().next().is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn corrupt_files_are_not_left_extracted() {
+        use std::io::{self, Cursor};
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{archive, extract, ArchiveFormat, Fs},
+            path::Path,
+            Error,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        TokioFs.create_dir_all(&dir.child("source")).await.unwrap();
+        TokioFs
+            .write_file(&dir.child("source").child("a"), b"hello".to_vec())
+            .await
+            .0
+            .unwrap();
+        let mut bytes = vec![];
+        archive(
+            &TokioFs,
+            &dir.child("source"),
+            ArchiveFormat::Zip,
+            &mut Cursor::new(&mut bytes),
+        )
+        .await
+        .unwrap();
+        let at = bytes.windows(5).position(|w| w == b"hello").unwrap();
+        bytes[at] = b'j';
+
+        assert!(matches!(
+            extract(&TokioFs, &mut &mut bytes, &dir.child("restored")).await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+        assert!(!tmp_dir.path().join("restored").join("a").exists());
+    }
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        use super::crc32;
+
+        assert_eq!(crc32(0, b"123456789"), 0xcbf43926);
+        // by parts as by the whole
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf43926);
+    }
+}
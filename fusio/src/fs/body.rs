@@ -0,0 +1,149 @@
+use std::pin::pin;
+
+use async_stream::stream;
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::{
+    fs::{Fs, OpenOptions},
+    path::Path,
+    Error, Read, Write,
+};
+
+/// The size of the chunks the files are streamed in by [`Fs::get_stream`] by default.
+pub const STREAM_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Opens the file at `path` of `fs` and streams its content in chunks of [`STREAM_CHUNK_SIZE`],
+/// each read when the previous one is consumed.
+pub(crate) async fn read_chunks<F>(
+    fs: &F,
+    path: &Path,
+) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error>
+where
+    F: Fs + ?Sized,
+{
+    let mut file = fs.open_options(path, OpenOptions::default()).await?;
+    let size = file.size().await?;
+
+    Ok(stream! {
+        let mut offset = 0;
+        while offset < size {
+            let len = STREAM_CHUNK_SIZE.min(size - offset);
+            let (result, chunk) = file.read_exact_at(vec![0; len as usize], offset).await;
+            if let Err(e) = result {
+                yield Err(e);
+                return;
+            }
+            offset += len;
+            yield Ok(Bytes::from(chunk));
+        }
+    })
+}
+
+/// Writes the chunks of `stream` to `file` as they come, returning it once the stream ended.
+pub(crate) async fn write_chunks<W, S>(mut file: W, stream: S) -> Result<W, Error>
+where
+    W: Write,
+    S: Stream<Item = Result<Bytes, Error>>,
+{
+    let mut stream = pin!(stream);
+    while let Some(chunk) = stream.next().await {
+        let (result, _) = file.write_all(chunk?).await;
+        result?;
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn streamed_chunks_make_up_the_file() {
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, STREAM_CHUNK_SIZE},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        async fn collect<F: Fs>(fs: &F, path: &Path) -> (usize, Vec<u8>) {
+            let mut stream = std::pin::pin!(fs.get_stream(path).await.unwrap());
+            let (mut chunks, mut content) = (0, vec![]);
+            while let Some(chunk) = stream.next().await {
+                content.extend_from_slice(&chunk.unwrap());
+                chunks += 1;
+            }
+            (chunks, content)
+        }
+
+        let content = (0..2 * STREAM_CHUNK_SIZE + 100)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let path = dir.child("file");
+        TokioFs.write_file(&path, content.clone()).await.0.unwrap();
+        assert_eq!(collect(&TokioFs, &path).await, (3, content.clone()));
+
+        let empty = dir.child("empty");
+        TokioFs.write_file(&empty, vec![]).await.0.unwrap();
+        assert_eq!(collect(&TokioFs, &empty).await, (0, vec![]));
+
+        // the chunks of the response are yielded as they are received
+        let mock = MockS3::new();
+        mock.chunked();
+        mock.put_object("object", content[..10].to_vec());
+        let (chunks, streamed) = collect(&mock.fs(), &Path::from("object")).await;
+        assert!(chunks > 1);
+        assert_eq!(streamed, &content[..10]);
+
+        assert!(TokioFs.get_stream(&dir.child("missing")).await.is_err());
+        assert!(mock.fs().get_stream(&Path::from("missing")).await.is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn put_streams_write_all_or_nothing() {
+        use std::io;
+
+        use bytes::Bytes;
+        use futures_util::stream;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, Error};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+
+        let chunks = ["hello", ", ", "world"].map(|chunk| Ok(Bytes::from(chunk)));
+        let meta = TokioFs
+            .put_stream(&path, stream::iter(chunks), None)
+            .await
+            .unwrap();
+        assert_eq!(meta.size, 12);
+        assert_eq!(
+            std::fs::read(tmp_dir.path().join("file")).unwrap(),
+            b"hello, world"
+        );
+
+        // the error is returned and the file left as it was, without the partial content
+        let chunks = vec![
+            Ok(Bytes::from("partial")),
+            Err(Error::Io(io::Error::other("broken stream"))),
+        ];
+        let result = TokioFs.put_stream(&path, stream::iter(chunks), None).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.to_string() == "broken stream"));
+        assert_eq!(
+            std::fs::read(tmp_dir.path().join("file")).unwrap(),
+            b"hello, world"
+        );
+        assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 1);
+    }
+}
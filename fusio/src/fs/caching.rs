@@ -0,0 +1,701 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    io,
+    ops::Range,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        parent, DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs,
+        ListOptions, OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+/// How much a [`CachingFs`] keeps in its local cache.
+#[derive(Debug, Clone, Copy)]
+pub struct CachePolicy {
+    /// The total size of the cached files, in bytes. The least recently used files are evicted
+    /// once it is exceeded, files larger than it are never cached.
+    pub max_size: u64,
+    /// Revalidates cached files with a conditional read (`If-None-Match` in S3) of the remote
+    /// file instead of comparing its metadata, so that a changed file is fetched in the same round
+    /// trip.
+    pub conditional_get: bool,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            max_size: 256 * 1024 * 1024,
+            conditional_get: false,
+        }
+    }
+}
+
+struct CacheEntry {
+    meta: FileMeta,
+    size: u64,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<Path, CacheEntry>,
+    order: BTreeMap<u64, Path>,
+    size: u64,
+    tick: u64,
+}
+
+impl Lru {
+    /// Returns the remote metadata of the cached copy of `path`, marking it as recently used.
+    fn touch(&mut self, path: &Path) -> Option<&FileMeta> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(path)?;
+        self.order.remove(&entry.tick);
+        entry.tick = self.tick;
+        self.order.insert(entry.tick, path.clone());
+        Some(&entry.meta)
+    }
+
+    /// Returns whether a cached copy of `meta.path` matches `meta`, marking it as recently used.
+    fn hit(&mut self, meta: &FileMeta) -> bool {
+        self.touch(&meta.path)
+            .is_some_and(|cached| cached.size == meta.size && cached.etag == meta.etag)
+    }
+
+    /// Records a cached copy of `meta.path`, returning the paths evicted to stay within
+    /// `max_size`.
+    fn insert(&mut self, meta: FileMeta, size: u64, max_size: u64) -> Vec<Path> {
+        self.remove(&meta.path);
+        self.tick += 1;
+        self.order.insert(self.tick, meta.path.clone());
+        self.entries.insert(
+            meta.path.clone(),
+            CacheEntry {
+                meta,
+                size,
+                tick: self.tick,
+            },
+        );
+        self.size += size;
+
+        let mut evicted = vec![];
+        while self.size > max_size {
+            let Some((_, path)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&path) {
+                self.size -= entry.size;
+            }
+            evicted.push(path);
+        }
+        evicted
+    }
+
+    fn remove(&mut self, path: &Path) -> bool {
+        match self.entries.remove(path) {
+            Some(entry) => {
+                self.order.remove(&entry.tick);
+                self.size -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn ignore_not_found(result: Result<(), Error>) -> Result<(), Error> {
+    match result {
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// A [`Fs`] that caches the files read from a `remote` file system in a `cache` one, typically a
+/// local disk. The metadata of the remote file is checked on every open and a cached copy is only
+/// served while its size and ETag still match, otherwise the file is fetched again. Writes and
+/// removals go to the remote file system and invalidate the cached copy.
+///
+/// The cache file system is expected to be dedicated to the cache, e.g. a [`ScopedFs`] over a
+/// local directory, as files are stored in it under the same paths as in the remote one.
+///
+/// [`ScopedFs`]: super::ScopedFs
+pub struct CachingFs<R, C> {
+    remote: R,
+    cache: C,
+    policy: CachePolicy,
+    lru: Mutex<Lru>,
+}
+
+impl<R: Fs, C: Fs> CachingFs<R, C> {
+    pub fn new(remote: R, cache: C, policy: CachePolicy) -> Self {
+        Self {
+            remote,
+            cache,
+            policy,
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+
+    pub fn remote(&self) -> &R {
+        &self.remote
+    }
+
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    async fn invalidate(&self, path: &Path) -> Result<(), Error> {
+        let cached = self.lru.lock().unwrap().remove(path);
+        if cached {
+            ignore_not_found(self.cache.remove(path).await)?;
+        }
+        Ok(())
+    }
+
+    async fn fetch(&self, meta: FileMeta) -> Result<(), Error> {
+        let mut remote = self
+            .remote
+            .open_options(&meta.path, OpenOptions::default())
+            .await?;
+        let (result, buf) = remote.read_to_end_at(vec![], 0).await;
+        result?;
+        self.store(meta, buf).await
+    }
+
+    async fn store(&self, meta: FileMeta, buf: Vec<u8>) -> Result<(), Error> {
+        let size = buf.len() as u64;
+
+        self.cache.create_dir_all(&parent(&meta.path)).await?;
+        let mut file = self
+            .cache
+            .open_options(
+                &meta.path,
+                OpenOptions::default().create(true).truncate(true),
+            )
+            .await?;
+        file.write_all(buf).await.0?;
+        file.close().await?;
+
+        let evicted = self
+            .lru
+            .lock()
+            .unwrap()
+            .insert(meta, size, self.policy.max_size);
+        for path in evicted {
+            ignore_not_found(self.cache.remove(&path).await)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Fs, C: Fs> Fs for CachingFs<R, C> {
+    type File = CachingFile<C::File, R::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        if options.write || options.create || options.truncate {
+            self.invalidate(path).await?;
+            return Ok(CachingFile::Remote(
+                self.remote.open_options(path, options).await?,
+            ));
+        }
+
+        if self.policy.conditional_get {
+            let etag = self
+                .lru
+                .lock()
+                .unwrap()
+                .touch(path)
+                .and_then(|meta| meta.etag.clone());
+            if let Some(etag) = etag {
+                if let Some((meta, buf)) = self.remote.read_if_none_match(path, &etag).await? {
+                    if meta.size > self.policy.max_size {
+                        self.invalidate(path).await?;
+                        return Ok(CachingFile::Remote(
+                            self.remote.open_options(path, options).await?,
+                        ));
+                    }
+                    self.store(meta, buf).await?;
+                }
+                return Ok(CachingFile::Cached(
+                    self.cache.open_options(path, options).await?,
+                ));
+            }
+        }
+
+        let meta = self.remote.metadata(path).await?;
+        if meta.size > self.policy.max_size {
+            return Ok(CachingFile::Remote(
+                self.remote.open_options(path, options).await?,
+            ));
+        }
+        let hit = self.lru.lock().unwrap().hit(&meta);
+        if !hit {
+            self.fetch(meta).await?;
+        }
+        Ok(CachingFile::Cached(
+            self.cache.open_options(path, options).await?,
+        ))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.remote.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.remote.list(path).await
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.remote.list_with(path, options).await
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.remote.list_page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.remote.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.remote.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.invalidate(path).await?;
+        self.remote.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        let cached: Vec<Path> = self
+            .lru
+            .lock()
+            .unwrap()
+            .entries
+            .keys()
+            .filter(|cached| cached.prefix_matches(path))
+            .cloned()
+            .collect();
+        for cached in cached {
+            self.invalidate(&cached).await?;
+        }
+        self.remote.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.remote.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.remote.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        Ok(CachingFile::Remote(self.remote.anon_temp_file(dir).await?))
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.remote.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.remote.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.remote.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.remote.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        if let Err(e) = self.invalidate(path).await {
+            return (Err(e), buf);
+        }
+        self.remote.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        if let Err(e) = self.invalidate(path).await {
+            return (Err(e), buf);
+        }
+        self.remote.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(to).await?;
+        self.remote.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.invalidate(to).await?;
+        self.remote
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.invalidate(to).await?;
+        self.remote.copy_range(from, range, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(from).await?;
+        self.invalidate(to).await?;
+        self.remote.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(from).await?;
+        self.invalidate(to).await?;
+        self.remote.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.invalidate(from).await?;
+        self.invalidate(to).await?;
+        self.remote.rename_with_meta(from, to).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.remote.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        // the remote may rewrite the file to set it, as S3 copies objects onto themselves
+        self.invalidate(path).await?;
+        self.remote.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.remote.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.remote.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.remote.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.remote.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.remote.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.remote.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        self.invalidate(path).await?;
+        self.remote.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.remote.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.remote.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.remote.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.remote.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.remote.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.remote.is_remote()
+    }
+}
+
+/// The file of a [`CachingFs`], either a cached copy or, when it is written to or too large to be
+/// cached, the remote file itself.
+pub enum CachingFile<C, R> {
+    Cached(C),
+    Remote(R),
+}
+
+impl<C: Read, R: Read> Read for CachingFile<C, R> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        match self {
+            CachingFile::Cached(file) => file.read_exact_at(buf, pos).await,
+            CachingFile::Remote(file) => file.read_exact_at(buf, pos).await,
+        }
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        match self {
+            CachingFile::Cached(file) => file.read_to_end_at(buf, pos).await,
+            CachingFile::Remote(file) => file.read_to_end_at(buf, pos).await,
+        }
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        match self {
+            CachingFile::Cached(file) => file.size().await,
+            CachingFile::Remote(file) => file.size().await,
+        }
+    }
+}
+
+impl<C: Write, R: Write> Write for CachingFile<C, R> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        match self {
+            CachingFile::Cached(file) => file.write_all(buf).await,
+            CachingFile::Remote(file) => file.write_all(buf).await,
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            CachingFile::Cached(file) => file.flush().await,
+            CachingFile::Remote(file) => file.flush().await,
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        match self {
+            CachingFile::Cached(file) => file.close().await,
+            CachingFile::Remote(file) => file.close().await,
+        }
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        match self {
+            CachingFile::Cached(file) => file.close_with_meta().await,
+            CachingFile::Remote(file) => file.close_with_meta().await,
+        }
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        match self {
+            CachingFile::Cached(file) => file.allocate(len).await,
+            CachingFile::Remote(file) => file.allocate(len).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    async fn write(path: &std::path::Path, content: &'static [u8]) {
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions},
+            path::Path,
+            Write,
+        };
+
+        let mut file = TokioFs
+            .open_options(
+                &Path::from_absolute_path(path).unwrap(),
+                OpenOptions::default().create(true).truncate(true),
+            )
+            .await
+            .unwrap();
+        file.write_all(content).await.0.unwrap();
+        file.close().await.unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn read<F: crate::fs::Fs>(fs: &F, path: &crate::path::Path) -> Vec<u8> {
+        use crate::Read;
+
+        let mut file = fs.open_options(path, Default::default()).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        buf
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn second_read_hits_cache() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{CachePolicy, CachingFs, FaultyFs, FsOperation, ScopedFs, Trigger},
+            path::Path,
+        };
+
+        let remote_dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        write(&remote_dir.path().join("a"), b"hello").await;
+
+        // any remote read after the first one fails, so a second successful read must be served
+        // from the cache
+        let remote = FaultyFs::new(ScopedFs::new(
+            TokioFs,
+            Path::from_filesystem_path(remote_dir.path()).unwrap(),
+        ))
+        .fail(FsOperation::Read, Trigger::After(1), io::ErrorKind::Other);
+        let cache = ScopedFs::new(
+            TokioFs,
+            Path::from_filesystem_path(cache_dir.path()).unwrap(),
+        );
+        let fs = CachingFs::new(remote, cache, CachePolicy::default());
+
+        assert_eq!(read(&fs, &Path::from("a")).await, b"hello");
+        assert!(cache_dir.path().join("a").exists());
+        assert_eq!(read(&fs, &Path::from("a")).await, b"hello");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn evict_least_recently_used() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{CachePolicy, CachingFs, ScopedFs},
+            path::Path,
+        };
+
+        let remote_dir = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        write(&remote_dir.path().join("a"), b"aaaaa").await;
+        write(&remote_dir.path().join("b"), b"bbbbb").await;
+
+        let fs = CachingFs::new(
+            ScopedFs::new(
+                TokioFs,
+                Path::from_filesystem_path(remote_dir.path()).unwrap(),
+            ),
+            ScopedFs::new(
+                TokioFs,
+                Path::from_filesystem_path(cache_dir.path()).unwrap(),
+            ),
+            CachePolicy {
+                max_size: 8,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(read(&fs, &Path::from("a")).await, b"aaaaa");
+        assert_eq!(read(&fs, &Path::from("b")).await, b"bbbbb");
+        assert!(!cache_dir.path().join("a").exists());
+        assert!(cache_dir.path().join("b").exists());
+    }
+
+    #[cfg(all(feature = "aws", feature = "tokio"))]
+    #[tokio::test]
+    async fn revalidate_with_conditional_get() {
+        use http::{Method, StatusCode};
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{CachePolicy, CachingFs, ScopedFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        mock.put_object("a", "hello");
+
+        let cache_dir = TempDir::new().unwrap();
+        let fs = CachingFs::new(
+            mock.fs(),
+            ScopedFs::new(
+                TokioFs,
+                Path::from_filesystem_path(cache_dir.path()).unwrap(),
+            ),
+            CachePolicy {
+                conditional_get: true,
+                ..Default::default()
+            },
+        );
+
+        // the download reads from the start on, a range S3 answers with a partial content
+        assert_eq!(read(&fs, &Path::from("a")).await, b"hello");
+        assert_eq!(
+            mock.requests(Method::GET),
+            vec![StatusCode::PARTIAL_CONTENT]
+        );
+
+        assert_eq!(read(&fs, &Path::from("a")).await, b"hello");
+        assert_eq!(
+            mock.requests(Method::GET).last(),
+            Some(&StatusCode::NOT_MODIFIED)
+        );
+
+        mock.put_object("a", "world");
+        assert_eq!(read(&fs, &Path::from("a")).await, b"world");
+        assert_eq!(mock.requests(Method::GET).last(), Some(&StatusCode::OK));
+        assert_eq!(std::fs::read(cache_dir.path().join("a")).unwrap(), b"world");
+    }
+}
@@ -0,0 +1,535 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ffi::OsStr,
+    future::Future,
+    ops::Range,
+    path::PathBuf,
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use async_stream::stream;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::{
+    future::{select, Either},
+    StreamExt,
+};
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs, ListOptions,
+        OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+#[derive(Default)]
+struct Waiters {
+    next_key: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    waiters: Mutex<Waiters>,
+}
+
+/// A token to cancel the operations of a [`CancellableFs`], shared by its clones.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operations in flight and the ones started afterwards.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        let wakers = std::mem::take(&mut self.inner.waiters.lock().unwrap().wakers);
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Completes once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+
+    /// Runs `future` unless the token is cancelled first, in which case `future` is dropped,
+    /// aborting what it was doing.
+    async fn run<T>(&self, future: impl Future<Output = Result<T, Error>>) -> Result<T, Error> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        match select(pin!(future), self.cancelled()).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(Error::Cancelled),
+        }
+    }
+
+    /// Yields the items of `stream` until the token is cancelled, then [`Error::Cancelled`].
+    fn run_stream<T, S>(&self, stream: S) -> impl Stream<Item = Result<T, Error>>
+    where
+        S: Stream<Item = Result<T, Error>>,
+    {
+        // the stream owns its clone of the token, borrowing only what `stream` does
+        let token = self.clone();
+        stream! {
+            let mut stream = pin!(stream);
+            loop {
+                match token.run(async { Ok(stream.next().await) }).await {
+                    Ok(Some(item)) => yield item,
+                    Ok(None) => break,
+                    Err(e) => {
+                        yield Err(e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The future returned by [`CancellationToken::cancelled`].
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let mut waiters = this.token.inner.waiters.lock().unwrap();
+        // checked again with the lock held, `cancel` takes it after marking the token
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let key = *this.key.get_or_insert_with(|| {
+            waiters.next_key += 1;
+            waiters.next_key
+        });
+        waiters.wakers.insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.token.inner.waiters.lock().unwrap().wakers.remove(&key);
+        }
+    }
+}
+
+/// A [`Fs`] whose operations, and those of the files it opens, fail with [`Error::Cancelled`]
+/// once its [`CancellationToken`] is cancelled. Operations in flight are aborted by dropping
+/// them, e.g. closing the connection of an HTTP request, rather than waiting for them to end.
+///
+/// Operations taking a buffer work on a copy of it, so that it can be returned when they are
+/// cancelled.
+pub struct CancellableFs<F> {
+    fs: F,
+    token: CancellationToken,
+}
+
+impl<F: Fs> CancellableFs<F> {
+    pub fn new(fs: F, token: CancellationToken) -> Self {
+        Self { fs, token }
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+}
+
+impl<F: Fs> Fs for CancellableFs<F> {
+    type File = CancellableFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        let file = self.token.run(self.fs.open_options(path, options)).await?;
+        Ok(CancellableFile {
+            file,
+            token: self.token.clone(),
+        })
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        let file = self.token.run(self.fs.open_with_len(path, len)).await?;
+        Ok(CancellableFile {
+            file,
+            token: self.token.clone(),
+        })
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.create_dir_all(path)).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        let stream = self.token.run(self.fs.list(path)).await?;
+        Ok(self.token.run_stream(stream))
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        let stream = self.token.run(self.fs.list_with(path, options)).await?;
+        Ok(self.token.run_stream(stream))
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.token
+            .run(self.fs.list_page(path, cursor, page_size))
+            .await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.token.run(self.fs.list_delimited(path)).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.token.run(self.fs.list_versions(path)).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.remove(path)).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.remove_dir_all(path)).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.token.run(self.fs.metadata(path)).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.token.run(self.fs.file_type(path)).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        let file = self.token.run(self.fs.anon_temp_file(dir)).await?;
+        Ok(CancellableFile {
+            file,
+            token: self.token.clone(),
+        })
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.token.run(self.fs.dir_stats(path)).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.token.run(self.fs.read_if_none_match(path, etag)).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.token.run(self.fs.read_head(path, n)).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.token.run(self.fs.read_tail(path, n)).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        let copy = buf.as_slice().to_vec();
+        let result = self
+            .token
+            .run(async { self.fs.write_file(path, copy).await.0 })
+            .await;
+        (result, buf)
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        let copy = buf.as_slice().to_vec();
+        let result = self
+            .token
+            .run(async { self.fs.write_file_with_checksum(path, copy).await.0 })
+            .await;
+        (result, buf)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.copy(from, to)).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.token
+            .run(self.fs.copy_if_source_matches(from, to, expected_etag))
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.copy_range(from, range, to)).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.rename(from, to)).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.token.run(self.fs.rename_durable(from, to)).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.token.run(self.fs.rename_with_meta(from, to)).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.token.run(self.fs.get_xattr(path, name)).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.token.run(self.fs.set_xattr(path, name, value)).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.token.run(self.fs.list_xattr(path)).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.token.run(self.fs.lock_shared(path)).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.token.run(self.fs.lock_exclusive(path)).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.token.run(self.fs.mmap(path)).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.token.run(self.fs.localize(path)).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        let stream = self.token.run(self.fs.get_stream(path)).await?;
+        Ok(self.token.run_stream(stream))
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        let stream = self.token.run_stream(stream);
+        self.token
+            .run(self.fs.put_stream(path, stream, size_hint))
+            .await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.token.run(self.fs.checksum(path, algorithm)).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.token.run(self.fs.list_digest(path)).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+/// The file of a [`CancellableFs`].
+pub struct CancellableFile<F> {
+    file: F,
+    token: CancellationToken,
+}
+
+impl<F: Read> Read for CancellableFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let len = buf.as_slice().len();
+        let file = &mut self.file;
+        let result = self
+            .token
+            .run(async {
+                let (result, read) = file.read_exact_at(vec![0; len], pos).await;
+                result.map(|_| read)
+            })
+            .await;
+        match result {
+            Ok(read) => {
+                buf.as_slice_mut().copy_from_slice(&read);
+                (Ok(()), buf)
+            }
+            Err(e) => (Err(e), buf),
+        }
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let file = &mut self.file;
+        let result = self
+            .token
+            .run(async {
+                let (result, read) = file.read_to_end_at(vec![], pos).await;
+                result.map(|_| read)
+            })
+            .await;
+        match result {
+            Ok(read) => (Ok(()), read),
+            Err(e) => (Err(e), buf),
+        }
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        self.token.run(self.file.size()).await
+    }
+}
+
+impl<F: Write> Write for CancellableFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        let copy = buf.as_slice().to_vec();
+        let file = &mut self.file;
+        let result = self.token.run(async { file.write_all(copy).await.0 }).await;
+        (result, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.token.run(self.file.flush()).await
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.token.run(self.file.close()).await
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        self.token.run(self.file.close_with_meta()).await
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        self.token.run(self.file.allocate(len)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn cancel_aborts_read_in_flight() {
+        use std::time::{Duration, Instant};
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{CancellableFs, CancellationToken, Delay, DelayedFs, Fs, FsOperation},
+            path::Path,
+            Error, Read,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        TokioFs
+            .write_file(&path, b"hello".to_vec())
+            .await
+            .0
+            .unwrap();
+
+        let token = CancellationToken::new();
+        let fs = CancellableFs::new(
+            DelayedFs::new(TokioFs).delay(FsOperation::Read, Delay::Fixed(Duration::from_secs(60))),
+            token.clone(),
+        );
+        let mut file = fs.open(&path).await.unwrap();
+
+        tokio::spawn({
+            let token = token.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                token.cancel();
+            }
+        });
+        let start = Instant::now();
+        let (result, buf) = file.read_to_end_at(vec![1, 2], 0).await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(buf, [1, 2]);
+        assert!(start.elapsed() < Duration::from_secs(60));
+
+        assert!(matches!(fs.metadata(&path).await, Err(Error::Cancelled)));
+    }
+}
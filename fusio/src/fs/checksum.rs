@@ -0,0 +1,154 @@
+use ring::digest::{self, Context};
+
+use crate::{
+    fs::{FileMeta, Fs, OpenOptions},
+    path::Path,
+    Error, Read,
+};
+
+/// Files are hashed in blocks of this size rather than read in whole.
+const BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// A hash function [`Fs::checksum`] can compute the digest of a file with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    fn algorithm(&self) -> &'static digest::Algorithm {
+        match self {
+            ChecksumAlgorithm::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            ChecksumAlgorithm::Sha256 => &digest::SHA256,
+        }
+    }
+}
+
+/// Returns the SHA-256 digest of `bytes`.
+pub(crate) fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut digest = [0; 32];
+    digest.copy_from_slice(digest::digest(&digest::SHA256, bytes).as_ref());
+    digest
+}
+
+/// Computes the digest of the file at `path` by reading it block by block.
+pub(crate) async fn digest_file<F: Fs + ?Sized>(
+    fs: &F,
+    path: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> Result<Vec<u8>, Error> {
+    let mut file = fs.open_options(path, OpenOptions::default()).await?;
+    let size = file.size().await?;
+
+    let mut context = Context::new(algorithm.algorithm());
+    let mut buf = vec![];
+    let mut pos = 0;
+    while pos < size {
+        buf.resize(BLOCK_SIZE.min(size - pos) as usize, 0);
+        let (result, read) = file.read_exact_at(buf, pos).await;
+        result?;
+        context.update(&read);
+        pos += read.len() as u64;
+        buf = read;
+    }
+    Ok(context.finish().as_ref().to_vec())
+}
+
+/// Computes the digest [`Fs::list_digest`] returns for the `entries` listed under `path`, the
+/// SHA-256 of their relative paths, sizes and ETags. The entries are sorted by path first, so that
+/// the digest doesn't depend on the order a backend lists them in.
+pub(crate) fn listing_digest(path: &Path, entries: Vec<FileMeta>) -> Result<[u8; 32], Error> {
+    let mut entries = entries
+        .into_iter()
+        .map(|meta| {
+            let key = meta
+                .path
+                .prefix_match(path)
+                .map(Path::from_iter)
+                .ok_or_else(|| {
+                    Error::Other(
+                        format!("listed \"{}\" is not under \"{}\"", meta.path, path).into(),
+                    )
+                })?;
+            Ok((key.as_ref().to_string(), meta.size, meta.etag))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    entries.sort();
+
+    let mut context = Context::new(&digest::SHA256);
+    for (key, size, etag) in &entries {
+        // the lengths delimit the fields, so that different listings can't hash the same bytes
+        let etag = etag.as_deref().unwrap_or_default();
+        context.update(&(key.len() as u64).to_le_bytes());
+        context.update(key.as_bytes());
+        context.update(&size.to_le_bytes());
+        context.update(&(etag.len() as u64).to_le_bytes());
+        context.update(etag.as_bytes());
+    }
+    let mut digest = [0; 32];
+    digest.copy_from_slice(context.finish().as_ref());
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn listing_digests_ignore_the_order_of_entries() {
+        use super::listing_digest;
+        use crate::{fs::FileMeta, path::Path};
+
+        let meta = |dir: &str, name: &str, size| FileMeta {
+            path: Path::from(dir).child(name),
+            size,
+            etag: None,
+            content_encoding: None,
+            storage_class: None,
+            restore_status: None,
+            local_version: None,
+        };
+        let root = Path::from("root");
+        let entries = vec![meta("root", "a", 1), meta("root", "b", 2)];
+        let digest = listing_digest(&root, entries.clone()).unwrap();
+
+        let reversed = entries.iter().rev().cloned().collect();
+        assert_eq!(listing_digest(&root, reversed).unwrap(), digest);
+        let resized = vec![meta("root", "a", 1), meta("root", "b", 3)];
+        assert_ne!(listing_digest(&root, resized).unwrap(), digest);
+        // the paths are relative to the listed one
+        let moved = vec![meta("moved", "a", 1), meta("moved", "b", 2)];
+        assert_eq!(listing_digest(&Path::from("moved"), moved).unwrap(), digest);
+        assert!(listing_digest(&Path::from("other"), entries).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn sha256_of_local_file() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{ChecksumAlgorithm, Fs},
+            path::Path,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("hello");
+        let fs = TokioFs;
+        fs.write_file(&path, b"hello world".to_vec())
+            .await
+            .0
+            .unwrap();
+
+        let digest = fs.checksum(&path, ChecksumAlgorithm::Sha256).await.unwrap();
+        assert_eq!(
+            digest
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+}
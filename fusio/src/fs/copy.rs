@@ -0,0 +1,83 @@
+use crate::{
+    fs::{Fs, OpenOptions},
+    path::Path,
+    Error, MaybeSend, Read, Write,
+};
+
+/// Copies go through memory in blocks of this size, the progress is reported once per block.
+const BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Called with the number of bytes copied so far by [`Fs::copy_with_progress`] and
+/// [`copy_between`].
+pub trait CopyProgress: FnMut(u64) + MaybeSend {}
+
+impl<P> CopyProgress for P where P: FnMut(u64) + MaybeSend {}
+
+/// Copies the file at `from` in `from_fs` to `to` in `to_fs`, replacing `to` if it exists, e.g.
+/// from a local disk to S3. The content is streamed block by block, `progress` is called with
+/// the number of bytes copied so far after each one, ending with the size of the file.
+pub async fn copy_between<F, T>(
+    from_fs: &F,
+    from: &Path,
+    to_fs: &T,
+    to: &Path,
+    mut progress: impl CopyProgress,
+) -> Result<(), Error>
+where
+    F: Fs + ?Sized,
+    T: Fs + ?Sized,
+{
+    let mut source = from_fs.open_options(from, OpenOptions::default()).await?;
+    let size = source.size().await?;
+    let mut dest = to_fs
+        .open_options(to, OpenOptions::default().create(true).truncate(true))
+        .await?;
+
+    let mut buf = vec![];
+    let mut copied = 0;
+    while copied < size {
+        buf.resize(BLOCK_SIZE.min(size - copied) as usize, 0);
+        let (result, read) = source.read_exact_at(buf, copied).await;
+        result?;
+        let (result, written) = dest.write_all(read).await;
+        result?;
+        copied += written.len() as u64;
+        buf = written;
+        progress(copied);
+    }
+    dest.close().await
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn progress_counts_up_to_size() {
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, Read};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let (from, to) = (dir.child("from"), dir.child("to"));
+        let content = (0..5 * 1024 * 1024 / 2)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        let fs = TokioFs;
+        fs.write_file(&from, content.clone()).await.0.unwrap();
+
+        let mut reported = vec![];
+        fs.copy_with_progress(&from, &to, |copied| reported.push(copied))
+            .await
+            .unwrap();
+        assert_eq!(
+            reported,
+            [1024 * 1024, 2 * 1024 * 1024, 5 * 1024 * 1024 / 2]
+        );
+
+        let mut file = fs.open(&to).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, content);
+    }
+}
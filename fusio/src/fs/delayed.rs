@@ -0,0 +1,428 @@
+use std::{
+    borrow::Cow, collections::HashMap, ffi::OsStr, ops::Range, path::PathBuf, sync::Arc,
+    time::Duration,
+};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        faulty::SplitMix64, sleep, DelimitedListing, DirStats, FileLock, FileMeta, FileType,
+        FileVersion, Fs, FsOperation, ListOptions, OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+/// How long a delayed operation waits before it runs.
+#[derive(Debug, Clone, Copy)]
+pub enum Delay {
+    Fixed(Duration),
+    /// A delay drawn uniformly from `[min, max)`.
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+}
+
+struct Delays {
+    delays: HashMap<FsOperation, Delay>,
+    rng: SplitMix64,
+}
+
+impl Delays {
+    async fn wait(&self, operation: FsOperation) {
+        let delay = match self.delays.get(&operation) {
+            Some(Delay::Fixed(delay)) => *delay,
+            Some(Delay::Uniform { min, max }) => {
+                *min + max.saturating_sub(*min).mul_f64(self.rng.next_f64())
+            }
+            None => return,
+        };
+        sleep(delay).await;
+    }
+}
+
+/// A [`Fs`] that adds artificial latency to the operations of the underlying file system and of
+/// the files it opens, waiting on the timer of the async runtime, so that timeouts and
+/// backpressure can be tested.
+pub struct DelayedFs<F> {
+    fs: F,
+    delays: Arc<Delays>,
+}
+
+impl<F: Fs> DelayedFs<F> {
+    pub fn new(fs: F) -> Self {
+        Self {
+            fs,
+            delays: Arc::new(Delays {
+                delays: HashMap::new(),
+                rng: SplitMix64::new(0),
+            }),
+        }
+    }
+
+    /// Delays every call of `operation` by `delay`.
+    pub fn delay(mut self, operation: FsOperation, delay: Delay) -> Self {
+        self.delays_mut().delays.insert(operation, delay);
+        self
+    }
+
+    /// Seeds the generator [`Delay::Uniform`] draws from, so that the delays are reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.delays_mut().rng = SplitMix64::new(seed);
+        self
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    fn delays_mut(&mut self) -> &mut Delays {
+        Arc::get_mut(&mut self.delays).expect("delays are configured before opening files")
+    }
+}
+
+impl<F: Fs> Fs for DelayedFs<F> {
+    type File = DelayedFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        self.delays.wait(FsOperation::Open).await;
+        Ok(DelayedFile {
+            file: self.fs.open_options(path, options).await?,
+            delays: self.delays.clone(),
+        })
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        self.delays.wait(FsOperation::Open).await;
+        Ok(DelayedFile {
+            file: self.fs.open_with_len(path, len).await?,
+            delays: self.delays.clone(),
+        })
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::CreateDirAll).await;
+        self.fs.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.list(path).await
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.list_with(path, options).await
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.list_page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Remove).await;
+        self.fs.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Remove).await;
+        self.fs.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.delays.wait(FsOperation::Metadata).await;
+        self.fs.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.delays.wait(FsOperation::Metadata).await;
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        self.delays.wait(FsOperation::Open).await;
+        Ok(DelayedFile {
+            file: self.fs.anon_temp_file(dir).await?,
+            delays: self.delays.clone(),
+        })
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.delays.wait(FsOperation::Read).await;
+        self.fs.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.delays.wait(FsOperation::Read).await;
+        self.fs.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.delays.wait(FsOperation::Read).await;
+        self.fs.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        self.delays.wait(FsOperation::Write).await;
+        self.fs.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        self.delays.wait(FsOperation::Write).await;
+        self.fs.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Copy).await;
+        self.fs.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Copy).await;
+        self.fs
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Copy).await;
+        self.fs.copy_range(from, range, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Rename).await;
+        self.fs.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Rename).await;
+        self.fs.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.delays.wait(FsOperation::Rename).await;
+        self.fs.rename_with_meta(from, to).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.delays.wait(FsOperation::Metadata).await;
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Write).await;
+        self.fs.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.delays.wait(FsOperation::Metadata).await;
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.delays.wait(FsOperation::Open).await;
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.delays.wait(FsOperation::Open).await;
+        self.fs.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.delays.wait(FsOperation::Open).await;
+        self.fs.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.delays.wait(FsOperation::Open).await;
+        self.fs.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.delays.wait(FsOperation::Read).await;
+        self.fs.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        self.delays.wait(FsOperation::Write).await;
+        self.fs.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.delays.wait(FsOperation::Read).await;
+        self.fs.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.delays.wait(FsOperation::List).await;
+        self.fs.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+/// The file of a [`DelayedFs`], delaying [`FsOperation::Read`] and [`FsOperation::Write`].
+pub struct DelayedFile<F> {
+    file: F,
+    delays: Arc<Delays>,
+}
+
+impl<F: Read> Read for DelayedFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        self.delays.wait(FsOperation::Read).await;
+        self.file.read_exact_at(buf, pos).await
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        self.delays.wait(FsOperation::Read).await;
+        self.file.read_to_end_at(buf, pos).await
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        self.file.size().await
+    }
+}
+
+impl<F: Write> Write for DelayedFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        self.delays.wait(FsOperation::Write).await;
+        self.file.write_all(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.file.close().await
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        self.file.close_with_meta().await
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        self.delays.wait(FsOperation::Write).await;
+        self.file.allocate(len).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn delayed_read() {
+        use std::time::{Duration, Instant};
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Delay, DelayedFs, Fs, FsOperation, OpenOptions},
+            path::Path,
+            Read, Write,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+
+        let fs = DelayedFs::new(TokioFs)
+            .delay(FsOperation::Read, Delay::Fixed(Duration::from_millis(100)));
+        let mut file = fs
+            .open_options(&path, OpenOptions::default().create(true))
+            .await
+            .unwrap();
+        file.write_all(&b"hello"[..]).await.0.unwrap();
+        file.close().await.unwrap();
+
+        let mut file = fs.open(&path).await.unwrap();
+        let start = Instant::now();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(buf, b"hello");
+    }
+}
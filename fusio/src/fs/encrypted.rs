@@ -0,0 +1,719 @@
+use std::{borrow::Cow, ffi::OsStr, io};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hkdf::{Salt, HKDF_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs, ListOptions,
+        OpenOptions,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+/// How many bytes of the content are encrypted together. Each block is decrypted on its own, so
+/// that a ranged read only reads and decrypts the blocks it covers.
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// The length of the authentication tag following each block.
+const TAG_LEN: u64 = 16;
+
+/// The length of the random salt the key of a file is derived with, stored at its start.
+const HEADER_LEN: u64 = 32;
+
+/// The context the keys of the files are derived in, so that they differ from keys derived from
+/// the same key for other purposes.
+const KEY_INFO: &[u8] = b"fusio EncryptedFs file key";
+
+/// A [`Fs`] encrypting the content of the files of the underlying file system at rest with
+/// AES-256-GCM, so that the backend, e.g. S3, only ever stores ciphertext. Files are encrypted
+/// with `key` as they are written and decrypted as they are read. Paths and metadata other than
+/// sizes are stored as they are.
+///
+/// A file is stored as a random 32-byte salt followed by its content in blocks of 64 KiB. Each
+/// file is encrypted with its own key, derived from `key` and its salt with HKDF-SHA256, so that
+/// the nonces, the indexes of the blocks, are never reused under a key however many files are
+/// written. Each block is followed by its tag. The last block is marked as such, so that a file cut
+/// short at a block boundary fails to decrypt rather than reading shorter. Reads at any position
+/// only read and decrypt the blocks they cover, so that e.g. the footer of a columnar file read
+/// with [`Fs::read_tail`] only takes a ranged request of its last blocks from S3.
+///
+/// Files are written from scratch: opening a file with content for writing without
+/// [`OpenOptions::truncate`] fails with [`Error::Unsupported`], as appending to it would mean
+/// decrypting and rewriting its last block. A file opened for writing can't be read, and its
+/// last block is only written when it is closed. The sizes listed, returned in metadata and added
+/// up by [`Fs::dir_stats`] are those of the plaintext.
+pub struct EncryptedFs<F> {
+    fs: F,
+    key: [u8; 32],
+}
+
+impl<F: Fs> EncryptedFs<F> {
+    pub fn new(fs: F, key: [u8; 32]) -> Self {
+        Self { fs, key }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    async fn writer(&self, mut file: F::File) -> Result<EncryptedFile<F::File>, Error> {
+        let salt = random_salt()?;
+        let (result, _) = file.write_all(salt.to_vec()).await;
+        result?;
+        Ok(EncryptedFile {
+            file,
+            key: file_key(&self.key, &salt),
+            stored_len: HEADER_LEN,
+            size: 0,
+            pending: Some(Vec::new()),
+        })
+    }
+
+    async fn reader(&self, mut file: F::File) -> Result<EncryptedFile<F::File>, Error> {
+        let stored_len = file.size().await?;
+        let size = plaintext_len(stored_len)?;
+        let (result, header) = file.read_exact_at(vec![0; HEADER_LEN as usize], 0).await;
+        result?;
+        Ok(EncryptedFile {
+            file,
+            key: file_key(&self.key, &header),
+            stored_len,
+            size,
+            pending: None,
+        })
+    }
+}
+
+/// The size of the plaintext of a file stored with `len` bytes.
+fn plaintext_len(len: u64) -> Result<u64, Error> {
+    if len < HEADER_LEN + TAG_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the file is too short to have been encrypted by `EncryptedFs`",
+        )
+        .into());
+    }
+    let blocks = (len - HEADER_LEN).div_ceil(BLOCK_SIZE + TAG_LEN);
+    Ok(len - HEADER_LEN - blocks * TAG_LEN)
+}
+
+/// The size a plaintext of `len` bytes is stored with, an empty one still having a block.
+fn stored_len(len: u64) -> u64 {
+    HEADER_LEN + len + len.div_ceil(BLOCK_SIZE).max(1) * TAG_LEN
+}
+
+fn plaintext_meta(mut meta: FileMeta) -> Result<FileMeta, Error> {
+    meta.size = plaintext_len(meta.size)?;
+    Ok(meta)
+}
+
+fn random_salt() -> Result<[u8; HEADER_LEN as usize], Error> {
+    let mut salt = [0; HEADER_LEN as usize];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| io::Error::other("no randomness for the salt of a file"))?;
+    Ok(salt)
+}
+
+/// The key of the file with the given salt.
+fn file_key(key: &[u8; 32], salt: &[u8]) -> LessSafeKey {
+    let prk = Salt::new(HKDF_SHA256, salt).extract(key);
+    let key = prk
+        .expand(&[KEY_INFO], &AES_256_GCM)
+        .expect("HKDF-SHA256 expands to keys of 32 bytes");
+    LessSafeKey::new(UnboundKey::from(key))
+}
+
+/// The nonce of the block `index`, unique as each file has its own key.
+fn nonce(index: u64) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[NONCE_LEN - 8..].copy_from_slice(&index.to_be_bytes());
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Encrypts the block `index` of a file, appending its tag.
+fn seal_block(
+    key: &LessSafeKey,
+    index: u64,
+    plaintext: &[u8],
+    last: bool,
+) -> Result<Vec<u8>, Error> {
+    let mut block = Vec::with_capacity(plaintext.len() + TAG_LEN as usize);
+    block.extend_from_slice(plaintext);
+    key.seal_in_place_append_tag(nonce(index), Aad::from([last as u8]), &mut block)
+        .map_err(|_| io::Error::other("failed to encrypt a block"))?;
+    Ok(block)
+}
+
+/// Decrypts the block `index` of a file in place, returning its plaintext.
+fn open_block<'a>(
+    key: &LessSafeKey,
+    index: u64,
+    block: &'a mut [u8],
+    last: bool,
+) -> Result<&'a mut [u8], Error> {
+    key.open_in_place(nonce(index), Aad::from([last as u8]), block)
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "block {} of the file failed to decrypt, it was encrypted with another key or \
+                     is corrupted",
+                    index
+                ),
+            )
+            .into()
+        })
+}
+
+/// Encrypts a whole file.
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let salt = random_salt()?;
+    let key = file_key(key, &salt);
+    let mut stored = Vec::with_capacity(stored_len(plaintext.len() as u64) as usize);
+    stored.extend_from_slice(&salt);
+    let blocks = plaintext.len().div_ceil(BLOCK_SIZE as usize).max(1);
+    for index in 0..blocks {
+        let start = index * BLOCK_SIZE as usize;
+        let end = (start + BLOCK_SIZE as usize).min(plaintext.len());
+        let block = seal_block(
+            &key,
+            index as u64,
+            &plaintext[start..end],
+            index + 1 == blocks,
+        )?;
+        stored.extend_from_slice(&block);
+    }
+    Ok(stored)
+}
+
+/// Decrypts a whole file.
+fn decrypt(key: &[u8; 32], mut stored: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let len = plaintext_len(stored.len() as u64)?;
+    let key = file_key(key, &stored[..HEADER_LEN as usize]);
+    let mut plaintext = Vec::with_capacity(len as usize);
+    let blocks = stored[HEADER_LEN as usize..].chunks_mut((BLOCK_SIZE + TAG_LEN) as usize);
+    let count = blocks.len();
+    for (index, block) in blocks.enumerate() {
+        let block = open_block(&key, index as u64, block, index + 1 == count)?;
+        plaintext.extend_from_slice(block);
+    }
+    Ok(plaintext)
+}
+
+impl<F: Fs> Fs for EncryptedFs<F> {
+    type File = EncryptedFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        let file = self.fs.open_options(path, options).await?;
+        if !options.write {
+            return self.reader(file).await;
+        }
+        if !options.truncate && file.size().await? > 0 {
+            return Err(Error::Unsupported {
+                message: format!(
+                    "can't write to \"{}\" without truncating it, its content is encrypted",
+                    path
+                ),
+            });
+        }
+        self.writer(file).await
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        let file = self.fs.open_with_len(path, stored_len(len)).await?;
+        self.reader(file).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        Ok(self
+            .fs
+            .list(path)
+            .await?
+            .map(|meta| meta.and_then(plaintext_meta)))
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        Ok(self
+            .fs
+            .list_with(path, options)
+            .await?
+            .map(|meta| meta.and_then(plaintext_meta)))
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        let (entries, cursor) = self.fs.list_page(path, cursor, page_size).await?;
+        let entries = entries
+            .into_iter()
+            .map(plaintext_meta)
+            .collect::<Result<_, _>>()?;
+        Ok((entries, cursor))
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        let mut listing = self.fs.list_delimited(path).await?;
+        listing.files = listing
+            .files
+            .into_iter()
+            .map(plaintext_meta)
+            .collect::<Result<_, _>>()?;
+        Ok(listing)
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        let mut versions = self.fs.list_versions(path).await?;
+        for version in versions
+            .iter_mut()
+            .filter(|version| !version.is_delete_marker)
+        {
+            version.meta.size = plaintext_len(version.meta.size)?;
+        }
+        Ok(versions)
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        plaintext_meta(self.fs.metadata(path).await?)
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        let file = self.fs.anon_temp_file(dir).await?;
+        self.writer(file).await
+    }
+
+    /// Walks the tree with [`Fs::list_delimited`] to add up the sizes of the plaintexts, as those
+    /// of the backend include the salts and the tags. S3 takes a listing per directory.
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        let mut stats = DirStats::default();
+        let mut dirs = vec![path.clone()];
+        while let Some(dir) = dirs.pop() {
+            let listing = self.list_delimited(&dir).await?;
+            stats.entry_count += listing.files.len() as u64;
+            stats.total_size += listing.files.iter().map(|meta| meta.size).sum::<u64>();
+            dirs.extend(listing.prefixes);
+        }
+        Ok(stats)
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        match self.fs.read_if_none_match(path, etag).await? {
+            Some((mut meta, stored)) => {
+                let plaintext = decrypt(&self.key, stored)?;
+                meta.size = plaintext.len() as u64;
+                Ok(Some((meta, plaintext)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        let stored = match encrypt(&self.key, buf.as_slice()) {
+            Ok(stored) => stored,
+            Err(e) => return (Err(e), buf),
+        };
+        let (result, _) = self.fs.write_file(path, stored).await;
+        (result.and_then(plaintext_meta), buf)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.fs
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        plaintext_meta(self.fs.rename_with_meta(from, to).await?)
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.fs.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_exclusive(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+/// The file of an [`EncryptedFs`], decrypting the blocks it reads, or encrypting the blocks it
+/// writes as they fill up.
+pub struct EncryptedFile<F> {
+    file: F,
+    key: LessSafeKey,
+    /// The size of the file as stored, or written so far.
+    stored_len: u64,
+    /// The size of the plaintext, or of the blocks written so far.
+    size: u64,
+    /// The plaintext written and not yet encrypted, if the file is opened for writing and not
+    /// closed yet. It always holds the last block, which is only known once the file is closed.
+    pending: Option<Vec<u8>>,
+}
+
+impl<F: Read> EncryptedFile<F> {
+    async fn read_into(&mut self, out: &mut [u8], pos: u64) -> Result<(), Error> {
+        if self.pending.is_some() {
+            return Err(Error::Unsupported {
+                message: "can't read an encrypted file being written".into(),
+            });
+        }
+        if out.is_empty() {
+            return Ok(());
+        }
+        let end = pos + out.len() as u64;
+        if end > self.size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "read up to {} past the end of the file at {}",
+                    end, self.size
+                ),
+            )
+            .into());
+        }
+
+        let (first, last) = (pos / BLOCK_SIZE, (end - 1) / BLOCK_SIZE);
+        let start = HEADER_LEN + first * (BLOCK_SIZE + TAG_LEN);
+        let stop = (HEADER_LEN + (last + 1) * (BLOCK_SIZE + TAG_LEN)).min(self.stored_len);
+        let (result, mut blocks) = self
+            .file
+            .read_exact_at(vec![0; (stop - start) as usize], start)
+            .await;
+        result?;
+
+        let count = (self.stored_len - HEADER_LEN).div_ceil(BLOCK_SIZE + TAG_LEN);
+        let mut written = 0;
+        for (index, block) in (first..).zip(blocks.chunks_mut((BLOCK_SIZE + TAG_LEN) as usize)) {
+            let plaintext = open_block(&self.key, index, block, index + 1 == count)?;
+            let block_start = index * BLOCK_SIZE;
+            let from = (pos.max(block_start) - block_start) as usize;
+            let to = (end.min(block_start + plaintext.len() as u64) - block_start) as usize;
+            out[written..written + to - from].copy_from_slice(&plaintext[from..to]);
+            written += to - from;
+        }
+        Ok(())
+    }
+}
+
+impl<F: Read> Read for EncryptedFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let result = self.read_into(buf.as_slice_mut(), pos).await;
+        (result, buf)
+    }
+
+    async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let mut read = vec![0; self.size.saturating_sub(pos) as usize];
+        let result = self.read_into(&mut read, pos).await;
+        if result.is_ok() {
+            buf.extend_from_slice(&read);
+        }
+        (result, buf)
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        Ok(self.size
+            + self
+                .pending
+                .as_ref()
+                .map_or(0, |pending| pending.len() as u64))
+    }
+}
+
+impl<F: Write> EncryptedFile<F> {
+    /// Encrypts and writes the block at the start of the pending plaintext.
+    async fn write_block(&mut self, len: usize, last: bool) -> Result<(), Error> {
+        let pending = self.pending.as_mut().expect("the file is being written");
+        let index = self.size / BLOCK_SIZE;
+        let block = seal_block(&self.key, index, &pending[..len], last)?;
+        pending.drain(..len);
+        self.stored_len += block.len() as u64;
+        self.size += len as u64;
+        let (result, _) = self.file.write_all(block).await;
+        result
+    }
+
+    /// Writes the last block, once.
+    async fn finish(&mut self) -> Result<(), Error> {
+        if let Some(len) = self.pending.as_ref().map(Vec::len) {
+            self.write_block(len, true).await?;
+            self.pending = None;
+        }
+        Ok(())
+    }
+}
+
+impl<F: Write> Write for EncryptedFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        let Some(pending) = self.pending.as_mut() else {
+            return (
+                Err(Error::Unsupported {
+                    message: "can't write an encrypted file opened for reading or closed".into(),
+                }),
+                buf,
+            );
+        };
+        pending.extend_from_slice(buf.as_slice());
+        // the last block is kept until the file is closed
+        while self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.len() as u64 > BLOCK_SIZE)
+        {
+            if let Err(e) = self.write_block(BLOCK_SIZE as usize, false).await {
+                return (Err(e), buf);
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Flushes the blocks written, the last one is only written when the file is closed.
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.finish().await?;
+        self.file.close().await
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        self.finish().await?;
+        plaintext_meta(self.file.close_with_meta().await?)
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        self.file.allocate(stored_len(len)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn content_round_trips_and_is_stored_encrypted() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{EncryptedFs, Fs, OpenOptions},
+            path::Path,
+            Read, Write,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let fs = EncryptedFs::new(TokioFs, [7; 32]);
+        let content = (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        let path = dir.child("file");
+        let meta = fs.write_file(&path, content.clone()).await.0.unwrap();
+        assert_eq!(meta.size, content.len() as u64);
+        // a salt, and 4 blocks with their tags
+        let stored = std::fs::read(tmp_dir.path().join("file")).unwrap();
+        assert_eq!(stored.len(), 32 + content.len() + 4 * 16);
+        assert!(!stored.windows(64).any(|window| window == &content[..64]));
+        // the same content is encrypted with another key, derived from another salt
+        fs.write_file(&dir.child("copy"), content.clone())
+            .await
+            .0
+            .unwrap();
+        let copy = std::fs::read(tmp_dir.path().join("copy")).unwrap();
+        assert_ne!(copy[..32], stored[..32]);
+        assert_ne!(copy[32..96], stored[32..96]);
+        // nor does a block decrypt with the salt of another file
+        let mut spliced = copy[..32].to_vec();
+        spliced.extend_from_slice(&stored[32..]);
+        std::fs::write(tmp_dir.path().join("spliced"), spliced).unwrap();
+        let mut file = fs.open(&dir.child("spliced")).await.unwrap();
+        let (result, _) = file.read_exact_at(vec![0; 10], 0).await;
+        assert!(result.is_err());
+
+        let mut file = fs.open(&path).await.unwrap();
+        assert_eq!(file.size().await.unwrap(), content.len() as u64);
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, content);
+        // a range across the boundary of two blocks
+        let (result, buf) = file.read_exact_at(vec![0; 100], 65_500).await;
+        result.unwrap();
+        assert_eq!(buf, &content[65_500..65_600]);
+        assert_eq!(fs.metadata(&path).await.unwrap().size, content.len() as u64);
+
+        // written in pieces, including nothing at all
+        for (name, len) in [("streamed", content.len()), ("empty", 0)] {
+            let path = dir.child(name);
+            let mut file = fs
+                .open_options(&path, OpenOptions::default().create(true).truncate(true))
+                .await
+                .unwrap();
+            for piece in content[..len].chunks(30_000) {
+                file.write_all(piece.to_vec()).await.0.unwrap();
+            }
+            file.close().await.unwrap();
+            let mut file = fs.open(&path).await.unwrap();
+            let (result, buf) = file.read_to_end_at(vec![], 0).await;
+            result.unwrap();
+            assert_eq!(buf, &content[..len]);
+        }
+
+        // another key can't read it
+        let other = EncryptedFs::new(TokioFs, [8; 32]);
+        let mut file = other.open(&path).await.unwrap();
+        let (result, _) = file.read_exact_at(vec![0; 10], 0).await;
+        assert!(result.is_err());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn dir_stats_add_up_plaintext_sizes() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{DirStats, EncryptedFs, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        async fn check<F: Fs>(fs: &EncryptedFs<F>, root: &Path) {
+            fs.create_dir_all(&root.child("sub")).await.unwrap();
+            for (path, size) in [
+                (root.child("a"), 1),
+                (root.child("b"), 100_000),
+                (root.child("sub").child("c"), 0),
+            ] {
+                fs.write_file(&path, vec![0; size]).await.0.unwrap();
+            }
+            let expected = DirStats {
+                entry_count: 3,
+                total_size: 100_001,
+            };
+            assert_eq!(fs.dir_stats(root).await.unwrap(), expected);
+            assert_eq!(fs.total_size(root).await.unwrap(), 100_001);
+            // the backend adds up the salts and the tags too
+            assert_eq!(
+                fs.inner().total_size(root).await.unwrap(),
+                100_001 + 3 * 32 + 4 * 16
+            );
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let root = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        check(&EncryptedFs::new(TokioFs, [7; 32]), &root).await;
+        let mock = MockS3::new();
+        check(&EncryptedFs::new(mock.fs(), [7; 32]), &Path::from("dir")).await;
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn ranged_reads_only_fetch_the_blocks_they_cover() {
+        use crate::{
+            fs::{EncryptedFs, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Read,
+        };
+
+        const BLOCK: u64 = 64 * 1024 + 16;
+
+        let mock = MockS3::new();
+        let fs = EncryptedFs::new(mock.fs(), [7; 32]);
+        let content = (0..1024 * 1024u32)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let path = Path::from("table.parquet");
+        fs.write_file(&path, content.clone()).await.0.unwrap();
+
+        // the footer is in the last block
+        let served = mock.bytes_served();
+        let footer = fs.read_tail(&path, 100).await.unwrap();
+        assert_eq!(footer, &content[content.len() - 100..]);
+        // the salt, then the last block
+        assert_eq!(mock.bytes_served() - served, 32 + BLOCK);
+
+        // a range across two blocks
+        let served = mock.bytes_served();
+        let mut file = fs.open(&path).await.unwrap();
+        let (result, buf) = file.read_exact_at(vec![0; 100], 3 * 65_536 - 50).await;
+        result.unwrap();
+        assert_eq!(buf, &content[3 * 65_536 - 50..3 * 65_536 + 50]);
+        assert_eq!(mock.bytes_served() - served, 32 + 2 * BLOCK);
+    }
+}
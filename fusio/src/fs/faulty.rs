@@ -0,0 +1,546 @@
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    future::Future,
+    io,
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs, ListOptions,
+        OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, MaybeSend, Read, Write,
+};
+
+/// The operations of a [`Fs`] and of the files it opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsOperation {
+    Open,
+    CreateDirAll,
+    List,
+    Remove,
+    Metadata,
+    Copy,
+    Rename,
+    /// Positional reads of a file.
+    Read,
+    /// Writes to a file.
+    Write,
+}
+
+/// When an injected fault fires.
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger {
+    /// Fails only the `n`-th call, counting from 1.
+    Nth(usize),
+    /// Fails every call after the first `n` ones.
+    After(usize),
+    /// Fails each call with the given probability, drawn from a generator seeded with `seed` so
+    /// that the sequence of failures is reproducible.
+    Probability { probability: f64, seed: u64 },
+}
+
+/// A small deterministic pseudo random generator (SplitMix64), good enough for test tooling.
+pub(crate) struct SplitMix64 {
+    state: AtomicU64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    pub(crate) fn next_u64(&self) -> u64 {
+        const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+        let mut z = self
+            .state
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number uniformly distributed in `[0, 1)`.
+    pub(crate) fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+struct Fault {
+    operation: FsOperation,
+    trigger: Trigger,
+    kind: io::ErrorKind,
+    calls: AtomicUsize,
+    rng: SplitMix64,
+}
+
+fn inject(faults: &[Fault], operation: FsOperation, path: &Path) -> Result<(), Error> {
+    for fault in faults.iter().filter(|f| f.operation == operation) {
+        let call = fault.calls.fetch_add(1, Ordering::Relaxed) + 1;
+        let fire = match fault.trigger {
+            Trigger::Nth(n) => call == n,
+            Trigger::After(n) => call > n,
+            Trigger::Probability { probability, .. } => fault.rng.next_f64() < probability,
+        };
+        if fire {
+            return Err(io::Error::new(
+                fault.kind,
+                format!("injected fault on {:?} of \"{}\"", operation, path),
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// A [`Fs`] that fails configured operations of the underlying file system, or of the files it
+/// opens, on purpose so that error handling such as retries or recovery can be tested
+/// deterministically.
+pub struct FaultyFs<F> {
+    fs: F,
+    faults: Arc<Vec<Fault>>,
+}
+
+impl<F: Fs> FaultyFs<F> {
+    pub fn new(fs: F) -> Self {
+        Self {
+            fs,
+            faults: Arc::new(vec![]),
+        }
+    }
+
+    /// Fails `operation` with an error of `kind` whenever `trigger` fires.
+    pub fn fail(mut self, operation: FsOperation, trigger: Trigger, kind: io::ErrorKind) -> Self {
+        let seed = match trigger {
+            Trigger::Probability { seed, .. } => seed,
+            _ => 0,
+        };
+        Arc::get_mut(&mut self.faults)
+            .expect("faults are configured before opening files")
+            .push(Fault {
+                operation,
+                trigger,
+                kind,
+                calls: AtomicUsize::new(0),
+                rng: SplitMix64::new(seed),
+            });
+        self
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    fn inject(&self, operation: FsOperation, path: &Path) -> Result<(), Error> {
+        inject(&self.faults, operation, path)
+    }
+}
+
+impl<F: Fs> Fs for FaultyFs<F> {
+    type File = FaultyFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        self.inject(FsOperation::Open, path)?;
+        Ok(FaultyFile {
+            file: self.fs.open_options(path, options).await?,
+            path: path.clone(),
+            faults: self.faults.clone(),
+        })
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        self.inject(FsOperation::Open, path)?;
+        Ok(FaultyFile {
+            file: self.fs.open_with_len(path, len).await?,
+            path: path.clone(),
+            faults: self.faults.clone(),
+        })
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::CreateDirAll, path)?;
+        self.fs.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.list(path).await
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.list_with(path, options).await
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.list_page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::Remove, path)?;
+        self.fs.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::Remove, path)?;
+        self.fs.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.inject(FsOperation::Metadata, path)?;
+        self.fs.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.inject(FsOperation::Metadata, path)?;
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        self.inject(FsOperation::Open, dir)?;
+        Ok(FaultyFile {
+            file: self.fs.anon_temp_file(dir).await?,
+            path: dir.clone(),
+            faults: self.faults.clone(),
+        })
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.inject(FsOperation::Read, path)?;
+        self.fs.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.inject(FsOperation::Read, path)?;
+        self.fs.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.inject(FsOperation::Read, path)?;
+        self.fs.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        if let Err(e) = self.inject(FsOperation::Write, path) {
+            return (Err(e), buf);
+        }
+        self.fs.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        if let Err(e) = self.inject(FsOperation::Write, path) {
+            return (Err(e), buf);
+        }
+        self.fs.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::Copy, from)?;
+        self.fs.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.inject(FsOperation::Copy, from)?;
+        self.fs
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::Copy, from)?;
+        self.fs.copy_range(from, range, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::Rename, from)?;
+        self.fs.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.inject(FsOperation::Rename, from)?;
+        self.fs.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.inject(FsOperation::Rename, from)?;
+        self.fs.rename_with_meta(from, to).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.inject(FsOperation::Metadata, path)?;
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.inject(FsOperation::Write, path)?;
+        self.fs.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.inject(FsOperation::Metadata, path)?;
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.inject(FsOperation::Open, path)?;
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.inject(FsOperation::Open, path)?;
+        self.fs.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.inject(FsOperation::Open, path)?;
+        self.fs.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.inject(FsOperation::Open, path)?;
+        self.fs.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.inject(FsOperation::Read, path)?;
+        self.fs.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + MaybeSend,
+    {
+        self.inject(FsOperation::Write, path)?;
+        self.fs.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.inject(FsOperation::Read, path)?;
+        self.fs.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.inject(FsOperation::List, path)?;
+        self.fs.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+/// The file of a [`FaultyFs`], injecting faults into [`FsOperation::Read`] and
+/// [`FsOperation::Write`].
+pub struct FaultyFile<F> {
+    file: F,
+    path: Path,
+    faults: Arc<Vec<Fault>>,
+}
+
+impl<F: Read> Read for FaultyFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        if let Err(e) = inject(&self.faults, FsOperation::Read, &self.path) {
+            return (Err(e), buf);
+        }
+        self.file.read_exact_at(buf, pos).await
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        if let Err(e) = inject(&self.faults, FsOperation::Read, &self.path) {
+            return (Err(e), buf);
+        }
+        self.file.read_to_end_at(buf, pos).await
+    }
+
+    fn size(&self) -> impl Future<Output = Result<u64, Error>> + MaybeSend {
+        self.file.size()
+    }
+}
+
+impl<F: Write> Write for FaultyFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        if let Err(e) = inject(&self.faults, FsOperation::Write, &self.path) {
+            return (Err(e), buf);
+        }
+        self.file.write_all(buf).await
+    }
+
+    fn flush(&mut self) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        self.file.flush()
+    }
+
+    fn close(&mut self) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        self.file.close()
+    }
+
+    fn close_with_meta(&mut self) -> impl Future<Output = Result<FileMeta, Error>> + MaybeSend {
+        self.file.close_with_meta()
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        inject(&self.faults, FsOperation::Write, &self.path)?;
+        self.file.allocate(len).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn fail_nth_open() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FaultyFs, Fs, FsOperation, OpenOptions, Trigger},
+            path::Path,
+            Error,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+
+        let fs = FaultyFs::new(TokioFs).fail(
+            FsOperation::Open,
+            Trigger::Nth(3),
+            io::ErrorKind::NotFound,
+        );
+        fs.open_options(&path, OpenOptions::default().create(true))
+            .await
+            .unwrap();
+        fs.open(&path).await.unwrap();
+        match fs.open(&path).await {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            _ => panic!("the third open should fail"),
+        }
+        fs.open(&path).await.unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn fail_with_seeded_probability() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FaultyFs, Fs, FsOperation, Trigger},
+            path::Path,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+
+        let mut outcomes = vec![];
+        for _ in 0..2 {
+            let fs = FaultyFs::new(TokioFs).fail(
+                FsOperation::CreateDirAll,
+                Trigger::Probability {
+                    probability: 0.5,
+                    seed: 42,
+                },
+                io::ErrorKind::Interrupted,
+            );
+            let mut outcome = vec![];
+            for _ in 0..32 {
+                outcome.push(fs.create_dir_all(&path).await.is_ok());
+            }
+            outcomes.push(outcome);
+        }
+        assert_eq!(outcomes[0], outcomes[1]);
+        assert!(outcomes[0].contains(&true));
+        assert!(outcomes[0].contains(&false));
+    }
+}
@@ -0,0 +1,129 @@
+use std::{io::Write as _, path::PathBuf};
+
+use crate::{
+    fs::{temp_name, Fs, OpenOptions},
+    path::Path,
+    Error, Read,
+};
+
+/// Downloads go through memory in blocks of this size.
+const BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Keeps the local file returned by [`Fs::localize`] for as long as it is held. A file
+/// downloaded into the temporary directory is removed when the guard is dropped, a file that
+/// was local already is left as is.
+#[must_use = "the downloaded file is removed when dropped"]
+#[derive(Debug)]
+pub struct TempGuard {
+    path: Option<PathBuf>,
+}
+
+impl TempGuard {
+    /// A guard of a file that was local already, removing nothing.
+    #[cfg(all(
+        feature = "fs",
+        any(
+            feature = "tokio",
+            feature = "monoio",
+            all(feature = "tokio-uring", target_os = "linux")
+        )
+    ))]
+    pub(crate) fn none() -> Self {
+        Self { path: None }
+    }
+
+    /// Whether the local file is a temporary copy, removed when the guard is dropped.
+    pub fn is_temporary(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+impl Drop for TempGuard {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Downloads the file at `path` of `fs` block by block into a new file of the temporary
+/// directory, named with the extension of `path` for tools telling formats by their extension.
+pub(crate) async fn download<F>(fs: &F, path: &Path) -> Result<(PathBuf, TempGuard), Error>
+where
+    F: Fs + ?Sized,
+{
+    let mut source = fs.open_options(path, OpenOptions::default()).await?;
+    let size = source.size().await?;
+
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}", temp_name("fusio-"), extension),
+        None => temp_name("fusio-"),
+    };
+    let local = std::env::temp_dir().join(name);
+    let mut dest = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&local)?;
+    // removes the partial download if it fails
+    let guard = TempGuard {
+        path: Some(local.clone()),
+    };
+
+    let mut buf = vec![];
+    let mut copied = 0;
+    while copied < size {
+        buf.resize(BLOCK_SIZE.min(size - copied) as usize, 0);
+        let (result, read) = source.read_exact_at(buf, copied).await;
+        result?;
+        dest.write_all(&read)?;
+        copied += read.len() as u64;
+        buf = read;
+    }
+    dest.sync_all()?;
+    Ok((local, guard))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn objects_are_downloaded_and_local_files_are_not() {
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let mock = MockS3::new();
+        let content = (0..3 * 1024 * 1024 / 2)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        mock.put_object("dir/data.parquet", content.clone());
+
+        let (local, guard) = mock
+            .fs()
+            .localize(&Path::from("dir/data.parquet"))
+            .await
+            .unwrap();
+        assert!(guard.is_temporary());
+        assert_eq!(local.extension().unwrap(), "parquet");
+        assert_eq!(std::fs::read(&local).unwrap(), content);
+        drop(guard);
+        assert!(!local.exists());
+
+        assert!(mock.fs().localize(&Path::from("missing")).await.is_err());
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        TokioFs
+            .write_file(&path, b"hello".to_vec())
+            .await
+            .0
+            .unwrap();
+        let (local, guard) = TokioFs.localize(&path).await.unwrap();
+        assert!(!guard.is_temporary());
+        assert_eq!(local, tmp_dir.path().join("file").canonicalize().unwrap());
+        drop(guard);
+        assert_eq!(std::fs::read(&local).unwrap(), b"hello");
+    }
+}
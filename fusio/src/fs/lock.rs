@@ -0,0 +1,112 @@
+use std::fs::File;
+
+use crate::{path::Path, Error};
+
+/// How a [`FileLock`] is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Held by any number of holders at once, as long as none holds it exclusively.
+    Shared,
+    /// Held by a single holder.
+    Exclusive,
+}
+
+/// An advisory lock on a local file, taken with [`Fs::lock_shared`] or [`Fs::lock_exclusive`].
+/// It only coordinates the processes that take locks on the file, the file itself can still be
+/// read and written by anyone. The lock is released by [`FileLock::unlock`] or when it is dropped.
+///
+/// [`Fs::lock_shared`]: super::Fs::lock_shared
+/// [`Fs::lock_exclusive`]: super::Fs::lock_exclusive
+#[must_use = "the lock is released when dropped"]
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    mode: LockMode,
+}
+
+impl FileLock {
+    /// Wraps a handle on which the lock is held, the lock being released when it is closed.
+    #[cfg(all(
+        unix,
+        feature = "fs",
+        any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+    ))]
+    pub(crate) fn new(file: File, mode: LockMode) -> Self {
+        Self { file, mode }
+    }
+
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+
+    /// Releases the lock, reporting the errors dropping it ignores.
+    pub fn unlock(self) -> Result<(), Error> {
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+
+            if unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) } < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn locks_unsupported(path: &Path) -> Error {
+    Error::Unsupported {
+        message: format!("can't lock \"{}\" in this file system", path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(unix, feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn exclusive_locks_exclude_other_handles() {
+        use std::time::Duration;
+
+        use tempfile::TempDir;
+        use tokio::time::timeout;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, LockMode},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Error,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let fs = TokioFs;
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("lock");
+        fs.write_file(&path, vec![]).await.0.unwrap();
+        let wait = Duration::from_millis(100);
+
+        let exclusive = fs.lock_exclusive(&path).await.unwrap();
+        assert_eq!(exclusive.mode(), LockMode::Exclusive);
+        assert!(timeout(wait, fs.lock_exclusive(&path)).await.is_err());
+        assert!(timeout(wait, fs.lock_shared(&path)).await.is_err());
+        exclusive.unlock().unwrap();
+
+        // shared locks only exclude exclusive ones
+        let shared = fs.lock_shared(&path).await.unwrap();
+        let other = fs.lock_shared(&path).await.unwrap();
+        assert!(timeout(wait, fs.lock_exclusive(&path)).await.is_err());
+        drop(shared);
+        drop(other);
+        let exclusive = timeout(Duration::from_secs(5), fs.lock_exclusive(&path))
+            .await
+            .unwrap()
+            .unwrap();
+        drop(exclusive);
+
+        assert!(fs.lock_exclusive(&path.child("missing")).await.is_err());
+        assert!(matches!(
+            MockS3::new().fs().lock_exclusive(&Path::from("lock")).await,
+            Err(Error::Unsupported { .. })
+        ));
+    }
+}
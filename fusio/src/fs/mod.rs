@@ -1,19 +1,291 @@
+mod archive;
+#[cfg(feature = "bytes")]
+mod body;
+mod caching;
+mod cancel;
+#[cfg(feature = "checksum")]
+mod checksum;
+mod copy;
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+mod delayed;
+#[cfg(feature = "encryption")]
+mod encrypted;
+mod faulty;
+mod localize;
+mod lock;
 mod options;
+mod overlay;
+mod pooled;
+mod presign;
+mod range_cache;
+mod read_only;
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+mod retry_list;
+mod scoped;
+mod temp;
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+mod watch;
 
-use std::future::Future;
+use std::{
+    borrow::Cow, ffi::OsStr, future::Future, io, ops::Range, path::PathBuf, pin::pin, sync::Arc,
+};
 
+pub use archive::{archive, extract, ArchiveFormat};
+use async_stream::stream;
+#[cfg(feature = "bytes")]
+pub use body::STREAM_CHUNK_SIZE;
+#[cfg(feature = "bytes")]
+use body::{read_chunks, write_chunks};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+pub use caching::{CachePolicy, CachingFile, CachingFs};
+pub use cancel::{CancellableFile, CancellableFs, CancellationToken, Cancelled};
+#[cfg(feature = "checksum")]
+pub use checksum::ChecksumAlgorithm;
+#[cfg(feature = "checksum")]
+pub(crate) use checksum::{digest_file, listing_digest, sha256};
+pub use copy::{copy_between, CopyProgress};
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+pub use delayed::{Delay, DelayedFile, DelayedFs};
+#[cfg(feature = "encryption")]
+pub use encrypted::{EncryptedFile, EncryptedFs};
+pub use faulty::{FaultyFs, FsOperation, Trigger};
 use futures_core::Stream;
+use futures_util::{future::Either, StreamExt};
+use localize::download;
+pub use localize::TempGuard;
+use lock::locks_unsupported;
+pub use lock::{FileLock, LockMode};
+#[cfg(feature = "mmap")]
+pub use memmap2::Mmap;
 pub use options::*;
+pub use overlay::{OverlayFile, OverlayFs};
+pub use pooled::{PooledFile, PooledFs};
+pub(crate) use presign::presignable;
+pub use presign::PresignedFs;
+pub use range_cache::{RangeCachingFile, RangeCachingFs};
+pub use read_only::{ReadOnlyFile, ReadOnlyFs};
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+pub use retry_list::RetryingListFs;
+pub use scoped::ScopedFs;
+pub(crate) use temp::temp_name;
+#[cfg(all(feature = "tokio", feature = "fs", target_os = "linux"))]
+pub(crate) use watch::poll_watch;
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+pub use watch::WatchEvent;
 
-use crate::{path::Path, Error, MaybeSend, MaybeSync, Read, Write};
+use crate::{io::SharedReader, path::Path, Error, IoBuf, MaybeSend, MaybeSync, Read, Write};
 
-#[derive(Debug)]
+/// The metadata of a file. Backends fill in what they know, more fields may be added: build one
+/// with [`FileMeta::new`] outside of fusio.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct FileMeta {
     pub path: Path,
     pub size: u64,
+    /// An identifier of the content of the file, if the backend provides one (e.g. the `ETag` of
+    /// an object in S3).
+    pub etag: Option<String>,
+    /// The `Content-Encoding` the file was declared with, if the backend records one. The content
+    /// is stored as written, consumers are expected to decode it.
+    pub content_encoding: Option<String>,
+    /// The storage class of the file, if the backend reports one other than its default, e.g.
+    /// `GLACIER` for an object of S3 that is archived and has to be restored to be read.
+    pub storage_class: Option<String>,
+    /// How the restoration of an archived file is going, `None` if none was requested.
+    pub restore_status: Option<RestoreStatus>,
+    /// A hash of the modification time, size and inode of a local file, which has no `ETag` to
+    /// tell its versions apart. `None` for other backends.
+    pub local_version: Option<u64>,
+}
+
+impl FileMeta {
+    /// The metadata of the file at `path` of `size` bytes, with nothing else known of it.
+    pub fn new(path: Path, size: u64) -> Self {
+        Self {
+            path,
+            size,
+            etag: None,
+            content_encoding: None,
+            storage_class: None,
+            restore_status: None,
+            local_version: None,
+        }
+    }
+
+    /// Sets the identifier of the content of the file, see [`FileMeta::etag`].
+    pub fn with_etag(mut self, etag: Option<String>) -> Self {
+        self.etag = etag;
+        self
+    }
+
+    /// Returns a token that changes whenever the content of the file does: the `ETag` of an
+    /// object in S3, or for a local file a hash of its modification time, size and inode. `None`
+    /// if the backend provides neither.
+    pub fn version_token(&self) -> Option<String> {
+        self.etag.clone().or_else(|| {
+            self.local_version
+                .map(|version| format!("{:016x}", version))
+        })
+    }
+}
+
+/// The restoration status of an archived object, as reported by the `x-amz-restore` header of S3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreStatus {
+    /// The object is being restored and can't be read yet.
+    InProgress,
+    /// A temporary copy of the object can be read until `expiry_date`, an HTTP date.
+    Restored { expiry_date: Option<String> },
+}
+
+/// The sizes of the files under a directory, added up by [`Fs::dir_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirStats {
+    /// The number of files under the directory, at any depth.
+    pub entry_count: u64,
+    /// The sum of their sizes.
+    pub total_size: u64,
+}
+
+/// What a path points to, as told by [`Fs::file_type`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    /// A directory, or for object stores a prefix other objects are stored under.
+    Dir,
+}
+
+/// The immediate children of a directory, as listed by [`Fs::list_delimited`]: its files, and the
+/// subdirectories, or for object stores the prefixes, holding the deeper ones. Both are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DelimitedListing {
+    pub files: Vec<FileMeta>,
+    /// The full paths of the subdirectories, like those of the files.
+    pub prefixes: Vec<Path>,
+}
+
+/// A version of a file kept by a versioned store, as listed by [`Fs::list_versions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVersion {
+    /// The file as of this version, empty for a delete marker.
+    pub meta: FileMeta,
+    pub version_id: String,
+    /// Whether this is the current version of the file.
+    pub is_latest: bool,
+    /// Whether this version records the removal of the file rather than a content, the file
+    /// being missing while it is the latest.
+    pub is_delete_marker: bool,
+}
+
+/// Returns the path of the directory containing `path`.
+fn parent(path: &Path) -> Path {
+    let mut parts = path.parts().collect::<Vec<_>>();
+    parts.pop();
+    Path::from_iter(parts)
+}
+
+/// Checks that the byte `range` to copy with [`Fs::copy_range`] is within the `size` bytes of the
+/// file at `path`.
+pub(crate) fn check_copy_range(path: &Path, range: &Range<u64>, size: u64) -> Result<(), Error> {
+    if range.start > range.end || range.end > size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "can't copy the bytes {}..{} of \"{}\", {} bytes long",
+                range.start, range.end, path, size
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn xattrs_unsupported(path: &Path) -> Error {
+    Error::Unsupported {
+        message: format!(
+            "can't access the extended attributes of \"{}\" in this file system",
+            path
+        ),
+    }
+}
+
+/// Shapes the paths of `stream`, listed under `path`, and ends it after errors as `options` asks,
+/// see [`Fs::list_with`].
+pub(crate) fn shape_listing(
+    stream: impl Stream<Item = Result<FileMeta, Error>>,
+    path: Path,
+    options: ListOptions,
+) -> impl Stream<Item = Result<FileMeta, Error>> {
+    stream! {
+        let mut stream = pin!(stream);
+        while let Some(meta) = stream.next().await {
+            let meta = meta.and_then(|mut meta| {
+                if options.relative {
+                    let relative = match options.case_insensitive {
+                        true => relative_ignoring_case(&meta.path, &path),
+                        false => meta.path.prefix_match(&path).map(Path::from_iter),
+                    };
+                    meta.path = relative.ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("listed \"{}\" is not under \"{}\"", meta.path, path),
+                            )
+                        })?;
+                }
+                Ok(meta)
+            });
+            let failed = meta.is_err();
+            yield meta;
+            if failed && !options.continue_on_error {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns `path` relative to `prefix`, their parts compared regardless of case.
+fn relative_ignoring_case(path: &Path, prefix: &Path) -> Option<Path> {
+    let mut parts = path.parts();
+    for expected in prefix.parts() {
+        if parts.next()?.as_ref().to_lowercase() != expected.as_ref().to_lowercase() {
+            return None;
+        }
+    }
+    Some(Path::from_iter(parts))
+}
+
+/// Returns the directories next to `path` whose names differ from its own by case alone, itself
+/// included if it exists, for [`ListOptions::case_insensitive`].
+pub(crate) async fn case_variants<F: Fs + ?Sized>(fs: &F, path: &Path) -> Result<Vec<Path>, Error> {
+    let Some(name) = path.filename() else {
+        return Ok(vec![path.clone()]);
+    };
+    let name = name.to_lowercase();
+    let listing = fs.list_delimited(&parent(path)).await?;
+    Ok(listing
+        .prefixes
+        .into_iter()
+        .filter(|prefix| prefix.filename().is_some_and(|n| n.to_lowercase() == name))
+        .collect())
+}
+
+/// Waits for `duration` on the timer of the async runtime.
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+pub(crate) async fn sleep(duration: std::time::Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+    #[cfg(all(feature = "monoio", not(feature = "tokio")))]
+    monoio::time::sleep(duration).await;
+}
+
+fn is_not_found(err: &Error) -> bool {
+    matches!(err, Error::Io(e) if e.kind() == io::ErrorKind::NotFound)
 }
 
 pub trait Fs: MaybeSend + MaybeSync {
+    /// The files of the file system, read and written through [`Read`] and [`Write`] alone, so
+    /// that code generic over `Fs` needs nothing of the backend.
     type File: Read + Write + MaybeSend + 'static;
 
     fn open(&self, path: &Path) -> impl Future<Output = Result<Self::File, Error>> {
@@ -26,12 +298,1855 @@ pub trait Fs: MaybeSend + MaybeSync {
         options: OpenOptions,
     ) -> impl Future<Output = Result<Self::File, Error>> + MaybeSend;
 
-    fn create_dir_all(path: &Path) -> impl Future<Output = Result<(), Error>> + MaybeSend;
+    /// Opens the file at `path` to be read by many tasks through a single handle rather than one
+    /// each, e.g. a hot file read concurrently, sharing the `Arc`.
+    fn open_shared(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<Arc<SharedReader<Self::File>>, Error>> + MaybeSend {
+        async move {
+            let file = self.open_options(path, OpenOptions::default()).await?;
+            Ok(Arc::new(SharedReader::new(file)))
+        }
+    }
+
+    /// Opens the file at `path` for reading, its size already known to be `len`, e.g. from a
+    /// listing. Object stores then needn't ask for it before ranged reads, and reads past `len`
+    /// fail without a request. The default opens it as [`Fs::open`] does.
+    fn open_with_len(
+        &self,
+        path: &Path,
+        len: u64,
+    ) -> impl Future<Output = Result<Self::File, Error>> + MaybeSend {
+        let _ = len;
+        self.open_options(path, OpenOptions::default())
+    }
+
+    /// Creates the directory at `path` along with its missing parents. The default does nothing,
+    /// as suits object stores and other flat namespaces where files are written under any path,
+    /// backends with directories override it.
+    fn create_dir_all(&self, _path: &Path) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        async move { Ok(()) }
+    }
 
+    /// Lists the files under `path`. An entry that can't be listed is yielded as an error, and
+    /// the listing goes on with the others where the file system can, see
+    /// [`ListOptions::continue_on_error`]. The directory markers of object stores are skipped,
+    /// see [`ListOptions::markers`].
     fn list(
         &self,
         path: &Path,
     ) -> impl Future<Output = Result<impl Stream<Item = Result<FileMeta, Error>>, Error>> + MaybeSend;
 
+    /// Lists the files under `path` like [`Fs::list`], as `options` asks. The default lists
+    /// them with [`Fs::list`], so yields no directory markers: backends that have them override
+    /// it. With [`ListOptions::case_insensitive`], it lists each of the directories whose paths
+    /// differ from `path` by case alone one after the other.
+    fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<FileMeta, Error>>, Error>> + MaybeSend
+    {
+        async move {
+            if !options.case_insensitive {
+                let stream = self.list(path).await?;
+                return Ok(Either::Left(shape_listing(stream, path.clone(), options)));
+            }
+            let dirs = case_variants(self, path).await?;
+            let stream = stream! {
+                for dir in dirs {
+                    match self.list(&dir).await {
+                        Ok(listing) => {
+                            let mut listing = pin!(listing);
+                            while let Some(meta) = listing.next().await {
+                                yield meta;
+                            }
+                        }
+                        Err(e) => yield Err(e),
+                    }
+                }
+            };
+            Ok(Either::Right(shape_listing(stream, path.clone(), options)))
+        }
+    }
+
+    /// Lists a page of at most `page_size` files under `path`, in the order of their names,
+    /// along with an opaque cursor to pass back to get the next page, `None` after the last one.
+    /// The first page is listed without a cursor. Cursors stay valid as files are added or
+    /// removed, e.g. to paginate a listing in a web UI.
+    fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> impl Future<Output = Result<(Vec<FileMeta>, Option<String>), Error>> + MaybeSend {
+        let _ = (cursor, page_size);
+        async move {
+            Err(Error::Unsupported {
+                message: format!("can't list \"{}\" by pages in this file system", path),
+            })
+        }
+    }
+
+    /// Lists the immediate children of `path`, the files right under it and the subdirectories
+    /// holding the others, like a listing of S3 with the `/` delimiter, so that code browsing a
+    /// tree treats every backend alike. Backends that can't list a single level return
+    /// [`Error::Unsupported`].
+    fn list_delimited(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<DelimitedListing, Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't list the children of \"{}\" in this file system",
+                    path
+                ),
+            })
+        }
+    }
+
+    /// Lists every version of the files under `path` kept by a versioned store, the removed
+    /// files included, sorted by path and from the latest version of each on, e.g. to inspect
+    /// or restore prior contents. Backends that keep no versions return [`Error::Unsupported`].
+    fn list_versions(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<Vec<FileVersion>, Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't list the versions of the files under \"{}\" in this file system",
+                    path
+                ),
+            })
+        }
+    }
+
     fn remove(&self, path: &Path) -> impl Future<Output = Result<(), Error>> + MaybeSend;
+
+    /// Removes the file at `path` like [`Fs::remove`], succeeding when there is none, for cleanups
+    /// that may run more than once. Returns whether there was a file to remove, sparing a check
+    /// beforehand. S3 deletes objects whether they exist or not without telling which, it always
+    /// returns `true`.
+    fn remove_if_exists(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<bool, Error>> + MaybeSend {
+        async move {
+            match self.remove(path).await {
+                Ok(()) => Ok(true),
+                Err(e) if is_not_found(&e) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// Removes the directory at `path` with everything under it. Local file systems remove the
+    /// files of large trees on several threads. An entry that can't be removed doesn't stop the
+    /// removal of the others, the error tells how many were left and the first one that failed.
+    fn remove_dir_all(&self, path: &Path) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't remove the directory \"{}\" in this file system",
+                    path
+                ),
+            })
+        }
+    }
+
+    /// Returns the metadata of the file at `path`. The default opens the file for its size,
+    /// backends that store more, or can tell it without opening the file, override it.
+    fn metadata(&self, path: &Path) -> impl Future<Output = Result<FileMeta, Error>> + MaybeSend {
+        async move {
+            let file = self.open_options(path, OpenOptions::default()).await?;
+            Ok(FileMeta {
+                path: path.clone(),
+                size: file.size().await?,
+                etag: None,
+                content_encoding: None,
+                storage_class: None,
+                restore_status: None,
+                local_version: None,
+            })
+        }
+    }
+
+    /// Tells whether `path` is a file or a directory, `None` if there is nothing at `path`.
+    /// Object stores have no directories, a path with no object at it but objects under it is
+    /// reported as one. The default only knows of files, backends with directories override it.
+    fn file_type(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<Option<FileType>, Error>> + MaybeSend {
+        async move {
+            match self.metadata(path).await {
+                Ok(_) => Ok(Some(FileType::File)),
+                Err(e) if is_not_found(&e) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// Counts the files under the directory at `path`, at any depth, and adds up their sizes.
+    /// The whole tree is walked or listed, which is as costly as it is large.
+    fn dir_stats(&self, path: &Path) -> impl Future<Output = Result<DirStats, Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't compute the stats of \"{}\" in this file system",
+                    path
+                ),
+            })
+        }
+    }
+
+    /// Adds up the sizes of the files under `prefix`, at any depth, e.g. to check a quota: the
+    /// [`DirStats::total_size`] of [`Fs::dir_stats`]. S3 adds up the sizes of the listing, without
+    /// a request per object, local backends walk the tree.
+    fn total_size(&self, prefix: &Path) -> impl Future<Output = Result<u64, Error>> + MaybeSend {
+        async move { Ok(self.dir_stats(prefix).await?.total_size) }
+    }
+
+    /// Whether there is a file or a directory at `path`.
+    fn exists(&self, path: &Path) -> impl Future<Output = Result<bool, Error>> + MaybeSend {
+        async move { Ok(self.file_type(path).await?.is_some()) }
+    }
+
+    /// Whether `path` is a directory, `false` if there is nothing at `path`.
+    fn is_dir(&self, path: &Path) -> impl Future<Output = Result<bool, Error>> + MaybeSend {
+        async move { Ok(self.file_type(path).await? == Some(FileType::Dir)) }
+    }
+
+    /// Whether `path` is a file, `false` if there is nothing at `path`.
+    fn is_file(&self, path: &Path) -> impl Future<Output = Result<bool, Error>> + MaybeSend {
+        async move { Ok(self.file_type(path).await? == Some(FileType::File)) }
+    }
+
+    /// Reads the whole file at `path` unless its ETag still is `etag`, in which case `None` is
+    /// returned. Backends supporting conditional requests answer it in a single round trip.
+    fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> impl Future<Output = Result<Option<(FileMeta, Vec<u8>)>, Error>> + MaybeSend {
+        async move {
+            let mut meta = self.metadata(path).await?;
+            if meta.etag.as_deref() == Some(etag) {
+                return Ok(None);
+            }
+            let mut file = self.open_options(path, OpenOptions::default()).await?;
+            let (result, buf) = file.read_to_end_at(vec![], 0).await;
+            result?;
+            meta.size = buf.len() as u64;
+            Ok(Some((meta, buf)))
+        }
+    }
+
+    /// Reads the first `n` bytes of the file at `path`, or all of it if it's shorter, e.g. to
+    /// sniff its format from its magic bytes. Local files are read with a single positioned read,
+    /// S3 objects with a single ranged request.
+    fn read_head(
+        &self,
+        path: &Path,
+        n: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + MaybeSend {
+        async move {
+            let mut file = self.open_options(path, OpenOptions::default()).await?;
+            let len = file.size().await?.min(n as u64);
+            let (result, buf) = file.read_exact_at(vec![0; len as usize], 0).await;
+            result?;
+            Ok(buf)
+        }
+    }
+
+    /// Reads the last `n` bytes of the file at `path`, or all of it if it's shorter, e.g. the
+    /// footer of a columnar file. Local files are read with a single positioned read from the end,
+    /// S3 objects with a single request of a suffix range.
+    fn read_tail(
+        &self,
+        path: &Path,
+        n: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + MaybeSend {
+        async move {
+            let mut file = self.open_options(path, OpenOptions::default()).await?;
+            let size = file.size().await?;
+            let pos = size.saturating_sub(n as u64);
+            let (result, buf) = file
+                .read_exact_at(vec![0; (size - pos) as usize], pos)
+                .await;
+            result?;
+            Ok(buf)
+        }
+    }
+
+    /// Replaces the content of the file at `path` with `buf` in one go, returning the metadata of
+    /// the written file. Remote backends upload it with a single request.
+    fn write_file<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> impl Future<Output = (Result<FileMeta, Error>, B)> + MaybeSend {
+        async move {
+            let mut file = match self
+                .open_options(path, OpenOptions::default().create(true).truncate(true))
+                .await
+            {
+                Ok(file) => file,
+                Err(e) => return (Err(e), buf),
+            };
+            let (result, buf) = file.write_all(buf).await;
+            if let Err(e) = result {
+                return (Err(e), buf);
+            }
+            if let Err(e) = file.close().await {
+                return (Err(e), buf);
+            }
+            (self.metadata(path).await, buf)
+        }
+    }
+
+    /// Replaces the content of the file at `path` with `buf` like [`Fs::write_file`], returning
+    /// the SHA-256 digest of the content along with the metadata of the written file, so that
+    /// callers can record its integrity without reading it back. The digest is computed in the
+    /// single pass over `buf` that writing it takes: S3 sends it as the `x-amz-checksum-sha256`
+    /// of the `PUT`, which signs the payload with it rather than hashing it again and has S3
+    /// verify it.
+    #[cfg(feature = "checksum")]
+    fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> impl Future<Output = (Result<(FileMeta, [u8; 32]), Error>, B)> + MaybeSend {
+        async move {
+            let digest = sha256(buf.as_slice());
+            let (result, buf) = self.write_file(path, buf).await;
+            (result.map(|meta| (meta, digest)), buf)
+        }
+    }
+
+    /// Creates a new, empty file under `dir` for writing, named `prefix` followed by a random
+    /// suffix, and returns its path along with the opened file. Concurrent calls get distinct
+    /// paths. Local backends create it exclusively (`O_EXCL`), retrying with another name on
+    /// clashes, object stores pick a random key.
+    fn temp_file(
+        &self,
+        dir: &Path,
+        prefix: &str,
+    ) -> impl Future<Output = Result<(Path, Self::File), Error>> + MaybeSend {
+        async move {
+            let path = dir.child(temp_name(prefix));
+            let file = self
+                .open_options(&path, OpenOptions::default().create(true).truncate(true))
+                .await?;
+            Ok((path, file))
+        }
+    }
+
+    /// Opens a new file in the directory `dir` for reading and writing, which has no name: it is
+    /// never listed and disappears once closed, e.g. to spool intermediate data. Local backends
+    /// open it with `O_TMPFILE` on Linux, elsewhere they create a temporary file and remove it
+    /// right away. Object stores can't store unnamed objects.
+    fn anon_temp_file(
+        &self,
+        dir: &Path,
+    ) -> impl Future<Output = Result<Self::File, Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't open an unnamed file in \"{}\" in this file system",
+                    dir
+                ),
+            })
+        }
+    }
+
+    /// Replaces the content of the file at `path` with `buf` like [`Fs::write_file`], so that
+    /// readers see either the old content or the new one in full. The content is written to a
+    /// [temporary file](Fs::temp_file) next to `path` which is then renamed over it, backends
+    /// whose writes already are atomic (e.g. S3 `PUT`s) write it directly.
+    fn write_file_atomic<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> impl Future<Output = (Result<FileMeta, Error>, B)> + MaybeSend {
+        async move {
+            let prefix = format!(".{}.", path.filename().unwrap_or_default());
+            let (temp, mut file) = match self.temp_file(&parent(path), &prefix).await {
+                Ok(temp) => temp,
+                Err(e) => return (Err(e), buf),
+            };
+            let (result, buf) = file.write_all(buf).await;
+            let result = match result {
+                Ok(()) => file.close().await,
+                Err(e) => Err(e),
+            };
+            drop(file);
+            let result = match result {
+                Ok(()) => self.rename_durable(&temp, path).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let _ = self.remove(&temp).await;
+                return (Err(e), buf);
+            }
+            (self.metadata(path).await, buf)
+        }
+    }
+
+    /// Writes the file at `path` with `write`, which is handed the opened file and returns it once
+    /// done, so that readers see either the old content or the new one in full like
+    /// [`Fs::write_file_atomic`], without holding the content in memory. The file is a temporary
+    /// one next to `path`, closed and renamed over it once `write` succeeds, removed if it fails.
+    /// Backends whose writes already are atomic (e.g. S3 uploads, completed on close) write `path`
+    /// directly.
+    fn publish<W, Fut>(
+        &self,
+        path: &Path,
+        write: W,
+    ) -> impl Future<Output = Result<FileMeta, Error>> + MaybeSend
+    where
+        W: FnOnce(Self::File) -> Fut + MaybeSend,
+        Fut: Future<Output = Result<Self::File, Error>> + MaybeSend,
+    {
+        async move {
+            let prefix = format!(".{}.", path.filename().unwrap_or_default());
+            let (temp, file) = self.temp_file(&parent(path), &prefix).await?;
+            let result = match write(file).await {
+                Ok(mut file) => file.close().await,
+                Err(e) => Err(e),
+            };
+            let result = match result {
+                Ok(()) => self.rename_durable(&temp, path).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let _ = self.remove(&temp).await;
+                return Err(e);
+            }
+            self.metadata(path).await
+        }
+    }
+
+    /// Copies the file at `from` to `to`, replacing `to` if it exists. Backends copy server side
+    /// where they can, keeping the metadata of the source. A file copied onto itself is left as
+    /// is.
+    fn copy(&self, from: &Path, to: &Path) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        async move {
+            if from == to {
+                return self.metadata(from).await.map(|_| ());
+            }
+            let mut file = self.open_options(from, OpenOptions::default()).await?;
+            let (result, buf) = file.read_to_end_at(vec![], 0).await;
+            result?;
+            self.write_file(to, buf).await.0?;
+            Ok(())
+        }
+    }
+
+    /// Copies the file at `from` to `to` like [`Fs::copy`], only if the ETag of `from` still is
+    /// `expected_etag`, so that a file changed since it was looked at isn't archived or migrated
+    /// in place of what was meant. S3 checks it in the copy itself, failing with
+    /// [`S3Error::PreconditionFailed`](crate::remotes::aws::S3Error::PreconditionFailed).
+    /// Backends without ETags return [`Error::Unsupported`].
+    fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        let _ = (to, expected_etag);
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't copy \"{}\" on the condition of its ETag in this file system",
+                    from
+                ),
+            })
+        }
+    }
+
+    /// Copies the file at `from` to `to` like [`Fs::copy`], streaming it through memory block by
+    /// block to call `progress` with the number of bytes copied so far after each block, e.g. to
+    /// show a progress bar.
+    fn copy_with_progress(
+        &self,
+        from: &Path,
+        to: &Path,
+        mut progress: impl CopyProgress,
+    ) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        async move {
+            // opening the destination for the copy would truncate the source
+            if from == to {
+                progress(self.metadata(from).await?.size);
+                return Ok(());
+            }
+            copy_between(self, from, self, to, progress).await
+        }
+    }
+
+    /// Copies the bytes `range` of the file at `from` to a new file at `to`, replacing `to` if it
+    /// exists, e.g. to extract a block out of a larger file. The range must be within the file,
+    /// otherwise [`io::ErrorKind::InvalidInput`] is returned. S3 copies it server side with
+    /// `UploadPartCopy`, the default reads the range then writes it.
+    fn copy_range(
+        &self,
+        from: &Path,
+        range: Range<u64>,
+        to: &Path,
+    ) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        async move {
+            let mut file = self.open_options(from, OpenOptions::default()).await?;
+            check_copy_range(from, &range, file.size().await?)?;
+            let (result, buf) = file
+                .read_exact_at(vec![0; (range.end - range.start) as usize], range.start)
+                .await;
+            result?;
+            self.write_file(to, buf).await.0?;
+            Ok(())
+        }
+    }
+
+    /// Moves the file at `from` to `to`, replacing `to` if it exists. Backends that can't rename
+    /// natively copy the file and remove the source, keeping as much of its metadata as they
+    /// can.
+    fn rename(
+        &self,
+        from: &Path,
+        to: &Path,
+    ) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        async move {
+            self.copy(from, to).await?;
+            self.remove(from).await
+        }
+    }
+
+    /// Moves the file at `from` to `to` like [`Fs::rename`], durably: once it returns, the rename
+    /// survives a crash. Local backends sync the directories holding `from` and `to` after the
+    /// rename, as their entries aren't durable until then. The renames of object stores are
+    /// durable once acknowledged, the default is [`Fs::rename`]. [`Fs::write_file_atomic`] and
+    /// [`Fs::publish`] rename their temporary files with it.
+    fn rename_durable(
+        &self,
+        from: &Path,
+        to: &Path,
+    ) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        self.rename(from, to)
+    }
+
+    /// Moves the file at `from` to `to` like [`Fs::rename`], returning the metadata of `to`
+    /// without a separate [`Fs::metadata`] where the backend can.
+    fn rename_with_meta(
+        &self,
+        from: &Path,
+        to: &Path,
+    ) -> impl Future<Output = Result<FileMeta, Error>> + MaybeSend {
+        async move {
+            self.rename(from, to).await?;
+            self.metadata(to).await
+        }
+    }
+
+    /// Returns the value of the extended attribute `name`, e.g. `user.origin`, of the file at
+    /// `path`, `None` if it isn't set. Local files keep them in the file system where it
+    /// supports them, S3 maps the `user.*` ones to the user metadata of objects.
+    fn get_xattr(
+        &self,
+        path: &Path,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, Error>> + MaybeSend {
+        let _ = name;
+        async move { Err(xattrs_unsupported(path)) }
+    }
+
+    /// Sets the extended attribute `name` of the file at `path` to `value`, see
+    /// [`Fs::get_xattr`].
+    fn set_xattr(
+        &self,
+        path: &Path,
+        name: &str,
+        value: &[u8],
+    ) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        let _ = (name, value);
+        async move { Err(xattrs_unsupported(path)) }
+    }
+
+    /// Lists the names of the extended attributes of the file at `path`, see [`Fs::get_xattr`].
+    fn list_xattr(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<Vec<String>, Error>> + MaybeSend {
+        async move { Err(xattrs_unsupported(path)) }
+    }
+
+    /// Takes a shared [`FileLock`] on the file at `path`, waiting until no exclusive one is held.
+    /// Locks are advisory and only supported by local file systems.
+    fn lock_shared(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<FileLock, Error>> + MaybeSend {
+        async move { Err(locks_unsupported(path)) }
+    }
+
+    /// Takes an exclusive [`FileLock`] on the file at `path`, waiting until no other lock is held.
+    fn lock_exclusive(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<FileLock, Error>> + MaybeSend {
+        async move { Err(locks_unsupported(path)) }
+    }
+
+    /// Maps the file at `path` into memory, read-only, for random reads served from the page
+    /// cache without a call per read. Only local file systems can, others return
+    /// [`Error::Unsupported`].
+    ///
+    /// The mapping shows the file as it is, not as it was when mapped: writes to the file by
+    /// this or another process show through, and accessing the mapping past the end of a file
+    /// truncated in the meantime crashes the process with `SIGBUS`. Only map files that are
+    /// no longer written, e.g. immutable data files.
+    #[cfg(feature = "mmap")]
+    fn mmap(&self, path: &Path) -> impl Future<Output = Result<Mmap, Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!("can't map \"{}\" into memory in this file system", path),
+            })
+        }
+    }
+
+    /// Returns a local path the file at `path` can be read at, for libraries that only take
+    /// paths, e.g. to map it or hand it to another process, along with a guard to hold for as
+    /// long as the path is used. Local file systems return the path of the file itself, others
+    /// download it into a new file of the temporary directory, removed when the guard is dropped.
+    fn localize(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<(PathBuf, TempGuard), Error>> + MaybeSend {
+        download(self, path)
+    }
+
+    /// Streams the content of the file at `path`, e.g. to pipe it into an HTTP response without
+    /// going through [`Read`]. S3 yields the frames of the body of a `GET` as they are received,
+    /// the default reads the file in chunks of [`STREAM_CHUNK_SIZE`], each when the previous one
+    /// is consumed.
+    #[cfg(feature = "bytes")]
+    fn get_stream(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<Bytes, Error>>, Error>> + MaybeSend
+    {
+        read_chunks(self, path)
+    }
+
+    /// Replaces the content of the file at `path` with the chunks of `stream`, e.g. to pipe the
+    /// body of an HTTP request into it without collecting it first. `size_hint` is the size of
+    /// the content if it is known: S3 puts a content fitting in a part with a single `PUT` and
+    /// sizes the parts of larger ones after it, uploading them one after the other so that only
+    /// a part is buffered. The default writes the chunks with [`Fs::publish`]. An error of the
+    /// stream fails the write and leaves the file as it was, aborting the upload of S3. Returns
+    /// the metadata of the written file.
+    #[cfg(feature = "bytes")]
+    fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        _size_hint: Option<u64>,
+    ) -> impl Future<Output = Result<FileMeta, Error>> + MaybeSend
+    where
+        S: Stream<Item = Result<Bytes, Error>> + MaybeSend,
+    {
+        self.publish(path, |file| write_chunks(file, stream))
+    }
+
+    /// Computes the `algorithm` digest of the content of the file at `path`. Backends that store
+    /// checksums return theirs, others read the file and hash it.
+    #[cfg(feature = "checksum")]
+    fn checksum(
+        &self,
+        path: &Path,
+        algorithm: ChecksumAlgorithm,
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + MaybeSend {
+        digest_file(self, path, algorithm)
+    }
+
+    /// Computes a digest of the listing of the files under `path`, at any depth: of their paths
+    /// relative to `path`, sizes and ETags. Comparing it to a previous one tells whether anything
+    /// under `path` changed without scanning the files again. Local files have no ETag, one
+    /// rewritten with the same size leaves the digest unchanged.
+    #[cfg(feature = "checksum")]
+    fn list_digest(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = Result<[u8; 32], Error>> + MaybeSend {
+        async move {
+            Err(Error::Unsupported {
+                message: format!(
+                    "can't digest the listing of \"{}\" in this file system",
+                    path
+                ),
+            })
+        }
+    }
+
+    /// Watches the files under `prefix`, at any depth, yielding an event for each one created,
+    /// modified or removed after the watch starts, when the stream is first polled. The default
+    /// lists `prefix` every `interval` and compares the listing to the previous one, which works
+    /// with every backend but tells a file modified only when its size or ETag changed: local files
+    /// rewritten with the same size aren't noticed. A file created and removed between two listings
+    /// isn't reported either. A listing that fails yields its error, and the watch goes on at the
+    /// next interval. The stream never ends, drop it to stop watching.
+    #[cfg(any(feature = "tokio", feature = "monoio"))]
+    fn watch(
+        &self,
+        prefix: &Path,
+        interval: std::time::Duration,
+    ) -> impl Stream<Item = Result<WatchEvent, Error>> {
+        watch::poll_watch(self, prefix.clone(), interval)
+    }
+
+    /// Returns a URI identifying `path` whatever the backend, for logs and manifests:
+    /// `s3://bucket/key` for S3, and by default `file:///abs/path`, the local file the path
+    /// stands for. The parts of the path are percent-encoded.
+    fn uri(&self, path: &Path) -> String {
+        crate::path::path_to_url(path).to_string()
+    }
+
+    /// Returns what tools outside fusio, e.g. `aws s3` or a native library, call `path`: the key
+    /// of the object for S3, and by default the absolute local path of the file. A path with no
+    /// local counterpart is returned as it is.
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        match crate::path::path_to_local(path) {
+            Ok(local) => Cow::Owned(local.into_os_string()),
+            Err(_) => Cow::Borrowed(OsStr::new(path.as_ref())),
+        }
+    }
+
+    /// Returns this file system as a [`PresignedFs`] if its files can be accessed through
+    /// presigned URLs, `None` otherwise.
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        None
+    }
+
+    /// Whether the files are reached over the network, each request costing a round trip and
+    /// maybe money, e.g. for S3, rather than on a local disk. Generic code may tell by it whether
+    /// caching or prefetching is worth it.
+    fn is_remote(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Writes a file of `fs`, then reads it back at various positions, through nothing but the
+    /// bounds of `Fs::File`.
+    #[allow(unused)]
+    async fn generic_round_trip<F: super::Fs>(fs: &F) {
+        use tempfile::TempDir;
+
+        use crate::{fs::OpenOptions, path::Path, Read, Write};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        let mut file = fs
+            .open_options(&path, OpenOptions::default().create(true).write(true))
+            .await
+            .unwrap();
+        let (result, _) = file.write_all(&b"hello"[..]).await;
+        result.unwrap();
+        let (result, _) = file.write_all(b" world".to_vec()).await;
+        result.unwrap();
+        file.flush().await.unwrap();
+
+        assert_eq!(file.size().await.unwrap(), 11);
+        let (result, buf) = file.read_exact_at(vec![0; 5], 6).await;
+        result.unwrap();
+        assert_eq!(buf, b"world");
+        let (result, buf) = file.read_exact_at(vec![0; 4], 1).await;
+        result.unwrap();
+        assert_eq!(buf, b"ello");
+        let (result, buf) = file.read_to_end_at(Vec::new(), 3).await;
+        result.unwrap();
+        assert_eq!(buf, b"lo world");
+        file.close().await.unwrap();
+
+        let mut file = fs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(Vec::new(), 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello world");
+        file.close().await.unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn generic_code_reads_and_writes_tokio_files() {
+        use crate::disk::TokioFs;
+
+        generic_round_trip(&TokioFs).await;
+    }
+
+    #[cfg(feature = "monoio")]
+    #[monoio::test]
+    async fn generic_code_reads_and_writes_monoio_files() {
+        use crate::disk::MonoIoFs;
+
+        generic_round_trip(&MonoIoFs::default()).await;
+    }
+
+    #[cfg(all(target_os = "linux", feature = "tokio-uring"))]
+    #[test]
+    fn generic_code_reads_and_writes_tokio_uring_files() {
+        use crate::disk::TokioUringFs;
+
+        tokio_uring::start(generic_round_trip(&TokioUringFs::default()));
+    }
+
+    #[cfg(all(unix, feature = "tokio", feature = "aws"))]
+    #[test]
+    fn uris_round_trip() {
+        use url::Url;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ScopedFs},
+            path::{path_to_local, Path},
+            remotes::aws::{mock::MockS3, S3Location},
+        };
+
+        // special characters are percent-encoded, and decoded back
+        let path = Path::from("tmp/data/a b+c#d/part=1%.parquet");
+        let uri = TokioFs.uri(&path);
+        assert!(uri.starts_with("file:///tmp/data/a%20b"), "{}", uri);
+        let local = Url::parse(&uri).unwrap().to_file_path().unwrap();
+        assert_eq!(local, path_to_local(&path).unwrap());
+        assert_eq!(Path::from_absolute_path(&local).unwrap(), path);
+        let scoped = ScopedFs::new(TokioFs, Path::from("tmp"));
+        assert_eq!(scoped.uri(&Path::from("data/a b+c#d/part=1%.parquet")), uri);
+
+        let s3 = MockS3::new().fs();
+        let uri = s3.uri(&path);
+        assert!(
+            uri.starts_with(&format!("s3://{}/tmp/", s3.bucket())),
+            "{}",
+            uri
+        );
+        assert_eq!(S3Location::parse(&uri).unwrap(), s3.location(&path));
+    }
+
+    #[cfg(feature = "monoio")]
+    #[test]
+    fn local_files_are_not_remote() {
+        use crate::{disk::MonoIoFs, fs::Fs};
+
+        assert!(!MonoIoFs::default().is_remote());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[test]
+    fn objects_are_remote() {
+        use crate::{
+            disk::TokioFs,
+            fs::{CachePolicy, CachingFs, Fs, ScopedFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let s3 = MockS3::new().fs();
+        assert!(s3.is_remote());
+        let scoped = ScopedFs::new(s3.clone(), Path::from("tmp"));
+        assert!(scoped.is_remote());
+        // a local cache doesn't make the files it caches local
+        let caching = CachingFs::new(s3, TokioFs, CachePolicy::default());
+        assert!(caching.is_remote());
+        let dyn_fs: &dyn crate::DynFs = &caching;
+        assert!(dyn_fs.is_remote());
+        assert!(!ScopedFs::new(TokioFs, Path::from("tmp")).is_remote());
+    }
+
+    #[cfg(all(unix, feature = "tokio", feature = "aws"))]
+    #[test]
+    fn native_paths_are_local_paths_and_keys() {
+        use std::ffi::OsStr;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ScopedFs},
+            path::{path_to_local, Path},
+            remotes::aws::mock::MockS3,
+        };
+
+        let path = Path::from("tmp/data/a b+c#d/part=1%.parquet");
+        let local = TokioFs.native_path(&path);
+        assert!(std::path::Path::new(&local).is_absolute());
+        assert_eq!(local, path_to_local(&path).unwrap().as_os_str());
+        let scoped = ScopedFs::new(TokioFs, Path::from("tmp"));
+        assert_eq!(
+            scoped.native_path(&Path::from("data/a b+c#d/part=1%.parquet")),
+            local
+        );
+
+        // the key holds the parts as the path encodes them
+        let s3 = MockS3::new().fs();
+        assert_eq!(
+            s3.native_path(&path),
+            OsStr::new("tmp/data/a b+c%23d/part=1%25.parquet")
+        );
+        let scoped = ScopedFs::new(s3, Path::from("tmp"));
+        assert_eq!(
+            scoped.native_path(&Path::from("data/a b+c#d/part=1%.parquet")),
+            OsStr::new("tmp/data/a b+c%23d/part=1%25.parquet")
+        );
+    }
+
+    #[cfg(all(feature = "tokio", feature = "fs"))]
+    #[tokio::test]
+    async fn copy_range_copies_a_slice_of_local_files() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, Error};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let (source, slice) = (dir.child("source"), dir.child("slice"));
+        let content = (0..300).map(|i| i as u8).collect::<Vec<_>>();
+        TokioFs
+            .write_file(&source, content.clone())
+            .await
+            .0
+            .unwrap();
+
+        TokioFs.copy_range(&source, 100..200, &slice).await.unwrap();
+        let copy = std::fs::read(tmp_dir.path().join("slice")).unwrap();
+        assert_eq!(copy.len(), 100);
+        assert_eq!(copy, &content[100..200]);
+
+        assert!(matches!(
+            TokioFs.copy_range(&source, 250..301, &dir.child("past")).await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+        assert!(!tmp_dir.path().join("past").exists());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn version_tokens_change_with_the_content() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let path = dir.child("file");
+        TokioFs
+            .write_file(&path, b"hello".to_vec())
+            .await
+            .0
+            .unwrap();
+        let before = TokioFs.metadata(&path).await.unwrap().version_token();
+        assert!(before.is_some());
+        // listings tell the same version as the metadata
+        let mut listed = pin!(TokioFs.list(&dir).await.unwrap());
+        let meta = listed.next().await.unwrap().unwrap();
+        assert_eq!(meta.version_token(), before);
+
+        TokioFs
+            .write_file(&path, b"hello world".to_vec())
+            .await
+            .0
+            .unwrap();
+        let after = TokioFs.metadata(&path).await.unwrap().version_token();
+        assert!(after.is_some());
+        assert_ne!(after, before);
+
+        let mock = MockS3::new();
+        mock.put_object("file", b"hello".to_vec());
+        let meta = mock.fs().metadata(&Path::from("file")).await.unwrap();
+        assert!(meta.etag.is_some());
+        assert_eq!(meta.version_token(), meta.etag);
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn remove_if_exists_ignores_missing_files() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3, Error};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let local = TokioFs;
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("missing");
+
+        assert!(matches!(
+            local.remove(&path).await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+        assert!(!local.remove_if_exists(&path).await.unwrap());
+
+        local.write_file(&path, b"x".to_vec()).await.0.unwrap();
+        assert!(local.remove_if_exists(&path).await.unwrap());
+        assert!(local.metadata(&path).await.is_err());
+
+        let s3 = MockS3::new().fs();
+        // S3 doesn't tell whether there was an object
+        assert!(s3.remove_if_exists(&Path::from("missing")).await.unwrap());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn minimal_backends_get_the_defaults() {
+        use futures_core::Stream;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FileMeta, Fs, OpenOptions},
+            path::Path,
+            Error, Read,
+        };
+
+        /// A backend implementing only the methods without a default.
+        struct Minimal(TokioFs);
+
+        impl Fs for Minimal {
+            type File = <TokioFs as Fs>::File;
+
+            async fn open_options(
+                &self,
+                path: &Path,
+                options: OpenOptions,
+            ) -> Result<Self::File, Error> {
+                self.0.open_options(path, options).await
+            }
+
+            async fn list(
+                &self,
+                path: &Path,
+            ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+                self.0.list(path).await
+            }
+
+            async fn remove(&self, path: &Path) -> Result<(), Error> {
+                self.0.remove(path).await
+            }
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let fs = Minimal(TokioFs);
+        let (a, b, c) = (dir.child("a"), dir.child("b"), dir.child("c"));
+
+        fs.create_dir_all(&dir).await.unwrap();
+        let meta = fs.write_file(&a, b"hello".to_vec()).await.0.unwrap();
+        assert_eq!(meta.size, 5);
+        assert!(fs.exists(&a).await.unwrap());
+        assert!(!fs.exists(&b).await.unwrap());
+
+        fs.copy(&a, &b).await.unwrap();
+        fs.rename(&b, &c).await.unwrap();
+        assert!(!fs.exists(&b).await.unwrap());
+        assert_eq!(fs.metadata(&c).await.unwrap().size, 5);
+        let mut file = fs.open(&c).await.unwrap();
+        let (result, buf) = file.read_to_end_at(Vec::new(), 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello");
+
+        assert!(fs.remove_if_exists(&c).await.unwrap());
+        assert!(!fs.remove_if_exists(&c).await.unwrap());
+        assert!(fs.metadata(&c).await.is_err());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn copies_onto_themselves_keep_the_content() {
+        use http::Method;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, CopyOptions},
+            Read,
+        };
+
+        async fn content<F: Fs>(fs: &F, path: &Path) -> Vec<u8> {
+            let mut file = fs.open(path).await.unwrap();
+            let (result, buf) = file.read_to_end_at(Vec::new(), 0).await;
+            result.unwrap();
+            buf
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let path = dir.child("file");
+        let local = TokioFs;
+        local.write_file(&path, b"hello".to_vec()).await.0.unwrap();
+        local.copy(&path, &path).await.unwrap();
+        assert_eq!(content(&local, &path).await, b"hello");
+        let mut reported = Vec::new();
+        local
+            .copy_with_progress(&path, &path, |copied| reported.push(copied))
+            .await
+            .unwrap();
+        assert_eq!(reported, [5]);
+        assert_eq!(content(&local, &path).await, b"hello");
+        #[cfg(unix)]
+        {
+            // the same file under another name
+            let link = dir.child("link");
+            std::os::unix::fs::symlink(tmp_dir.path().join("file"), tmp_dir.path().join("link"))
+                .unwrap();
+            local.copy(&path, &link).await.unwrap();
+            assert_eq!(content(&local, &path).await, b"hello");
+        }
+        assert!(local
+            .copy(&dir.child("missing"), &dir.child("missing"))
+            .await
+            .is_err());
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("object", "hello");
+        let path = Path::from("object");
+        s3.copy(&path, &path).await.unwrap();
+        assert_eq!(content(&s3, &path).await, b"hello");
+        assert!(mock.requests(Method::PUT).is_empty());
+        // unless the metadata is replaced, which S3 does by copying objects onto themselves
+        s3.copy_with(
+            &path,
+            &path,
+            &CopyOptions::default().replace_metadata([("origin", "test")]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(mock.requests(Method::PUT).len(), 1);
+        assert_eq!(content(&s3, &path).await, b"hello");
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws", feature = "mmap"))]
+    #[tokio::test]
+    async fn local_files_map_into_memory() {
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3, Error};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let content = (0..64 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let local = TokioFs;
+        local
+            .write_file(&dir.child("file"), content.clone())
+            .await
+            .0
+            .unwrap();
+        local
+            .write_file(&dir.child("empty"), Vec::new())
+            .await
+            .0
+            .unwrap();
+
+        let map = local.mmap(&dir.child("file")).await.unwrap();
+        assert_eq!(&map[..], &content[..]);
+        assert!(local.mmap(&dir.child("empty")).await.unwrap().is_empty());
+        assert!(local.mmap(&dir.child("missing")).await.is_err());
+
+        let mock = MockS3::new();
+        mock.put_object("object", "hello");
+        assert!(matches!(
+            mock.fs().mmap(&Path::from("object")).await,
+            Err(Error::Unsupported { .. })
+        ));
+    }
+
+    #[cfg(all(target_os = "linux", feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn local_files_reserve_allocated_space() {
+        use std::os::unix::fs::MetadataExt;
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Error, Read, Write,
+        };
+
+        const LEN: u64 = 1024 * 1024 * 1024;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        let options = OpenOptions::default().create(true).write(true);
+        let mut file = TokioFs.open_options(&path, options).await.unwrap();
+        let (result, _) = file.write_all(&b"hello"[..]).await;
+        result.unwrap();
+        match file.allocate(LEN).await {
+            Ok(()) => {}
+            // the temporary directory has no room for the reservation
+            Err(Error::Io(e)) if e.raw_os_error() == Some(libc::ENOSPC) => return,
+            Err(e) => panic!("{}", e),
+        }
+        file.flush().await.unwrap();
+
+        // the space is taken, but the size and the content are as they were
+        let metadata = std::fs::metadata(tmp_dir.path().join("file")).unwrap();
+        assert!(metadata.blocks() * 512 >= LEN);
+        assert_eq!(file.size().await.unwrap(), 5);
+        let (result, _) = file.write_all(&b" world"[..]).await;
+        result.unwrap();
+        file.close().await.unwrap();
+        let mut file = TokioFs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(Vec::new(), 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello world");
+
+        let s3 = MockS3::new().fs();
+        let mut file = s3
+            .open_options(&Path::from("object"), options)
+            .await
+            .unwrap();
+        assert!(matches!(
+            file.allocate(LEN).await,
+            Err(Error::Unsupported { .. })
+        ));
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn remove_dir_all_of_local_trees() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ScopedFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Error,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let local = ScopedFs::new(TokioFs, Path::from_filesystem_path(tmp_dir.path()).unwrap());
+        for dir in ["tree/a/b", "tree/c"] {
+            local.create_dir_all(&Path::from(dir)).await.unwrap();
+            let file = Path::from(dir).child("file");
+            local.write_file(&file, b"x".to_vec()).await.0.unwrap();
+        }
+
+        local.remove_dir_all(&Path::from("tree")).await.unwrap();
+        assert_eq!(local.file_type(&Path::from("tree")).await.unwrap(), None);
+        assert!(local.remove_dir_all(&Path::from("tree")).await.is_err());
+
+        let s3 = MockS3::new().fs();
+        assert!(matches!(
+            s3.remove_dir_all(&Path::from("tree")).await,
+            Err(Error::Unsupported { .. })
+        ));
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn file_types_of_local_and_s3_paths() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FileType, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        async fn check<F: Fs>(fs: &F, path: impl Fn(&str) -> Path) {
+            assert_eq!(
+                fs.file_type(&path("dir")).await.unwrap(),
+                Some(FileType::Dir)
+            );
+            assert_eq!(
+                fs.file_type(&path("dir/file")).await.unwrap(),
+                Some(FileType::File)
+            );
+            assert_eq!(fs.file_type(&path("missing")).await.unwrap(), None);
+            // a name prefixing other objects without being a directory of them
+            assert_eq!(fs.file_type(&path("di")).await.unwrap(), None);
+
+            assert!(fs.is_dir(&path("dir")).await.unwrap());
+            assert!(!fs.is_file(&path("dir")).await.unwrap());
+            assert!(fs.is_file(&path("dir/file")).await.unwrap());
+            assert!(!fs.is_dir(&path("dir/file")).await.unwrap());
+            assert!(!fs.is_dir(&path("missing")).await.unwrap());
+            assert!(!fs.is_file(&path("missing")).await.unwrap());
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let local = TokioFs;
+        let root = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        local.create_dir_all(&root.child("dir")).await.unwrap();
+        local
+            .write_file(&root.child("dir").child("file"), b"x".to_vec())
+            .await
+            .0
+            .unwrap();
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("dir/file", "x");
+        mock.put_object("dirty", "x");
+
+        check(&local, |name| {
+            name.split('/')
+                .fold(root.clone(), |path, part| path.child(part))
+        })
+        .await;
+        check(&s3, |name| name.into()).await;
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn dir_stats_add_up_files() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{DirStats, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let files = [("a", 1), ("b", 10), ("sub/c", 100)];
+        let expected = DirStats {
+            entry_count: 3,
+            total_size: 111,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let local = TokioFs;
+        let root = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        local.create_dir_all(&root.child("sub")).await.unwrap();
+        for (name, size) in files {
+            let path = name
+                .split('/')
+                .fold(root.clone(), |path, part| path.child(part));
+            local.write_file(&path, vec![0; size]).await.0.unwrap();
+        }
+        assert_eq!(local.dir_stats(&root).await.unwrap(), expected);
+        assert_eq!(local.total_size(&root).await.unwrap(), 111);
+        assert_eq!(local.total_size(&root.child("sub")).await.unwrap(), 100);
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        for (name, size) in files {
+            mock.put_object(&format!("dir/{}", name), vec![0; size]);
+        }
+        mock.put_object("dirty", "not under dir/");
+        assert_eq!(s3.dir_stats(&Path::from("dir")).await.unwrap(), expected);
+        assert_eq!(s3.total_size(&Path::from("dir")).await.unwrap(), 111);
+        assert_eq!(s3.total_size(&Path::from("dir/sub")).await.unwrap(), 100);
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn list_digests_change_with_the_files() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ScopedFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        async fn check<F: Fs>(fs: &F) {
+            let dir = Path::from("dir");
+            fs.create_dir_all(&dir.child("sub")).await.unwrap();
+            fs.write_file(&dir.child("a"), b"a".to_vec())
+                .await
+                .0
+                .unwrap();
+            fs.write_file(&dir.child("sub").child("b"), b"b".to_vec())
+                .await
+                .0
+                .unwrap();
+            let digest = fs.list_digest(&dir).await.unwrap();
+            assert_eq!(fs.list_digest(&dir).await.unwrap(), digest);
+
+            fs.write_file(&dir.child("c"), b"c".to_vec())
+                .await
+                .0
+                .unwrap();
+            assert_ne!(fs.list_digest(&dir).await.unwrap(), digest);
+            fs.remove(&dir.child("c")).await.unwrap();
+            assert_eq!(fs.list_digest(&dir).await.unwrap(), digest);
+
+            fs.write_file(&dir.child("a"), b"longer".to_vec())
+                .await
+                .0
+                .unwrap();
+            assert_ne!(fs.list_digest(&dir).await.unwrap(), digest);
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        check(&ScopedFs::new(
+            TokioFs,
+            Path::from_filesystem_path(tmp_dir.path()).unwrap(),
+        ))
+        .await;
+        check(&MockS3::new().fs()).await;
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn list_pages_resume_from_cursors() {
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        async fn pages<F: Fs>(fs: &F, dir: &Path) {
+            for i in 0..25 {
+                fs.write_file(&dir.child(format!("{:02}", i)), vec![0u8; i])
+                    .await
+                    .0
+                    .unwrap();
+            }
+
+            let mut cursor = None;
+            let mut sizes = vec![];
+            let mut listed = vec![];
+            loop {
+                let (entries, next) = fs.list_page(dir, cursor.as_deref(), 10).await.unwrap();
+                sizes.push(entries.len());
+                listed.extend(entries.into_iter().map(|meta| (meta.path, meta.size)));
+                match next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(sizes, [10, 10, 5]);
+            assert_eq!(
+                listed,
+                (0..25)
+                    .map(|i| (dir.child(format!("{:02}", i)), i as u64))
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        pages(
+            &TokioFs,
+            &Path::from_filesystem_path(tmp_dir.path()).unwrap(),
+        )
+        .await;
+
+        let mock = MockS3::new();
+        pages(&mock.fs(), &Path::from("dir")).await;
+    }
+
+    /// The paths listed by every backend are full paths under the listed one, which are the same
+    /// relative paths for the same logical listing.
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn list_paths_agree_across_backends() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ListOptions},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        async fn list<F: Fs>(fs: &F, path: &Path, options: ListOptions) -> Vec<Path> {
+            let mut stream = pin!(fs.list_with(path, options).await.unwrap());
+            let mut paths = vec![];
+            while let Some(meta) = stream.next().await {
+                paths.push(meta.unwrap().path);
+            }
+            paths.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+            paths
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let local = TokioFs;
+        let local_dir = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("dir");
+        local.create_dir_all(&local_dir).await.unwrap();
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let s3_dir = Path::from("dir");
+        mock.put_object("dirty", "sibling");
+
+        for name in ["a", "b"] {
+            local
+                .write_file(&local_dir.child(name), b"x".to_vec())
+                .await
+                .0
+                .unwrap();
+            mock.put_object(&format!("dir/{}", name), "x");
+        }
+
+        let relative = ListOptions::default().relative(true);
+        let expected = vec![Path::from("a"), Path::from("b")];
+        assert_eq!(list(&local, &local_dir, relative).await, expected);
+        assert_eq!(list(&s3, &s3_dir, relative).await, expected);
+
+        let full = ListOptions::default();
+        assert_eq!(
+            list(&local, &local_dir, full).await,
+            vec![local_dir.child("a"), local_dir.child("b")]
+        );
+        assert_eq!(
+            list(&s3, &s3_dir, full).await,
+            vec![Path::from("dir/a"), Path::from("dir/b")]
+        );
+    }
+
+    #[cfg(all(target_os = "linux", feature = "tokio"))]
+    #[tokio::test]
+    async fn unreadable_entry_does_not_end_listing() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ListOptions},
+            path::Path,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let names = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+        for name in &names {
+            std::fs::write(tmp_dir.path().join(name), b"x").unwrap();
+        }
+
+        let options = ListOptions::default().continue_on_error(true);
+        let mut entries = pin!(TokioFs.list_with(&dir, options).await.unwrap());
+        // the names of a small directory are all read with its first entry, an entry removed
+        // afterwards is listed but can't be stat'ed
+        let first = entries.next().await.unwrap().unwrap();
+        let removed = names
+            .iter()
+            .find(|name| first.path.filename() != Some(name.as_str()))
+            .unwrap();
+        std::fs::remove_file(tmp_dir.path().join(removed)).unwrap();
+
+        let rest = entries.collect::<Vec<_>>().await;
+        assert_eq!(rest.iter().filter(|meta| meta.is_err()).count(), 1);
+        assert_eq!(rest.iter().filter(|meta| meta.is_ok()).count(), 8);
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn rename_with_meta_returns_destination_metadata() {
+        use http::Method;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FileMeta, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        async fn rename<F: Fs>(fs: &F, dir: &Path, renamed: impl FnOnce()) -> FileMeta {
+            let (from, to) = (dir.child("from"), dir.child("to"));
+            fs.write_file(&from, b"hello".to_vec()).await.0.unwrap();
+            fs.write_file(&to, b"bye".to_vec()).await.0.unwrap();
+
+            let meta = fs.rename_with_meta(&from, &to).await.unwrap();
+            renamed();
+            assert_eq!(meta.size, 5);
+            assert_eq!(meta, fs.metadata(&to).await.unwrap());
+            meta
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        rename(&TokioFs, &dir, || {}).await;
+        assert!(!tmp_dir.path().join("from").exists());
+
+        let mock = MockS3::new();
+        let meta = rename(&mock.fs(), &Path::from("dir"), || {
+            // the source is looked up to be copied, the destination isn't
+            assert_eq!(mock.requests(Method::HEAD).len(), 1);
+        })
+        .await;
+        assert!(meta.etag.is_some());
+        assert!(mock.object("dir/from").is_none());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn xattrs_of_local_files_and_s3_objects() {
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3, Error};
+
+        async fn check<F: Fs>(fs: &F, path: &Path) -> Result<(), Error> {
+            fs.write_file(path, b"hello".to_vec()).await.0?;
+            assert_eq!(fs.get_xattr(path, "user.fusio.test").await?, None);
+
+            fs.set_xattr(path, "user.fusio.test", b"value").await?;
+            assert_eq!(
+                fs.get_xattr(path, "user.fusio.test").await?.as_deref(),
+                Some(&b"value"[..])
+            );
+            assert!(fs
+                .list_xattr(path)
+                .await?
+                .contains(&"user.fusio.test".to_string()));
+            let (_, content) = fs.read_if_none_match(path, "").await?.unwrap();
+            assert_eq!(content, b"hello");
+            Ok(())
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        match check(&TokioFs, &dir.child("a")).await {
+            // e.g. the tmpfs of older kernels, which has no `user.*` attributes
+            Err(Error::Unsupported { .. }) => {}
+            result => result.unwrap(),
+        }
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        check(&s3, &Path::from("a")).await.unwrap();
+        assert_eq!(
+            mock.object("a").unwrap().headers["x-amz-meta-fusio.test"],
+            "value"
+        );
+        // S3 has nowhere to store the other namespaces
+        assert!(matches!(
+            s3.set_xattr(&Path::from("a"), "trusted.fusio", b"value")
+                .await,
+            Err(Error::Unsupported { .. })
+        ));
+    }
+
+    /// A local directory and an S3 prefix holding the same tree list the same immediate
+    /// children, the entries deeper than a level being rolled up into prefixes.
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn delimited_listings_agree_across_backends() {
+        use tempfile::TempDir;
+
+        use super::parent;
+        use crate::{
+            disk::TokioFs,
+            fs::{DelimitedListing, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        /// The files, with their sizes, and the prefixes listed under `dir`, relative to it.
+        async fn list<F: Fs>(fs: &F, dir: &Path) -> (Vec<(Path, u64)>, Vec<Path>) {
+            let DelimitedListing { files, prefixes } = fs.list_delimited(dir).await.unwrap();
+            let relative = |path: &Path| Path::from_iter(path.prefix_match(dir).unwrap());
+            (
+                files
+                    .iter()
+                    .map(|meta| (relative(&meta.path), meta.size))
+                    .collect(),
+                prefixes.iter().map(relative).collect(),
+            )
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let local_dir = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("dir");
+        let mock = MockS3::new();
+        // more children than a page of the listing holds
+        mock.set_list_page_size(2);
+        let s3 = mock.fs();
+        let s3_dir = Path::from("dir");
+        mock.put_object("dirty", "sibling");
+        mock.put_object("dir/", "");
+
+        for name in ["a", "b/c", "b/d", "b/e/f", "g/h", "i"] {
+            let local = Path::from(format!("{}/{}", local_dir, name));
+            TokioFs.create_dir_all(&parent(&local)).await.unwrap();
+            TokioFs
+                .write_file(&local, name.as_bytes().to_vec())
+                .await
+                .0
+                .unwrap();
+            mock.put_object(&format!("dir/{}", name), name);
+        }
+        // an empty directory, and its marker
+        TokioFs.create_dir_all(&local_dir.child("j")).await.unwrap();
+        mock.put_object("dir/j/", "");
+
+        let expected = (
+            vec![(Path::from("a"), 1), (Path::from("i"), 1)],
+            vec![Path::from("b"), Path::from("g"), Path::from("j")],
+        );
+        assert_eq!(list(&TokioFs, &local_dir).await, expected);
+        assert_eq!(list(&s3, &s3_dir).await, expected);
+
+        let expected = (
+            vec![(Path::from("c"), 3), (Path::from("d"), 3)],
+            vec![Path::from("e")],
+        );
+        assert_eq!(list(&TokioFs, &local_dir.child("b")).await, expected);
+        assert_eq!(list(&s3, &s3_dir.child("b")).await, expected);
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn read_head_sniffs_magic_bytes() {
+        use http::Method;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let content = b"PAR1\x15\x04\x15\x80\x01\x15\x82\x01\x4c\x15\x02\x15\x00\x12\x00\x00";
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        for len in [0, 3, 8, 20] {
+            let name = format!("file-{}", len);
+            let local = dir.child(name.as_str());
+            TokioFs
+                .write_file(&local, content[..len].to_vec())
+                .await
+                .0
+                .unwrap();
+            mock.put_object(&name, &content[..len]);
+
+            let expected = &content[..len.min(8)];
+            assert_eq!(TokioFs.read_head(&local, 8).await.unwrap(), expected);
+            assert_eq!(
+                s3.read_head(&Path::from(name.as_str()), 8).await.unwrap(),
+                expected
+            );
+            assert!(TokioFs.read_head(&local, 0).await.unwrap().is_empty());
+        }
+        // each read of an object is a single request
+        assert_eq!(mock.requests(Method::GET).len(), 4);
+
+        assert!(TokioFs.read_head(&dir.child("missing"), 8).await.is_err());
+        assert!(s3.read_head(&Path::from("missing"), 8).await.is_err());
+        assert!(s3.read_head(&Path::from("missing"), 0).await.is_err());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn read_tail_reads_footers() {
+        use http::Method;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        // a Parquet-like file: the data, the footer, its length and the magic bytes
+        let mut content = b"PAR1".to_vec();
+        content.extend(0..100u8);
+        content.extend(b"footer");
+        content.extend(6u32.to_le_bytes());
+        content.extend(b"PAR1");
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        for len in [0, 3, 8, content.len()] {
+            let name = format!("file-{}", len);
+            let local = dir.child(name.as_str());
+            TokioFs
+                .write_file(&local, content[..len].to_vec())
+                .await
+                .0
+                .unwrap();
+            mock.put_object(&name, content[..len].to_vec());
+
+            let expected = &content[len.saturating_sub(8)..len];
+            assert_eq!(TokioFs.read_tail(&local, 8).await.unwrap(), expected);
+            assert_eq!(
+                s3.read_tail(&Path::from(name.as_str()), 8).await.unwrap(),
+                expected
+            );
+            assert!(TokioFs.read_tail(&local, 0).await.unwrap().is_empty());
+        }
+        assert_eq!(mock.requests(Method::GET).len(), 4);
+
+        // the length in the trailer locates the footer
+        let path = Path::from(format!("file-{}", content.len()).as_str());
+        let trailer = s3.read_tail(&path, 8).await.unwrap();
+        let footer_len = u32::from_le_bytes(trailer[..4].try_into().unwrap()) as usize;
+        let footer = s3.read_tail(&path, footer_len + 8).await.unwrap();
+        assert_eq!(&footer[..footer_len], b"footer");
+
+        assert!(TokioFs.read_tail(&dir.child("missing"), 8).await.is_err());
+        assert!(s3.read_tail(&Path::from("missing"), 8).await.is_err());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn written_files_return_their_sha256() {
+        use ring::digest::{digest, SHA256};
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{ChecksumAlgorithm, Fs},
+            path::Path,
+            remotes::{aws::mock::MockS3, encoding::base64_encode},
+        };
+
+        let content = (0..4096u32).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        let expected = digest(&SHA256, &content);
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        let (result, _) = TokioFs
+            .write_file_with_checksum(&path, content.clone())
+            .await;
+        let (meta, checksum) = result.unwrap();
+        assert_eq!(meta.size, 4096);
+        assert_eq!(checksum, expected.as_ref());
+        assert_eq!(
+            TokioFs
+                .checksum(&path, ChecksumAlgorithm::Sha256)
+                .await
+                .unwrap(),
+            expected.as_ref()
+        );
+
+        let mock = MockS3::new();
+        let (result, _) = mock
+            .fs()
+            .write_file_with_checksum(&Path::from("object"), content.clone())
+            .await;
+        let (meta, checksum) = result.unwrap();
+        assert_eq!(meta.size, 4096);
+        assert_eq!(checksum, expected.as_ref());
+        // S3 got it to verify the content with
+        let object = mock.object("object").unwrap();
+        assert_eq!(object.body, content);
+        assert_eq!(
+            object.headers["x-amz-checksum-sha256"],
+            base64_encode(expected.as_ref()).as_str()
+        );
+    }
 }
@@ -1,8 +1,10 @@
+#[derive(Debug, Clone, Copy)]
 pub struct OpenOptions {
     pub read: bool,
     pub write: bool,
     pub create: bool,
     pub truncate: bool,
+    pub direct: bool,
 }
 
 impl Default for OpenOptions {
@@ -12,11 +14,14 @@ impl Default for OpenOptions {
             write: false,
             create: false,
             truncate: false,
+            direct: false,
         }
     }
 }
 
 impl OpenOptions {
+    /// Opens the file for reading, the default. Local files opened with `read(false)` are
+    /// write-only and fail to be read. Object stores have no write-only handles, S3 ignores it.
     pub fn read(mut self, read: bool) -> Self {
         self.read = read;
         self
@@ -37,4 +42,68 @@ impl OpenOptions {
         self.truncate = truncate;
         self
     }
+
+    /// Opens the file bypassing the page cache of the OS, with `O_DIRECT`, for workloads that
+    /// cache what they need themselves, e.g. databases. Local files on Linux support it with the
+    /// io_uring runtimes, which hand buffers to the kernel as they are; other local file systems
+    /// fail with [`Error::Unsupported`](crate::Error::Unsupported). Object stores have no page
+    /// cache, S3 ignores it.
+    ///
+    /// The buffers of the reads and writes must be aligned in memory, and their positions and
+    /// lengths multiples, to the logical block size of the device, which
+    /// [`DIRECT_IO_ALIGNMENT`](crate::disk::DIRECT_IO_ALIGNMENT) suits; other reads and writes
+    /// fail with `EINVAL`. [`Read::read_to_end_at`](crate::Read::read_to_end_at) reads in a
+    /// buffer of its own, unaligned, use `read_exact_at` instead. Not every file system supports
+    /// it, tmpfs fails to open files with `EINVAL`.
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+}
+
+/// How [`Fs::list_with`](super::Fs::list_with) lists: the shape of the paths it yields, whether
+/// it goes on after errors, whether it yields directory markers and how it matches the listed
+/// path.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListOptions {
+    /// Yields paths relative to the listed path instead of full ones.
+    pub relative: bool,
+    /// Yields the error of an entry that can't be listed and goes on with the others, instead of
+    /// ending the listing. Errors after which nothing more can be listed, such as a page of an S3
+    /// listing that failed, still end it.
+    pub continue_on_error: bool,
+    /// Yields the directory markers of object stores, the empty objects with keys ending in `/`
+    /// that tools like the S3 console create for empty directories, including the one at the
+    /// listed path itself. They are skipped by default, as [`Fs::list`](super::Fs::list) does,
+    /// local file systems having no such entries. The paths of markers are yielded without their
+    /// trailing `/`, that of the listed path being the path itself, or empty when relative.
+    pub markers: bool,
+    /// Matches the last part of the listed path regardless of case, also listing `data` when
+    /// listing `Data`, as the case-insensitive local file systems of macOS and Windows do. S3,
+    /// like most Linux file systems, is case-sensitive: the directories next to the listed one
+    /// are listed to find those whose names differ from its own by case alone, and the files
+    /// under each of them are listed. The parts above the last one are matched as given.
+    pub case_insensitive: bool,
+}
+
+impl ListOptions {
+    pub fn relative(mut self, relative: bool) -> Self {
+        self.relative = relative;
+        self
+    }
+
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    pub fn markers(mut self, markers: bool) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
 }
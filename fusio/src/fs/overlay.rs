@@ -0,0 +1,680 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+    ffi::OsStr,
+    io,
+    path::PathBuf,
+    pin::pin,
+};
+
+use async_stream::stream;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+#[cfg(feature = "bytes")]
+use futures_util::future::Either;
+use futures_util::StreamExt;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        is_not_found, parent, DelimitedListing, FileLock, FileMeta, FileType, Fs, OpenOptions,
+        TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, MaybeSend, Read, Write,
+};
+
+/// Marks a file of the lower layer as removed when an entry prefixed with it exists next to it in
+/// the upper layer, like the whiteouts of overlayfs.
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+fn not_found(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("\"{}\" was removed", path)).into()
+}
+
+fn whiteout(path: &Path) -> Path {
+    parent(path)
+        .child(format!("{}{}", WHITEOUT_PREFIX, path.filename().unwrap_or_default()).as_str())
+}
+
+/// A [`Fs`] that stacks a writable `upper` layer over a read-only `lower` one, e.g. a local cache
+/// over a remote backend. Reads prefer the upper layer and fall through to the lower one, writes
+/// only ever go to the upper layer and listing merges both. Removing a file of the lower layer
+/// leaves a whiteout in the upper layer that hides it.
+///
+/// Both layers must share the same path namespace, wrap them in [`ScopedFs`](super::ScopedFs)
+/// when they are rooted at different prefixes.
+pub struct OverlayFs<U, L> {
+    upper: U,
+    lower: L,
+}
+
+impl<U: Fs, L: Fs> OverlayFs<U, L> {
+    pub fn new(upper: U, lower: L) -> Self {
+        Self { upper, lower }
+    }
+
+    pub fn upper(&self) -> &U {
+        &self.upper
+    }
+
+    pub fn lower(&self) -> &L {
+        &self.lower
+    }
+
+    async fn exists_in<F: Fs>(fs: &F, path: &Path) -> Result<bool, Error> {
+        match fs.open_options(path, OpenOptions::default()).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_not_found(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copies a file of the lower layer into the upper layer, so that it can be modified in place.
+    async fn copy_up(&self, path: &Path) -> Result<(), Error> {
+        if Self::exists_in(&self.upper, path).await? {
+            return Ok(());
+        }
+        let mut lower = match self.lower.open_options(path, OpenOptions::default()).await {
+            Ok(lower) => lower,
+            Err(e) if is_not_found(&e) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let (result, buf) = lower.read_to_end_at(vec![], 0).await;
+        result?;
+
+        self.upper.create_dir_all(&parent(path)).await?;
+        let mut upper = self
+            .upper
+            .open_options(path, OpenOptions::default().create(true).truncate(true))
+            .await?;
+        upper.write_all(buf).await.0?;
+        upper.close().await
+    }
+
+    /// Leaves a whiteout over `path` if the lower layer has it.
+    async fn hide(&self, path: &Path) -> Result<(), Error> {
+        if Self::exists_in(&self.lower, path).await? {
+            self.upper.create_dir_all(&parent(path)).await?;
+            self.upper
+                .open_options(&whiteout(path), OpenOptions::default().create(true))
+                .await?
+                .close()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Moves `from` to `to` in the upper layer, copying it up first, durably if `durable`.
+    async fn rename_in_upper(&self, from: &Path, to: &Path, durable: bool) -> Result<(), Error> {
+        if Self::exists_in(&self.upper, &whiteout(from)).await? {
+            return Err(not_found(from));
+        }
+        self.copy_up(from).await?;
+        if Self::exists_in(&self.upper, &whiteout(to)).await? {
+            self.upper.remove(&whiteout(to)).await?;
+        }
+        self.upper.create_dir_all(&parent(to)).await?;
+        match durable {
+            true => self.upper.rename_durable(from, to).await?,
+            false => self.upper.rename(from, to).await?,
+        }
+        self.hide(from).await
+    }
+}
+
+impl<U: Fs, L: Fs> Fs for OverlayFs<U, L> {
+    type File = OverlayFile<U::File, L::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        let whited_out = Self::exists_in(&self.upper, &whiteout(path)).await?;
+
+        if options.write || options.create || options.truncate {
+            if whited_out {
+                if !options.create {
+                    return Err(not_found(path));
+                }
+                self.upper.remove(&whiteout(path)).await?;
+            } else if !options.truncate {
+                self.copy_up(path).await?;
+            }
+            return Ok(OverlayFile::Upper(
+                self.upper.open_options(path, options).await?,
+            ));
+        }
+
+        if whited_out {
+            return Err(not_found(path));
+        }
+        match self.upper.open_options(path, options).await {
+            Ok(file) => Ok(OverlayFile::Upper(file)),
+            Err(e) if is_not_found(&e) => Ok(OverlayFile::Lower(
+                self.lower.open_options(path, options).await?,
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.upper.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        let path = path.clone();
+
+        Ok(stream! {
+            let mut shadowed = HashSet::new();
+
+            let upper = match self.upper.list(&path).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let mut upper = pin!(upper);
+            while let Some(meta) = upper.next().await {
+                // the listing of the upper layer must be complete for the whiteouts to apply
+                let meta = match meta {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                match meta.path.filename().and_then(|name| name.strip_prefix(WHITEOUT_PREFIX)) {
+                    Some(name) => {
+                        shadowed.insert(parent(&meta.path).child(name));
+                    }
+                    None => {
+                        shadowed.insert(meta.path.clone());
+                        yield Ok(meta);
+                    }
+                }
+            }
+
+            let lower = match self.lower.list(&path).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let mut lower = pin!(lower);
+            while let Some(meta) = lower.next().await {
+                let meta = match meta {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                if !shadowed.contains(&meta.path) {
+                    yield Ok(meta);
+                }
+            }
+        })
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        let upper = self.upper.list_delimited(path).await?;
+        let lower = self.lower.list_delimited(path).await?;
+
+        let mut shadowed = HashSet::new();
+        let mut files = vec![];
+        for meta in upper.files {
+            match meta
+                .path
+                .filename()
+                .and_then(|name| name.strip_prefix(WHITEOUT_PREFIX))
+            {
+                Some(name) => {
+                    shadowed.insert(parent(&meta.path).child(name));
+                }
+                None => {
+                    shadowed.insert(meta.path.clone());
+                    files.push(meta);
+                }
+            }
+        }
+        let mut prefixes = upper.prefixes.into_iter().collect::<BTreeSet<_>>();
+        files.extend(
+            lower
+                .files
+                .into_iter()
+                .filter(|meta| !shadowed.contains(&meta.path) && !prefixes.contains(&meta.path)),
+        );
+        prefixes.extend(
+            lower
+                .prefixes
+                .into_iter()
+                .filter(|prefix| !shadowed.contains(prefix)),
+        );
+        files.sort_by(|a, b| a.path.as_ref().cmp(b.path.as_ref()));
+        Ok(DelimitedListing {
+            files,
+            prefixes: prefixes.into_iter().collect(),
+        })
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        match self.upper.remove(path).await {
+            Err(e) if !is_not_found(&e) => return Err(e),
+            _ => {}
+        }
+        self.hide(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.metadata(path).await {
+            Err(e) if is_not_found(&e) => self.lower.metadata(path).await,
+            result => result,
+        }
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Ok(None);
+        }
+        match self.upper.file_type(path).await? {
+            Some(file_type) => Ok(Some(file_type)),
+            None => self.lower.file_type(path).await,
+        }
+    }
+
+    /// Unnamed files are opened in the upper layer, like every write.
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        Ok(OverlayFile::Upper(self.upper.anon_temp_file(dir).await?))
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.read_if_none_match(path, etag).await {
+            Err(e) if is_not_found(&e) => self.lower.read_if_none_match(path, etag).await,
+            result => result,
+        }
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.read_head(path, n).await {
+            Err(e) if is_not_found(&e) => self.lower.read_head(path, n).await,
+            result => result,
+        }
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.read_tail(path, n).await {
+            Err(e) if is_not_found(&e) => self.lower.read_tail(path, n).await,
+            result => result,
+        }
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        let result = async {
+            if Self::exists_in(&self.upper, &whiteout(path)).await? {
+                self.upper.remove(&whiteout(path)).await?;
+            }
+            self.upper.create_dir_all(&parent(path)).await
+        }
+        .await;
+        if let Err(e) = result {
+            return (Err(e), buf);
+        }
+        self.upper.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        let result = async {
+            if Self::exists_in(&self.upper, &whiteout(path)).await? {
+                self.upper.remove(&whiteout(path)).await?;
+            }
+            self.upper.create_dir_all(&parent(path)).await
+        }
+        .await;
+        if let Err(e) = result {
+            return (Err(e), buf);
+        }
+        self.upper.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        if Self::exists_in(&self.upper, &whiteout(from)).await? {
+            return Err(not_found(from));
+        }
+        if !Self::exists_in(&self.upper, from).await? {
+            let mut file = self
+                .lower
+                .open_options(from, OpenOptions::default())
+                .await?;
+            let (result, buf) = file.read_to_end_at(vec![], 0).await;
+            result?;
+            return self.write_file(to, buf).await.0.map(|_| ());
+        }
+        if Self::exists_in(&self.upper, &whiteout(to)).await? {
+            self.upper.remove(&whiteout(to)).await?;
+        }
+        self.upper.create_dir_all(&parent(to)).await?;
+        self.upper.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.rename_in_upper(from, to, false).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.rename_in_upper(from, to, true).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.get_xattr(path, name).await {
+            Err(e) if is_not_found(&e) => self.lower.get_xattr(path, name).await,
+            result => result,
+        }
+    }
+
+    /// Copies the file up first, without the attributes it had in the lower layer.
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        self.copy_up(path).await?;
+        self.upper.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.list_xattr(path).await {
+            Err(e) if is_not_found(&e) => self.lower.list_xattr(path).await,
+            result => result,
+        }
+    }
+
+    /// Locks the file where it is, in the upper layer if it was copied up, else in the lower one.
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.lock_shared(path).await {
+            Err(e) if is_not_found(&e) => self.lower.lock_shared(path).await,
+            result => result,
+        }
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.lock_exclusive(path).await {
+            Err(e) if is_not_found(&e) => self.lower.lock_exclusive(path).await,
+            result => result,
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.mmap(path).await {
+            Err(e) if is_not_found(&e) => self.lower.mmap(path).await,
+            result => result,
+        }
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.localize(path).await {
+            Err(e) if is_not_found(&e) => self.lower.localize(path).await,
+            result => result,
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        // the result of the upper layer must not be held while streaming from the lower one
+        let e = match self.upper.get_stream(path).await {
+            Ok(stream) => return Ok(Either::Left(stream)),
+            Err(e) => e,
+        };
+        if !is_not_found(&e) {
+            return Err(e);
+        }
+        Ok(Either::Right(self.lower.get_stream(path).await?))
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + MaybeSend,
+    {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            self.upper.remove(&whiteout(path)).await?;
+        }
+        self.upper.create_dir_all(&parent(path)).await?;
+        self.upper.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        if Self::exists_in(&self.upper, &whiteout(path)).await? {
+            return Err(not_found(path));
+        }
+        match self.upper.checksum(path, algorithm).await {
+            Err(e) if is_not_found(&e) => self.lower.checksum(path, algorithm).await,
+            result => result,
+        }
+    }
+
+    /// The URI of `path` in the upper layer, where it's written to.
+    fn uri(&self, path: &Path) -> String {
+        self.upper.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.upper.native_path(path)
+    }
+
+    fn is_remote(&self) -> bool {
+        self.upper.is_remote() || self.lower.is_remote()
+    }
+}
+
+/// The file of an [`OverlayFs`], opened from either of its layers. Files of the lower layer are
+/// only ever opened for reading, writes to them are rejected.
+pub enum OverlayFile<U, L> {
+    Upper(U),
+    Lower(L),
+}
+
+impl<U: Read, L: Read> Read for OverlayFile<U, L> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        match self {
+            OverlayFile::Upper(file) => file.read_exact_at(buf, pos).await,
+            OverlayFile::Lower(file) => file.read_exact_at(buf, pos).await,
+        }
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        match self {
+            OverlayFile::Upper(file) => file.read_to_end_at(buf, pos).await,
+            OverlayFile::Lower(file) => file.read_to_end_at(buf, pos).await,
+        }
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        match self {
+            OverlayFile::Upper(file) => file.size().await,
+            OverlayFile::Lower(file) => file.size().await,
+        }
+    }
+}
+
+impl<U: Write, L: MaybeSend> Write for OverlayFile<U, L> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        match self {
+            OverlayFile::Upper(file) => file.write_all(buf).await,
+            OverlayFile::Lower(_) => (
+                Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "write to the lower layer of an overlay",
+                )
+                .into()),
+                buf,
+            ),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            OverlayFile::Upper(file) => file.flush().await,
+            OverlayFile::Lower(_) => Ok(()),
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        match self {
+            OverlayFile::Upper(file) => file.close().await,
+            OverlayFile::Lower(_) => Ok(()),
+        }
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        match self {
+            OverlayFile::Upper(file) => file.close_with_meta().await,
+            OverlayFile::Lower(_) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "write to the lower layer of an overlay",
+            )
+            .into()),
+        }
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        match self {
+            OverlayFile::Upper(file) => file.allocate(len).await,
+            OverlayFile::Lower(_) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "allocate in the lower layer of an overlay",
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn overlay_fall_through_and_shadow() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions, OverlayFs, ScopedFs},
+            path::Path,
+            Read, Write,
+        };
+
+        async fn read<F: Fs>(fs: &F, path: &Path) -> Vec<u8> {
+            let mut file = fs.open(path).await.unwrap();
+            let (result, buf) = file.read_to_end_at(vec![], 0).await;
+            result.unwrap();
+            buf
+        }
+
+        async fn write<F: Fs>(fs: &F, path: &Path, content: &'static [u8]) {
+            let mut file = fs
+                .open_options(
+                    path,
+                    OpenOptions::default()
+                        .create(true)
+                        .truncate(true)
+                        .write(true),
+                )
+                .await
+                .unwrap();
+            file.write_all(content).await.0.unwrap();
+            file.close().await.unwrap();
+        }
+
+        let upper_dir = TempDir::new().unwrap();
+        let lower_dir = TempDir::new().unwrap();
+        let upper = ScopedFs::new(
+            TokioFs,
+            Path::from_filesystem_path(upper_dir.path()).unwrap(),
+        );
+        let lower = ScopedFs::new(
+            TokioFs,
+            Path::from_filesystem_path(lower_dir.path()).unwrap(),
+        );
+        write(&lower, &Path::from("a"), b"lower").await;
+        write(&lower, &Path::from("b"), b"lower").await;
+
+        let fs = OverlayFs::new(upper, lower);
+        assert_eq!(read(&fs, &Path::from("a")).await, b"lower");
+
+        write(&fs, &Path::from("a"), b"upper").await;
+        assert_eq!(read(&fs, &Path::from("a")).await, b"upper");
+        assert_eq!(read(fs.lower(), &Path::from("a")).await, b"lower");
+
+        fs.remove(&Path::from("b")).await.unwrap();
+        assert!(fs.open(&Path::from("b")).await.is_err());
+        assert!(lower_dir.path().join("b").exists());
+
+        let root = Path::default();
+        let mut stream = pin!(fs.list(&root).await.unwrap());
+        let mut paths = vec![];
+        while let Some(meta) = stream.next().await {
+            paths.push(meta.unwrap().path);
+        }
+        assert_eq!(paths, vec![Path::from("a")]);
+    }
+}
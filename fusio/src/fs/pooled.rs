@@ -0,0 +1,628 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ffi::OsStr,
+    io,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs, ListOptions,
+        OpenOptions, PresignedFs, TempGuard,
+    },
+    io::SharedReader,
+    path::Path,
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+/// How long a pooled handle is kept without being opened again by default.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct PooledHandle<F> {
+    reader: Arc<SharedReader<F>>,
+    meta: FileMeta,
+    last_used: Instant,
+}
+
+struct Pool<F> {
+    handles: HashMap<Path, PooledHandle<F>>,
+    /// Bumped by each change of a file: a handle opened across one may have been opened with
+    /// the metadata of the version before, it isn't pooled.
+    generation: u64,
+    /// The number of handles writing each file, which isn't pooled until they are all closed.
+    writers: HashMap<Path, usize>,
+}
+
+impl<F> Pool<F> {
+    fn invalidate(&mut self, path: &Path) {
+        self.handles.remove(path);
+        self.generation += 1;
+    }
+}
+
+/// Keeps the file a [`PooledFile`] writes out of the pool until it is closed or dropped.
+struct Writer<F> {
+    pool: Arc<Mutex<Pool<F>>>,
+    path: Path,
+}
+
+impl<F> Writer<F> {
+    fn new(pool: Arc<Mutex<Pool<F>>>, path: &Path) -> Self {
+        {
+            let mut pool = pool.lock().unwrap();
+            pool.invalidate(path);
+            *pool.writers.entry(path.clone()).or_default() += 1;
+        }
+        Self {
+            pool,
+            path: path.clone(),
+        }
+    }
+}
+
+impl<F> Drop for Writer<F> {
+    fn drop(&mut self) {
+        let mut pool = self.pool.lock().unwrap();
+        // the handles opened while the file was written were of the version before
+        pool.invalidate(&self.path);
+        if let Some(writers) = pool.writers.get_mut(&self.path) {
+            *writers -= 1;
+            if *writers == 0 {
+                pool.writers.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// A [`Fs`] keeping the handles of the files of another one opened for reading, so that opening
+/// the same file again returns a clone of the pooled handle instead of opening it anew, e.g. the
+/// files of a table opened by every query. The handles are opened with the size of the file
+/// taken from its metadata, which is kept along with them: S3 handles, which are cheap to open,
+/// then don't ask for it again, and the [`FileMeta`] of the version read, ETag included, is told
+/// by [`PooledFile::meta`].
+///
+/// The reads of a pooled handle take turns on it, as those of [`Fs::open_shared`] do. Handles
+/// not opened again for `idle_timeout` are dropped from the pool. Writes, removals, copies and
+/// renames through this file system drop the handles of the files they change, files opened
+/// before keep reading the handle they were given. Files opened for writing, with
+/// [`OpenOptions::direct`] or with [`Fs::open_with_len`] aren't pooled, nor are the files being
+/// written until their writing handles are closed or dropped.
+pub struct PooledFs<F: Fs> {
+    fs: F,
+    idle_timeout: Duration,
+    pool: Arc<Mutex<Pool<F::File>>>,
+}
+
+impl<F: Fs> PooledFs<F> {
+    pub fn new(fs: F) -> Self {
+        Self {
+            fs,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            pool: Arc::new(Mutex::new(Pool {
+                handles: HashMap::new(),
+                generation: 0,
+                writers: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Drops pooled handles once they haven't been opened again for `idle_timeout`, 60 seconds
+    /// by default.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    /// The number of handles in the pool, including those idle for longer than `idle_timeout`
+    /// that weren't dropped yet.
+    pub fn pooled_count(&self) -> usize {
+        self.pool.lock().unwrap().handles.len()
+    }
+
+    fn invalidate(&self, path: &Path) {
+        self.pool.lock().unwrap().invalidate(path);
+    }
+
+    /// Returns the pooled handle of `path` if it wasn't idle for too long, dropping those that
+    /// were, or else the generation of the pool a handle opened now is pooled at.
+    fn pooled(&self, path: &Path) -> Result<PooledFile<F::File>, u64> {
+        let now = Instant::now();
+        let mut pool = self.pool.lock().unwrap();
+        pool.handles
+            .retain(|_, handle| now.duration_since(handle.last_used) < self.idle_timeout);
+        let generation = pool.generation;
+        let handle = pool.handles.get_mut(path).ok_or(generation)?;
+        handle.last_used = now;
+        Ok(PooledFile {
+            handle: Handle::Pooled(handle.reader.clone(), handle.meta.clone()),
+            writer: None,
+        })
+    }
+}
+
+impl<F: Fs> Fs for PooledFs<F> {
+    type File = PooledFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        if options.write || options.create || options.truncate {
+            let writer = Writer::new(self.pool.clone(), path);
+            return Ok(PooledFile {
+                handle: Handle::Owned(self.fs.open_options(path, options).await?),
+                writer: Some(writer),
+            });
+        }
+        if options.direct || !options.read {
+            return Ok(PooledFile::owned(
+                self.fs.open_options(path, options).await?,
+            ));
+        }
+        let generation = match self.pooled(path) {
+            Ok(file) => return Ok(file),
+            Err(generation) => generation,
+        };
+
+        let meta = self.fs.metadata(path).await?;
+        let reader = Arc::new(SharedReader::new(
+            self.fs.open_with_len(path, meta.size).await?,
+        ));
+        let mut pool = self.pool.lock().unwrap();
+        // a file changed or written since the metadata was asked for may be of another version
+        if pool.generation == generation && !pool.writers.contains_key(path) {
+            pool.handles.insert(
+                path.clone(),
+                PooledHandle {
+                    reader: reader.clone(),
+                    meta: meta.clone(),
+                    last_used: Instant::now(),
+                },
+            );
+        }
+        Ok(PooledFile {
+            handle: Handle::Pooled(reader, meta),
+            writer: None,
+        })
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        Ok(PooledFile::owned(self.fs.open_with_len(path, len).await?))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list(path).await
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list_with(path, options).await
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.fs.list_page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.fs.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.fs.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.invalidate(path);
+        self.fs.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        {
+            let mut pool = self.pool.lock().unwrap();
+            pool.handles
+                .retain(|pooled, _| !pooled.prefix_matches(path));
+            pool.generation += 1;
+        }
+        self.fs.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.fs.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        Ok(PooledFile::owned(self.fs.anon_temp_file(dir).await?))
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.fs.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.fs.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        self.invalidate(path);
+        self.fs.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        self.invalidate(path);
+        self.fs.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(to);
+        self.fs.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.invalidate(to);
+        self.fs
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.invalidate(to);
+        self.fs.copy_range(from, range, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(from);
+        self.invalidate(to);
+        self.fs.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(from);
+        self.invalidate(to);
+        self.fs.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.invalidate(from);
+        self.invalidate(to);
+        self.fs.rename_with_meta(from, to).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        // the file system may rewrite the file to set it, as S3 copies objects onto themselves
+        self.invalidate(path);
+        self.fs.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.fs.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.fs.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.fs.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        self.invalidate(path);
+        self.fs.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.fs.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.fs.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+enum Handle<F> {
+    /// A handle of the pool, along with the metadata it was opened with.
+    Pooled(Arc<SharedReader<F>>, FileMeta),
+    /// A handle of its own, e.g. of a file opened for writing.
+    Owned(F),
+}
+
+/// The file of a [`PooledFs`]: a clone of a pooled handle, read-only, or a handle of its own for
+/// the files that aren't pooled.
+pub struct PooledFile<F> {
+    handle: Handle<F>,
+    writer: Option<Writer<F>>,
+}
+
+impl<F> PooledFile<F> {
+    fn owned(file: F) -> Self {
+        Self {
+            handle: Handle::Owned(file),
+            writer: None,
+        }
+    }
+
+    /// The metadata the pooled handle was opened with, `None` if the file isn't pooled.
+    pub fn meta(&self) -> Option<&FileMeta> {
+        match &self.handle {
+            Handle::Pooled(_, meta) => Some(meta),
+            Handle::Owned(_) => None,
+        }
+    }
+}
+
+fn read_only() -> Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "pooled files are opened for reading, open the file with `OpenOptions::write` to write it",
+    )
+    .into()
+}
+
+impl<F: Read> Read for PooledFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        match &mut self.handle {
+            Handle::Pooled(reader, _) => SharedReader::read_exact_at(reader, buf, pos).await,
+            Handle::Owned(file) => file.read_exact_at(buf, pos).await,
+        }
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        match &mut self.handle {
+            Handle::Pooled(reader, _) => SharedReader::read_to_end_at(reader, buf, pos).await,
+            Handle::Owned(file) => file.read_to_end_at(buf, pos).await,
+        }
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        match &self.handle {
+            Handle::Pooled(reader, _) => SharedReader::size(reader).await,
+            Handle::Owned(file) => file.size().await,
+        }
+    }
+}
+
+impl<F: Write> Write for PooledFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        match &mut self.handle {
+            Handle::Pooled(..) => (Err(read_only()), buf),
+            Handle::Owned(file) => file.write_all(buf).await,
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        match &mut self.handle {
+            Handle::Pooled(..) => Ok(()),
+            Handle::Owned(file) => file.flush().await,
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        let result = match &mut self.handle {
+            Handle::Pooled(..) => Ok(()),
+            Handle::Owned(file) => file.close().await,
+        };
+        // the file can be pooled again once written
+        self.writer.take();
+        result
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        let result = match &mut self.handle {
+            Handle::Pooled(..) => Err(read_only()),
+            Handle::Owned(file) => file.close_with_meta().await,
+        };
+        self.writer.take();
+        result
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        match &mut self.handle {
+            Handle::Pooled(..) => Err(read_only()),
+            Handle::Owned(file) => file.allocate(len).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "aws", feature = "tokio"))]
+    #[tokio::test]
+    async fn reopened_files_reuse_the_pooled_handle() {
+        use std::time::Duration;
+
+        use http::Method;
+
+        use crate::{
+            fs::{Fs, PooledFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Read,
+        };
+
+        let mock = MockS3::new();
+        mock.put_object("a", "hello world");
+        let fs = PooledFs::new(mock.fs());
+        let path = Path::from("a");
+        let heads = || mock.requests(Method::HEAD).len();
+
+        let file = fs.open(&path).await.unwrap();
+        assert_eq!(file.size().await.unwrap(), 11);
+        let etag = file.meta().unwrap().etag.clone();
+        assert!(etag.is_some());
+        assert_eq!(heads(), 1);
+        assert_eq!(fs.pooled_count(), 1);
+
+        // the size of the pooled handle is known, it isn't asked for again
+        let mut reopened = fs.open(&path).await.unwrap();
+        assert_eq!(reopened.size().await.unwrap(), 11);
+        assert_eq!(reopened.meta().unwrap().etag, etag);
+        let (result, buf) = reopened.read_exact_at(vec![0; 5], 6).await;
+        result.unwrap();
+        assert_eq!(buf, b"world");
+        assert_eq!(heads(), 1);
+
+        // a write drops the handle, the next open sees the new version
+        fs.write_file(&path, b"bye".to_vec()).await.0.unwrap();
+        assert_eq!(fs.pooled_count(), 0);
+        let written = heads();
+        let mut file = fs.open(&path).await.unwrap();
+        assert_eq!(file.size().await.unwrap(), 3);
+        assert_ne!(file.meta().unwrap().etag, etag);
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"bye");
+        assert_eq!(heads(), written + 1);
+
+        // idle handles are dropped
+        let fs = PooledFs::new(mock.fs()).idle_timeout(Duration::ZERO);
+        fs.open(&path).await.unwrap();
+        fs.open(&path).await.unwrap();
+        assert_eq!(heads(), written + 3);
+    }
+
+    #[cfg(all(feature = "aws", feature = "tokio"))]
+    #[tokio::test]
+    async fn files_being_written_are_not_pooled() {
+        use crate::{
+            fs::{Fs, OpenOptions, PooledFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Read, Write,
+        };
+
+        let mock = MockS3::new();
+        mock.put_object("a", "old");
+        let fs = PooledFs::new(mock.fs());
+        let path = Path::from("a");
+        let truncate = OpenOptions::default().create(true).truncate(true);
+
+        let mut writer = fs.open_options(&path, truncate).await.unwrap();
+        writer.write_all(&b"new"[..]).await.0.unwrap();
+        // the object isn't uploaded before the writer is closed, the read is of the old one
+        let mut file = fs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"old");
+        assert_eq!(fs.pooled_count(), 0);
+        writer.write_all(&b" content"[..]).await.0.unwrap();
+        writer.close().await.unwrap();
+
+        let mut file = fs.open(&path).await.unwrap();
+        assert_eq!(file.size().await.unwrap(), 11);
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"new content");
+        assert_eq!(fs.pooled_count(), 1);
+
+        // a writer dropped without being closed lets the file be pooled again too
+        let writer = fs.open_options(&path, truncate).await.unwrap();
+        assert_eq!(fs.pooled_count(), 0);
+        fs.open(&path).await.unwrap();
+        assert_eq!(fs.pooled_count(), 0);
+        drop(writer);
+        fs.open(&path).await.unwrap();
+        assert_eq!(fs.pooled_count(), 1);
+    }
+}
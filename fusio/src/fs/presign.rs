@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use url::Url;
+
+use crate::{fs::Fs, path::Path, Error, MaybeSend, MaybeSync};
+
+/// A file system whose files can be accessed over HTTP without credentials through presigned
+/// URLs, e.g. to hand out downloads to browsers. Generic code gets hold of it through
+/// [`Fs::as_presignable`], which is `None` for file systems that can't presign.
+pub trait PresignedFs: MaybeSend + MaybeSync {
+    /// Presigns a `GET` of the file at `path`, valid for `expires_in`.
+    fn presign_get(&self, path: &Path, expires_in: Duration) -> Result<Url, Error>;
+
+    /// Presigns a `PUT` of the file at `path`, valid for `expires_in`.
+    fn presign_put(&self, path: &Path, expires_in: Duration) -> Result<Url, Error>;
+}
+
+/// Returns `fs` as a [`PresignedFs`], failing with [`Error::Unsupported`] if it can't presign.
+pub(crate) fn presignable<F: Fs>(fs: &F) -> Result<&dyn PresignedFs, Error> {
+    fs.as_presignable().ok_or_else(|| Error::Unsupported {
+        message: "can't presign URLs in this file system".into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn s3_urls_are_presignable_and_local_ones_are_not() {
+        use std::time::Duration;
+
+        use bytes::Bytes;
+        use http::{Request, StatusCode};
+        use http_body_util::{BodyExt, Empty};
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, ScopedFs},
+            path::Path,
+            remotes::{aws::mock::MockS3, http::HttpClient},
+        };
+
+        let mock = MockS3::new();
+        mock.put_object("dir/a", "hello");
+
+        let s3 = ScopedFs::new(mock.fs(), Path::from("dir"));
+        let url = s3
+            .as_presignable()
+            .unwrap()
+            .presign_get(&Path::from("a"), Duration::from_secs(60))
+            .unwrap();
+        assert!(url.path().ends_with("/dir/a"));
+        assert!(url.query_pairs().any(|(name, _)| name == "X-Amz-Signature"));
+
+        let response = mock
+            .send_request(
+                Request::get(url.as_str())
+                    .body(Empty::<Bytes>::new())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+
+        assert!(TokioFs.as_presignable().is_none());
+        assert!(ScopedFs::new(TokioFs, Path::from("dir"))
+            .as_presignable()
+            .is_none());
+    }
+}
@@ -0,0 +1,556 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs, ListOptions,
+        OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, Read, Write,
+};
+
+struct CachedRange {
+    data: Vec<u8>,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct RangeLru {
+    /// The ETag of the version of each file whose ranges are cached, along with the ranges by
+    /// their start.
+    files: HashMap<Path, (String, BTreeMap<u64, CachedRange>)>,
+    order: BTreeMap<u64, (Path, u64)>,
+    size: u64,
+    tick: u64,
+}
+
+impl RangeLru {
+    /// Copies the bytes at `pos` of the version `etag` of `path` to `buf` if a single cached
+    /// range holds them all, marking it as recently used.
+    fn get(&mut self, path: &Path, etag: &str, pos: u64, buf: &mut [u8]) -> bool {
+        let Some((cached_etag, ranges)) = self.files.get_mut(path) else {
+            return false;
+        };
+        if cached_etag != etag {
+            return false;
+        }
+        let Some((start, range)) = ranges.range_mut(..=pos).next_back() else {
+            return false;
+        };
+        let offset = (pos - start) as usize;
+        if offset + buf.len() > range.data.len() {
+            return false;
+        }
+        buf.copy_from_slice(&range.data[offset..offset + buf.len()]);
+
+        self.tick += 1;
+        self.order.remove(&range.tick);
+        range.tick = self.tick;
+        self.order.insert(range.tick, (path.clone(), *start));
+        true
+    }
+
+    /// Records the bytes at `pos` of the version `etag` of `path`, evicting the least recently
+    /// used ranges to stay within `max_size`. Ranges larger than it are never cached.
+    fn insert(&mut self, path: &Path, etag: &str, pos: u64, data: Vec<u8>, max_size: u64) {
+        let size = data.len() as u64;
+        if size == 0 || size > max_size {
+            return;
+        }
+        self.retain_version(path, etag);
+        self.tick += 1;
+        let (_, ranges) = self
+            .files
+            .entry(path.clone())
+            .or_insert_with(|| (etag.to_string(), BTreeMap::new()));
+        let range = CachedRange {
+            data,
+            tick: self.tick,
+        };
+        if let Some(replaced) = ranges.insert(pos, range) {
+            self.order.remove(&replaced.tick);
+            self.size -= replaced.data.len() as u64;
+        }
+        self.order.insert(self.tick, (path.clone(), pos));
+        self.size += size;
+
+        while self.size > max_size {
+            let Some((_, (path, start))) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((_, ranges)) = self.files.get_mut(&path) {
+                if let Some(range) = ranges.remove(&start) {
+                    self.size -= range.data.len() as u64;
+                }
+                if ranges.is_empty() {
+                    self.files.remove(&path);
+                }
+            }
+        }
+    }
+
+    /// Drops the cached ranges of `path` unless they are of the version `etag`.
+    fn retain_version(&mut self, path: &Path, etag: &str) {
+        if self
+            .files
+            .get(path)
+            .is_some_and(|(cached_etag, _)| cached_etag != etag)
+        {
+            self.remove(path);
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some((_, ranges)) = self.files.remove(path) {
+            for range in ranges.into_values() {
+                self.order.remove(&range.tick);
+                self.size -= range.data.len() as u64;
+            }
+        }
+    }
+}
+
+/// A [`Fs`] keeping the byte ranges read from the files of another one in memory, so that
+/// reading the same range of the same version of a file again, or a part of it, is served without
+/// reading the file, e.g. the footers of Parquet files read by every query. Unlike a
+/// [`CachingFs`], which fetches whole files, only the ranges actually read are cached.
+///
+/// Ranges are keyed by the path and ETag of the file along with their position: the metadata of
+/// the file is checked on every open, and the ranges of other versions of it are dropped.
+/// Writes, removals, copies and renames drop the ranges of the files they change. The total size
+/// of the cached ranges is bounded by `max_size`, the least recently used ones being evicted once
+/// it is exceeded. Files without an ETag, like local ones, and files opened with
+/// [`Fs::open_with_len`], whose version isn't checked, are read without caching.
+///
+/// [`CachingFs`]: super::CachingFs
+pub struct RangeCachingFs<F> {
+    fs: F,
+    max_size: u64,
+    lru: Arc<Mutex<RangeLru>>,
+}
+
+impl<F: Fs> RangeCachingFs<F> {
+    pub fn new(fs: F, max_size: u64) -> Self {
+        Self {
+            fs,
+            max_size,
+            lru: Arc::new(Mutex::new(RangeLru::default())),
+        }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    /// The total size of the cached ranges, in bytes.
+    pub fn cached_size(&self) -> u64 {
+        self.lru.lock().unwrap().size
+    }
+
+    fn invalidate(&self, path: &Path) {
+        self.lru.lock().unwrap().remove(path);
+    }
+
+    fn uncached(&self, file: F::File) -> RangeCachingFile<F::File> {
+        RangeCachingFile {
+            file,
+            version: None,
+            max_size: self.max_size,
+            lru: self.lru.clone(),
+        }
+    }
+}
+
+impl<F: Fs> Fs for RangeCachingFs<F> {
+    type File = RangeCachingFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        if options.write || options.create || options.truncate {
+            self.invalidate(path);
+            return Ok(self.uncached(self.fs.open_options(path, options).await?));
+        }
+
+        let meta = self.fs.metadata(path).await?;
+        let file = self.fs.open_options(path, options).await?;
+        let Some(etag) = meta.etag else {
+            return Ok(self.uncached(file));
+        };
+        self.lru.lock().unwrap().retain_version(path, &etag);
+        Ok(RangeCachingFile {
+            file,
+            version: Some((path.clone(), etag)),
+            max_size: self.max_size,
+            lru: self.lru.clone(),
+        })
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        Ok(self.uncached(self.fs.open_with_len(path, len).await?))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list(path).await
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list_with(path, options).await
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.fs.list_page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.fs.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.fs.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.invalidate(path);
+        self.fs.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        {
+            let mut lru = self.lru.lock().unwrap();
+            let cached: Vec<Path> = lru
+                .files
+                .keys()
+                .filter(|cached| cached.prefix_matches(path))
+                .cloned()
+                .collect();
+            for cached in cached {
+                lru.remove(&cached);
+            }
+        }
+        self.fs.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.fs.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        Ok(self.uncached(self.fs.anon_temp_file(dir).await?))
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.fs.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.fs.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        self.invalidate(path);
+        self.fs.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        self.invalidate(path);
+        self.fs.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(to);
+        self.fs.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.invalidate(to);
+        self.fs
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.invalidate(to);
+        self.fs.copy_range(from, range, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(from);
+        self.invalidate(to);
+        self.fs.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.invalidate(from);
+        self.invalidate(to);
+        self.fs.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.invalidate(from);
+        self.invalidate(to);
+        self.fs.rename_with_meta(from, to).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        // the file system may rewrite the file to set it, as S3 copies objects onto themselves
+        self.invalidate(path);
+        self.fs.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.fs.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.fs.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.fs.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        self.invalidate(path);
+        self.fs.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.fs.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.fs.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+/// The file of a [`RangeCachingFs`], whose reads are served from the cached ranges of its
+/// version when they hold them.
+pub struct RangeCachingFile<F> {
+    file: F,
+    /// The path and ETag of the file, `None` if its reads aren't cached.
+    version: Option<(Path, String)>,
+    max_size: u64,
+    lru: Arc<Mutex<RangeLru>>,
+}
+
+impl<F: Read> Read for RangeCachingFile<F> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let Some((path, etag)) = &self.version else {
+            return self.file.read_exact_at(buf, pos).await;
+        };
+        if self
+            .lru
+            .lock()
+            .unwrap()
+            .get(path, etag, pos, buf.as_slice_mut())
+        {
+            return (Ok(()), buf);
+        }
+
+        let (result, buf) = self.file.read_exact_at(buf, pos).await;
+        if result.is_ok() {
+            self.lru.lock().unwrap().insert(
+                path,
+                etag,
+                pos,
+                buf.as_slice().to_vec(),
+                self.max_size,
+            );
+        }
+        (result, buf)
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        self.file.read_to_end_at(buf, pos).await
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        self.file.size().await
+    }
+}
+
+impl<F: Write> Write for RangeCachingFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        self.file.write_all(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        self.file.close().await
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        self.file.close_with_meta().await
+    }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        self.file.allocate(len).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "aws", feature = "tokio"))]
+    #[tokio::test]
+    async fn ranges_are_served_from_memory_until_the_etag_changes() {
+        use http::Method;
+
+        use crate::{
+            fs::{Fs, OpenOptions, RangeCachingFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Read,
+        };
+
+        async fn read(file: &mut impl Read, len: usize, pos: u64) -> Vec<u8> {
+            let (result, buf) = file.read_exact_at(vec![0; len], pos).await;
+            result.unwrap();
+            buf
+        }
+
+        let mock = MockS3::new();
+        mock.put_object("a", "hello world");
+        let fs = RangeCachingFs::new(mock.fs(), 8);
+        let path = Path::from("a");
+        let gets = || mock.requests(Method::GET).len();
+
+        let mut file = fs
+            .open_options(&path, OpenOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(read(&mut file, 5, 0).await, b"hello");
+        let first = gets();
+        assert_eq!(read(&mut file, 5, 0).await, b"hello");
+        // a part of a cached range
+        assert_eq!(read(&mut file, 3, 1).await, b"ell");
+        assert_eq!(gets(), first);
+        assert_eq!(fs.cached_size(), 5);
+
+        // the least recently used range is evicted beyond 8 bytes
+        assert_eq!(read(&mut file, 5, 6).await, b"world");
+        assert_eq!(fs.cached_size(), 5);
+        assert_eq!(read(&mut file, 5, 0).await, b"hello");
+        assert_eq!(gets(), first + 2);
+
+        // a new version drops the ranges of the previous one
+        mock.put_object("a", "HELLO WORLD");
+        let mut file = fs
+            .open_options(&path, OpenOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(fs.cached_size(), 0);
+        assert_eq!(read(&mut file, 5, 0).await, b"HELLO");
+        assert_eq!(gets(), first + 3);
+
+        // as do writes
+        fs.write_file(&path, b"bye".to_vec()).await.0.unwrap();
+        assert_eq!(fs.cached_size(), 0);
+    }
+}
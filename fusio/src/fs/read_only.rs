@@ -0,0 +1,418 @@
+use std::{borrow::Cow, ffi::OsStr, future::Future, io, ops::Range, path::PathBuf, time::Duration};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+use url::Url;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        presignable, DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs,
+        ListOptions, OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf, IoBufMut, MaybeSend, Read, Write,
+};
+
+fn permission_denied(operation: &str, path: Option<&Path>) -> Error {
+    let message = match path {
+        Some(path) => format!("{} \"{}\" on a read-only file system", operation, path),
+        None => format!("{} on a read-only file system", operation),
+    };
+    io::Error::new(io::ErrorKind::PermissionDenied, message).into()
+}
+
+/// A [`Fs`] that only allows non-mutating operations on the underlying file system. Anything
+/// that would modify it fails with [`io::ErrorKind::PermissionDenied`].
+pub struct ReadOnlyFs<F> {
+    fs: F,
+}
+
+impl<F: Fs> ReadOnlyFs<F> {
+    pub fn new(fs: F) -> Self {
+        Self { fs }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+}
+
+impl<F: Fs> Fs for ReadOnlyFs<F> {
+    type File = ReadOnlyFile<F::File>;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        if options.write || options.create || options.truncate {
+            return Err(permission_denied("open for write", Some(path)));
+        }
+        Ok(ReadOnlyFile {
+            file: self.fs.open_options(path, options).await?,
+        })
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        Ok(ReadOnlyFile {
+            file: self.fs.open_with_len(path, len).await?,
+        })
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        Err(permission_denied("create directory", Some(path)))
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list(path).await
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        self.fs.list_with(path, options).await
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.fs.list_page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.fs.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.fs.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        Err(permission_denied("remove", Some(path)))
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        Err(permission_denied("remove", Some(path)))
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.fs.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        Err(permission_denied("create a temporary file in", Some(dir)))
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.fs.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.fs.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        (Err(permission_denied("write", Some(path))), buf)
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        (Err(permission_denied("write", Some(path))), buf)
+    }
+
+    async fn copy(&self, _: &Path, to: &Path) -> Result<(), Error> {
+        Err(permission_denied("copy", Some(to)))
+    }
+
+    async fn copy_if_source_matches(&self, _: &Path, to: &Path, _: &str) -> Result<(), Error> {
+        Err(permission_denied("copy", Some(to)))
+    }
+
+    async fn copy_range(&self, _: &Path, _: Range<u64>, to: &Path) -> Result<(), Error> {
+        Err(permission_denied("copy", Some(to)))
+    }
+
+    async fn rename(&self, from: &Path, _: &Path) -> Result<(), Error> {
+        Err(permission_denied("rename", Some(from)))
+    }
+
+    async fn rename_durable(&self, from: &Path, _: &Path) -> Result<(), Error> {
+        Err(permission_denied("rename", Some(from)))
+    }
+
+    async fn rename_with_meta(&self, from: &Path, _: &Path) -> Result<FileMeta, Error> {
+        Err(permission_denied("rename", Some(from)))
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, _: &str, _: &[u8]) -> Result<(), Error> {
+        Err(permission_denied(
+            "set an extended attribute of",
+            Some(path),
+        ))
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.fs.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.fs.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.fs.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        _stream: S,
+        _size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + MaybeSend,
+    {
+        Err(permission_denied("write", Some(path)))
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.fs.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.fs.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable().map(|_| self as &dyn PresignedFs)
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+impl<F: Fs> PresignedFs for ReadOnlyFs<F> {
+    fn presign_get(&self, path: &Path, expires_in: Duration) -> Result<Url, Error> {
+        presignable(&self.fs)?.presign_get(path, expires_in)
+    }
+
+    fn presign_put(&self, path: &Path, _: Duration) -> Result<Url, Error> {
+        Err(permission_denied("presign a write of", Some(path)))
+    }
+}
+
+/// The file of a [`ReadOnlyFs`], every write to it is rejected.
+pub struct ReadOnlyFile<F> {
+    file: F,
+}
+
+impl<F: Read> Read for ReadOnlyFile<F> {
+    fn read_exact_at<B: IoBufMut>(
+        &mut self,
+        buf: B,
+        pos: u64,
+    ) -> impl Future<Output = (Result<(), Error>, B)> + MaybeSend {
+        self.file.read_exact_at(buf, pos)
+    }
+
+    fn read_to_end_at(
+        &mut self,
+        buf: Vec<u8>,
+        pos: u64,
+    ) -> impl Future<Output = (Result<(), Error>, Vec<u8>)> + MaybeSend {
+        self.file.read_to_end_at(buf, pos)
+    }
+
+    fn size(&self) -> impl Future<Output = Result<u64, Error>> + MaybeSend {
+        self.file.size()
+    }
+}
+
+impl<F: MaybeSend> Write for ReadOnlyFile<F> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        (Err(permission_denied("write", None)), buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        Err(permission_denied("write", None))
+    }
+
+    async fn allocate(&mut self, _len: u64) -> Result<(), Error> {
+        Err(permission_denied("allocate", None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn read_only_fs_rejects_writes() {
+        use std::{io, pin::pin};
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions, ReadOnlyFs},
+            path::Path,
+            Error, Read, Write,
+        };
+
+        fn is_permission_denied(result: Result<impl Sized, Error>) -> bool {
+            matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::PermissionDenied)
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let root = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let path = root.child("file");
+
+        let mut file = TokioFs
+            .open_options(&path, OpenOptions::default().create(true))
+            .await
+            .unwrap();
+        file.write_all(&b"hello"[..]).await.0.unwrap();
+        file.close().await.unwrap();
+
+        let fs = ReadOnlyFs::new(TokioFs);
+
+        let mut file = fs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello");
+        assert!(is_permission_denied(file.write_all(&b"world"[..]).await.0));
+
+        let mut stream = pin!(fs.list(&root).await.unwrap());
+        assert_eq!(stream.next().await.unwrap().unwrap().path, path);
+
+        assert!(is_permission_denied(
+            fs.open_options(&path, OpenOptions::default().write(true))
+                .await
+        ));
+        assert!(is_permission_denied(
+            fs.open_options(&root.child("new"), OpenOptions::default().create(true))
+                .await
+        ));
+        assert!(is_permission_denied(
+            fs.create_dir_all(&root.child("dir")).await
+        ));
+        assert!(is_permission_denied(fs.remove(&path).await));
+        assert!(is_permission_denied(fs.remove_dir_all(&root).await));
+        assert!(is_permission_denied(fs.anon_temp_file(&root).await));
+
+        let other = root.child("other");
+        assert!(is_permission_denied(fs.copy(&path, &other).await));
+        assert!(is_permission_denied(
+            fs.copy_if_source_matches(&path, &other, "etag").await
+        ));
+        assert!(is_permission_denied(
+            fs.copy_range(&path, 0..2, &other).await
+        ));
+        assert!(is_permission_denied(fs.rename(&path, &other).await));
+        assert!(is_permission_denied(fs.rename_durable(&path, &other).await));
+        assert!(is_permission_denied(
+            fs.rename_with_meta(&path, &other).await
+        ));
+        assert!(is_permission_denied(
+            fs.set_xattr(&path, "user.fusio", b"value").await
+        ));
+        assert!(is_permission_denied(
+            fs.write_file(&other, b"world".to_vec()).await.0
+        ));
+        assert!(is_permission_denied(
+            fs.write_file_atomic(&other, b"world".to_vec()).await.0
+        ));
+        #[cfg(feature = "checksum")]
+        assert!(is_permission_denied(
+            fs.write_file_with_checksum(&other, b"world".to_vec())
+                .await
+                .0
+        ));
+        #[cfg(feature = "bytes")]
+        {
+            use bytes::Bytes;
+            use futures_util::stream;
+
+            let chunks = [Ok(Bytes::from("world"))];
+            assert!(is_permission_denied(
+                fs.put_stream(&other, stream::iter(chunks), None).await
+            ));
+        }
+
+        assert_eq!(
+            std::fs::read(tmp_dir.path().join("file")).unwrap(),
+            b"hello"
+        );
+        assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 1);
+    }
+}
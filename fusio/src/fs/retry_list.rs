@@ -0,0 +1,399 @@
+use std::{borrow::Cow, ffi::OsStr, io, ops::Range, path::PathBuf, time::Duration};
+
+use async_stream::stream;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::future::Either;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        sleep, DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs,
+        OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf,
+};
+
+/// How many times a page is requested again by default.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// How long the first retry of a page waits by default, each following one twice as long.
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// How many files a page of [`RetryingListFs::list`] holds at most, as many as S3 lists at once.
+const PAGE_SIZE: usize = 1000;
+
+/// A [`Fs`] listing files with [`Fs::list_page`] of the underlying file system page after page,
+/// so that a page failing with a transient error, e.g. a `503 Slow Down` of S3 or a connection
+/// reset, is requested again from the cursor of the last page listed instead of failing the
+/// whole listing. A page is retried up to `max_retries` times, waiting longer before each retry,
+/// then the listing yields the error and ends. Calls of [`Fs::list_page`] are retried alike.
+///
+/// File systems that can't list by pages are listed with their [`Fs::list`], without retries.
+/// The other operations, including those listing files themselves like [`Fs::dir_stats`], are
+/// the underlying file system's.
+pub struct RetryingListFs<F> {
+    fs: F,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl<F: Fs> RetryingListFs<F> {
+    pub fn new(fs: F) -> Self {
+        Self {
+            fs,
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+
+    /// Requests a failing page at most `max_retries` more times, 3 by default.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Waits `backoff` before the first retry of a page, 100 milliseconds by default, and twice
+    /// as long as the previous wait before each following one.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    async fn page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            match self.fs.list_page(path, cursor, page_size).await {
+                Err(e) if retries < self.max_retries && is_transient(&e) => {
+                    retries += 1;
+                    sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether `error` may not happen again if the operation is retried: a server error or a
+/// throttling of S3, or a connection broken on the way.
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Io(e) => matches!(
+            e.kind(),
+            io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        #[cfg(feature = "aws")]
+        Error::S3Error(crate::remotes::aws::S3Error::HttpError(
+            crate::remotes::http::HttpError::HttpNotSuccess { status, .. },
+        )) => status.is_server_error() || *status == http::StatusCode::TOO_MANY_REQUESTS,
+        #[cfg(all(feature = "aws", feature = "tokio-http"))]
+        Error::S3Error(crate::remotes::aws::S3Error::HttpError(
+            crate::remotes::http::HttpError::Reqwest(e),
+        )) => e.is_timeout() || e.is_connect(),
+        _ => false,
+    }
+}
+
+impl<F: Fs> Fs for RetryingListFs<F> {
+    type File = F::File;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        self.fs.open_options(path, options).await
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        self.fs.open_with_len(path, len).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.create_dir_all(path).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        let first = match self.page(path, None, PAGE_SIZE).await {
+            Ok(page) => page,
+            Err(Error::Unsupported { .. }) => return Ok(Either::Left(self.fs.list(path).await?)),
+            Err(e) => return Err(e),
+        };
+        let path = path.clone();
+        Ok(Either::Right(stream! {
+            let (mut entries, mut cursor) = first;
+            loop {
+                for entry in entries {
+                    yield Ok(entry);
+                }
+                let Some(next) = cursor else {
+                    break;
+                };
+                match self.page(&path, Some(&next), PAGE_SIZE).await {
+                    Ok(page) => (entries, cursor) = page,
+                    Err(e) => {
+                        yield Err(e);
+                        break;
+                    }
+                }
+            }
+        }))
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        self.page(path, cursor, page_size).await
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        self.fs.list_delimited(path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        self.fs.list_versions(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove_dir_all(path).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        self.fs.metadata(path).await
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.fs.file_type(path).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        self.fs.anon_temp_file(dir).await
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.fs.dir_stats(path).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        self.fs.read_if_none_match(path, etag).await
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_head(path, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_tail(path, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        self.fs.write_file(path, buf).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        self.fs.write_file_with_checksum(path, buf).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.copy(from, to).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.fs
+            .copy_if_source_matches(from, to, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.fs.copy_range(from, range, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.rename(from, to).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.rename_durable(from, to).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        self.fs.rename_with_meta(from, to).await
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.fs.get_xattr(path, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.fs.set_xattr(path, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.fs.list_xattr(path).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_shared(path).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_exclusive(path).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.fs.mmap(path).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.fs.localize(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        self.fs.get_stream(path).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        self.fs.put_stream(path, stream, size_hint).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.fs.checksum(path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.fs.list_digest(path).await
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.fs.uri(path)
+    }
+
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        self.fs.native_path(path)
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn failed_pages_are_requested_again() {
+        use std::{pin::pin, time::Duration};
+
+        use futures_util::StreamExt;
+        use http::{Method, StatusCode};
+
+        use crate::{
+            fs::{Fs, RetryingListFs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        mock.set_list_page_size(2);
+        for i in 0..5 {
+            mock.put_object(&format!("dir/{}", i), vec![0; i]);
+        }
+        let dir = Path::from("dir");
+        let expected = (0..5).map(|i| dir.child(i.to_string())).collect::<Vec<_>>();
+
+        // the second page fails once, and is requested again from the cursor of the first
+        mock.fail_next_pages(1);
+        let fs = RetryingListFs::new(mock.fs()).backoff(Duration::from_millis(1));
+        let mut listed = vec![];
+        let mut stream = pin!(fs.list(&dir).await.unwrap());
+        while let Some(meta) = stream.next().await {
+            listed.push(meta.unwrap().path);
+        }
+        assert_eq!(listed, expected);
+        assert_eq!(
+            mock.requests(Method::GET),
+            [
+                StatusCode::OK,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::OK,
+                StatusCode::OK
+            ]
+        );
+
+        // a page failing more often than it is retried ends the listing with the error
+        mock.fail_next_pages(3);
+        let fs = RetryingListFs::new(mock.fs())
+            .max_retries(2)
+            .backoff(Duration::from_millis(1));
+        let mut listed = vec![];
+        let mut stream = pin!(fs.list(&dir).await.unwrap());
+        while let Some(meta) = stream.next().await {
+            listed.push(meta);
+        }
+        assert_eq!(listed.len(), 3);
+        assert!(listed[..2].iter().all(Result::is_ok));
+        assert!(listed[2].is_err());
+    }
+}
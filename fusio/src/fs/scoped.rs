@@ -0,0 +1,483 @@
+use std::{borrow::Cow, ffi::OsStr, io, ops::Range, path::PathBuf, pin::pin, time::Duration};
+
+use async_stream::stream;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use url::Url;
+
+#[cfg(feature = "checksum")]
+use crate::fs::ChecksumAlgorithm;
+#[cfg(feature = "mmap")]
+use crate::fs::Mmap;
+use crate::{
+    fs::{
+        presignable, DelimitedListing, DirStats, FileLock, FileMeta, FileType, FileVersion, Fs,
+        ListOptions, OpenOptions, PresignedFs, TempGuard,
+    },
+    path::Path,
+    Error, IoBuf,
+};
+
+/// A [`Fs`] that scopes every operation of the underlying file system to `prefix`, like a
+/// `chroot`. Paths given to it are relative to the prefix and paths listed from it are stripped
+/// of it, so nothing outside the prefix can be reached.
+pub struct ScopedFs<F> {
+    fs: F,
+    prefix: Path,
+}
+
+impl<F: Fs> ScopedFs<F> {
+    pub fn new(fs: F, prefix: Path) -> Self {
+        Self { fs, prefix }
+    }
+
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fs
+    }
+
+    fn scoped(&self, path: &Path) -> Result<Path, Error> {
+        let mut scoped = self.prefix.clone();
+        for part in path.parts() {
+            if matches!(part.as_ref(), "." | "..") {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("path \"{}\" escapes scope \"{}\"", path, self.prefix),
+                )
+                .into());
+            }
+            scoped = scoped.child(part);
+        }
+        Ok(scoped)
+    }
+
+    fn unscoped(&self, path: &Path) -> Result<Path, Error> {
+        let parts = path.prefix_match(&self.prefix).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("path \"{}\" is out of scope \"{}\"", path, self.prefix),
+            )
+        })?;
+        Ok(Path::from_iter(parts))
+    }
+}
+
+impl<F: Fs> Fs for ScopedFs<F> {
+    type File = F::File;
+
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        self.fs.open_options(&self.scoped(path)?, options).await
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        self.fs.open_with_len(&self.scoped(path)?, len).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.create_dir_all(&self.scoped(path)?).await
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        let path = self.scoped(path)?;
+
+        Ok(stream! {
+            let stream = match self.fs.list(&path).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let mut stream = pin!(stream);
+            while let Some(meta) = stream.next().await {
+                yield meta.and_then(|mut meta| {
+                    meta.path = self.unscoped(&meta.path)?;
+                    Ok(meta)
+                });
+            }
+        })
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        // the root of the scope is matched as given, those differing by case alone are out of it
+        let options =
+            options.case_insensitive(options.case_insensitive && path.filename().is_some());
+        let path = self.scoped(path)?;
+
+        Ok(stream! {
+            let stream = match self.fs.list_with(&path, options).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let mut stream = pin!(stream);
+            while let Some(meta) = stream.next().await {
+                // relative paths are the same in and out of the scope
+                yield meta.and_then(|mut meta| {
+                    if !options.relative {
+                        meta.path = self.unscoped(&meta.path)?;
+                    }
+                    Ok(meta)
+                });
+            }
+        })
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        let (mut entries, cursor) = self
+            .fs
+            .list_page(&self.scoped(path)?, cursor, page_size)
+            .await?;
+        for meta in &mut entries {
+            meta.path = self.unscoped(&meta.path)?;
+        }
+        Ok((entries, cursor))
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        let mut listing = self.fs.list_delimited(&self.scoped(path)?).await?;
+        for meta in &mut listing.files {
+            meta.path = self.unscoped(&meta.path)?;
+        }
+        for prefix in &mut listing.prefixes {
+            *prefix = self.unscoped(prefix)?;
+        }
+        Ok(listing)
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        let mut versions = self.fs.list_versions(&self.scoped(path)?).await?;
+        for version in &mut versions {
+            version.meta.path = self.unscoped(&version.meta.path)?;
+        }
+        Ok(versions)
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove(&self.scoped(path)?).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        self.fs.remove_dir_all(&self.scoped(path)?).await
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        let mut meta = self.fs.metadata(&self.scoped(path)?).await?;
+        meta.path = self.unscoped(&meta.path)?;
+        Ok(meta)
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        self.fs.file_type(&self.scoped(path)?).await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        self.fs.anon_temp_file(&self.scoped(dir)?).await
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        self.fs.dir_stats(&self.scoped(path)?).await
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        match self
+            .fs
+            .read_if_none_match(&self.scoped(path)?, etag)
+            .await?
+        {
+            Some((mut meta, buf)) => {
+                meta.path = self.unscoped(&meta.path)?;
+                Ok(Some((meta, buf)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_head(&self.scoped(path)?, n).await
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        self.fs.read_tail(&self.scoped(path)?, n).await
+    }
+
+    async fn write_file<B: IoBuf>(&self, path: &Path, buf: B) -> (Result<FileMeta, Error>, B) {
+        let path = match self.scoped(path) {
+            Ok(path) => path,
+            Err(e) => return (Err(e), buf),
+        };
+        let (result, buf) = self.fs.write_file(&path, buf).await;
+        (
+            result.and_then(|mut meta| {
+                meta.path = self.unscoped(&meta.path)?;
+                Ok(meta)
+            }),
+            buf,
+        )
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        let path = match self.scoped(path) {
+            Ok(path) => path,
+            Err(e) => return (Err(e), buf),
+        };
+        let (result, buf) = self.fs.write_file_with_checksum(&path, buf).await;
+        (
+            result.and_then(|(mut meta, digest)| {
+                meta.path = self.unscoped(&meta.path)?;
+                Ok((meta, digest))
+            }),
+            buf,
+        )
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.copy(&self.scoped(from)?, &self.scoped(to)?).await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.fs
+            .copy_if_source_matches(&self.scoped(from)?, &self.scoped(to)?, expected_etag)
+            .await
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        self.fs
+            .copy_range(&self.scoped(from)?, range, &self.scoped(to)?)
+            .await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs.rename(&self.scoped(from)?, &self.scoped(to)?).await
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.fs
+            .rename_durable(&self.scoped(from)?, &self.scoped(to)?)
+            .await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        let mut meta = self
+            .fs
+            .rename_with_meta(&self.scoped(from)?, &self.scoped(to)?)
+            .await?;
+        meta.path = self.unscoped(&meta.path)?;
+        Ok(meta)
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.fs.get_xattr(&self.scoped(path)?, name).await
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.fs.set_xattr(&self.scoped(path)?, name, value).await
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        self.fs.list_xattr(&self.scoped(path)?).await
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_shared(&self.scoped(path)?).await
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        self.fs.lock_exclusive(&self.scoped(path)?).await
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        self.fs.mmap(&self.scoped(path)?).await
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        self.fs.localize(&self.scoped(path)?).await
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        let path = self.scoped(path)?;
+
+        Ok(stream! {
+            let stream = match self.fs.get_stream(&path).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let mut stream = pin!(stream);
+            while let Some(chunk) = stream.next().await {
+                yield chunk;
+            }
+        })
+    }
+
+    #[cfg(feature = "bytes")]
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + crate::MaybeSend,
+    {
+        let mut meta = self
+            .fs
+            .put_stream(&self.scoped(path)?, stream, size_hint)
+            .await?;
+        meta.path = self.unscoped(&meta.path)?;
+        Ok(meta)
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        self.fs.checksum(&self.scoped(path)?, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        self.fs.list_digest(&self.scoped(path)?).await
+    }
+
+    /// The URI of `path` under the prefix. It only names the path, so one escaping the scope
+    /// isn't rejected but named as it is.
+    fn uri(&self, path: &Path) -> String {
+        let scoped = path
+            .parts()
+            .fold(self.prefix.clone(), |scoped, part| scoped.child(part));
+        self.fs.uri(&scoped)
+    }
+
+    /// The native path of `path` under the prefix, which like [`ScopedFs::uri`] isn't rejected
+    /// when it escapes the scope.
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        let scoped = path
+            .parts()
+            .fold(self.prefix.clone(), |scoped, part| scoped.child(part));
+        Cow::Owned(self.fs.native_path(&scoped).into_owned())
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        self.fs.as_presignable().map(|_| self as &dyn PresignedFs)
+    }
+
+    fn is_remote(&self) -> bool {
+        self.fs.is_remote()
+    }
+}
+
+impl<F: Fs> PresignedFs for ScopedFs<F> {
+    fn presign_get(&self, path: &Path, expires_in: Duration) -> Result<Url, Error> {
+        presignable(&self.fs)?.presign_get(&self.scoped(path)?, expires_in)
+    }
+
+    fn presign_put(&self, path: &Path, expires_in: Duration) -> Result<Url, Error> {
+        presignable(&self.fs)?.presign_put(&self.scoped(path)?, expires_in)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn scoped_fs_cannot_escape() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions, ScopedFs},
+            path::Path,
+            Read, Write,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let root = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+
+        let fs = TokioFs;
+        fs.create_dir_all(&root.child("tenant")).await.unwrap();
+        fs.create_dir_all(&root.child("sibling")).await.unwrap();
+        let mut secret = fs
+            .open_options(
+                &root.child("sibling").child("secret"),
+                OpenOptions::default().create(true),
+            )
+            .await
+            .unwrap();
+        secret.write_all(&b"secret"[..]).await.0.unwrap();
+        secret.close().await.unwrap();
+
+        let scoped = ScopedFs::new(TokioFs, root.child("tenant"));
+        let mut file = scoped
+            .open_options(&Path::from("a.txt"), OpenOptions::default().create(true))
+            .await
+            .unwrap();
+        file.write_all(&b"hello"[..]).await.0.unwrap();
+        file.close().await.unwrap();
+        assert!(tmp_dir.path().join("tenant").join("a.txt").exists());
+
+        let mut file = scoped.open(&Path::from("a.txt")).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello");
+
+        assert!(Path::parse("../sibling/secret").is_err());
+        assert!(scoped
+            .open(&Path::from_iter(["..", "sibling", "secret"]))
+            .await
+            .is_err());
+        assert!(scoped
+            .open(&Path::from_iter(["sibling", "secret"]))
+            .await
+            .is_err());
+
+        let root = Path::default();
+        let mut stream = pin!(scoped.list(&root).await.unwrap());
+        let mut paths = vec![];
+        while let Some(meta) = stream.next().await {
+            paths.push(meta.unwrap().path);
+        }
+        assert_eq!(paths, vec![Path::from("a.txt")]);
+    }
+}
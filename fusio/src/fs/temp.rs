@@ -0,0 +1,130 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Returns `prefix` followed by a random suffix. Names are distinct within the process, the
+/// counter is mixed in, and unlikely to clash across processes, the hasher is randomly keyed.
+pub(crate) fn temp_name(prefix: &str) -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{}{:016x}", prefix, hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn concurrent_temp_files_are_distinct() {
+        use std::collections::HashSet;
+
+        use futures_util::future::join_all;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        async fn paths<F: Fs>(fs: &F, dir: &Path) -> HashSet<Path> {
+            let files = join_all((0..64).map(|_| fs.temp_file(dir, "tmp-"))).await;
+            files
+                .into_iter()
+                .map(|result| {
+                    let (path, _) = result.unwrap();
+                    assert!(path.filename().unwrap().starts_with("tmp-"));
+                    path
+                })
+                .collect()
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        assert_eq!(paths(&TokioFs, &dir).await.len(), 64);
+
+        let mock = MockS3::new();
+        assert_eq!(paths(&mock.fs(), &Path::from("dir")).await.len(), 64);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn write_file_atomic_leaves_no_temp_file() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, Read};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let path = dir.child("file");
+
+        for content in [&b"hello"[..], &b"bye"[..]] {
+            let (result, _) = TokioFs.write_file_atomic(&path, content.to_vec()).await;
+            assert_eq!(result.unwrap().size, content.len() as u64);
+
+            let mut file = TokioFs.open(&path).await.unwrap();
+            let (result, buf) = file.read_to_end_at(vec![], 0).await;
+            result.unwrap();
+            assert_eq!(buf, content);
+        }
+
+        let mut entries = pin!(TokioFs.list(&dir).await.unwrap());
+        assert_eq!(entries.next().await.unwrap().unwrap().path, path);
+        assert!(entries.next().await.is_none());
+    }
+
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn publish_writes_all_or_nothing() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs, fs::Fs, path::Path, remotes::aws::mock::MockS3, Error, Read, Write,
+        };
+
+        /// Publishes "hello" at `path`, failing halfway through if `fail`.
+        async fn publish<F: Fs>(fs: &F, path: &Path, fail: bool) -> Result<(), Error> {
+            fs.publish(path, |mut file| async move {
+                let (result, _) = file.write_all(&b"hel"[..]).await;
+                result?;
+                if fail {
+                    return Err(Error::Other("the writer failed".into()));
+                }
+                let (result, _) = file.write_all(&b"lo"[..]).await;
+                result?;
+                Ok(file)
+            })
+            .await
+            .map(|meta| assert_eq!(meta.size, 5))
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let path = dir.child("file");
+        assert!(publish(&TokioFs, &path, true).await.is_err());
+        assert!(pin!(TokioFs.list(&dir).await.unwrap())
+            .next()
+            .await
+            .is_none());
+        publish(&TokioFs, &path, false).await.unwrap();
+        let mut file = TokioFs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello");
+        let mut entries = pin!(TokioFs.list(&dir).await.unwrap());
+        assert_eq!(entries.next().await.unwrap().unwrap().path, path);
+        assert!(entries.next().await.is_none());
+
+        let mock = MockS3::new();
+        let path = Path::from("object");
+        assert!(publish(&mock.fs(), &path, true).await.is_err());
+        assert!(mock.object("object").is_none());
+        publish(&mock.fs(), &path, false).await.unwrap();
+        assert_eq!(mock.object("object").unwrap().body, "hello");
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::BTreeMap, pin::pin, time::Duration};
+
+use async_stream::stream;
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::{
+    fs::{is_not_found, sleep, FileMeta, Fs},
+    path::Path,
+    Error,
+};
+
+/// A change to a file under the prefix watched by [`Fs::watch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    Created(FileMeta),
+    /// The file was rewritten, its size or ETag changed.
+    Modified(FileMeta),
+    Removed(Path),
+}
+
+/// Lists the files under `prefix`, at any depth, none if it doesn't exist.
+async fn snapshot<F: Fs + ?Sized>(
+    fs: &F,
+    prefix: &Path,
+) -> Result<BTreeMap<Path, FileMeta>, Error> {
+    let mut files = BTreeMap::new();
+    let stream = match fs.list(prefix).await {
+        Ok(stream) => stream,
+        Err(e) if is_not_found(&e) => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    let mut stream = pin!(stream);
+    while let Some(meta) = stream.next().await {
+        let meta = meta?;
+        files.insert(meta.path.clone(), meta);
+    }
+    Ok(files)
+}
+
+/// Returns the events turning the `previous` snapshot into the `current` one, in the order of
+/// their paths.
+fn diff(
+    previous: &BTreeMap<Path, FileMeta>,
+    current: &BTreeMap<Path, FileMeta>,
+) -> Vec<WatchEvent> {
+    let mut events = vec![];
+    for (path, meta) in current {
+        match previous.get(path) {
+            None => events.push(WatchEvent::Created(meta.clone())),
+            Some(before) if before.size != meta.size || before.etag != meta.etag => {
+                events.push(WatchEvent::Modified(meta.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for path in previous.keys() {
+        if !current.contains_key(path) {
+            events.push(WatchEvent::Removed(path.clone()));
+        }
+    }
+    events.sort_by(|a, b| event_path(a).cmp(event_path(b)));
+    events
+}
+
+fn event_path(event: &WatchEvent) -> &Path {
+    match event {
+        WatchEvent::Created(meta) | WatchEvent::Modified(meta) => &meta.path,
+        WatchEvent::Removed(path) => path,
+    }
+}
+
+/// Watches `prefix` for [`Fs::watch`] by listing it every `interval` and comparing the listing
+/// to the previous one.
+pub(crate) fn poll_watch<F: Fs + ?Sized>(
+    fs: &F,
+    prefix: Path,
+    interval: Duration,
+) -> impl Stream<Item = Result<WatchEvent, Error>> + '_ {
+    stream! {
+        let mut previous = None;
+        loop {
+            match snapshot(fs, &prefix).await {
+                Ok(current) => {
+                    if let Some(previous) = &previous {
+                        for event in diff(previous, &current) {
+                            yield Ok(event);
+                        }
+                    }
+                    previous = Some(current);
+                }
+                Err(e) => yield Err(e),
+            }
+            sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn created_and_removed_files_are_reported() {
+        use std::{pin::pin, time::Duration};
+
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, WatchEvent},
+            path::Path,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let base = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let dir = base.child("watched");
+        let path = dir.child("file");
+        TokioFs.create_dir_all(&dir).await.unwrap();
+        TokioFs
+            .write_file(&dir.child("existing"), b"old".to_vec())
+            .await
+            .0
+            .unwrap();
+        // files are written next to the watched directory then moved in, so that the watch can't
+        // list them half written
+        let put = |content: &'static [u8]| {
+            let (staged, path) = (base.child("staged"), &path);
+            async move {
+                TokioFs.write_file(&staged, content).await.0.unwrap();
+                TokioFs.rename(&staged, path).await.unwrap();
+            }
+        };
+
+        let mut events = pin!(TokioFs.watch(&dir, Duration::from_millis(10)));
+        // the files already there when the watch starts aren't reported
+        let (event, ()) = tokio::join!(events.next(), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            put(b"hello").await;
+        });
+        match event.unwrap().unwrap() {
+            WatchEvent::Created(meta) => {
+                assert_eq!(meta.path, path);
+                assert_eq!(meta.size, 5);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+
+        let (event, ()) = tokio::join!(events.next(), put(b"hi"));
+        assert!(matches!(event, Some(Ok(WatchEvent::Modified(meta))) if meta.size == 2));
+
+        let (event, ()) = tokio::join!(events.next(), async {
+            TokioFs.remove(&path).await.unwrap();
+        });
+        assert_eq!(event.unwrap().unwrap(), WatchEvent::Removed(path));
+    }
+}
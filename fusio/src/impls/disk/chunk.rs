@@ -0,0 +1,151 @@
+use std::{cmp, future::Future, io};
+
+use crate::{disk::read_error, Error};
+
+/// The size of the reads and writes local files split larger ones into on completion-based
+/// runtimes, unless configured otherwise with the `chunk_size` of their file system.
+pub const DEFAULT_CHUNK_SIZE: usize = 128 * 1024;
+
+/// Reads from `pos` to `end`, or the end of the file if it comes first, with reads of at most
+/// `chunk_size` bytes made by `read_at`, appending what is read to `buf`. A single buffer of the
+/// chunk size is submitted over and over, rather than one the size of the whole read.
+pub(crate) async fn read_chunks<F, Fut>(
+    mut buf: Vec<u8>,
+    mut pos: u64,
+    end: u64,
+    chunk_size: usize,
+    mut read_at: F,
+) -> (Result<(), Error>, Vec<u8>)
+where
+    F: FnMut(Vec<u8>, u64) -> Fut,
+    Fut: Future<Output = (io::Result<usize>, Vec<u8>)>,
+{
+    buf.reserve(end.saturating_sub(pos) as usize);
+    let mut chunk = Vec::new();
+    while pos < end {
+        chunk.resize(cmp::min(chunk_size as u64, end - pos) as usize, 0);
+        let (result, read) = read_at(chunk, pos).await;
+        chunk = read;
+        match result {
+            Ok(0) => break,
+            Ok(len) => {
+                buf.extend_from_slice(&chunk[..len]);
+                pos += len as u64;
+            }
+            Err(e) => return (Err(read_error(e)), buf),
+        }
+    }
+    (Ok(()), buf)
+}
+
+/// Writes `bytes` at `pos` with writes of at most `chunk_size` bytes made by `write_at`, each
+/// from the same buffer of the chunk size.
+pub(crate) async fn write_chunks<F, Fut>(
+    bytes: &[u8],
+    pos: u64,
+    chunk_size: usize,
+    mut write_at: F,
+) -> io::Result<()>
+where
+    F: FnMut(Vec<u8>, u64) -> Fut,
+    Fut: Future<Output = (io::Result<()>, Vec<u8>)>,
+{
+    let mut chunk = Vec::with_capacity(cmp::min(chunk_size, bytes.len()));
+    let mut offset = pos;
+    for part in bytes.chunks(chunk_size) {
+        chunk.clear();
+        chunk.extend_from_slice(part);
+        let (result, written) = write_at(chunk, offset).await;
+        result?;
+        chunk = written;
+        offset += part.len() as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "monoio")]
+    #[monoio::test]
+    async fn reads_and_writes_are_split_into_chunks() {
+        use std::{cell::RefCell, cmp};
+
+        use super::{read_chunks, write_chunks};
+
+        let file = RefCell::new(Vec::new());
+        let writes = RefCell::new(Vec::new());
+        let bytes = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+        write_chunks(&bytes, 0, 128, |chunk, pos| {
+            writes.borrow_mut().push(chunk.len());
+            let mut file = file.borrow_mut();
+            let pos = pos as usize;
+            if file.len() < pos + chunk.len() {
+                file.resize(pos + chunk.len(), 0);
+            }
+            file[pos..pos + chunk.len()].copy_from_slice(&chunk);
+            async move { (Ok(()), chunk) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(*file.borrow(), bytes);
+        assert_eq!(writes.borrow().len(), 8);
+        assert_eq!(writes.borrow().last(), Some(&(1000 - 7 * 128)));
+
+        let reads = RefCell::new(0);
+        let read_at = |mut chunk: Vec<u8>, pos: u64| {
+            *reads.borrow_mut() += 1;
+            let file = file.borrow();
+            let pos = cmp::min(pos as usize, file.len());
+            let len = cmp::min(chunk.len(), file.len() - pos);
+            chunk[..len].copy_from_slice(&file[pos..pos + len]);
+            async move { (Ok(len), chunk) }
+        };
+        let (result, buf) = read_chunks(b"head".to_vec(), 100, 1000, 256, read_at).await;
+        result.unwrap();
+        assert_eq!(&buf[..4], b"head");
+        assert_eq!(&buf[4..], &bytes[100..]);
+        assert_eq!(*reads.borrow(), 4);
+
+        // a file shorter than expected ends the read early
+        *reads.borrow_mut() = 0;
+        let (result, buf) = read_chunks(Vec::new(), 900, 2000, 64, read_at).await;
+        result.unwrap();
+        assert_eq!(buf, &bytes[900..]);
+        assert_eq!(*reads.borrow(), 3);
+    }
+
+    #[cfg(all(feature = "monoio", feature = "fs"))]
+    #[monoio::test]
+    async fn files_round_trip_in_small_chunks() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::MonoIoFs,
+            fs::{Fs, OpenOptions},
+            path::Path,
+            Read, Write,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        let fs = MonoIoFs::default().chunk_size(7);
+        let bytes = (0..100u8).collect::<Vec<_>>();
+
+        let mut file = fs
+            .open_options(&path, OpenOptions::default().create(true).write(true))
+            .await
+            .unwrap();
+        let (result, _) = file.write_all(bytes.clone()).await;
+        result.unwrap();
+        let (result, _) = file.write_all(vec![100u8]).await;
+        result.unwrap();
+        file.close().await.unwrap();
+
+        let mut file = fs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(Vec::new(), 3).await;
+        result.unwrap();
+        assert_eq!(buf, (3..=100u8).collect::<Vec<_>>());
+    }
+}
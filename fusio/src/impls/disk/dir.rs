@@ -0,0 +1,176 @@
+use std::{
+    ffi::CString,
+    io,
+    mem::MaybeUninit,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::PathBuf,
+};
+
+use crate::{
+    disk::{local_version, LocalFs},
+    fs::{FileMeta, Fs, OpenOptions},
+    path::{path_to_local, Path},
+    Error,
+};
+
+fn c_path(path: PathBuf) -> Result<CString, Error> {
+    Ok(CString::new(path.as_os_str().as_bytes()).map_err(io::Error::from)?)
+}
+
+/// Converts `path` to a path relative to a directory, `.` for the directory itself.
+fn relative(path: &Path) -> Result<CString, Error> {
+    let local = path_to_local(path)?;
+    match local.strip_prefix("/") {
+        Ok(relative) if !relative.as_os_str().is_empty() => c_path(relative.to_path_buf()),
+        _ => c_path(PathBuf::from(".")),
+    }
+}
+
+fn open_at(dir: RawFd, path: &CString, flags: libc::c_int) -> Result<OwnedFd, Error> {
+    let fd = unsafe { libc::openat(dir, path.as_ptr(), flags | libc::O_CLOEXEC, 0o666) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// A handle on an opened directory that resolves paths relative to it (with `openat`, `unlinkat`
+/// and `fstatat`) instead of from the root. Once the directory is opened, renaming or swapping any
+/// of its parents no longer changes which directory the operations act on, so there is no window
+/// between resolving a path and operating on it.
+pub struct DirHandle {
+    fd: OwnedFd,
+}
+
+impl DirHandle {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let fd = open_at(
+            libc::AT_FDCWD,
+            &c_path(path_to_local(path)?)?,
+            libc::O_RDONLY | libc::O_DIRECTORY,
+        )?;
+        Ok(Self { fd })
+    }
+
+    /// Opens the directory at `path` relative to this one.
+    pub fn open_dir_at(&self, path: &Path) -> Result<Self, Error> {
+        let fd = open_at(
+            self.fd.as_raw_fd(),
+            &relative(path)?,
+            libc::O_RDONLY | libc::O_DIRECTORY,
+        )?;
+        Ok(Self { fd })
+    }
+
+    /// Opens the file at `path` relative to this directory as a file of the [`LocalFs`].
+    pub fn open_at(
+        &self,
+        path: &Path,
+        options: OpenOptions,
+    ) -> Result<<LocalFs as Fs>::File, Error> {
+        if options.direct {
+            return Err(Error::Unsupported {
+                message: format!(
+                    "can't open \"{}\" relative to a directory bypassing the page cache",
+                    path
+                ),
+            });
+        }
+        let mut flags = match (options.read, options.write) {
+            (true, true) => libc::O_RDWR,
+            (false, true) => libc::O_WRONLY,
+            _ => libc::O_RDONLY,
+        };
+        if options.create {
+            flags |= libc::O_CREAT;
+        }
+        if options.truncate {
+            flags |= libc::O_TRUNC;
+        }
+        let fd = open_at(self.fd.as_raw_fd(), &relative(path)?, flags)?;
+
+        LocalFs::default().open_fd(fd)
+    }
+
+    /// Removes the file at `path` relative to this directory.
+    pub fn remove_at(&self, path: &Path) -> Result<(), Error> {
+        let result = unsafe { libc::unlinkat(self.fd.as_raw_fd(), relative(path)?.as_ptr(), 0) };
+        if result < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Returns the metadata of the file at `path` relative to this directory.
+    // the fields of `stat` have other types on other platforms
+    #[allow(clippy::unnecessary_cast)]
+    pub fn metadata_at(&self, path: &Path) -> Result<FileMeta, Error> {
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        let result = unsafe {
+            libc::fstatat(
+                self.fd.as_raw_fd(),
+                relative(path)?.as_ptr(),
+                stat.as_mut_ptr(),
+                0,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let stat = unsafe { stat.assume_init() };
+
+        Ok(FileMeta {
+            path: path.clone(),
+            size: stat.st_size as u64,
+            etag: None,
+            content_encoding: None,
+            storage_class: None,
+            restore_status: None,
+            local_version: Some(local_version(
+                stat.st_mtime as i128 * 1_000_000_000 + stat.st_mtime_nsec as i128,
+                stat.st_size as u64,
+                stat.st_ino as u64,
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn dir_handle_survives_swapped_parent() {
+        use std::fs;
+
+        use tempfile::TempDir;
+
+        use crate::{disk::DirHandle, path::Path, Read};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("a").join("dir")).unwrap();
+        fs::write(root.join("a").join("dir").join("file"), b"one").unwrap();
+
+        let dir = DirHandle::open(&Path::from_filesystem_path(root.join("a").join("dir")).unwrap())
+            .unwrap();
+
+        // swap the parent for another tree with the same layout
+        fs::rename(root.join("a"), root.join("old")).unwrap();
+        fs::create_dir_all(root.join("a").join("dir")).unwrap();
+        fs::write(root.join("a").join("dir").join("file"), b"three").unwrap();
+
+        let path = Path::from("file");
+        assert_eq!(dir.metadata_at(&path).unwrap().size, 3);
+        let mut file = dir.open_at(&path, Default::default()).unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"one");
+
+        dir.remove_at(&path).unwrap();
+        assert!(!root.join("old").join("dir").join("file").exists());
+        assert!(root.join("a").join("dir").join("file").exists());
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use crate::Error;
+
+/// The alignment of the buffers, positions and lengths of the reads and writes of files opened
+/// with [`OpenOptions::direct`](crate::fs::OpenOptions::direct) that suits every common device,
+/// whose logical block size is at most 4 KiB.
+pub const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// Opens the local file at `path` with `O_DIRECT` for `OpenOptions::direct`, its reads and writes
+/// bypassing the page cache. Writes are positioned, not appended.
+#[cfg(any(feature = "monoio", feature = "tokio-uring"))]
+pub(crate) fn open_direct(
+    path: &Path,
+    options: crate::fs::OpenOptions,
+) -> Result<std::fs::File, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        Ok(std::fs::OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .create(options.create)
+            .truncate(options.truncate)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)?)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = options;
+        Err(direct_unsupported(path))
+    }
+}
+
+#[cfg(any(feature = "tokio", not(target_os = "linux")))]
+pub(crate) fn direct_unsupported(path: &Path) -> Error {
+    Error::Unsupported {
+        message: format!(
+            "can't open \"{}\" bypassing the page cache in this file system",
+            path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Writes aligned blocks to a file opened with `direct`, then reads them back.
+    #[allow(unused)]
+    async fn direct_round_trip<F: crate::fs::Fs>(fs: &F) {
+        use std::ops::RangeFrom;
+
+        use tempfile::TempDir;
+
+        use super::DIRECT_IO_ALIGNMENT;
+        use crate::{fs::OpenOptions, path::Path, Error, IoBuf, IoBufMut, Read, Write};
+
+        /// A vector long enough to hold `len` bytes from an offset aligned for direct I/O, along
+        /// with the range of these bytes.
+        fn aligned(len: usize) -> (Vec<u8>, RangeFrom<usize>) {
+            let mut vec = vec![0; len + DIRECT_IO_ALIGNMENT];
+            let offset = vec.as_ptr().align_offset(DIRECT_IO_ALIGNMENT);
+            vec.truncate(offset + len);
+            (vec, offset..)
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        let len = 2 * DIRECT_IO_ALIGNMENT;
+
+        let (mut vec, range) = aligned(len);
+        for (i, byte) in vec[range.start..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let expected = vec[range.start..].to_vec();
+        let mut file = match fs
+            .open_options(
+                &path,
+                OpenOptions::default()
+                    .create(true)
+                    .truncate(true)
+                    .direct(true),
+            )
+            .await
+        {
+            Ok(file) => file,
+            // the file system of the temporary directory doesn't support it, e.g. tmpfs
+            Err(Error::Io(e)) if e.raw_os_error() == Some(libc::EINVAL) => return,
+            Err(e) => panic!("{}", e),
+        };
+        // SAFETY: the vectors are recovered from their slices before they drop
+        let (result, slice) = file.write_all(unsafe { vec.slice_unchecked(range) }).await;
+        result.unwrap();
+        drop(unsafe { Vec::<u8>::recover_from_slice(slice) });
+        file.close().await.unwrap();
+
+        let mut file = fs
+            .open_options(&path, OpenOptions::default().direct(true))
+            .await
+            .unwrap();
+        let (vec, range) = aligned(len);
+        let (result, slice) = file
+            .read_exact_at(unsafe { vec.slice_mut_unchecked(range.clone()) }, 0)
+            .await;
+        result.unwrap();
+        let vec = unsafe { Vec::<u8>::recover_from_slice_mut(slice) };
+        assert_eq!(&vec[range], &expected[..]);
+        file.close().await.unwrap();
+    }
+
+    #[cfg(all(target_os = "linux", feature = "monoio", feature = "fs"))]
+    #[monoio::test]
+    async fn direct_writes_and_reads_of_aligned_blocks() {
+        use crate::disk::MonoIoFs;
+
+        direct_round_trip(&MonoIoFs::default()).await;
+    }
+
+    #[cfg(all(target_os = "linux", feature = "tokio-uring", feature = "fs"))]
+    #[test]
+    fn direct_writes_and_reads_of_aligned_blocks_on_tokio_uring() {
+        use crate::disk::TokioUringFs;
+
+        tokio_uring::start(direct_round_trip(&TokioUringFs::default()));
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use crate::{
+    fs::{FileLock, LockMode},
+    Error,
+};
+
+/// Locks the local file at `path` in `mode`, waiting for the conflicting locks to be released.
+/// The lock is taken with `flock`, on a handle of its own: it conflicts with the locks taken on
+/// any other handle, in this process or another one.
+#[cfg(unix)]
+pub(crate) fn lock(path: &Path, mode: LockMode) -> Result<FileLock, Error> {
+    use std::{fs::File, io, os::fd::AsRawFd};
+
+    let file = File::open(path)?;
+    let operation = match mode {
+        LockMode::Shared => libc::LOCK_SH,
+        LockMode::Exclusive => libc::LOCK_EX,
+    };
+    while unsafe { libc::flock(file.as_raw_fd(), operation) } < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+    Ok(FileLock::new(file, mode))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn lock(path: &Path, _: LockMode) -> Result<FileLock, Error> {
+    Err(Error::Unsupported {
+        message: format!("can't lock \"{}\" on this platform", path.display()),
+    })
+}
@@ -1,10 +1,56 @@
+#[cfg(any(feature = "monoio", all(feature = "tokio-uring", target_os = "linux")))]
+mod chunk;
+#[cfg(all(
+    unix,
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+mod dir;
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+mod direct;
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+mod lock;
 #[cfg(feature = "monoio")]
 pub(crate) mod monoio;
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+mod remove_dir;
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+mod rename;
 #[cfg(feature = "tokio")]
 pub(crate) mod tokio;
 #[cfg(all(feature = "tokio-uring", target_os = "linux"))]
 pub(crate) mod tokio_uring;
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+mod xattr;
 
+#[cfg(any(feature = "monoio", all(feature = "tokio-uring", target_os = "linux")))]
+pub use chunk::DEFAULT_CHUNK_SIZE;
+#[cfg(all(
+    unix,
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub use dir::DirHandle;
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub use direct::DIRECT_IO_ALIGNMENT;
 #[cfg(all(feature = "monoio", feature = "fs"))]
 #[allow(unused)]
 pub use monoio::fs::*;
@@ -21,6 +67,473 @@ pub use tokio_uring::fs::*;
 #[allow(unused)]
 pub use tokio_uring::TokioUringFile;
 
+/// Converts the error of a read, explaining the `EBADF` reads of files opened without read access
+/// (`OpenOptions::read(false)`) fail with.
+#[cfg(any(feature = "tokio", feature = "monoio", feature = "tokio-uring"))]
+pub(crate) fn read_error(e: std::io::Error) -> crate::Error {
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::EBADF) {
+        return std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "the file is write-only, it was opened with `OpenOptions::read(false)`",
+        )
+        .into();
+    }
+    e.into()
+}
+
+/// Hashes the modification time, in nanoseconds since the Unix epoch, the size and the inode of a
+/// local file into its [`FileMeta::local_version`](crate::fs::FileMeta::local_version). FNV-1a
+/// rather than the hasher of the standard library, whose hashes may change between releases.
+#[cfg(all(
+    any(feature = "fs", feature = "checksum"),
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn local_version(modified: i128, size: u64, inode: u64) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let bytes = modified.to_le_bytes().into_iter();
+    for byte in bytes.chain(size.to_le_bytes()).chain(inode.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Returns the [`FileMeta::local_version`](crate::fs::FileMeta::local_version) of a local file
+/// from its `metadata`, `None` if the platform doesn't record modification times.
+#[cfg(all(
+    any(feature = "fs", feature = "checksum"),
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn metadata_version(metadata: &std::fs::Metadata) -> Option<u64> {
+    let modified = match metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+    {
+        Ok(since) => since.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    };
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+    #[cfg(not(unix))]
+    let inode = 0;
+    Some(local_version(modified, metadata.len(), inode))
+}
+
+/// Walks the local directory at `path`, adding up the sizes of the files under it.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn walk_dir_stats(
+    path: std::path::PathBuf,
+) -> Result<crate::fs::DirStats, crate::Error> {
+    let mut stats = crate::fs::DirStats::default();
+    let mut dirs = vec![path];
+    while let Some(dir) = dirs.pop() {
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                stats.entry_count += 1;
+                stats.total_size += entry.metadata()?.len();
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Walks the local directory at `local_path`, listed as `path`, returning the metadata of the
+/// files under it at any depth.
+#[cfg(all(
+    feature = "checksum",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn walk_dir(
+    local_path: std::path::PathBuf,
+    path: &crate::path::Path,
+) -> Result<Vec<crate::fs::FileMeta>, crate::Error> {
+    let mut files = vec![];
+    let mut dirs = vec![(local_path, path.clone())];
+    while let Some((dir, listed)) = dirs.pop() {
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            let child = listed.child(entry.file_name().to_string_lossy().as_ref());
+            if entry.file_type()?.is_dir() {
+                dirs.push((entry.path(), child));
+            } else {
+                let metadata = entry.metadata()?;
+                files.push(crate::fs::FileMeta {
+                    path: child,
+                    size: metadata.len(),
+                    etag: None,
+                    content_encoding: None,
+                    storage_class: None,
+                    restore_status: None,
+                    local_version: metadata_version(&metadata),
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the metadata of an `entry` of a local directory listed as `path`.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn dir_entry_meta(
+    path: &crate::path::Path,
+    entry: std::io::Result<std::fs::DirEntry>,
+) -> Result<crate::fs::FileMeta, crate::Error> {
+    let entry = entry?;
+    let metadata = entry.metadata()?;
+    Ok(crate::fs::FileMeta {
+        path: path.child(entry.file_name().to_string_lossy().as_ref()),
+        size: metadata.len(),
+        etag: None,
+        content_encoding: None,
+        storage_class: None,
+        restore_status: None,
+        local_version: metadata_version(&metadata),
+    })
+}
+
+/// Lists a page of the local directory `local_path`, listed as `path`: the `page_size` entries
+/// whose names come after the `cursor`, which is the name of the last entry of the previous page.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn list_dir_page(
+    local_path: std::path::PathBuf,
+    path: &crate::path::Path,
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<(Vec<crate::fs::FileMeta>, Option<String>), crate::Error> {
+    // an empty page would never advance
+    let page_size = page_size.max(1);
+    let mut entries = local_path
+        .read_dir()?
+        .map(|entry| {
+            let entry = entry?;
+            Ok((entry.file_name().to_string_lossy().into_owned(), entry))
+        })
+        .filter(|entry| match (entry, cursor) {
+            (Ok((name, _)), Some(cursor)) => name.as_str() > cursor,
+            _ => true,
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let next_cursor = match entries.len() > page_size {
+        true => Some(entries[page_size - 1].0.clone()),
+        false => None,
+    };
+    let page = entries
+        .into_iter()
+        .take(page_size)
+        .map(|(name, entry)| {
+            let metadata = entry.metadata()?;
+            Ok(crate::fs::FileMeta {
+                path: path.child(name.as_str()),
+                size: metadata.len(),
+                etag: None,
+                content_encoding: None,
+                storage_class: None,
+                restore_status: None,
+                local_version: metadata_version(&metadata),
+            })
+        })
+        .collect::<Result<_, std::io::Error>>()?;
+    Ok((page, next_cursor))
+}
+
+/// Lists the immediate children of the local directory `local_path`, listed as `path`, for
+/// [`Fs::list_delimited`](crate::fs::Fs::list_delimited): its files, and its subdirectories as
+/// prefixes.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn list_dir_delimited(
+    local_path: std::path::PathBuf,
+    path: &crate::path::Path,
+) -> Result<crate::fs::DelimitedListing, crate::Error> {
+    let mut listing = crate::fs::DelimitedListing::default();
+    for entry in local_path.read_dir()? {
+        let entry = entry?;
+        let child = path.child(entry.file_name().to_string_lossy().as_ref());
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            listing.prefixes.push(child);
+            continue;
+        }
+        listing.files.push(crate::fs::FileMeta {
+            path: child,
+            size: metadata.len(),
+            etag: None,
+            content_encoding: None,
+            storage_class: None,
+            restore_status: None,
+            local_version: metadata_version(&metadata),
+        });
+    }
+    listing
+        .files
+        .sort_by(|a, b| a.path.as_ref().cmp(b.path.as_ref()));
+    listing.prefixes.sort();
+    Ok(listing)
+}
+
+/// Opens an unnamed file in the local directory `dir` for reading and writing: with `O_TMPFILE`
+/// on Linux when the file system supports it, else by creating a file and removing it right away.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn open_anon_temp(dir: std::path::PathBuf) -> Result<std::fs::File, crate::Error> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .mode(0o600)
+            .custom_flags(libc::O_TMPFILE)
+            .open(&dir)
+        {
+            Ok(file) => return Ok(file),
+            // kernels and file systems without `O_TMPFILE` fail with one or the other
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP | libc::EISDIR)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        for _ in 0..16 {
+            let path = dir.join(crate::fs::temp_name(".tmp-"));
+            match std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => {
+                    std::fs::remove_file(&path)?;
+                    return Ok(file);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("no unused temporary name in `{}`", dir.display()),
+        )
+        .into())
+    }
+
+    // open files can't be removed on Windows
+    #[cfg(not(unix))]
+    Err(crate::Error::Unsupported {
+        message: format!("can't open an unnamed file in `{}`", dir.display()),
+    })
+}
+
+/// Maps the local file at `path` into memory, read-only.
+#[cfg(all(
+    feature = "mmap",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn map_file(path: &std::path::Path) -> Result<memmap2::Mmap, crate::Error> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the mapping is only as stable as the file under it, as `Fs::mmap` warns
+    Ok(unsafe { memmap2::Mmap::map(&file)? })
+}
+
+/// Reserves space for the first `len` bytes of the local file `fd` with `fallocate`, keeping its
+/// size so that appends still go at its end. File systems that can't, e.g. some network ones, are
+/// left to take the space as it's written.
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn allocate(fd: std::os::fd::RawFd, len: u64) -> std::io::Result<()> {
+    if len == 0 {
+        return Ok(());
+    }
+    let len =
+        libc::off_t::try_from(len).map_err(|_| std::io::Error::from_raw_os_error(libc::EFBIG))?;
+    loop {
+        // SAFETY: `fallocate` only reads its arguments, a closed `fd` is an error
+        if unsafe { libc::fallocate(fd, libc::FALLOC_FL_KEEP_SIZE, 0, len) } == 0 {
+            return Ok(());
+        }
+        let e = std::io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EOPNOTSUPP) => return Ok(()),
+            _ => return Err(e),
+        }
+    }
+}
+
+/// Copies the local file at `from` to `to`. A file copied onto itself, once symbolic links and
+/// relative components are resolved, is left as is rather than truncated by opening it for the
+/// copy. On Linux the holes of sparse files are kept, see [`copy_sparse`].
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) fn copy_file(from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+    let from = std::fs::canonicalize(from)?;
+    if std::fs::canonicalize(to).is_ok_and(|to| to == from) {
+        return Ok(());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        copy_sparse(&from, to)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        std::fs::copy(from, to)?;
+        Ok(())
+    }
+}
+
+/// Copies the local file at `from` to `to` writing only its data, found with `SEEK_DATA` and
+/// `SEEK_HOLE`, so that the holes of a sparse file stay holes rather than being filled with
+/// zeroes. File systems that don't track holes report the whole file as data, and kernels without
+/// `SEEK_DATA` get a dense copy with [`std::fs::copy`].
+#[cfg(all(
+    target_os = "linux",
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+fn copy_sparse(from: &std::path::Path, to: &std::path::Path) -> std::io::Result<()> {
+    use std::os::{fd::AsRawFd, unix::fs::FileExt};
+
+    /// Seeks the `fd` to the next data or hole from `offset`, `None` if there's no data past it.
+    fn seek(
+        fd: std::os::fd::RawFd,
+        offset: u64,
+        whence: libc::c_int,
+    ) -> std::io::Result<Option<u64>> {
+        let offset = libc::off_t::try_from(offset)
+            .map_err(|_| std::io::Error::from_raw_os_error(libc::EFBIG))?;
+        // SAFETY: `lseek` only reads its arguments, a closed `fd` is an error
+        match unsafe { libc::lseek(fd, offset, whence) } {
+            offset if offset >= 0 => Ok(Some(offset as u64)),
+            _ => match std::io::Error::last_os_error() {
+                e if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+                e => Err(e),
+            },
+        }
+    }
+
+    let source = std::fs::File::open(from)?;
+    let fd = source.as_raw_fd();
+    let metadata = source.metadata()?;
+    match seek(fd, 0, libc::SEEK_DATA) {
+        Ok(_) => {}
+        Err(e) if matches!(e.raw_os_error(), Some(libc::EINVAL | libc::EOPNOTSUPP)) => {
+            std::fs::copy(from, to)?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    }
+
+    let target = std::fs::File::create(to)?;
+    target.set_permissions(metadata.permissions())?;
+    let mut buf = vec![0; 256 * 1024];
+    let mut pos = 0;
+    while let Some(data) = seek(fd, pos, libc::SEEK_DATA)? {
+        // the end of the file counts as a hole
+        let hole = seek(fd, data, libc::SEEK_HOLE)?.unwrap_or(metadata.len());
+        let mut offset = data;
+        while offset < hole {
+            let len = buf.len().min((hole - offset) as usize);
+            let read = source.read_at(&mut buf[..len], offset)?;
+            if read == 0 {
+                break;
+            }
+            target.write_all_at(&buf[..read], offset)?;
+            offset += read as u64;
+        }
+        pos = hole;
+    }
+    // the holes at the end aren't written, the length covers them
+    target.set_len(metadata.len().max(pos))?;
+    Ok(())
+}
+
+/// Runs the local operation `op` again when it fails with `EINTR`, e.g. interrupted by the
+/// delivery of a signal, or `EAGAIN`, up to a few times, instead of surfacing such transient
+/// errors.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) async fn retry_transient<T, Fut>(mut op: impl FnMut() -> Fut) -> std::io::Result<T>
+where
+    Fut: std::future::Future<Output = std::io::Result<T>>,
+{
+    const MAX_RETRIES: usize = 8;
+
+    let mut retries = 0;
+    loop {
+        match op().await {
+            Err(e)
+                if retries < MAX_RETRIES
+                    && matches!(
+                        e.kind(),
+                        std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+                    ) =>
+            {
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Creates a file under `dir` named `prefix` followed by a random suffix with `create`, which
+/// opens it exclusively (`O_EXCL`), trying other names when one already exists.
+#[cfg(all(
+    feature = "fs",
+    any(feature = "tokio", feature = "monoio", feature = "tokio-uring")
+))]
+pub(crate) async fn create_temp<T, Fut>(
+    dir: &crate::path::Path,
+    prefix: &str,
+    mut create: impl FnMut(std::path::PathBuf) -> Fut,
+) -> Result<(crate::path::Path, T), crate::Error>
+where
+    Fut: std::future::Future<Output = std::io::Result<T>>,
+{
+    // a clash means another process picked the same random name, a few retries are plenty
+    for _ in 0..16 {
+        let path = dir.child(crate::fs::temp_name(prefix));
+        let local_path = crate::path::path_to_local(&path)?;
+        match retry_transient(|| create(local_path.clone())).await {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("no unused name starting with `{}` in `{}`", prefix, dir),
+    )
+    .into())
+}
+
 #[cfg(feature = "fs")]
 cfg_if::cfg_if! {
     if #[cfg(feature = "tokio")] {
@@ -31,3 +544,82 @@ cfg_if::cfg_if! {
         pub type LocalFs = TokioUringFs;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(unix, feature = "tokio", feature = "fs"))]
+    #[tokio::test]
+    async fn transient_errors_are_retried() {
+        use std::{
+            io::{Error, ErrorKind},
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        use super::retry_transient;
+
+        let calls = AtomicUsize::new(0);
+        let result = retry_transient(|| async {
+            match calls.fetch_add(1, Ordering::Relaxed) {
+                0 => Err(Error::from_raw_os_error(libc::EINTR)),
+                1 => Err(Error::from_raw_os_error(libc::EAGAIN)),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+
+        let calls = AtomicUsize::new(0);
+        let result = retry_transient(|| async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err::<(), _>(Error::from(ErrorKind::WouldBlock))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(calls.load(Ordering::Relaxed), 9);
+
+        let calls = AtomicUsize::new(0);
+        let result = retry_transient(|| async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err::<(), _>(Error::from(ErrorKind::NotFound))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "tokio", feature = "fs"))]
+    #[tokio::test]
+    async fn copies_of_sparse_files_keep_their_holes() {
+        use std::os::unix::fs::{FileExt, MetadataExt};
+
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path};
+
+        const LEN: u64 = 64 * 1024 * 1024;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        let (from, to) = (dir.child("sparse"), dir.child("copy"));
+        // 64 KiB of data in the middle of 64 MiB, and a hole at the end
+        let file = std::fs::File::create(tmp_dir.path().join("sparse")).unwrap();
+        file.set_len(LEN).unwrap();
+        file.write_all_at(&[7; 64 * 1024], LEN / 2).unwrap();
+        drop(file);
+
+        TokioFs.copy(&from, &to).await.unwrap();
+
+        let source = std::fs::metadata(tmp_dir.path().join("sparse")).unwrap();
+        let copy = std::fs::metadata(tmp_dir.path().join("copy")).unwrap();
+        assert_eq!(copy.len(), LEN);
+        // blocks of 512 bytes, give or take the allocation granularity of the file system
+        assert!(source.blocks() * 512 < LEN / 4);
+        assert!(copy.blocks() <= source.blocks() + 2048);
+        let content = std::fs::read(tmp_dir.path().join("copy")).unwrap();
+        assert!(content[..(LEN / 2) as usize].iter().all(|&b| b == 0));
+        assert_eq!(&content[(LEN / 2) as usize..][..64 * 1024], &[7; 64 * 1024]);
+        assert!(content[(LEN / 2) as usize + 64 * 1024..]
+            .iter()
+            .all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    cmp::Reverse,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+    thread,
+};
+
+/// How many threads remove the files of a tree at most.
+const MAX_THREADS: usize = 8;
+
+/// The directories left to empty, and how many are being emptied, which may find more.
+struct Queue {
+    dirs: Vec<PathBuf>,
+    busy: usize,
+}
+
+type Errors = Mutex<Vec<(PathBuf, io::Error)>>;
+
+/// Removes the local directory at `path` with everything under it. Threads take the directories
+/// of the tree in parallel, removing their files and handing their subdirectories to the others,
+/// then the emptied directories are removed bottom-up. Errors don't stop the removal, everything
+/// else is still removed: the first one is returned along with how many entries were left.
+pub(crate) fn remove_dir_all(path: &Path) -> io::Result<()> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("\"{}\" isn't a directory", path.display()),
+        ));
+    }
+
+    let threads = thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(MAX_THREADS);
+    let queue = Mutex::new(Queue {
+        dirs: vec![path.to_path_buf()],
+        busy: 0,
+    });
+    let ready = Condvar::new();
+    let emptied = Mutex::new(Vec::new());
+    let errors = Errors::default();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let dir = {
+                    let mut queue = queue.lock().unwrap();
+                    loop {
+                        if let Some(dir) = queue.dirs.pop() {
+                            queue.busy += 1;
+                            break dir;
+                        }
+                        if queue.busy == 0 {
+                            return;
+                        }
+                        queue = ready.wait(queue).unwrap();
+                    }
+                };
+
+                let subdirs = remove_files(&dir, &errors);
+                emptied.lock().unwrap().push(dir);
+
+                let mut queue = queue.lock().unwrap();
+                queue.dirs.extend(subdirs);
+                queue.busy -= 1;
+                ready.notify_all();
+            });
+        }
+    });
+
+    let mut errors = errors.into_inner().unwrap();
+    let mut emptied = emptied.into_inner().unwrap();
+    // the deepest first, so that every directory goes after its subdirectories
+    emptied.sort_by_key(|dir| Reverse(dir.components().count()));
+    for dir in emptied {
+        if let Err(e) = fs::remove_dir(&dir) {
+            errors.push((dir, e));
+        }
+    }
+
+    let left = errors.len();
+    match errors.into_iter().next() {
+        None => Ok(()),
+        Some((entry, e)) => Err(io::Error::new(
+            e.kind(),
+            format!(
+                "{} entries under \"{}\" couldn't be removed, the first one \"{}\": {}",
+                left,
+                path.display(),
+                entry.display(),
+                e
+            ),
+        )),
+    }
+}
+
+/// Removes the files of `dir`, returning its subdirectories. Symbolic links are removed rather
+/// than followed.
+fn remove_files(dir: &Path, errors: &Errors) -> Vec<PathBuf> {
+    let mut subdirs = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.lock().unwrap().push((dir.to_path_buf(), e));
+            return subdirs;
+        }
+    };
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.lock().unwrap().push((dir.to_path_buf(), e));
+                continue;
+            }
+        };
+        let path = entry.path();
+        let result = match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                subdirs.push(path);
+                continue;
+            }
+            Ok(_) => fs::remove_file(&path),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            errors.lock().unwrap().push((path, e));
+        }
+    }
+    subdirs
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn wide_and_deep_trees_are_removed() {
+        use std::fs;
+
+        use tempfile::TempDir;
+
+        use super::remove_dir_all;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("tree");
+        for branch in 0..16 {
+            let mut dir = root.join(branch.to_string());
+            for depth in 0..8 {
+                fs::create_dir_all(&dir).unwrap();
+                for file in 0..4 {
+                    fs::write(dir.join(format!("{}.txt", file)), b"x").unwrap();
+                }
+                dir = dir.join(depth.to_string());
+            }
+        }
+
+        remove_dir_all(&root).unwrap();
+        assert!(!root.exists());
+        assert!(remove_dir_all(&root).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn entries_that_cant_be_removed_are_reported() {
+        use std::{
+            fs::{self, Permissions},
+            os::unix::fs::PermissionsExt,
+        };
+
+        use tempfile::TempDir;
+
+        use super::remove_dir_all;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("tree");
+        let locked = root.join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        fs::create_dir_all(root.join("open")).unwrap();
+        fs::write(locked.join("a"), b"x").unwrap();
+        fs::write(root.join("open").join("b"), b"x").unwrap();
+        // files can't be removed from a directory that isn't writable
+        fs::set_permissions(&locked, Permissions::from_mode(0o555)).unwrap();
+        if fs::remove_file(locked.join("a")).is_ok() {
+            // running as root, permissions don't apply
+            return;
+        }
+
+        let err = remove_dir_all(&root).unwrap_err();
+        assert!(err.to_string().contains("couldn't be removed"));
+        // the rest of the tree is removed all the same
+        assert!(!root.join("open").exists());
+        assert!(locked.join("a").exists());
+
+        fs::set_permissions(&locked, Permissions::from_mode(0o755)).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    fs::{self, File, FileTimes},
+    io,
+    path::Path,
+};
+
+fn crosses_devices(err: &io::Error) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            err.raw_os_error() == Some(libc::EXDEV)
+        } else if #[cfg(windows)] {
+            // ERROR_NOT_SAME_DEVICE
+            err.raw_os_error() == Some(17)
+        } else {
+            let _ = err;
+            false
+        }
+    }
+}
+
+/// Renames `from` to `to`, falling back to copying and removing the source when they are on
+/// different devices.
+pub(crate) fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if crosses_devices(&e) => rename_by_copy(from, to),
+        result => result,
+    }
+}
+
+/// Renames `from` to `to` like [`rename`], then syncs the directories holding them: the entries
+/// of a directory, and so the rename, only survive a crash once the directory itself is synced.
+pub(crate) fn rename_durable(from: &Path, to: &Path) -> io::Result<()> {
+    rename_durable_with(from, to, sync_dir)
+}
+
+/// Renames `from` to `to` like [`rename_durable`], syncing the directories holding them with
+/// `sync`.
+fn rename_durable_with(
+    from: &Path,
+    to: &Path,
+    mut sync: impl FnMut(&Path) -> io::Result<()>,
+) -> io::Result<()> {
+    rename(from, to)?;
+    let (from_dir, to_dir) = (parent_dir(from), parent_dir(to));
+    sync(to_dir)?;
+    if from_dir != to_dir {
+        sync(from_dir)?;
+    }
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Syncs the directory at `path`. Windows can't open a directory as a file to sync it, and NTFS
+/// journals renames, nothing is done there.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    File::open(path)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Moves `from` to `to` by copying it, keeping its permissions and access/modification times as
+/// a real rename would.
+fn rename_by_copy(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::metadata(from)?;
+    fs::copy(from, to)?;
+
+    let mut times = FileTimes::new().set_modified(meta.modified()?);
+    if let Ok(accessed) = meta.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    File::open(to)?.set_times(times)?;
+    fs::set_permissions(to, meta.permissions())?;
+
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    #[test]
+    fn rename_by_copy_preserves_mode_and_mtime() {
+        use std::{
+            fs::{self, File, FileTimes, Permissions},
+            os::unix::fs::PermissionsExt,
+            time::{Duration, SystemTime},
+        };
+
+        use tempfile::TempDir;
+
+        use super::rename_by_copy;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let from = tmp_dir.path().join("from");
+        let to = tmp_dir.path().join("to");
+
+        fs::write(&from, b"hello").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&from)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(modified))
+            .unwrap();
+        fs::set_permissions(&from, Permissions::from_mode(0o640)).unwrap();
+
+        rename_by_copy(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        let meta = fs::metadata(&to).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+        assert_eq!(meta.modified().unwrap(), modified);
+        assert_eq!(fs::read(&to).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn durable_renames_sync_their_directories() {
+        use std::fs;
+
+        use tempfile::TempDir;
+
+        use super::{rename_durable, rename_durable_with};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let (a, b) = (tmp_dir.path().join("a"), tmp_dir.path().join("b"));
+        fs::create_dir(&a).unwrap();
+        fs::create_dir(&b).unwrap();
+        fs::write(a.join("file"), b"hello").unwrap();
+
+        let mut synced = Vec::new();
+        rename_durable_with(&a.join("file"), &b.join("file"), |dir| {
+            synced.push(dir.to_path_buf());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(fs::read(b.join("file")).unwrap(), b"hello");
+        assert_eq!(synced, [b.clone(), a]);
+
+        // a rename within a directory syncs it once
+        let mut synced = Vec::new();
+        rename_durable_with(&b.join("file"), &b.join("moved"), |dir| {
+            synced.push(dir.to_path_buf());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(synced, [b.as_path()]);
+
+        // the directories can be synced for real
+        rename_durable(&b.join("moved"), &b.join("file")).unwrap();
+        assert_eq!(fs::read(b.join("file")).unwrap(), b"hello");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn atomic_writes_rename_durably() {
+        use std::sync::Mutex;
+
+        use futures_core::Stream;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FileMeta, Fs, OpenOptions},
+            path::Path,
+            Error,
+        };
+
+        /// Counts the durable renames of the local file system.
+        struct Durable {
+            renames: Mutex<usize>,
+        }
+
+        impl Fs for Durable {
+            type File = <TokioFs as Fs>::File;
+
+            async fn open_options(
+                &self,
+                path: &Path,
+                options: OpenOptions,
+            ) -> Result<Self::File, Error> {
+                TokioFs.open_options(path, options).await
+            }
+
+            async fn list(
+                &self,
+                path: &Path,
+            ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+                TokioFs.list(path).await
+            }
+
+            async fn remove(&self, path: &Path) -> Result<(), Error> {
+                TokioFs.remove(path).await
+            }
+
+            async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+                *self.renames.lock().unwrap() += 1;
+                TokioFs.rename_durable(from, to).await
+            }
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path().join("dir");
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("dir")
+            .child("file");
+        std::fs::create_dir(&dir).unwrap();
+
+        let fs = Durable {
+            renames: Mutex::new(0),
+        };
+        fs.write_file_atomic(&path, b"hello".to_vec())
+            .await
+            .0
+            .unwrap();
+        assert_eq!(std::fs::read(dir.join("file")).unwrap(), b"hello");
+        // the temporary file is renamed over `path` with the directory synced
+        assert_eq!(*fs.renames.lock().unwrap(), 1);
+    }
+}
@@ -1,32 +1,77 @@
-use std::io;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+use std::{io, path::PathBuf};
 
 use async_stream::stream;
 use futures_core::Stream;
 use tokio::{
-    fs::{create_dir_all, remove_file, File},
+    fs::{create_dir_all, metadata, remove_file, File},
     task::spawn_blocking,
 };
 
+#[cfg(target_os = "linux")]
+use crate::fs::WatchEvent;
+#[cfg(feature = "mmap")]
+use crate::{disk::map_file, fs::Mmap};
+#[cfg(feature = "checksum")]
+use crate::{disk::walk_dir, fs::listing_digest};
 use crate::{
-    fs::{FileMeta, Fs, OpenOptions},
+    disk::{
+        copy_file, create_temp, dir_entry_meta,
+        direct::direct_unsupported,
+        list_dir_delimited, list_dir_page,
+        lock::lock,
+        metadata_version, open_anon_temp,
+        remove_dir::remove_dir_all,
+        rename::{rename, rename_durable},
+        retry_transient, walk_dir_stats,
+        xattr::{get_xattr, list_xattr, set_xattr},
+    },
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, Fs, LockMode, OpenOptions,
+        TempGuard,
+    },
     path::{path_to_local, Path},
     Error,
 };
 
+#[derive(Default)]
 pub struct TokioFs;
 
+impl TokioFs {
+    /// Wraps an already opened file descriptor, e.g. one handed over by a sandbox or opened
+    /// relative to a directory with `openat`, into a file of this file system.
+    #[cfg(unix)]
+    pub fn open_fd(&self, fd: OwnedFd) -> Result<File, Error> {
+        Ok(File::from_std(std::fs::File::from(fd)))
+    }
+}
+
 impl Fs for TokioFs {
     type File = File;
 
     async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
         let local_path = path_to_local(path)?;
+        // tokio copies reads and writes through buffers of its own, which aren't aligned
+        if options.direct {
+            return Err(direct_unsupported(&local_path));
+        }
+        // a directory opens as a file, only to fail on its first read
+        if metadata(&local_path).await.is_ok_and(|meta| meta.is_dir()) {
+            return Err(Error::IsADirectory {
+                path: path.to_string(),
+            });
+        }
 
-        let file = tokio::fs::OpenOptions::new()
-            .read(options.read)
-            .append(options.write)
-            .create(options.create)
-            .open(&local_path)
-            .await?;
+        let file = retry_transient(|| async {
+            tokio::fs::OpenOptions::new()
+                .read(options.read)
+                .append(options.write)
+                .create(options.create)
+                .open(&local_path)
+                .await
+        })
+        .await?;
 
         if options.truncate {
             file.set_len(0).await?;
@@ -35,7 +80,28 @@ impl Fs for TokioFs {
         Ok(file)
     }
 
-    async fn create_dir_all(path: &Path) -> Result<(), Error> {
+    async fn temp_file(&self, dir: &Path, prefix: &str) -> Result<(Path, Self::File), Error> {
+        create_temp(dir, prefix, |path| async move {
+            tokio::fs::OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create_new(true)
+                .open(path)
+                .await
+        })
+        .await
+    }
+
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        let local_dir = path_to_local(dir)?;
+
+        let file = spawn_blocking(move || open_anon_temp(local_dir))
+            .await
+            .map_err(io::Error::from)??;
+        Ok(File::from_std(file))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
         let path = path_to_local(path)?;
         create_dir_all(path).await?;
 
@@ -46,14 +112,15 @@ impl Fs for TokioFs {
         &self,
         path: &Path,
     ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
-        let path = path_to_local(path)?;
+        let local_path = path_to_local(path)?;
+        let path = path.clone();
 
         spawn_blocking(move || {
-            let entries = path.read_dir()?;
+            let entries = local_path.read_dir()?;
             Ok::<_, Error>(stream! {
+                // an entry that can't be read doesn't end the listing
                 for entry in entries {
-                    let entry = entry?;
-                    yield Ok(FileMeta { path: Path::from_filesystem_path(entry.path())?, size: entry.metadata()?.len() });
+                    yield dir_entry_meta(&path, entry);
                 }
             })
         })
@@ -61,10 +128,295 @@ impl Fs for TokioFs {
         .map_err(io::Error::from)?
     }
 
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        let local_path = path_to_local(path)?;
+        let path = path.clone();
+        let cursor = cursor.map(str::to_string);
+
+        spawn_blocking(move || list_dir_page(local_path, &path, cursor.as_deref(), page_size))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        let local_path = path_to_local(path)?;
+        let path = path.clone();
+
+        spawn_blocking(move || list_dir_delimited(local_path, &path))
+            .await
+            .map_err(io::Error::from)?
+    }
+
     async fn remove(&self, path: &Path) -> Result<(), Error> {
         let path = path_to_local(path)?;
 
         remove_file(&path).await?;
         Ok(())
     }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        let path = path_to_local(path)?;
+
+        spawn_blocking(move || remove_dir_all(&path))
+            .await
+            .map_err(io::Error::from)??;
+        Ok(())
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        let local_path = path_to_local(path)?;
+        let metadata = metadata(&local_path).await?;
+
+        Ok(FileMeta {
+            path: path.clone(),
+            size: metadata.len(),
+            etag: None,
+            content_encoding: None,
+            storage_class: None,
+            restore_status: None,
+            local_version: metadata_version(&metadata),
+        })
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        let local_path = path_to_local(path)?;
+
+        match metadata(&local_path).await {
+            Ok(meta) if meta.is_dir() => Ok(Some(FileType::Dir)),
+            Ok(_) => Ok(Some(FileType::File)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        let local_path = path_to_local(path)?;
+
+        spawn_blocking(move || walk_dir_stats(local_path))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        let local_path = path_to_local(path)?;
+        let path = path.clone();
+
+        spawn_blocking(move || listing_digest(&path, walk_dir(local_path, &path)?))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = path_to_local(from)?;
+        let to = path_to_local(to)?;
+
+        spawn_blocking(move || copy_file(&from, &to))
+            .await
+            .map_err(io::Error::from)??;
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = path_to_local(from)?;
+        let to = path_to_local(to)?;
+
+        spawn_blocking(move || rename(&from, &to))
+            .await
+            .map_err(io::Error::from)??;
+        Ok(())
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = path_to_local(from)?;
+        let to = path_to_local(to)?;
+
+        spawn_blocking(move || rename_durable(&from, &to))
+            .await
+            .map_err(io::Error::from)??;
+        Ok(())
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let local_path = path_to_local(path)?;
+        let name = name.to_string();
+
+        spawn_blocking(move || get_xattr(&local_path, &name))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        let local_path = path_to_local(path)?;
+        let (name, value) = (name.to_string(), value.to_vec());
+
+        spawn_blocking(move || set_xattr(&local_path, &name, &value))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        let local_path = path_to_local(path)?;
+
+        spawn_blocking(move || list_xattr(&local_path))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        let local_path = path_to_local(path)?;
+
+        spawn_blocking(move || lock(&local_path, LockMode::Shared))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        let local_path = path_to_local(path)?;
+
+        spawn_blocking(move || lock(&local_path, LockMode::Exclusive))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        let local_path = path_to_local(path)?;
+
+        spawn_blocking(move || map_file(&local_path))
+            .await
+            .map_err(io::Error::from)?
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        let local_path = path_to_local(path)?;
+        // a missing file fails as its download would
+        metadata(&local_path).await?;
+        Ok((local_path, TempGuard::none()))
+    }
+
+    /// Watches `prefix` with `inotify`, reporting changes as they happen, files rewritten with
+    /// the same size included. A file created in place is reported created right away, then
+    /// modified once closed after being written. Falls back to listing `prefix` every `interval`
+    /// while it isn't a directory.
+    #[cfg(target_os = "linux")]
+    fn watch(
+        &self,
+        prefix: &Path,
+        interval: std::time::Duration,
+    ) -> impl Stream<Item = Result<WatchEvent, Error>> {
+        super::watch::watch(self, prefix.clone(), interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn directories_are_not_opened_as_files() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions},
+            path::Path,
+            Error,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        TokioFs.create_dir_all(&dir.child("sub")).await.unwrap();
+
+        for options in [
+            OpenOptions::default(),
+            OpenOptions::default().write(true).create(true),
+        ] {
+            let result = TokioFs.open_options(&dir.child("sub"), options).await;
+            assert!(matches!(
+                result,
+                Err(Error::IsADirectory { path }) if path.ends_with("sub")
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn write_only_file_rejects_reads() {
+        use std::io;
+
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, OpenOptions},
+            path::Path,
+            Error, Read, Write,
+        };
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+
+        let mut file = TokioFs
+            .open_options(&path, OpenOptions::default().read(false).create(true))
+            .await
+            .unwrap();
+        file.write_all(&b"hello"[..]).await.0.unwrap();
+        let (result, _) = file.read_to_end_at(vec![], 0).await;
+        assert!(matches!(
+            result,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::PermissionDenied
+                && e.to_string().contains("write-only")
+        ));
+        file.close().await.unwrap();
+
+        let mut file = TokioFs.open(&path).await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_fd() {
+        use std::{io::Write as _, os::fd::OwnedFd};
+
+        use tempfile::tempfile;
+
+        use crate::{disk::TokioFs, Read};
+
+        let mut file = tempfile().unwrap();
+        file.write_all(b"hello").unwrap();
+
+        let mut file = TokioFs.open_fd(OwnedFd::from(file)).unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"hello");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn anon_temp_file_has_no_entry() {
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, Read, Write};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+
+        let mut file = TokioFs.anon_temp_file(&dir).await.unwrap();
+        let (result, _) = file.write_all(&b"scratch"[..]).await;
+        result.unwrap();
+        file.flush().await.unwrap();
+        let (result, buf) = file.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"scratch");
+
+        assert_eq!(tmp_dir.path().read_dir().unwrap().count(), 0);
+    }
 }
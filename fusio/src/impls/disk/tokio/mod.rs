@@ -1,5 +1,7 @@
 #[cfg(feature = "fs")]
 pub mod fs;
+#[cfg(all(feature = "fs", target_os = "linux"))]
+mod watch;
 
 use std::{io::SeekFrom, ptr::slice_from_raw_parts};
 
@@ -8,7 +10,7 @@ use tokio::{
     io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
 };
 
-use crate::{buf::IoBufMut, Error, IoBuf, Read, Write};
+use crate::{buf::IoBufMut, disk::read_error, Error, IoBuf, Read, Write};
 
 impl Write for File {
     async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
@@ -31,6 +33,21 @@ impl Write for File {
         File::shutdown(self).await?;
         Ok(())
     }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::fd::AsRawFd;
+
+            let file = self.try_clone().await?.into_std().await;
+            tokio::task::spawn_blocking(move || crate::disk::allocate(file.as_raw_fd(), len))
+                .await
+                .map_err(std::io::Error::from)??;
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = len;
+        Ok(())
+    }
 }
 
 impl Read for File {
@@ -41,7 +58,7 @@ impl Read for File {
         }
         match AsyncReadExt::read_exact(self, buf.as_slice_mut()).await {
             Ok(_) => (Ok(()), buf),
-            Err(e) => (Err(Error::Io(e)), buf),
+            Err(e) => (Err(read_error(e)), buf),
         }
     }
 
@@ -52,7 +69,7 @@ impl Read for File {
         }
         match AsyncReadExt::read_to_end(self, &mut buf).await {
             Ok(_) => (Ok(()), buf),
-            Err(e) => (Err(Error::Io(e)), buf),
+            Err(e) => (Err(read_error(e)), buf),
         }
     }
 
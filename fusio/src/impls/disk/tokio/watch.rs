@@ -0,0 +1,337 @@
+//! [`Fs::watch`](crate::fs::Fs::watch) of [`TokioFs`](super::fs::TokioFs) on Linux, told of the
+//! changes by `inotify` rather than listing the watched directory over and over.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::{CString, OsStr},
+    io,
+    mem::size_of,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::PathBuf,
+    pin::pin,
+    time::Duration,
+};
+
+use async_stream::stream;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use tokio::io::unix::AsyncFd;
+
+use super::fs::TokioFs;
+use crate::{
+    disk::metadata_version,
+    fs::{poll_watch, FileMeta, WatchEvent},
+    path::{path_to_local, Path},
+    Error,
+};
+
+/// The changes each watched directory is told of. Files are reported modified once closed after
+/// being written, rather than at each write.
+const MASK: u32 = libc::IN_CREATE
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_ONLYDIR;
+
+/// An `inotify` instance watching a directory and its subdirectories.
+struct Inotify {
+    fd: AsyncFd<OwnedFd>,
+    /// The watched directories by watch descriptor, as listed and as local paths.
+    dirs: HashMap<libc::c_int, (Path, PathBuf)>,
+    /// The files under the watched directory, to tell the ones created from the ones replaced,
+    /// and to report those of a directory removed or moved away.
+    files: BTreeSet<Path>,
+}
+
+impl Inotify {
+    /// Watches the directory `prefix`. None of the directories are watched if it doesn't exist.
+    fn new(prefix: &Path) -> Result<Self, Error> {
+        // SAFETY: `inotify_init1` only reads its flags
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: `fd` was just opened and is owned by nothing else
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut inotify = Self {
+            fd: AsyncFd::new(fd)?,
+            dirs: HashMap::new(),
+            files: BTreeSet::new(),
+        };
+        inotify.add_tree(prefix.clone(), path_to_local(prefix)?, None)?;
+        Ok(inotify)
+    }
+
+    /// Watches the directory `path` and the ones under it, recording their files, which are
+    /// reported created to `events` if given.
+    fn add_tree(
+        &mut self,
+        path: Path,
+        local_path: PathBuf,
+        mut events: Option<&mut Vec<WatchEvent>>,
+    ) -> Result<(), Error> {
+        let mut dirs = vec![(path, local_path)];
+        while let Some((path, local_path)) = dirs.pop() {
+            let c_path =
+                CString::new(local_path.as_os_str().as_bytes()).map_err(io::Error::from)?;
+            // SAFETY: `c_path` is a valid C string, `fd` is open for as long as `self`
+            let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), c_path.as_ptr(), MASK) };
+            if wd < 0 {
+                let e = io::Error::last_os_error();
+                match e.raw_os_error() {
+                    // removed, or replaced with a file, since it was listed
+                    Some(libc::ENOENT | libc::ENOTDIR) => continue,
+                    _ => return Err(e.into()),
+                }
+            }
+            self.dirs.insert(wd, (path.clone(), local_path.clone()));
+
+            let entries = match local_path.read_dir() {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                let entry = entry?;
+                let child = path.child(entry.file_name().to_string_lossy().as_ref());
+                let metadata = entry.metadata()?;
+                if metadata.is_dir() {
+                    dirs.push((child, entry.path()));
+                    continue;
+                }
+                if self.files.insert(child.clone()) {
+                    if let Some(events) = events.as_deref_mut() {
+                        events.push(WatchEvent::Created(file_meta(child, &metadata)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops watching the directory `path` and the ones under it, reporting their files removed.
+    fn remove_tree(&mut self, path: &Path, events: &mut Vec<WatchEvent>) {
+        let removed = self
+            .files
+            .iter()
+            .filter(|file| file.prefix_matches(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for file in removed {
+            self.files.remove(&file);
+            events.push(WatchEvent::Removed(file));
+        }
+        let fd = self.fd.as_raw_fd();
+        self.dirs.retain(|wd, (dir, _)| {
+            if !dir.prefix_matches(path) {
+                return true;
+            }
+            // SAFETY: `inotify_rm_watch` only reads its arguments, a watch already gone is an
+            // error
+            unsafe { libc::inotify_rm_watch(fd, *wd) };
+            false
+        });
+    }
+
+    /// Waits for changes, returning their events.
+    async fn next_events(&mut self, buf: &mut [u8]) -> Result<Vec<WatchEvent>, Error> {
+        let len = loop {
+            let mut guard = self.fd.readable().await?;
+            match guard.try_io(|fd| {
+                // SAFETY: `read` writes at most `buf.len()` bytes into `buf`
+                let len = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                match len < 0 {
+                    true => Err(io::Error::last_os_error()),
+                    false => Ok(len as usize),
+                }
+            }) {
+                Ok(len) => break len?,
+                Err(_would_block) => continue,
+            }
+        };
+
+        let mut events = vec![];
+        let mut offset = 0;
+        while offset + size_of::<libc::inotify_event>() <= len {
+            // SAFETY: the kernel reads out whole events, the header is within `len`, and isn't
+            // aligned within the buffer
+            let event = unsafe {
+                std::ptr::read_unaligned(buf[offset..].as_ptr().cast::<libc::inotify_event>())
+            };
+            let name_start = offset + size_of::<libc::inotify_event>();
+            offset = name_start + event.len as usize;
+            // the name is padded with NULs
+            let name = &buf[name_start..offset.min(len)];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            self.handle(event.wd, event.mask, name, &mut events)?;
+        }
+        Ok(events)
+    }
+
+    fn handle(
+        &mut self,
+        wd: libc::c_int,
+        mask: u32,
+        name: &[u8],
+        events: &mut Vec<WatchEvent>,
+    ) -> Result<(), Error> {
+        if mask & libc::IN_Q_OVERFLOW != 0 {
+            return Err(io::Error::other(
+                "too many changes at once, the `inotify` queue overflowed and some were lost",
+            )
+            .into());
+        }
+        if mask & libc::IN_IGNORED != 0 {
+            self.dirs.remove(&wd);
+            return Ok(());
+        }
+        let Some((dir, local_dir)) = self.dirs.get(&wd) else {
+            return Ok(());
+        };
+        let path = dir.child(String::from_utf8_lossy(name).as_ref());
+        let local_path = local_dir.join(OsStr::from_bytes(name));
+
+        let removed = mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0;
+        if mask & libc::IN_ISDIR != 0 {
+            match removed {
+                true => self.remove_tree(&path, events),
+                false => self.add_tree(path, local_path, Some(events))?,
+            }
+            return Ok(());
+        }
+        if removed {
+            if self.files.remove(&path) {
+                events.push(WatchEvent::Removed(path));
+            }
+            return Ok(());
+        }
+
+        let metadata = match std::fs::metadata(&local_path) {
+            Ok(metadata) => metadata,
+            // removed since, its removal is reported next
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let created = self.files.insert(path.clone());
+        let meta = file_meta(path, &metadata);
+        match created {
+            true => events.push(WatchEvent::Created(meta)),
+            // a file recorded by the listing of its new directory is already reported
+            false if mask & libc::IN_CREATE != 0 => {}
+            false => events.push(WatchEvent::Modified(meta)),
+        }
+        Ok(())
+    }
+}
+
+fn file_meta(path: Path, metadata: &std::fs::Metadata) -> FileMeta {
+    FileMeta {
+        path,
+        size: metadata.len(),
+        etag: None,
+        content_encoding: None,
+        storage_class: None,
+        restore_status: None,
+        local_version: metadata_version(metadata),
+    }
+}
+
+/// Watches `prefix` with `inotify`, falling back to listing it every `interval` when it can't be
+/// watched, i.e. it isn't a directory, or once it's removed, or when `inotify` is out of watches.
+pub(crate) fn watch(
+    fs: &TokioFs,
+    prefix: Path,
+    interval: Duration,
+) -> impl Stream<Item = Result<WatchEvent, Error>> + '_ {
+    stream! {
+        if let Ok(mut inotify) = Inotify::new(&prefix) {
+            let mut buf = vec![0; 64 * 1024];
+            while !inotify.dirs.is_empty() {
+                match inotify.next_events(&mut buf).await {
+                    Ok(events) => {
+                        for event in events {
+                            yield Ok(event);
+                        }
+                    }
+                    Err(e) => yield Err(e),
+                }
+            }
+        }
+        let mut events = pin!(poll_watch(fs, prefix, interval));
+        while let Some(event) = events.next().await {
+            yield event;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn created_files_are_reported_promptly() {
+        use std::{pin::pin, time::Duration};
+
+        use futures_core::Stream;
+        use futures_util::StreamExt;
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{Fs, WatchEvent},
+            path::Path,
+            Error,
+        };
+
+        async fn next(
+            events: &mut (impl Stream<Item = Result<WatchEvent, Error>> + Unpin),
+        ) -> WatchEvent {
+            tokio::time::timeout(Duration::from_secs(1), events.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap()
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        // a listing every hour wouldn't notice anything within the test
+        let mut events = pin!(TokioFs.watch(&dir, Duration::from_secs(3600)));
+        // the watch starts once the stream is polled
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), events.next())
+                .await
+                .is_err()
+        );
+
+        std::fs::write(tmp_dir.path().join("file"), b"hello").unwrap();
+        match next(&mut events).await {
+            WatchEvent::Created(meta) => assert_eq!(meta.path, dir.child("file")),
+            event => panic!("unexpected {:?}", event),
+        }
+        // then modified once closed after the write
+        match next(&mut events).await {
+            WatchEvent::Modified(meta) => assert_eq!(meta.size, 5),
+            event => panic!("unexpected {:?}", event),
+        }
+
+        // in directories created after the watch started too
+        std::fs::create_dir(tmp_dir.path().join("sub")).unwrap();
+        std::fs::write(tmp_dir.path().join("sub/nested"), b"").unwrap();
+        let nested = dir.child("sub").child("nested");
+        assert!(matches!(
+            next(&mut events).await,
+            WatchEvent::Created(meta) if meta.path == nested
+        ));
+
+        std::fs::remove_dir_all(tmp_dir.path().join("sub")).unwrap();
+        let mut event = next(&mut events).await;
+        // the closing of the nested file may come before its removal
+        if matches!(event, WatchEvent::Modified(_)) {
+            event = next(&mut events).await;
+        }
+        assert_eq!(event, WatchEvent::Removed(nested));
+    }
+}
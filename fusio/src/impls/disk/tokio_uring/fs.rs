@@ -1,37 +1,125 @@
+use std::{io, os::fd::OwnedFd, path::PathBuf};
+
 use async_stream::stream;
 use futures_core::Stream;
 use tokio_uring::fs::{create_dir_all, remove_file};
 
+#[cfg(feature = "mmap")]
+use crate::{disk::map_file, fs::Mmap};
+#[cfg(feature = "checksum")]
+use crate::{disk::walk_dir, fs::listing_digest};
 use crate::{
-    disk::tokio_uring::TokioUringFile,
-    fs::{FileMeta, Fs, OpenOptions},
+    disk::{
+        copy_file, create_temp, dir_entry_meta,
+        direct::open_direct,
+        list_dir_delimited, list_dir_page,
+        lock::lock,
+        metadata_version, open_anon_temp,
+        remove_dir::remove_dir_all,
+        rename::{rename, rename_durable},
+        retry_transient,
+        tokio_uring::TokioUringFile,
+        walk_dir_stats,
+        xattr::{get_xattr, list_xattr, set_xattr},
+        DEFAULT_CHUNK_SIZE,
+    },
+    fs::{
+        DelimitedListing, DirStats, FileLock, FileMeta, FileType, Fs, LockMode, OpenOptions,
+        TempGuard,
+    },
     path::{path_to_local, Path},
     Error,
 };
 
-pub struct TokioUringFs;
+pub struct TokioUringFs {
+    chunk_size: usize,
+}
+
+impl Default for TokioUringFs {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl TokioUringFs {
+    /// Sets the size of the reads and writes the files opened by this file system split larger
+    /// ones into, [`DEFAULT_CHUNK_SIZE`] by default. io_uring throughput depends on it: larger
+    /// chunks mean fewer submissions, smaller ones less memory pinned per operation.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is 0.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "the chunk size must not be 0");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    fn file(&self, file: tokio_uring::fs::File) -> TokioUringFile {
+        TokioUringFile::from(file).with_chunk_size(self.chunk_size)
+    }
+
+    /// Wraps an already opened file descriptor, e.g. one handed over by a sandbox or opened
+    /// relative to a directory with `openat`, into a file of this file system.
+    pub fn open_fd(&self, fd: OwnedFd) -> Result<TokioUringFile, Error> {
+        Ok(self.file(tokio_uring::fs::File::from_std(std::fs::File::from(fd))))
+    }
+}
 
 impl Fs for TokioUringFs {
     type File = TokioUringFile;
 
     async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
         let local_path = path_to_local(path)?;
+        // a directory opens as a file, only to fail on its first read
+        if std::fs::metadata(&local_path).is_ok_and(|meta| meta.is_dir()) {
+            return Err(Error::IsADirectory {
+                path: path.to_string(),
+            });
+        }
+        if options.direct {
+            let file = open_direct(&local_path, options)?;
+            // buffers go to the kernel as they are, chunks copied from them would be misaligned
+            return Ok(self
+                .file(tokio_uring::fs::File::from_std(file))
+                .with_chunk_size(usize::MAX));
+        }
+
+        let file = retry_transient(|| async {
+            tokio_uring::fs::OpenOptions::new()
+                .read(options.read)
+                .write(options.write)
+                .create(options.create)
+                .truncate(options.truncate)
+                .open(&local_path)
+                .await
+        })
+        .await?;
+
+        Ok(self.file(file))
+    }
 
-        let file = tokio_uring::fs::OpenOptions::new()
-            .read(options.read)
-            .write(options.write)
-            .create(options.create)
-            .truncate(options.truncate)
-            .open(&local_path)
-            .await?;
-
-        Ok(TokioUringFile {
-            file: Some(file),
-            pos: 0,
+    async fn temp_file(&self, dir: &Path, prefix: &str) -> Result<(Path, Self::File), Error> {
+        let (path, file) = create_temp(dir, prefix, |path| async move {
+            tokio_uring::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .await
         })
+        .await?;
+        Ok((path, self.file(file)))
     }
 
-    async fn create_dir_all(path: &Path) -> Result<(), Error> {
+    async fn anon_temp_file(&self, dir: &Path) -> Result<Self::File, Error> {
+        let file = open_anon_temp(path_to_local(dir)?)?;
+        Ok(self.file(tokio_uring::fs::File::from_std(file)))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
         let path = path_to_local(path)?;
         create_dir_all(path).await?;
 
@@ -42,20 +130,125 @@ impl Fs for TokioUringFs {
         &self,
         path: &Path,
     ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
-        let path = path_to_local(path)?;
-        let dir = path.read_dir()?;
+        let dir = path_to_local(path)?.read_dir()?;
+        let path = path.clone();
 
         Ok(stream! {
+            // an entry that can't be read doesn't end the listing
             for entry in dir {
-                let entry = entry?;
-                yield Ok(FileMeta { path: Path::from_filesystem_path(entry.path())?, size: entry.metadata()?.len() });
+                yield dir_entry_meta(&path, entry);
             }
         })
     }
 
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        list_dir_page(path_to_local(path)?, path, cursor, page_size)
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        list_dir_delimited(path_to_local(path)?, path)
+    }
+
     async fn remove(&self, path: &Path) -> Result<(), Error> {
         let path = path_to_local(path)?;
 
         Ok(remove_file(path).await?)
     }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<(), Error> {
+        Ok(remove_dir_all(&path_to_local(path)?)?)
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        let local_path = path_to_local(path)?;
+        let metadata = std::fs::metadata(local_path)?;
+
+        Ok(FileMeta {
+            path: path.clone(),
+            size: metadata.len(),
+            etag: None,
+            content_encoding: None,
+            storage_class: None,
+            restore_status: None,
+            local_version: metadata_version(&metadata),
+        })
+    }
+
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        let local_path = path_to_local(path)?;
+
+        match std::fs::metadata(local_path) {
+            Ok(meta) if meta.is_dir() => Ok(Some(FileType::Dir)),
+            Ok(_) => Ok(Some(FileType::File)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        walk_dir_stats(path_to_local(path)?)
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        listing_digest(path, walk_dir(path_to_local(path)?, path)?)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = path_to_local(from)?;
+        let to = path_to_local(to)?;
+
+        Ok(copy_file(&from, &to)?)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = path_to_local(from)?;
+        let to = path_to_local(to)?;
+
+        Ok(rename(&from, &to)?)
+    }
+
+    async fn rename_durable(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = path_to_local(from)?;
+        let to = path_to_local(to)?;
+
+        Ok(rename_durable(&from, &to)?)
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        get_xattr(&path_to_local(path)?, name)
+    }
+
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        set_xattr(&path_to_local(path)?, name, value)
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        list_xattr(&path_to_local(path)?)
+    }
+
+    async fn lock_shared(&self, path: &Path) -> Result<FileLock, Error> {
+        lock(&path_to_local(path)?, LockMode::Shared)
+    }
+
+    async fn lock_exclusive(&self, path: &Path) -> Result<FileLock, Error> {
+        lock(&path_to_local(path)?, LockMode::Exclusive)
+    }
+
+    #[cfg(feature = "mmap")]
+    async fn mmap(&self, path: &Path) -> Result<Mmap, Error> {
+        map_file(&path_to_local(path)?)
+    }
+
+    async fn localize(&self, path: &Path) -> Result<(PathBuf, TempGuard), Error> {
+        let local_path = path_to_local(path)?;
+        // a missing file fails as its download would
+        std::fs::metadata(&local_path)?;
+        Ok((local_path, TempGuard::none()))
+    }
 }
@@ -6,7 +6,13 @@ pub mod fs;
 pub use fs::TokioUringFs;
 use tokio_uring::fs::File;
 
-use crate::{Error, IoBuf, IoBufMut, Read, Write};
+use crate::{
+    disk::{
+        chunk::{read_chunks, write_chunks},
+        read_error, DEFAULT_CHUNK_SIZE,
+    },
+    Error, IoBuf, IoBufMut, Read, Write,
+};
 
 #[repr(transparent)]
 struct TokioUringBuf<B> {
@@ -44,6 +50,21 @@ where
 pub struct TokioUringFile {
     file: Option<File>,
     pos: u64,
+    chunk_size: usize,
+}
+
+impl TokioUringFile {
+    /// Splits the reads and writes of more than `chunk_size` bytes into ones of that size,
+    /// [`DEFAULT_CHUNK_SIZE`] unless set.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is 0.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "the chunk size must not be 0");
+        self.chunk_size = chunk_size;
+        self
+    }
 }
 
 impl From<File> for TokioUringFile {
@@ -51,18 +72,32 @@ impl From<File> for TokioUringFile {
         Self {
             file: Some(file),
             pos: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
         }
     }
 }
 
 impl Write for TokioUringFile {
     async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
-        let (result, buf) = self
-            .file
-            .as_ref()
-            .expect("read file after closed")
-            .write_all_at(TokioUringBuf { buf }, self.pos)
+        let file = self.file.as_ref().expect("write file after closed");
+        let len = buf.bytes_init();
+        if len > self.chunk_size {
+            let result = write_chunks(
+                buf.as_slice(),
+                self.pos,
+                self.chunk_size,
+                |chunk, pos| async move {
+                    let (result, chunk) =
+                        file.write_all_at(TokioUringBuf { buf: chunk }, pos).await;
+                    (result, chunk.buf)
+                },
+            )
             .await;
+            self.pos += len as u64;
+            return (result.map_err(Error::from), buf);
+        }
+
+        let (result, buf) = file.write_all_at(TokioUringBuf { buf }, self.pos).await;
         self.pos += buf.buf.bytes_init() as u64;
         (result.map_err(Error::from), buf.buf)
     }
@@ -80,6 +115,12 @@ impl Write for TokioUringFile {
         File::close(self.file.take().expect("close file twice")).await?;
         Ok(())
     }
+
+    async fn allocate(&mut self, len: u64) -> Result<(), Error> {
+        let file = self.file.as_ref().expect("allocate file after closed");
+        crate::disk::allocate(std::os::fd::AsRawFd::as_raw_fd(file), len)?;
+        Ok(())
+    }
 }
 
 impl Read for TokioUringFile {
@@ -91,28 +132,21 @@ impl Read for TokioUringFile {
             .read_exact_at(TokioUringBuf { buf }, pos)
             .await;
 
-        (result.map_err(Error::from), buf.buf)
+        (result.map_err(read_error), buf.buf)
     }
 
-    async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
-        match self.size().await {
-            Ok(size) => {
-                buf.resize((size - pos) as usize, 0);
-            }
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let size = match self.size().await {
+            Ok(size) => size,
             Err(e) => return (Err(e), buf),
-        }
-
-        let (result, buf) = self
-            .file
-            .as_ref()
-            .expect("read file after closed")
-            .read_exact_at(TokioUringBuf { buf }, pos)
-            .await;
-
-        match result {
-            Ok(_) => (Ok(()), buf.buf),
-            Err(e) => (Err(Error::from(e)), buf.buf),
-        }
+        };
+
+        let file = self.file.as_ref().expect("read file after closed");
+        read_chunks(buf, pos, size, self.chunk_size, |chunk, pos| async move {
+            let (result, chunk) = file.read_at(TokioUringBuf { buf: chunk }, pos).await;
+            (result, chunk.buf)
+        })
+        .await
     }
 
     async fn size(&self) -> Result<u64, Error> {
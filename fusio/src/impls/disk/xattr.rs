@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use crate::Error;
+
+cfg_if::cfg_if! {
+    if #[cfg(any(target_os = "linux", target_os = "macos"))] {
+        use std::{ffi::CString, io, os::unix::ffi::OsStrExt, ptr};
+
+        fn c_string(bytes: &[u8]) -> io::Result<CString> {
+            CString::new(bytes).map_err(io::Error::from)
+        }
+
+        fn xattr_error(err: io::Error, path: &Path) -> Error {
+            match err.raw_os_error() {
+                Some(libc::ENOTSUP) => unsupported(path),
+                _ => err.into(),
+            }
+        }
+
+        /// Calls `read` with a buffer large enough for what it reads, retrying if it grew in the
+        /// meantime. `read` is first called without a buffer to get the size to allocate.
+        fn read_sized(mut read: impl FnMut(*mut u8, usize) -> isize) -> io::Result<Vec<u8>> {
+            loop {
+                let size = read(ptr::null_mut(), 0);
+                if size < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let mut buf = vec![0u8; size as usize];
+                let len = read(buf.as_mut_ptr(), buf.len());
+                if len >= 0 {
+                    buf.truncate(len as usize);
+                    return Ok(buf);
+                }
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(libc::ERANGE) {
+                    return Err(err);
+                }
+            }
+        }
+
+        /// Returns the value of the extended attribute `name` of the file at `path`, `None` if it
+        /// isn't set.
+        pub(crate) fn get_xattr(path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+            let c_path = c_string(path.as_os_str().as_bytes())?;
+            let c_name = c_string(name.as_bytes())?;
+            let value = read_sized(|buf, size| unsafe {
+                sys::getxattr(c_path.as_ptr(), c_name.as_ptr(), buf.cast(), size)
+            });
+            match value {
+                Ok(value) => Ok(Some(value)),
+                Err(e) if e.raw_os_error() == Some(sys::NO_ATTRIBUTE) => Ok(None),
+                Err(e) => Err(xattr_error(e, path)),
+            }
+        }
+
+        /// Sets the extended attribute `name` of the file at `path` to `value`.
+        pub(crate) fn set_xattr(path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+            let c_path = c_string(path.as_os_str().as_bytes())?;
+            let c_name = c_string(name.as_bytes())?;
+            let result = unsafe {
+                sys::setxattr(
+                    c_path.as_ptr(),
+                    c_name.as_ptr(),
+                    value.as_ptr().cast(),
+                    value.len(),
+                )
+            };
+            if result < 0 {
+                return Err(xattr_error(io::Error::last_os_error(), path));
+            }
+            Ok(())
+        }
+
+        /// Lists the names of the extended attributes of the file at `path`.
+        pub(crate) fn list_xattr(path: &Path) -> Result<Vec<String>, Error> {
+            let c_path = c_string(path.as_os_str().as_bytes())?;
+            let names = read_sized(|buf, size| unsafe {
+                sys::listxattr(c_path.as_ptr(), buf.cast(), size)
+            })
+            .map_err(|e| xattr_error(e, path))?;
+            // the names are NUL terminated, one after the other
+            Ok(names
+                .split(|byte| *byte == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .collect())
+        }
+    } else {
+        pub(crate) fn get_xattr(path: &Path, _: &str) -> Result<Option<Vec<u8>>, Error> {
+            Err(unsupported(path))
+        }
+
+        pub(crate) fn set_xattr(path: &Path, _: &str, _: &[u8]) -> Result<(), Error> {
+            Err(unsupported(path))
+        }
+
+        pub(crate) fn list_xattr(path: &Path) -> Result<Vec<String>, Error> {
+            Err(unsupported(path))
+        }
+    }
+}
+
+fn unsupported(path: &Path) -> Error {
+    Error::Unsupported {
+        message: format!(
+            "the file system of \"{}\" doesn't support extended attributes",
+            path.display()
+        ),
+    }
+}
+
+/// The calls of Linux, with the position and options macOS adds left out.
+#[cfg(target_os = "linux")]
+mod sys {
+    use libc::{c_char, c_int, c_void, size_t};
+    pub(super) use libc::{getxattr, listxattr};
+
+    /// The error of a missing attribute.
+    pub(super) const NO_ATTRIBUTE: c_int = libc::ENODATA;
+
+    pub(super) unsafe fn setxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *const c_void,
+        size: size_t,
+    ) -> c_int {
+        libc::setxattr(path, name, value, size, 0)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+
+    /// The error of a missing attribute.
+    pub(super) const NO_ATTRIBUTE: c_int = libc::ENOATTR;
+
+    pub(super) unsafe fn getxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *mut c_void,
+        size: size_t,
+    ) -> ssize_t {
+        libc::getxattr(path, name, value, size, 0, 0)
+    }
+
+    pub(super) unsafe fn setxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *const c_void,
+        size: size_t,
+    ) -> c_int {
+        libc::setxattr(path, name, value, size, 0, 0)
+    }
+
+    pub(super) unsafe fn listxattr(
+        path: *const c_char,
+        names: *mut c_char,
+        size: size_t,
+    ) -> ssize_t {
+        libc::listxattr(path, names, size, 0)
+    }
+}
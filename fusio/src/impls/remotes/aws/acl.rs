@@ -0,0 +1,223 @@
+use super::S3Error;
+use crate::{
+    remotes::serde::{AccessControlPolicy, AclGrantee},
+    Error,
+};
+
+pub(crate) const ACL_HEADER: &str = "x-amz-acl";
+
+/// An ACL predefined by S3, set with the `x-amz-acl` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CannedAcl {
+    /// Only the owner has access.
+    Private,
+    /// Anyone can read the object.
+    PublicRead,
+    /// Anyone can read and write the object.
+    PublicReadWrite,
+    /// Any authenticated AWS user can read the object.
+    AuthenticatedRead,
+    /// The owner of the bucket can read the object.
+    BucketOwnerRead,
+    /// The owner of the bucket has full control of the object.
+    BucketOwnerFullControl,
+}
+
+impl CannedAcl {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CannedAcl::Private => "private",
+            CannedAcl::PublicRead => "public-read",
+            CannedAcl::PublicReadWrite => "public-read-write",
+            CannedAcl::AuthenticatedRead => "authenticated-read",
+            CannedAcl::BucketOwnerRead => "bucket-owner-read",
+            CannedAcl::BucketOwnerFullControl => "bucket-owner-full-control",
+        }
+    }
+}
+
+/// The ACL of an object, as returned by
+/// [`AmazonS3::get_object_acl`](super::fs::AmazonS3::get_object_acl).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectAcl {
+    pub owner: Option<Owner>,
+    pub grants: Vec<Grant>,
+}
+
+/// The canonical user owning an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub id: String,
+    pub display_name: Option<String>,
+}
+
+/// A permission given to a grantee by an [`ObjectAcl`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub grantee: Grantee,
+    pub permission: Permission,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grantee {
+    CanonicalUser {
+        id: String,
+        display_name: Option<String>,
+    },
+    /// A predefined group of users, e.g. `http://acs.amazonaws.com/groups/global/AllUsers`.
+    Group {
+        uri: String,
+    },
+    AmazonCustomerByEmail {
+        email_address: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    FullControl,
+    Write,
+    WriteAcp,
+    Read,
+    ReadAcp,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::FullControl => "FULL_CONTROL",
+            Permission::Write => "WRITE",
+            Permission::WriteAcp => "WRITE_ACP",
+            Permission::Read => "READ",
+            Permission::ReadAcp => "READ_ACP",
+        }
+    }
+
+    fn parse(permission: &str) -> Option<Self> {
+        Some(match permission {
+            "FULL_CONTROL" => Permission::FullControl,
+            "WRITE" => Permission::Write,
+            "WRITE_ACP" => Permission::WriteAcp,
+            "READ" => Permission::Read,
+            "READ_ACP" => Permission::ReadAcp,
+            _ => return None,
+        })
+    }
+}
+
+/// Parses the `AccessControlPolicy` returned by a `GetObjectAcl`. The kind of a grantee is told
+/// by the element it is identified with rather than by its `xsi:type` attribute.
+pub(crate) fn parse_acl(xml: &[u8]) -> Result<ObjectAcl, Error> {
+    let policy: AccessControlPolicy = quick_xml::de::from_reader(xml).map_err(S3Error::from)?;
+
+    let grants = policy
+        .access_control_list
+        .grant
+        .into_iter()
+        .map(|grant| {
+            let permission = Permission::parse(&grant.permission).ok_or_else(|| {
+                Error::Other(format!("unknown ACL permission: {}", grant.permission).into())
+            })?;
+            let grantee = match grant.grantee {
+                AclGrantee { uri: Some(uri), .. } => Grantee::Group { uri },
+                AclGrantee {
+                    email_address: Some(email_address),
+                    ..
+                } => Grantee::AmazonCustomerByEmail { email_address },
+                AclGrantee {
+                    id: Some(id),
+                    display_name,
+                    ..
+                } => Grantee::CanonicalUser { id, display_name },
+                _ => return Err(Error::Other("ACL grant without a grantee".into())),
+            };
+            Ok(Grant {
+                grantee,
+                permission,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(ObjectAcl {
+        owner: policy.owner.map(|owner| Owner {
+            id: owner.id,
+            display_name: owner.display_name,
+        }),
+        grants,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_access_control_policy() {
+        use super::{parse_acl, Grant, Grantee, ObjectAcl, Owner, Permission};
+
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AccessControlPolicy xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Owner>
+    <ID>75aa57f09aa0c8caeab4f8c24e99d10f8e7faeebf76c078efc7c6caea54ba06a</ID>
+    <DisplayName>owner</DisplayName>
+  </Owner>
+  <AccessControlList>
+    <Grant>
+      <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="CanonicalUser">
+        <ID>75aa57f09aa0c8caeab4f8c24e99d10f8e7faeebf76c078efc7c6caea54ba06a</ID>
+        <DisplayName>owner</DisplayName>
+      </Grantee>
+      <Permission>FULL_CONTROL</Permission>
+    </Grant>
+    <Grant>
+      <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="Group">
+        <URI>http://acs.amazonaws.com/groups/global/AllUsers</URI>
+      </Grantee>
+      <Permission>READ</Permission>
+    </Grant>
+    <Grant>
+      <Grantee xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="AmazonCustomerByEmail">
+        <EmailAddress>auditor@example.com</EmailAddress>
+      </Grantee>
+      <Permission>READ_ACP</Permission>
+    </Grant>
+  </AccessControlList>
+</AccessControlPolicy>"#;
+
+        let id = "75aa57f09aa0c8caeab4f8c24e99d10f8e7faeebf76c078efc7c6caea54ba06a";
+        assert_eq!(
+            parse_acl(xml.as_bytes()).unwrap(),
+            ObjectAcl {
+                owner: Some(Owner {
+                    id: id.into(),
+                    display_name: Some("owner".into()),
+                }),
+                grants: vec![
+                    Grant {
+                        grantee: Grantee::CanonicalUser {
+                            id: id.into(),
+                            display_name: Some("owner".into()),
+                        },
+                        permission: Permission::FullControl,
+                    },
+                    Grant {
+                        grantee: Grantee::Group {
+                            uri: "http://acs.amazonaws.com/groups/global/AllUsers".into(),
+                        },
+                        permission: Permission::Read,
+                    },
+                    Grant {
+                        grantee: Grantee::AmazonCustomerByEmail {
+                            email_address: "auditor@example.com".into(),
+                        },
+                        permission: Permission::ReadAcp,
+                    },
+                ],
+            }
+        );
+
+        assert!(parse_acl(
+            b"<AccessControlPolicy><AccessControlList><Grant><Grantee><ID>a</ID></Grantee>\
+              <Permission>OWN</Permission></Grant></AccessControlList></AccessControlPolicy>"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use http::{
+    header::{CONTENT_ENCODING, CONTENT_TYPE},
+    request::Builder,
+    HeaderMap, HeaderName, HeaderValue,
+};
+use percent_encoding::utf8_percent_encode;
+use url::form_urlencoded;
+
+use super::{multipart_upload::UPLOAD_TOKEN_HEADER, STRICT_PATH_ENCODE_SET};
+use crate::path::Path;
+
+pub(crate) const COPY_SOURCE_HEADER: &str = "x-amz-copy-source";
+pub(crate) const COPY_SOURCE_RANGE_HEADER: &str = "x-amz-copy-source-range";
+/// Makes S3 refuse a copy with `412 Precondition Failed` unless the source has the given ETag.
+pub(crate) const COPY_SOURCE_IF_MATCH_HEADER: &str = "x-amz-copy-source-if-match";
+const METADATA_DIRECTIVE_HEADER: &str = "x-amz-metadata-directive";
+const TAGGING_DIRECTIVE_HEADER: &str = "x-amz-tagging-directive";
+const TAGGING_HEADER: &str = "x-amz-tagging";
+pub(crate) const METADATA_PREFIX: &str = "x-amz-meta-";
+
+/// The largest object `CopyObject` copies, larger ones are copied part by part.
+pub(crate) const MAX_COPY_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// The size of the parts of a multipart copy, unless the object needs larger ones to fit in
+/// [`MAX_PARTS`].
+const COPY_PART_SIZE: u64 = 512 * 1024 * 1024;
+pub(crate) const MAX_PARTS: u64 = 10_000;
+
+/// Returns the value of the `x-amz-copy-source` header copying `path` of `bucket`.
+pub(crate) fn copy_source(bucket: &str, path: &Path) -> String {
+    format!(
+        "/{}/{}",
+        bucket,
+        utf8_percent_encode(path.as_ref(), &STRICT_PATH_ENCODE_SET)
+    )
+}
+
+/// Splits an object of `size` bytes into the inclusive byte ranges of the parts of a multipart
+/// copy.
+pub(crate) fn copy_part_ranges(size: u64) -> impl Iterator<Item = (u64, u64)> {
+    let part_size = COPY_PART_SIZE.max(size.div_ceil(MAX_PARTS));
+    (0..size)
+        .step_by(part_size as usize)
+        .map(move |start| (start, (start + part_size).min(size) - 1))
+}
+
+/// How a server side copy treats the user metadata and tags of the source object. By default
+/// both are copied, [`CopyOptions::replace_metadata`] and [`CopyOptions::replace_tags`] give the
+/// copy its own instead.
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    metadata: Option<BTreeMap<String, String>>,
+    tags: Option<BTreeMap<String, String>>,
+    source_etag: Option<String>,
+}
+
+impl CopyOptions {
+    /// Replaces the user metadata (`x-amz-meta-*`) of the copy.
+    pub fn replace_metadata(
+        mut self,
+        metadata: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.metadata = Some(
+            metadata
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Replaces the tags of the copy.
+    pub fn replace_tags(
+        mut self,
+        tags: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.tags = Some(
+            tags.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Only copies the source if its ETag still is `etag` (`x-amz-copy-source-if-match`), failing
+    /// with [`S3Error::PreconditionFailed`](super::S3Error::PreconditionFailed) otherwise.
+    pub fn if_source_matches(mut self, etag: impl Into<String>) -> Self {
+        self.source_etag = Some(etag.into());
+        self
+    }
+
+    /// The ETag the source is expected to have, if the copy is conditional.
+    pub(crate) fn source_etag(&self) -> Option<&str> {
+        self.source_etag.as_deref()
+    }
+
+    /// Whether the copy gets metadata or tags of its own rather than those of the source.
+    pub(crate) fn replaces_anything(&self) -> bool {
+        self.metadata.is_some() || self.tags.is_some()
+    }
+
+    /// Sets the directives of a `CopyObject` of an object with `source` headers. Replacing the
+    /// metadata replaces the content headers too, those of the source are sent again.
+    pub(crate) fn apply(&self, mut builder: Builder, source: &HeaderMap) -> Builder {
+        if let Some(etag) = &self.source_etag {
+            builder = builder.header(COPY_SOURCE_IF_MATCH_HEADER, etag);
+        }
+        builder = match &self.metadata {
+            Some(metadata) => {
+                builder = builder.header(METADATA_DIRECTIVE_HEADER, "REPLACE");
+                for name in [CONTENT_TYPE, CONTENT_ENCODING] {
+                    if let Some(value) = source.get(&name) {
+                        builder = builder.header(name, value);
+                    }
+                }
+                for (key, value) in metadata {
+                    builder = builder.header(format!("{}{}", METADATA_PREFIX, key), value);
+                }
+                builder
+            }
+            None => builder.header(METADATA_DIRECTIVE_HEADER, "COPY"),
+        };
+        match &self.tags {
+            Some(tags) => builder.header(TAGGING_DIRECTIVE_HEADER, "REPLACE").header(
+                TAGGING_HEADER,
+                form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(tags)
+                    .finish(),
+            ),
+            None => builder.header(TAGGING_DIRECTIVE_HEADER, "COPY"),
+        }
+    }
+
+    /// Returns the headers initiating the multipart upload of a copy of an object with `source`
+    /// headers. Unlike `CopyObject`, `UploadPartCopy` copies the content alone: the user metadata
+    /// is taken from the `HEAD` of the source, but its tags can't be and are only set when
+    /// replaced.
+    pub(crate) fn multipart_headers(&self, source: &HeaderMap) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for name in [CONTENT_TYPE, CONTENT_ENCODING] {
+            if let Some(value) = source.get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        match &self.metadata {
+            Some(metadata) => {
+                for (key, value) in metadata {
+                    if let (Ok(name), Ok(value)) = (
+                        HeaderName::try_from(format!("{}{}", METADATA_PREFIX, key)),
+                        HeaderValue::try_from(value),
+                    ) {
+                        headers.insert(name, value);
+                    }
+                }
+            }
+            None => {
+                for (name, value) in source {
+                    if name.as_str().starts_with(METADATA_PREFIX) && name != UPLOAD_TOKEN_HEADER {
+                        headers.insert(name, value.clone());
+                    }
+                }
+            }
+        }
+        if let Some(tags) = &self.tags {
+            if let Ok(value) = HeaderValue::try_from(
+                form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(tags)
+                    .finish(),
+            ) {
+                headers.insert(TAGGING_HEADER, value);
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn part_ranges_cover_large_object() {
+        use super::{copy_part_ranges, MAX_COPY_OBJECT_SIZE, MAX_PARTS};
+
+        let size = 6 * 1024 * 1024 * 1024;
+        let ranges = copy_part_ranges(size).collect::<Vec<_>>();
+
+        assert!(ranges.len() > 1 && ranges.len() as u64 <= MAX_PARTS);
+        assert_eq!(ranges.first().unwrap().0, 0);
+        assert_eq!(ranges.last().unwrap().1, size - 1);
+        for window in ranges.windows(2) {
+            assert_eq!(window[0].1 + 1, window[1].0);
+        }
+        for (i, (start, end)) in ranges.iter().enumerate() {
+            let len = end - start + 1;
+            assert!(len <= MAX_COPY_OBJECT_SIZE);
+            // every part but the last must be at least 5 MiB
+            assert!(i == ranges.len() - 1 || len >= 5 * 1024 * 1024);
+        }
+
+        let size = 5 * 1024 * 1024 * 1024 * 1024;
+        assert!(copy_part_ranges(size).count() as u64 <= MAX_PARTS);
+    }
+}
@@ -0,0 +1,165 @@
+use url::Url;
+
+use super::{credential::AwsAuthorizer, AwsCredential};
+
+/// Endpoint prefixes whose SigV4 signing name differs from the prefix itself.
+const SERVICE_ALIASES: &[(&str, &str)] = &[
+    ("email", "ses"),
+    ("streams.dynamodb", "dynamodb"),
+    ("runtime.sagemaker", "sagemaker"),
+    ("runtime.lex", "lex"),
+    ("data.iot", "iotdata"),
+];
+
+fn is_region(label: &str) -> bool {
+    label.split('-').count() >= 3 && label.ends_with(|c: char| c.is_ascii_digit())
+}
+
+/// The service name and region a request to an AWS endpoint must be signed for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningScope {
+    pub service: String,
+    pub region: String,
+}
+
+impl SigningScope {
+    pub fn new(service: impl Into<String>, region: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+            region: region.into(),
+        }
+    }
+
+    /// Resolves the signing scope of a well-known AWS endpoint host, e.g.
+    /// `dynamodb.us-west-2.amazonaws.com` or `bucket.s3.eu-west-1.amazonaws.com`. Endpoints of
+    /// global services without a region in their host, such as `iam.amazonaws.com`, are signed
+    /// for the first region of their partition.
+    pub fn resolve(host: &str) -> Option<Self> {
+        let host = host.to_ascii_lowercase();
+        let (labels, global_region) = match host.strip_suffix(".amazonaws.com") {
+            Some(labels) => (labels, "us-east-1"),
+            None => (host.strip_suffix(".amazonaws.com.cn")?, "cn-north-1"),
+        };
+        let labels = labels.split('.').collect::<Vec<_>>();
+
+        // S3 also has legacy `s3-<region>` and dualstack hosts, and virtual-hosted buckets before
+        // the service label
+        if let Some(i) = labels
+            .iter()
+            .rposition(|label| *label == "s3" || label.starts_with("s3-"))
+        {
+            let rest = labels[i + 1..]
+                .iter()
+                .filter(|label| **label != "dualstack")
+                .collect::<Vec<_>>();
+            let region = match (labels[i].strip_prefix("s3-"), rest.as_slice()) {
+                (Some("external-1"), []) | (None, []) => global_region,
+                (Some(region), []) if is_region(region) => region,
+                (None, [region]) if is_region(region) => **region,
+                _ => return None,
+            };
+            return Some(Self::new("s3", region));
+        }
+
+        let (prefix, region) = match labels.split_last()? {
+            (region, prefix) if is_region(region) && !prefix.is_empty() => (prefix, *region),
+            (_, []) => (labels.as_slice(), global_region),
+            _ => return None,
+        };
+        let prefix_name = prefix.join(".");
+        let service = SERVICE_ALIASES
+            .iter()
+            .find(|(alias, _)| {
+                prefix_name == *alias || prefix_name.ends_with(&format!(".{}", alias))
+            })
+            .map(|(_, service)| *service)
+            .unwrap_or_else(|| prefix[prefix.len() - 1]);
+        let service = service.strip_suffix("-fips").unwrap_or(service);
+
+        Some(Self::new(service, region))
+    }
+
+    /// Resolves the signing scope of the host of `url`, see [`SigningScope::resolve`].
+    pub fn from_url(url: &Url) -> Option<Self> {
+        Self::resolve(url.host_str()?)
+    }
+
+    pub fn authorizer<'a>(&'a self, credential: &'a AwsCredential) -> AwsAuthorizer<'a> {
+        AwsAuthorizer::new(credential, &self.service, &self.region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::SigningScope;
+
+    fn resolve(host: &str) -> Option<(String, String)> {
+        SigningScope::resolve(host).map(|scope| (scope.service, scope.region))
+    }
+
+    #[test]
+    fn resolve_regional_service() {
+        assert_eq!(
+            resolve("dynamodb.us-west-2.amazonaws.com"),
+            Some(("dynamodb".into(), "us-west-2".into()))
+        );
+        assert_eq!(
+            resolve("sqs.eu-central-1.amazonaws.com"),
+            Some(("sqs".into(), "eu-central-1".into()))
+        );
+        assert_eq!(
+            resolve("email.us-east-2.amazonaws.com"),
+            Some(("ses".into(), "us-east-2".into()))
+        );
+        assert_eq!(
+            resolve("abc123.execute-api.ap-southeast-1.amazonaws.com"),
+            Some(("execute-api".into(), "ap-southeast-1".into()))
+        );
+        assert_eq!(
+            resolve("dynamodb.cn-northwest-1.amazonaws.com.cn"),
+            Some(("dynamodb".into(), "cn-northwest-1".into()))
+        );
+    }
+
+    #[test]
+    fn resolve_s3() {
+        assert_eq!(
+            resolve("fusio-test.s3.ap-southeast-1.amazonaws.com"),
+            Some(("s3".into(), "ap-southeast-1".into()))
+        );
+        assert_eq!(
+            resolve("s3.dualstack.us-west-2.amazonaws.com"),
+            Some(("s3".into(), "us-west-2".into()))
+        );
+        assert_eq!(
+            resolve("my.s3.bucket.s3-eu-west-1.amazonaws.com"),
+            Some(("s3".into(), "eu-west-1".into()))
+        );
+        assert_eq!(
+            resolve("s3.amazonaws.com"),
+            Some(("s3".into(), "us-east-1".into()))
+        );
+    }
+
+    #[test]
+    fn resolve_global_service() {
+        assert_eq!(
+            resolve("iam.amazonaws.com"),
+            Some(("iam".into(), "us-east-1".into()))
+        );
+        let url = Url::parse("https://sts.amazonaws.com/?Action=GetCallerIdentity").unwrap();
+        assert_eq!(
+            SigningScope::from_url(&url),
+            Some(SigningScope::new("sts", "us-east-1"))
+        );
+    }
+
+    #[test]
+    fn reject_unknown_host() {
+        assert_eq!(resolve("example.com"), None);
+        assert_eq!(resolve("amazonaws.com"), None);
+        assert_eq!(resolve("a.b.amazonaws.com"), None);
+    }
+}
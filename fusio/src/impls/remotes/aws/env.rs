@@ -0,0 +1,215 @@
+//! Resolution of the S3 configuration from the standard AWS environment variables and the shared
+//! `credentials` and `config` files.
+
+use std::{collections::HashMap, io, path::PathBuf};
+
+use super::credential::AwsCredential;
+use crate::Error;
+
+const DEFAULT_PROFILE: &str = "default";
+
+/// The configuration found in the environment, what is missing is left to the defaults.
+#[derive(Debug, Default)]
+pub(crate) struct EnvConfig {
+    pub(crate) credential: Option<AwsCredential>,
+    pub(crate) region: Option<String>,
+    pub(crate) endpoint: Option<String>,
+}
+
+/// Resolves the configuration from the environment variables read by `var`:
+///
+/// - the credential from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`, or
+///   else from the profile `AWS_PROFILE` (`default` if unset) of the shared credentials file,
+///   `AWS_SHARED_CREDENTIALS_FILE` or `~/.aws/credentials`,
+/// - the region from `AWS_REGION`, `AWS_DEFAULT_REGION`, or else from the profile of the shared
+///   config file, `AWS_CONFIG_FILE` or `~/.aws/config`,
+/// - the endpoint from `AWS_ENDPOINT_URL_S3`, `AWS_ENDPOINT_URL`, or else the `endpoint_url` of the
+///   profile.
+///
+/// Missing shared files are skipped, a profile set with `AWS_PROFILE` that is found in neither
+/// is an error.
+pub(crate) fn env_config(var: impl Fn(&str) -> Option<String>) -> Result<EnvConfig, Error> {
+    let profile_name = var("AWS_PROFILE");
+    let profile = profile_name.as_deref().unwrap_or(DEFAULT_PROFILE);
+    let home = var("HOME")
+        .or_else(|| var("USERPROFILE"))
+        .map(PathBuf::from);
+    let shared_file = |variable: &str, default: &str| {
+        var(variable)
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".aws").join(default)))
+    };
+
+    let credentials = read_profiles(shared_file("AWS_SHARED_CREDENTIALS_FILE", "credentials"))?;
+    let config = read_profiles(shared_file("AWS_CONFIG_FILE", "config"))?;
+    // the config file prefixes the names of profiles other than the default one
+    let config_section = match profile {
+        DEFAULT_PROFILE => DEFAULT_PROFILE.to_string(),
+        profile => format!("profile {}", profile),
+    };
+    let credentials = credentials.get(profile);
+    let config = config.get(&config_section);
+    if profile_name.is_some() && credentials.is_none() && config.is_none() {
+        return Err(Error::Other(
+            format!("AWS profile \"{}\" not found", profile).into(),
+        ));
+    }
+    let profile_value = |key: &str| {
+        credentials
+            .and_then(|section| section.get(key))
+            .or_else(|| config.and_then(|section| section.get(key)))
+            .cloned()
+    };
+
+    let credential = match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+        (Some(key_id), Some(secret_key)) => Some(AwsCredential {
+            key_id,
+            secret_key,
+            token: var("AWS_SESSION_TOKEN"),
+        }),
+        _ => match (
+            profile_value("aws_access_key_id"),
+            profile_value("aws_secret_access_key"),
+        ) {
+            (Some(key_id), Some(secret_key)) => Some(AwsCredential {
+                key_id,
+                secret_key,
+                token: profile_value("aws_session_token"),
+            }),
+            _ => None,
+        },
+    };
+
+    Ok(EnvConfig {
+        credential,
+        region: var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .or_else(|| config.and_then(|section| section.get("region")).cloned()),
+        endpoint: var("AWS_ENDPOINT_URL_S3")
+            .or_else(|| var("AWS_ENDPOINT_URL"))
+            .or_else(|| profile_value("endpoint_url")),
+    })
+}
+
+type Profiles = HashMap<String, HashMap<String, String>>;
+
+fn read_profiles(path: Option<PathBuf>) -> Result<Profiles, Error> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(Profiles::new()),
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Ok(parse_profiles(&content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Profiles::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Parses the sections of an INI file like the shared AWS files, ignoring comments and the
+/// indented lines of nested settings.
+fn parse_profiles(content: &str) -> Profiles {
+    let mut profiles = Profiles::new();
+    let mut section = None;
+    for line in content.lines() {
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = name.trim().to_string();
+            profiles.entry(name.clone()).or_default();
+            section = Some(name);
+        } else if let (Some(section), Some((key, value))) = (&section, line.split_once('=')) {
+            profiles
+                .get_mut(section)
+                .unwrap()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    profiles
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn profiles_of_shared_files() {
+        use std::collections::HashMap;
+
+        use tempfile::TempDir;
+
+        use super::env_config;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("credentials"),
+            "[default]\naws_access_key_id = default-key\naws_secret_access_key = \
+             default-secret\n\n# the profile of the tests\n[dev]\naws_access_key_id = \
+             dev-key\naws_secret_access_key = dev-secret\naws_session_token = dev-token\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("config"),
+            "[default]\nregion = us-east-1\n\n[profile dev]\nregion = eu-west-3\ns3 =\n  \
+             addressing_style = path\n",
+        )
+        .unwrap();
+        let vars = HashMap::from([
+            (
+                "HOME",
+                dir.path().join("home").to_str().unwrap().to_string(),
+            ),
+            (
+                "AWS_SHARED_CREDENTIALS_FILE",
+                dir.path().join("credentials").to_str().unwrap().to_string(),
+            ),
+            (
+                "AWS_CONFIG_FILE",
+                dir.path().join("config").to_str().unwrap().to_string(),
+            ),
+        ]);
+        let config = |extra: &[(&'static str, &str)]| {
+            let mut vars = vars.clone();
+            vars.extend(extra.iter().map(|(name, value)| (*name, value.to_string())));
+            env_config(|name| vars.get(name).cloned())
+        };
+
+        let default = config(&[]).unwrap();
+        assert_eq!(default.credential.unwrap().key_id, "default-key");
+        assert_eq!(default.region.as_deref(), Some("us-east-1"));
+
+        let dev = config(&[("AWS_PROFILE", "dev")]).unwrap();
+        let credential = dev.credential.unwrap();
+        assert_eq!(credential.key_id, "dev-key");
+        assert_eq!(credential.token.as_deref(), Some("dev-token"));
+        assert_eq!(dev.region.as_deref(), Some("eu-west-3"));
+
+        // variables take precedence over the shared files
+        let overridden = config(&[
+            ("AWS_PROFILE", "dev"),
+            ("AWS_ACCESS_KEY_ID", "env-key"),
+            ("AWS_SECRET_ACCESS_KEY", "env-secret"),
+            ("AWS_DEFAULT_REGION", "ap-south-1"),
+        ])
+        .unwrap();
+        assert_eq!(overridden.credential.unwrap().key_id, "env-key");
+        assert_eq!(overridden.region.as_deref(), Some("ap-south-1"));
+        let overridden = config(&[
+            ("AWS_REGION", "sa-east-1"),
+            ("AWS_DEFAULT_REGION", "ap-south-1"),
+        ])
+        .unwrap();
+        assert_eq!(overridden.region.as_deref(), Some("sa-east-1"));
+
+        assert!(config(&[("AWS_PROFILE", "missing")]).is_err());
+        // without shared files, only the variables count
+        let bare = env_config(|name| (name == "AWS_REGION").then(|| "us-west-2".into())).unwrap();
+        assert!(bare.credential.is_none());
+        assert_eq!(bare.region.as_deref(), Some("us-west-2"));
+    }
+}
@@ -3,6 +3,7 @@ use thiserror::Error;
 use crate::remotes::{aws::credential::AuthorizeError, http::HttpError};
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum S3Error {
     #[error("http error: {0}")]
     HttpError(#[from] HttpError),
@@ -10,4 +11,25 @@ pub enum S3Error {
     AuthorizeError(#[from] AuthorizeError),
     #[error("xml parse error: {0}")]
     XmlParseError(#[from] quick_xml::DeError),
+    #[error("invalid S3 location: {0}")]
+    InvalidLocation(String),
+    #[error("object {0} is archived, restore it with `AmazonS3::restore` before reading it")]
+    ObjectArchived(String),
+    #[error("redirected more than {max} times, last to {url}")]
+    TooManyRedirects { url: String, max: usize },
+    #[error("redirect loop through {0}")]
+    RedirectLoop(String),
+    #[error(
+        "bucket {bucket} is in region {region}, requests to it must be signed for that region"
+    )]
+    WrongRegion { bucket: String, region: String },
+    #[error("the condition of the write of {0} doesn't hold")]
+    PreconditionFailed(String),
+    #[error(
+        "multipart upload {upload_id} of {path} no longer exists, it expired or was aborted, \
+         restart the upload from scratch"
+    )]
+    NoSuchUpload { path: String, upload_id: String },
+    #[error("the S3 file system was shut down")]
+    ShutDown,
 }
@@ -1,31 +1,155 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    future::Future,
+    io, mem,
+    ops::Range,
+    pin::pin,
+    str::FromStr,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
 
 use async_stream::stream;
-use bytes::{Buf, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use chrono::{DateTime, Utc};
 use futures_core::Stream;
-use http::{Method, Request};
-use http_body_util::{BodyExt, Empty};
+use futures_util::{future::join_all, StreamExt};
+use http::{
+    header::{
+        CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MATCH,
+        IF_NONE_MATCH, RANGE,
+    },
+    HeaderMap, HeaderValue, Method, Request, Response, StatusCode,
+};
+use http_body::Body;
+use http_body_util::{BodyExt, Empty, Full};
+use percent_encoding::utf8_percent_encode;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use super::{credential::AwsCredential, options::S3Options, S3Error, S3File};
+use super::{
+    acl::{parse_acl, ACL_HEADER},
+    copy::{
+        copy_part_ranges, copy_source, CopyOptions, COPY_SOURCE_HEADER, MAX_COPY_OBJECT_SIZE,
+        MAX_PARTS, METADATA_PREFIX,
+    },
+    credential::{
+        AwsAuthorizer, AwsCredential, InstanceCredentialProvider, DEFAULT_METADATA_ENDPOINT,
+    },
+    env::env_config,
+    multipart_upload::{MultipartUpload, UPLOAD_TOKEN_HEADER},
+    options::{ResponseOverrides, S3Options, ShutdownPolicy, WriteOptions},
+    prefetch::S3PrefetchReader,
+    redirect::{send_following_redirects, BUCKET_REGION_HEADER},
+    restore::{read_error, restore_request, RESTORE_HEADER},
+    s3::SharedWriter,
+    sign::SignPayload,
+    writer::{S3Writer, S3_PART_MINIMUM_SIZE},
+    CannedAcl, ObjectAcl, RestoreStatus, RestoreTier, S3Error, S3File, S3Location, S3ResponseError,
+    CHECKSUM_HEADER, STRICT_PATH_ENCODE_SET,
+};
 use crate::{
-    fs::{FileMeta, Fs, OpenOptions},
+    fs::{
+        case_variants, check_copy_range, digest_file, listing_digest, sha256, shape_listing,
+        ChecksumAlgorithm, DelimitedListing, DirStats, FileMeta, FileType, FileVersion, Fs,
+        ListOptions, OpenOptions, PresignedFs,
+    },
     path::Path,
     remotes::{
-        aws::sign::Sign,
-        http::{DynHttpClient, HttpClient, HttpError},
+        encoding::{base64_decode, base64_encode},
+        http::{
+            mime::content_type,
+            range::{parse_content_range, validate_content_range},
+            BoxBody, DynHttpClient, HttpClient, HttpError,
+        },
+        serde::CopyObjectResult,
     },
-    Error,
+    Error, IoBuf, MaybeSend, Write,
 };
 
+/// Asks S3 to return the checksums stored with an object in the response of a `HEAD`.
+const CHECKSUM_MODE_HEADER: &str = "x-amz-checksum-mode";
+
+/// The storage class of an object, left out of the responses for the standard one.
+const STORAGE_CLASS_HEADER: &str = "x-amz-storage-class";
+
+/// Returns the size of the parts [`Fs::put_stream`] uploads a content of `size_hint` bytes in,
+/// the smallest S3 allows unless the content would need more parts than it allows.
+fn stream_part_size(size_hint: Option<u64>) -> usize {
+    let size = size_hint.unwrap_or_default();
+    (S3_PART_MINIMUM_SIZE as u64).max(size.div_ceil(MAX_PARTS)) as usize
+}
+
+/// The most connections [`AmazonS3::warmup`] opens.
+pub const MAX_WARMUP_CONNECTIONS: usize = 64;
+
+/// Returns the `ETag` and `Content-Encoding` of an object from the headers of a response.
+fn content_headers(headers: &HeaderMap) -> (Option<String>, Option<String>) {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    (header(ETAG), header(CONTENT_ENCODING))
+}
+
+/// Whether `err` is the `404 Not Found` of a request to an object that doesn't exist.
+pub(super) fn is_missing(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::S3Error(S3Error::HttpError(HttpError::HttpNotSuccess { status, .. }))
+            if *status == StatusCode::NOT_FOUND
+    )
+}
+
+/// The namespace of the extended attributes stored as user metadata.
+const USER_XATTR_PREFIX: &str = "user.";
+
+/// Returns the user metadata key the extended attribute `name` is stored under, only `user.*`
+/// attributes can be.
+fn metadata_key(name: &str) -> Result<String, Error> {
+    match name.strip_prefix(USER_XATTR_PREFIX) {
+        Some(key) if !key.is_empty() => Ok(key.to_ascii_lowercase()),
+        _ => Err(Error::Unsupported {
+            message: format!(
+                "S3 only stores `user.*` extended attributes, not \"{}\"",
+                name
+            ),
+        }),
+    }
+}
+
+/// Returns the size of an object from the `Content-Length` of a response, `None` if it has none,
+/// as with the chunked responses of some S3-compatible servers.
+fn content_length(headers: &HeaderMap) -> Result<Option<u64>, Error> {
+    headers
+        .get(CONTENT_LENGTH)
+        .map(|length| {
+            length
+                .to_str()
+                .map_err(|e| Error::Other(e.into()))?
+                .parse::<u64>()
+                .map_err(|e| Error::Other(e.into()))
+        })
+        .transpose()
+}
+
 pub struct AmazonS3Builder {
     region: String,
     bucket: String,
     credential: Option<AwsCredential>,
+    instance_credentials: bool,
     sign_payload: bool,
+    sign_payload_threshold: Option<u64>,
     checksum: bool,
+    append_emulation: bool,
+    expected_bucket_owner: Option<String>,
+    max_redirects: usize,
+    endpoint: Option<String>,
+    base_path: String,
     client: Box<dyn DynHttpClient>,
 }
 
@@ -39,8 +163,15 @@ impl AmazonS3Builder {
                     region: "us-east-1".into(),
                     bucket,
                     credential: None,
+                    instance_credentials: false,
                     sign_payload: false,
+                    sign_payload_threshold: None,
                     checksum: false,
+                    append_emulation: false,
+                    expected_bucket_owner: None,
+                    max_redirects: super::redirect::DEFAULT_MAX_REDIRECTS,
+                    endpoint: None,
+                    base_path: String::new(),
                     client,
                 }
             } else {
@@ -51,6 +182,25 @@ impl AmazonS3Builder {
 }
 
 impl AmazonS3Builder {
+    /// Creates a builder for `bucket` configured from the standard AWS environment variables:
+    /// the credential of `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`,
+    /// the region of `AWS_REGION` or `AWS_DEFAULT_REGION` and the endpoint of
+    /// `AWS_ENDPOINT_URL_S3` or `AWS_ENDPOINT_URL`. What they leave unset is read from the
+    /// profile `AWS_PROFILE` of the shared `~/.aws/credentials` and `~/.aws/config` files.
+    pub fn from_env(bucket: String) -> Result<Self, Error> {
+        Self::new(bucket).with_env(|name| std::env::var(name).ok())
+    }
+
+    fn with_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        let config = env_config(var)?;
+        self.credential = config.credential;
+        if let Some(region) = config.region {
+            self.region = region;
+        }
+        self.endpoint = config.endpoint;
+        Ok(self)
+    }
+
     pub fn region(mut self, region: String) -> Self {
         self.region = region;
         self
@@ -61,28 +211,117 @@ impl AmazonS3Builder {
         self
     }
 
+    /// Fetches the credential of the IAM role of the EC2 instance from its metadata service when
+    /// no credential is given, and again shortly before it expires. The fetches go through the
+    /// client of the data requests.
+    pub fn instance_credentials(mut self) -> Self {
+        self.instance_credentials = true;
+        self
+    }
+
+    /// Sends the requests, including the credential fetches, with `client`. Clients sharing a
+    /// connection pool, such as clones of a `TokioClient`, can be passed to several builders for
+    /// their file systems to reuse the same connections.
+    pub fn client(mut self, client: impl HttpClient + 'static) -> Self {
+        self.client = Box::new(client);
+        self
+    }
+
     pub fn sign_payload(mut self, sign_payload: bool) -> Self {
         self.sign_payload = sign_payload;
         self
     }
 
+    /// Signs the bodies of at most `threshold` bytes and sends the larger ones, and the streamed
+    /// ones, as `UNSIGNED-PAYLOAD`, sparing the hashing of large uploads while keeping small
+    /// requests signed. This takes precedence over [`AmazonS3Builder::sign_payload`], a single
+    /// write can still choose with [`WriteOptions::sign_payload`].
+    pub fn sign_payload_threshold(mut self, threshold: u64) -> Self {
+        self.sign_payload_threshold = Some(threshold);
+        self
+    }
+
     pub fn checksum(mut self, checksum: bool) -> Self {
         self.checksum = checksum;
         self
     }
 
+    /// Enables [`AmazonS3::append_emulated`], which is off by default as it rewrites the whole
+    /// object on every call.
+    pub fn append_emulation(mut self, append_emulation: bool) -> Self {
+        self.append_emulation = append_emulation;
+        self
+    }
+
+    /// Sends the id of the account expected to own the bucket with every request, signed. S3
+    /// rejects the requests with `403 Forbidden` when the bucket belongs to another account, e.g.
+    /// after it was deleted and its name taken over.
+    pub fn expected_bucket_owner(mut self, account_id: String) -> Self {
+        self.expected_bucket_owner = Some(account_id);
+        self
+    }
+
+    /// Follows at most `max_redirects` redirects per request, 10 by default. Requests are signed
+    /// again for every redirect, with `0` any redirect fails.
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Sends the requests to `endpoint`, e.g. `https://minio.example.com`, instead of AWS. The
+    /// bucket is addressed in the path of the requests rather than the host.
+    pub fn endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// Prepends `base_path` to the path of every request, for gateways served under a subpath
+    /// such as `https://gateway.example.com/s3/`.
+    pub fn base_path(mut self, base_path: String) -> Self {
+        self.base_path = base_path;
+        self
+    }
+
+    fn endpoint_url(&self) -> String {
+        let base_path = self.base_path.trim_matches('/');
+        let base_path = match base_path.is_empty() {
+            true => String::new(),
+            false => format!("/{}", base_path),
+        };
+        match &self.endpoint {
+            Some(endpoint) => format!(
+                "{}{}/{}",
+                endpoint.trim_end_matches('/'),
+                base_path,
+                self.bucket
+            ),
+            None => format!(
+                "https://{}.s3.{}.amazonaws.com{}",
+                self.bucket, self.region, base_path
+            ),
+        }
+    }
+
     pub fn build(self) -> AmazonS3 {
         AmazonS3 {
-            inner: Arc::new(AmazonS3Inner {
-                options: S3Options {
-                    endpoint: format!("https://{}.s3.{}.amazonaws.com", self.bucket, self.region),
+            inner: Arc::new(AmazonS3Inner::new(
+                S3Options {
+                    endpoint: self.endpoint_url(),
+                    bucket: self.bucket,
                     region: self.region,
                     credential: self.credential,
+                    instance_credentials: self
+                        .instance_credentials
+                        .then(|| InstanceCredentialProvider::new(DEFAULT_METADATA_ENDPOINT.into())),
                     sign_payload: self.sign_payload,
+                    sign_payload_threshold: self.sign_payload_threshold,
                     checksum: self.checksum,
+                    append_emulation: self.append_emulation,
+                    expected_bucket_owner: self.expected_bucket_owner,
+                    max_redirects: self.max_redirects,
                 },
-                client: self.client,
-            }),
+                self.client,
+            )),
         }
     }
 }
@@ -92,111 +331,413 @@ pub struct AmazonS3 {
     pub(super) inner: Arc<AmazonS3Inner>,
 }
 
-impl AsRef<AmazonS3Inner> for AmazonS3 {
-    fn as_ref(&self) -> &AmazonS3Inner {
-        self.inner.as_ref()
+impl AmazonS3 {
+    pub fn bucket(&self) -> &str {
+        &self.as_ref().options.bucket
     }
-}
-
-pub(super) struct AmazonS3Inner {
-    pub(super) options: S3Options,
-    pub(super) client: Box<dyn DynHttpClient>,
-}
 
-impl Fs for AmazonS3 {
-    type File = S3File;
+    /// Returns the [`S3Location`] of `path` in the bucket of this file system.
+    pub fn location(&self, path: &Path) -> S3Location {
+        S3Location::new(self.bucket(), path.clone())
+    }
 
-    async fn open_options(&self, path: &Path, _: OpenOptions) -> Result<Self::File, crate::Error> {
-        Ok(S3File::new(self.clone(), path.clone()))
+    /// Asks S3 for the region of the bucket, which it tells whichever region the request is
+    /// signed for, e.g. to build the file system again with [`AmazonS3Builder::region`] after a
+    /// request failed with [`S3Error::WrongRegion`].
+    pub async fn bucket_region(&self) -> Result<String, Error> {
+        let request = Request::builder()
+            .method(Method::HEAD)
+            .uri(self.as_ref().options.endpoint.as_str())
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = match self.send(request).await {
+            Err(S3Error::WrongRegion { region, .. }) => return Ok(region),
+            response => response?,
+        };
+        response
+            .headers()
+            .get(BUCKET_REGION_HEADER)
+            .and_then(|region| region.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                Error::Other(
+                    format!("S3 didn't tell the region of bucket {}", self.bucket()).into(),
+                )
+            })
     }
 
-    async fn create_dir_all(_path: &Path) -> Result<(), Error> {
+    /// Opens up to `n` connections to the endpoint of the bucket ahead of the first requests,
+    /// which then skip the TCP and TLS handshakes: the connections stay in the pool of the HTTP
+    /// client while idle, for as long as it keeps them. Nothing is warmed up unless asked, and at
+    /// most [`MAX_WARMUP_CONNECTIONS`] connections are opened whatever `n` is.
+    ///
+    /// Each connection is opened by a `HEAD` of the bucket, whose status doesn't matter: only
+    /// failing to reach the endpoint is an error.
+    pub async fn warmup(&self, n: usize) -> Result<(), Error> {
+        let heads = (0..n.min(MAX_WARMUP_CONNECTIONS)).map(|_| async {
+            let request = Request::builder()
+                .method(Method::HEAD)
+                .uri(self.as_ref().options.endpoint.as_str())
+                .body(Empty::<Bytes>::new())
+                .map_err(|e| S3Error::from(HttpError::from(e)))?;
+            match self.send(request).await {
+                Ok(response) => Ok(Some(response)),
+                Err(S3Error::WrongRegion { .. }) => Ok(None),
+                Err(e) => Err(Error::from(e)),
+            }
+        });
+        // the responses are all held until the last arrives, so that no request reuses the
+        // connection of another instead of opening its own
+        let responses = join_all(heads).await;
+        for response in responses {
+            if let Some(response) = response? {
+                response
+                    .into_body()
+                    .collect()
+                    .await
+                    .map_err(S3Error::from)?;
+            }
+        }
         Ok(())
     }
 
-    async fn list(
+    /// Signs `request` and sends it, following redirects.
+    pub(super) async fn send<B>(&self, request: Request<B>) -> Result<Response<BoxBody>, S3Error>
+    where
+        B: Body<Data = Bytes> + Clone + Unpin + Send + Sync + 'static,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let client = self.as_ref().client.lock().unwrap().clone();
+        let client = client.ok_or(S3Error::ShutDown)?;
+        self.send_with(&*client, request).await
+    }
+
+    async fn send_with<C, B>(
+        &self,
+        client: &C,
+        request: Request<B>,
+    ) -> Result<Response<C::RespBody>, S3Error>
+    where
+        C: HttpClient,
+        B: Body<Data = Bytes> + Clone + Unpin + Send + Sync + 'static,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        send_following_redirects(client, &self.as_ref().options, request).await
+    }
+
+    /// Shuts the file system down, e.g. before the application exits: deals with the files
+    /// opened for writing and not closed as `policy` says, aborts the multipart uploads left so
+    /// that none leaves its parts stored, and billed, in the bucket, then drops the HTTP client
+    /// along with its pool of connections. The requests sent afterwards, by this file system, its
+    /// clones or the files opened from them, fail with [`S3Error::ShutDown`]. Shutting down again
+    /// does nothing.
+    ///
+    /// With [`ShutdownPolicy::Complete`], the files written to are closed by the shutdown, their
+    /// buffered content uploaded: closing them afterwards does nothing. The content of the files
+    /// left open with [`ShutdownPolicy::Abort`], or not completed within the timeout, is lost.
+    /// Uploads of writes that failed without being aborted are aborted too.
+    pub async fn shutdown(&self, policy: ShutdownPolicy) -> Result<(), Error> {
+        if self.as_ref().client.lock().unwrap().is_none() {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        if let ShutdownPolicy::Complete { timeout } = policy {
+            result = within(timeout, self.complete_writers())
+                .await
+                .unwrap_or(Ok(()));
+        }
+        let Some(client) = self.as_ref().client.lock().unwrap().take() else {
+            return result;
+        };
+        let uploads = mem::take(&mut *self.as_ref().uploads.lock().unwrap());
+        for (upload_id, path) in uploads {
+            if let Err(e) = self.abort_upload(&*client, &path, &upload_id).await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Keeps track of the writer of a file opened for writing, for [`AmazonS3::shutdown`] to
+    /// complete it.
+    pub(super) fn register_writer(&self, writer: &SharedWriter) {
+        let mut writers = self.as_ref().writers.lock().unwrap();
+        writers.retain(|writer| writer.strong_count() > 0);
+        writers.push(Arc::downgrade(writer));
+    }
+
+    /// Closes the files written to and not closed yet, uploading what they buffered. A file
+    /// failing to close is left as it is, for its upload to be aborted.
+    async fn complete_writers(&self) -> Result<(), Error> {
+        let writers = mem::take(&mut *self.as_ref().writers.lock().unwrap());
+        let mut result = Ok(());
+        for writer in writers.iter().filter_map(Weak::upgrade) {
+            let mut writer = writer.lock().await;
+            // a file opened for writing and not written to isn't made an empty object
+            let Some(pending) = writer.as_mut().filter(|pending| pending.written() > 0) else {
+                continue;
+            };
+            match pending.close().await {
+                Ok(()) => *writer = None,
+                Err(e) => result = Err(e),
+            }
+        }
+        result
+    }
+
+    /// Aborts the multipart upload `upload_id` of the object at `path`, deleting its parts. An
+    /// upload S3 no longer knows of is already gone.
+    async fn abort_upload<C: HttpClient>(
         &self,
+        client: &C,
         path: &Path,
-    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
-        Ok(stream! {
-            let mut next_token = None::<String>;
-            loop {
-                let path = path.to_string();
-                let mut query = vec![("list-type", "2"), ("prefix", path.as_str())];
-                if let Some(token) = next_token.as_ref() {
-                    query.push(("continuation-token", token.as_str()));
-                }
+        upload_id: &str,
+    ) -> Result<(), Error> {
+        let url = format!(
+            "{}?uploadId={}",
+            self.object_url(path),
+            utf8_percent_encode(upload_id, &STRICT_PATH_ENCODE_SET)
+        );
+        let request = Request::builder()
+            .uri(url)
+            .method(Method::DELETE)
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send_with(client, request).await?;
+        let status = response.status();
+        if status.is_success() || status == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map(|body| body.to_bytes())
+            .unwrap_or_default();
+        Err(S3Error::from(HttpError::HttpNotSuccess {
+            status,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+        .into())
+    }
 
-                let mut url = Url::from_str(self.as_ref().options.endpoint.as_str()).map_err(|e| S3Error::from(HttpError::from(e)))?;
-                {
-                    let mut pairs = url.query_pairs_mut();
-                    let serializer = serde_urlencoded::Serializer::new(&mut pairs);
-                    query
-                        .serialize(serializer)
-                        .map_err(|e| S3Error::from(HttpError::from(e)))?;
-                }
+    /// Aborts the multipart upload `upload_id` of the object at `path`, so that its parts don't
+    /// stay stored, e.g. as the content to upload failed to be produced.
+    pub(super) async fn abort(&self, path: &Path, upload_id: &str) -> Result<(), Error> {
+        self.as_ref().uploads.lock().unwrap().remove(upload_id);
+        let client = self.as_ref().client.lock().unwrap().clone();
+        let client = client.ok_or(S3Error::ShutDown)?;
+        self.abort_upload(&*client, path, upload_id).await
+    }
 
-                let mut request = Request::builder()
-                    .method(Method::GET)
-                    .uri(url.as_str())
-                    .body(Empty::<Bytes>::new()).map_err(|e| S3Error::from(HttpError::from(e)))?;
-                request.sign(&self.as_ref().options).await.map_err(S3Error::from)?;
-                let response = self.as_ref().client.send_request(request).await.map_err(S3Error::from)?;
+    pub(super) fn object_url(&self, path: &Path) -> String {
+        format!(
+            "{}/{}",
+            self.as_ref().options.endpoint,
+            utf8_percent_encode(path.as_ref(), &STRICT_PATH_ENCODE_SET)
+        )
+    }
 
-                if !response.status().is_success() {
-                    yield Err(S3Error::from(HttpError::HttpNotSuccess { status: response.status(), body: String::from_utf8_lossy(
-                        &response
+    /// Appends `buf` to the object at `path`, creating it when it does not exist yet.
+    ///
+    /// S3 can't append to an object, so this downloads the whole object, concatenates `buf` and
+    /// uploads the result again. Every call is O(size of the object) and it is not atomic:
+    /// concurrent appends to the same object may lose each other's data. It is only meant for
+    /// small objects such as logs and has to be enabled with
+    /// [`AmazonS3Builder::append_emulation`].
+    pub async fn append_emulated<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<FileMeta, Error>, B) {
+        if !self.as_ref().options.append_emulation {
+            return (
+                Err(Error::Unsupported {
+                    message: "append to S3 objects, enable it with \
+                              `AmazonS3Builder::append_emulation`"
+                        .into(),
+                }),
+                buf,
+            );
+        }
+
+        let mut content = match self.get_object(path).await {
+            Ok(content) => content.unwrap_or_default(),
+            Err(e) => return (Err(e), buf),
+        };
+        content.extend_from_slice(buf.as_slice());
+        (self.write_file(path, content).await.0, buf)
+    }
+
+    /// Opens the object at `path` for a sequential scan, reading it in blocks of `block_size`
+    /// bytes with up to `depth` blocks fetched ahead of the one being read.
+    pub async fn open_prefetching(
+        &self,
+        path: &Path,
+        block_size: usize,
+        depth: usize,
+    ) -> Result<S3PrefetchReader, Error> {
+        let size = self.metadata(path).await?.size;
+        Ok(S3PrefetchReader::new(
+            self.clone(),
+            path.clone(),
+            size,
+            block_size,
+            depth,
+        ))
+    }
+
+    /// Starts restoring the archived object at `path` from Glacier or Deep Archive, keeping a
+    /// readable copy for `days`. Restoration takes from minutes to days depending on `tier`, poll
+    /// [`AmazonS3::restore_status`] to know when the object can be read.
+    pub async fn restore(&self, path: &Path, days: u32, tier: RestoreTier) -> Result<(), Error> {
+        let content = restore_request(days, tier)?;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("{}?restore", self.object_url(path)))
+            .header(CONTENT_LENGTH, content.len())
+            .header(CONTENT_TYPE, "application/xml")
+            .body(Full::new(Bytes::from(content)))
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        // 409 means that a restoration of the object is already in progress
+        if !response.status().is_success() && response.status() != StatusCode::CONFLICT {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
                         .collect()
                         .await
-                        .map_err(|e| Error::Other(e.into()))?.to_bytes()).to_string()
-                    }).into());
-                    return;
-                }
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
 
-                let mut response: ListResponse = quick_xml::de::from_reader(
-                    response
-                    .collect()
-                    .await
-                    .map_err(S3Error::from)?
-                    .aggregate().reader()
-                ).map_err(S3Error::from)?;
+        Ok(())
+    }
 
-                next_token = response.next_continuation_token.take();
+    /// Returns the restoration status of the object at `path`, or `None` when no restoration of
+    /// it was ever requested.
+    pub async fn restore_status(&self, path: &Path) -> Result<Option<RestoreStatus>, Error> {
+        let request = Request::builder()
+            .method(Method::HEAD)
+            .uri(self.object_url(path))
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
 
-                for content in &response.contents {
-                    yield Ok(FileMeta {
-                        path: Path::parse(&content.key)?,
-                        size: content.size as u64
-                    });
-                }
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::new(),
+            })
+            .into());
+        }
 
-                if next_token.is_none() {
-                    break;
-                }
-            }
-        })
+        response
+            .headers()
+            .get(RESTORE_HEADER)
+            .map(|header| {
+                header
+                    .to_str()
+                    .ok()
+                    .and_then(RestoreStatus::parse)
+                    .ok_or_else(|| {
+                        Error::Other(
+                            format!("invalid {} header: {:?}", RESTORE_HEADER, header).into(),
+                        )
+                    })
+            })
+            .transpose()
     }
 
-    async fn remove(&self, path: &Path) -> Result<(), Error> {
-        let mut url = Url::from_str(self.as_ref().options.endpoint.as_str())
-            .map_err(|e| S3Error::from(HttpError::from(e)))?;
-        url.set_path(path.as_ref());
+    /// Presigns a request with `method` to the object at `path`, valid for `expires_in`, e.g. to
+    /// let a client without credentials download or upload it.
+    pub fn presign(&self, method: Method, path: &Path, expires_in: Duration) -> Result<Url, Error> {
+        self.presign_url(method, path, expires_in, &ResponseOverrides::default())
+    }
 
-        let mut request = Request::builder()
-            .method(Method::DELETE)
-            .uri(url.as_str())
+    /// Presigns a `GET` of the object at `path` like [`AmazonS3::presign`], whose response is
+    /// sent with the headers of `overrides`.
+    pub fn presign_get_with(
+        &self,
+        path: &Path,
+        expires_in: Duration,
+        overrides: &ResponseOverrides,
+    ) -> Result<Url, Error> {
+        self.presign_url(Method::GET, path, expires_in, overrides)
+    }
+
+    fn presign_url(
+        &self,
+        method: Method,
+        path: &Path,
+        expires_in: Duration,
+        overrides: &ResponseOverrides,
+    ) -> Result<Url, Error> {
+        let options = &self.as_ref().options;
+        // presigning doesn't send requests, the instance credential is used once fetched
+        let instance_credential = options
+            .instance_credentials
+            .as_ref()
+            .and_then(|provider| provider.cached());
+        let credential = options
+            .credential
+            .as_ref()
+            .or(instance_credential.as_deref())
+            .ok_or_else(|| Error::Unsupported {
+                message: "can't presign URLs without credentials".into(),
+            })?;
+        let mut url =
+            Url::from_str(&self.object_url(path)).map_err(|e| S3Error::from(HttpError::from(e)))?;
+        overrides.apply(&mut url);
+        AwsAuthorizer::new(credential, "s3", &options.region)
+            .sign(method, &mut url, expires_in)
+            .map_err(S3Error::from)?;
+        Ok(url)
+    }
+
+    /// Returns the ACL of the object at `path`: its owner and the permissions granted on it.
+    pub async fn get_object_acl(&self, path: &Path) -> Result<ObjectAcl, Error> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(format!("{}?acl", self.object_url(path)))
             .body(Empty::<Bytes>::new())
             .map_err(|e| S3Error::from(HttpError::from(e)))?;
-        request
-            .sign(&self.as_ref().options)
-            .await
-            .map_err(S3Error::from)?;
-        let response = self
-            .as_ref()
-            .client
-            .send_request(request)
+        let response = self.send(request).await?;
+
+        let status = response.status();
+        let body = response
+            .collect()
             .await
-            .map_err(S3Error::from)?;
+            .map_err(|e| Error::Other(e.into()))?
+            .to_bytes();
+        if !status.is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status,
+                body: String::from_utf8_lossy(&body).to_string(),
+            })
+            .into());
+        }
+
+        parse_acl(&body)
+    }
+
+    /// Replaces the ACL of the object at `path` with the canned `acl`. Objects can also be
+    /// written with one, see [`WriteOptions::acl`].
+    pub async fn set_object_acl(&self, path: &Path, acl: CannedAcl) -> Result<(), Error> {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("{}?acl", self.object_url(path)))
+            .header(ACL_HEADER, acl.as_str())
+            .header(CONTENT_LENGTH, 0)
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
             return Err(S3Error::from(HttpError::HttpNotSuccess {
@@ -215,68 +756,2563 @@ impl Fs for AmazonS3 {
 
         Ok(())
     }
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct ListPrefix {
-    pub prefix: String,
-}
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct ListContents {
-    pub key: String,
-    pub size: usize,
-    pub last_modified: DateTime<Utc>,
-    #[serde(rename = "ETag")]
-    pub e_tag: Option<String>,
-}
+    /// Copies the object at `from` to `to` server side with `CopyObject`, treating the user
+    /// metadata and tags of the source as set by `options`. Returns the ETag of the copy.
+    ///
+    /// Objects larger than the 5 GiB `CopyObject` accepts are copied by ranges with
+    /// `UploadPartCopy` instead, which can't copy the tags of the source and doesn't return an
+    /// ETag.
+    pub async fn copy_with(
+        &self,
+        from: &Path,
+        to: &Path,
+        options: &CopyOptions,
+    ) -> Result<Option<String>, Error> {
+        Ok(self.copy_object(self, from, to, options).await?.1)
+    }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct ListResponse {
-    #[serde(default)]
-    pub contents: Vec<ListContents>,
-    #[serde(default)]
-    pub common_prefixes: Vec<ListPrefix>,
-    #[serde(default)]
-    pub next_continuation_token: Option<String>,
-}
+    /// Copies the object at `from` in the bucket of `source` to `to` in this one server side,
+    /// like [`AmazonS3::copy_with`], without downloading it. The buckets must be of the same
+    /// region, and the credential of this file system allowed to read the source. Returns the
+    /// ETag of the copy.
+    pub async fn copy_from_bucket(
+        &self,
+        source: &AmazonS3,
+        from: &Path,
+        to: &Path,
+        options: &CopyOptions,
+    ) -> Result<Option<String>, Error> {
+        Ok(self.copy_object(source, from, to, options).await?.1)
+    }
 
-#[cfg(test)]
-mod tests {
-    #[cfg(feature = "tokio-http")]
-    #[tokio::test]
-    async fn list_and_remove() {
-        use std::{env, pin::pin};
+    /// Moves the object at `from` in the bucket of `source` to `to` in this one, copying it
+    /// server side like [`AmazonS3::copy_from_bucket`] then removing it from `source`. As with
+    /// [`Fs::rename`], the move isn't atomic.
+    pub async fn rename_from_bucket(
+        &self,
+        source: &AmazonS3,
+        from: &Path,
+        to: &Path,
+    ) -> Result<(), Error> {
+        self.copy_from_bucket(source, from, to, &CopyOptions::default())
+            .await?;
+        source.remove(from).await
+    }
 
-        use futures_util::StreamExt;
+    /// Copies the object at `from` in the bucket of `source`, which may be this file system, like
+    /// [`AmazonS3::copy_with`], returning the headers of the source along with the ETag of the
+    /// copy.
+    async fn copy_object(
+        &self,
+        source: &AmazonS3,
+        from: &Path,
+        to: &Path,
+        options: &CopyOptions,
+    ) -> Result<(HeaderMap, Option<String>), Error> {
+        let headers = source.head_object(from).await?;
+        if let Some(expected) = options.source_etag() {
+            if headers.get(ETAG).map(HeaderValue::as_bytes) != Some(expected.as_bytes()) {
+                return Err(S3Error::PreconditionFailed(source.location(from).to_string()).into());
+            }
+        }
+        // copying an object onto itself is how its metadata is replaced, without a change to
+        // make there is nothing to copy, S3 would refuse it
+        if from == to && source.bucket() == self.bucket() && !options.replaces_anything() {
+            let etag = headers
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(str::to_string);
+            return Ok((headers, etag));
+        }
+        let size = source.object_size(from, &headers).await?;
+        if size > MAX_COPY_OBJECT_SIZE {
+            let etag = self
+                .copy_multipart(source, from, to, options, &headers, copy_part_ranges(size))
+                .await?;
+            return Ok((headers, etag));
+        }
 
-        use super::*;
+        let builder = Request::builder()
+            .method(Method::PUT)
+            .uri(self.object_url(to))
+            .header(COPY_SOURCE_HEADER, copy_source(source.bucket(), from));
+        let request = options
+            .apply(builder, &headers)
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
 
-        if env::var("AWS_ACCESS_KEY_ID").is_err() {
-            eprintln!("skipping AWS s3 test");
-            return;
+        // the source changed since its `HEAD`
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(S3Error::PreconditionFailed(source.location(from).to_string()).into());
         }
-        let key_id = env::var("AWS_ACCESS_KEY_ID").unwrap();
-        let secret_key = env::var("AWS_SECRET_ACCESS_KEY").unwrap();
-
-        let s3 = AmazonS3Builder::new("fusio-test".into())
-            .credential(AwsCredential {
-                key_id,
-                secret_key,
-                token: None,
+        let status = response.status();
+        let body = response
+            .collect()
+            .await
+            .map_err(|e| Error::Other(e.into()))?
+            .to_bytes();
+        // S3 may report a failed copy in the body of a 200 response
+        // https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html
+        let maybe_error: S3ResponseError =
+            quick_xml::de::from_reader(body.as_ref()).unwrap_or_default();
+        if !status.is_success() || !maybe_error.code.is_empty() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status,
+                body: String::from_utf8_lossy(&body).to_string(),
             })
-            .region("ap-southeast-1".into())
-            .sign_payload(true)
-            .build();
+            .into());
+        }
 
-        let path = Path::parse("test").unwrap();
+        let result: CopyObjectResult =
+            quick_xml::de::from_reader(body.as_ref()).map_err(S3Error::from)?;
+        Ok((headers, result.etag))
+    }
+
+    /// Copies the inclusive byte `ranges` of the object at `from` in the bucket of `source`, with
+    /// `headers`, to `to` as the parts of a multipart upload.
+    async fn copy_multipart(
+        &self,
+        source: &AmazonS3,
+        from: &Path,
+        to: &Path,
+        options: &CopyOptions,
+        headers: &HeaderMap,
+        ranges: impl Iterator<Item = (u64, u64)>,
+    ) -> Result<Option<String>, Error> {
+        let upload = MultipartUpload::new(self.clone(), to.clone());
+        let upload_id = upload
+            .initiate_with(options.multipart_headers(headers))
+            .await?;
+        let copy_source = copy_source(source.bucket(), from);
+
+        let mut parts = vec![];
+        for (part_num, range) in ranges.enumerate() {
+            parts.push(
+                upload
+                    .upload_part_copy(
+                        &upload_id,
+                        part_num,
+                        &copy_source,
+                        options.source_etag(),
+                        range,
+                    )
+                    .await
+                    .map_err(|e| match e {
+                        Error::S3Error(S3Error::PreconditionFailed(_)) => {
+                            S3Error::PreconditionFailed(source.location(from).to_string()).into()
+                        }
+                        e => e,
+                    })?,
+            );
+        }
+        upload.complete_part(&upload_id, &parts).await?;
+        Ok(None)
+    }
+
+    /// Replaces the content of the object at `path` with `buf` in a single `PUT`, like
+    /// [`Fs::write_file`], with the given `options`.
+    pub async fn write_file_with<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+        options: WriteOptions,
+    ) -> (Result<FileMeta, Error>, B) {
+        let bytes = buf.as_bytes();
+        let result: Result<FileMeta, Error> = async {
+            let size = bytes.len() as u64;
+            let mut builder = Request::builder()
+                .method(Method::PUT)
+                .uri(self.object_url(path))
+                .header(CONTENT_LENGTH, size)
+                .header(CONTENT_TYPE, content_type(path));
+            if let Some(content_encoding) = &options.content_encoding {
+                builder = builder.header(CONTENT_ENCODING, content_encoding);
+            }
+            if let Some(acl) = options.acl {
+                builder = builder.header(ACL_HEADER, acl.as_str());
+            }
+            if options.if_not_exists {
+                builder = builder.header(IF_NONE_MATCH, "*");
+            }
+            if let Some(etag) = &options.if_match {
+                builder = builder.header(IF_MATCH, etag);
+            }
+            for (name, value) in &options.metadata {
+                builder = builder.header(format!("{}{}", METADATA_PREFIX, name), value);
+            }
+            if let Some(sign_payload) = options.sign_payload {
+                builder = builder.extension(SignPayload(sign_payload));
+            }
+            // the payload is signed with the checksum if there is one, rather than hashed again
+            if let Some(digest) = &options.sha256 {
+                builder = builder.header(CHECKSUM_HEADER, base64_encode(digest));
+            }
+            let request = builder
+                .body(Full::new(bytes))
+                .map_err(|e| S3Error::from(HttpError::from(e)))?;
+            let response = self.send(request).await?;
+
+            // S3 answers `409 Conflict` to a conditional write racing with another one
+            let conditional = options.if_not_exists || options.if_match.is_some();
+            if response.status() == StatusCode::PRECONDITION_FAILED
+                || (conditional && response.status() == StatusCode::CONFLICT)
+            {
+                return Err(S3Error::PreconditionFailed(self.location(path).to_string()).into());
+            }
+            if !response.status().is_success() {
+                return Err(S3Error::from(HttpError::HttpNotSuccess {
+                    status: response.status(),
+                    body: String::from_utf8_lossy(
+                        &response
+                            .into_body()
+                            .collect()
+                            .await
+                            .map_err(|e| Error::Other(e.into()))?
+                            .to_bytes(),
+                    )
+                    .to_string(),
+                })
+                .into());
+            }
+
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(str::to_string);
+            Ok(FileMeta {
+                path: path.clone(),
+                size,
+                etag,
+                content_encoding: options.content_encoding,
+                storage_class: None,
+                restore_status: None,
+                local_version: None,
+            })
+        }
+        .await;
+
+        (result, buf)
+    }
+
+    /// Writes `buf` to `path` like [`AmazonS3::write_file_with`] if there is no object there yet,
+    /// atomically: of concurrent writers, a single one succeeds. Returns `None` when there was
+    /// already an object.
+    pub async fn put_if_not_exists<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+        options: WriteOptions,
+    ) -> (Result<Option<FileMeta>, Error>, B) {
+        let (result, buf) = self
+            .write_file_with(path, buf, options.if_not_exists())
+            .await;
+        let result = match result {
+            Ok(meta) => Ok(Some(meta)),
+            Err(Error::S3Error(S3Error::PreconditionFailed(_))) => Ok(None),
+            Err(e) => Err(e),
+        };
+        (result, buf)
+    }
+
+    /// Returns the size of the object at `path` from the `headers` of a `HEAD` of it, or if they
+    /// lack a `Content-Length`, from the `Content-Range` of a read of its first byte.
+    async fn object_size(&self, path: &Path, headers: &HeaderMap) -> Result<u64, Error> {
+        if let Some(size) = content_length(headers)? {
+            return Ok(size);
+        }
+        match self.read_range(path, "bytes=0-0".into()).await? {
+            // only an empty object has no first byte
+            None => Ok(0),
+            Some((Some(content_range), _)) => {
+                match parse_content_range(&content_range).map_err(S3Error::from)? {
+                    (_, _, Some(total)) => Ok(total),
+                    (_, _, None) => Err(Error::Other(
+                        format!("the size of \"{}\" is unknown", path).into(),
+                    )),
+                }
+            }
+            // the range was ignored, the whole object was read
+            Some((None, buf)) => Ok(buf.len() as u64),
+        }
+    }
+
+    /// Reads the bytes of the object at `path` in `range`, the value of a `Range` header, along
+    /// with the `Content-Range` they were answered with. A range S3 can't satisfy, e.g. any of an
+    /// empty object, reads nothing.
+    async fn read_range(
+        &self,
+        path: &Path,
+        range: String,
+    ) -> Result<Option<(Option<String>, Vec<u8>)>, Error> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(self.object_url(path))
+            .header(RANGE, range)
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response
+                .collect()
+                .await
+                .map(|b| b.to_bytes())
+                .unwrap_or_default();
+            return Err(read_error(
+                self.location(path).to_string(),
+                status,
+                String::from_utf8_lossy(&body).to_string(),
+            )
+            .into());
+        }
+
+        let content_range = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|range| range.to_str().ok())
+            .map(String::from);
+        let buf = response
+            .collect()
+            .await
+            .map_err(S3Error::from)?
+            .to_bytes()
+            .to_vec();
+        Ok(Some((content_range, buf)))
+    }
+
+    /// Lists the objects under `path`, with the directory markers among them if `markers`.
+    fn list_entries(
+        &self,
+        path: &Path,
+        markers: bool,
+    ) -> impl Stream<Item = Result<FileMeta, Error>> {
+        // a page is only requested once the entries of the previous one are consumed, dropping
+        // the stream early drops the request in flight and fetches no further pages. The stream
+        // owns its clone of the client, borrowing neither it nor `path`
+        let fs = self.clone();
+        let path = path.clone();
+        stream! {
+            let mut next_token = None::<String>;
+            loop {
+                let mut response = fs
+                    .list_request(&path, next_token.as_deref(), None, false)
+                    .await?;
+                next_token = response.next_continuation_token.take();
+
+                for content in &response.contents {
+                    if content.is_marker() && !markers {
+                        continue;
+                    }
+                    yield Ok(FileMeta {
+                        path: Path::parse(&content.key)?,
+                        size: content.size as u64,
+                        etag: content.e_tag.clone(),
+                        content_encoding: None,
+                        storage_class: content.storage_class(),
+                        restore_status: None,
+                        local_version: None,
+                    });
+                }
+
+                if next_token.is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Requests a page of the listing of the objects under `path`, starting after the page
+    /// `continuation_token` was returned with and holding at most `max_keys` objects. If
+    /// `delimited`, the objects deeper than the immediate children of `path` are rolled up into
+    /// the common prefixes of the response.
+    async fn list_request(
+        &self,
+        path: &Path,
+        continuation_token: Option<&str>,
+        max_keys: Option<usize>,
+        delimited: bool,
+    ) -> Result<ListResponse, Error> {
+        // list the entries under `path`, not the siblings sharing its name as a prefix
+        let prefix = match path.as_ref() {
+            "" => String::new(),
+            path => format!("{}/", path),
+        };
+        let max_keys = max_keys.map(|max_keys| max_keys.to_string());
+        let mut query = vec![("list-type", "2"), ("prefix", prefix.as_str())];
+        if let Some(token) = continuation_token {
+            query.push(("continuation-token", token));
+        }
+        if let Some(max_keys) = max_keys.as_deref() {
+            query.push(("max-keys", max_keys));
+        }
+        if delimited {
+            query.push(("delimiter", "/"));
+        }
+
+        let mut url = Url::from_str(self.as_ref().options.endpoint.as_str())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        {
+            let mut pairs = url.query_pairs_mut();
+            let serializer = serde_urlencoded::Serializer::new(&mut pairs);
+            query
+                .serialize(serializer)
+                .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        }
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(url.as_str())
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        Ok(quick_xml::de::from_reader(
+            response
+                .collect()
+                .await
+                .map_err(S3Error::from)?
+                .aggregate()
+                .reader(),
+        )
+        .map_err(S3Error::from)?)
+    }
+
+    /// Lists a page of the versions of the objects under `path`, going on after the version
+    /// `version_id` of `key` when given the markers of the previous page.
+    async fn list_versions_request(
+        &self,
+        path: &Path,
+        markers: Option<(&str, &str)>,
+    ) -> Result<ListVersionsResponse, Error> {
+        let prefix = match path.as_ref() {
+            "" => String::new(),
+            path => format!("{}/", path),
+        };
+        let mut url = Url::from_str(self.as_ref().options.endpoint.as_str())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs.append_key_only("versions");
+            pairs.append_pair("prefix", &prefix);
+            if let Some((key, version_id)) = markers {
+                pairs.append_pair("key-marker", key);
+                pairs.append_pair("version-id-marker", version_id);
+            }
+        }
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(url.as_str())
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        Ok(quick_xml::de::from_reader(
+            response
+                .collect()
+                .await
+                .map_err(S3Error::from)?
+                .aggregate()
+                .reader(),
+        )
+        .map_err(S3Error::from)?)
+    }
+
+    /// Returns the headers of the object at `path`, as answered to a `HEAD`.
+    pub(super) async fn head_object(&self, path: &Path) -> Result<HeaderMap, Error> {
+        let request = Request::builder()
+            .method(Method::HEAD)
+            .uri(self.object_url(path))
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        Ok(response.headers().clone())
+    }
+
+    /// Downloads the object at `path`, returning `None` when it does not exist.
+    async fn get_object(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(self.object_url(path))
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        Ok(Some(
+            response
+                .collect()
+                .await
+                .map_err(S3Error::from)?
+                .to_bytes()
+                .to_vec(),
+        ))
+    }
+
+    /// Returns the `algorithm` checksum S3 stored for the object at `path`, if it has one of the
+    /// whole object. Multipart uploads only have checksums of their parts.
+    async fn stored_checksum(
+        &self,
+        path: &Path,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let request = Request::builder()
+            .method(Method::HEAD)
+            .uri(self.object_url(path))
+            .header(CHECKSUM_MODE_HEADER, "ENABLED")
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::new(),
+            })
+            .into());
+        }
+
+        let header = match algorithm {
+            ChecksumAlgorithm::Sha1 => "x-amz-checksum-sha1",
+            ChecksumAlgorithm::Sha256 => "x-amz-checksum-sha256",
+        };
+        Ok(response
+            .headers()
+            .get(header)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.contains('-'))
+            .and_then(base64_decode))
+    }
+}
+
+/// Runs `future` for up to `timeout`, `None` if it didn't complete in time.
+#[cfg(any(feature = "tokio", feature = "monoio"))]
+async fn within<T>(timeout: Duration, future: impl Future<Output = T>) -> Option<T> {
+    use futures_util::future::{select, Either};
+
+    match select(pin!(future), pin!(crate::fs::sleep(timeout))).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+/// Without the timer of an async runtime, `future` runs to completion.
+#[cfg(not(any(feature = "tokio", feature = "monoio")))]
+async fn within<T>(_timeout: Duration, future: impl Future<Output = T>) -> Option<T> {
+    Some(future.await)
+}
+
+impl AsRef<AmazonS3Inner> for AmazonS3 {
+    fn as_ref(&self) -> &AmazonS3Inner {
+        self.inner.as_ref()
+    }
+}
+
+pub(super) struct AmazonS3Inner {
+    pub(super) options: S3Options,
+    /// The client requests are sent with, `None` once [`AmazonS3::shutdown`] dropped it.
+    client: Mutex<Option<Arc<Box<dyn DynHttpClient>>>>,
+    /// The multipart uploads initiated and neither completed nor aborted yet, with the paths of
+    /// their objects, by upload ID.
+    pub(super) uploads: Mutex<HashMap<String, Path>>,
+    /// The writers of the files opened for writing, until they are dropped.
+    writers: Mutex<Vec<Weak<futures_util::lock::Mutex<Option<S3Writer>>>>>,
+}
+
+impl AmazonS3Inner {
+    pub(super) fn new(options: S3Options, client: Box<dyn DynHttpClient>) -> Self {
+        Self {
+            options,
+            client: Mutex::new(Some(Arc::new(client))),
+            uploads: Mutex::new(HashMap::new()),
+            writers: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Fs for AmazonS3 {
+    type File = S3File;
+
+    /// Objects can't be modified in place, a file opened with `write` replaces the object on
+    /// close. With `create`, truncated or not, the object is replaced whether it exists or not,
+    /// as S3 files always were. Without either, the writes would modify the existing object,
+    /// which S3 can't: it fails with [`Error::Unsupported`].
+    async fn open_options(&self, path: &Path, options: OpenOptions) -> Result<Self::File, Error> {
+        if !options.write {
+            return Ok(S3File::new(self.clone(), path.clone()));
+        }
+        if !options.create {
+            let exists = match self.head_object(path).await {
+                Ok(_) => true,
+                Err(e) if is_missing(&e) => false,
+                Err(e) => return Err(e),
+            };
+            if exists && !options.truncate {
+                return Err(Error::Unsupported {
+                    message: format!(
+                        "S3 can't modify \"{}\" in place, open it with `truncate` to replace it",
+                        path
+                    ),
+                });
+            }
+            if !exists {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("\"{}\" doesn't exist", path),
+                )
+                .into());
+            }
+        }
+        Ok(S3File::writable(self.clone(), path.clone()))
+    }
+
+    async fn open_with_len(&self, path: &Path, len: u64) -> Result<Self::File, Error> {
+        Ok(S3File::with_len(self.clone(), path.clone(), len))
+    }
+
+    async fn create_dir_all(&self, _path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        Ok(self.list_entries(path, false))
+    }
+
+    async fn list_with(
+        &self,
+        path: &Path,
+        options: ListOptions,
+    ) -> Result<impl Stream<Item = Result<FileMeta, Error>>, Error> {
+        let dirs = match options.case_insensitive {
+            true => case_variants(self, path).await?,
+            false => vec![path.clone()],
+        };
+        let entries = stream! {
+            for dir in dirs {
+                let mut entries = pin!(self.list_entries(&dir, options.markers));
+                while let Some(meta) = entries.next().await {
+                    yield meta;
+                }
+            }
+        };
+        Ok(shape_listing(entries, path.clone(), options))
+    }
+
+    async fn list_page(
+        &self,
+        path: &Path,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Vec<FileMeta>, Option<String>), Error> {
+        let response = self
+            .list_request(path, cursor, Some(page_size.max(1)), false)
+            .await?;
+        let entries = response
+            .contents
+            .iter()
+            .filter(|content| !content.is_marker())
+            .map(|content| {
+                Ok(FileMeta {
+                    path: Path::parse(&content.key)?,
+                    size: content.size as u64,
+                    etag: content.e_tag.clone(),
+                    content_encoding: None,
+                    storage_class: content.storage_class(),
+                    restore_status: None,
+                    local_version: None,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok((entries, response.next_continuation_token))
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>, Error> {
+        let mut versions = vec![];
+        let mut markers = None::<(String, String)>;
+        loop {
+            let response = self
+                .list_versions_request(
+                    path,
+                    markers
+                        .as_ref()
+                        .map(|(key, version_id)| (key.as_str(), version_id.as_str())),
+                )
+                .await?;
+
+            for version in response.versions {
+                let meta = FileMeta {
+                    path: Path::parse(&version.key)?,
+                    size: version.size as u64,
+                    etag: version.e_tag,
+                    content_encoding: None,
+                    storage_class: version
+                        .storage_class
+                        .filter(|storage_class| storage_class != "STANDARD"),
+                    restore_status: None,
+                    local_version: None,
+                };
+                versions.push((
+                    version.last_modified,
+                    FileVersion {
+                        meta,
+                        version_id: version.version_id,
+                        is_latest: version.is_latest,
+                        is_delete_marker: false,
+                    },
+                ));
+            }
+            for marker in response.delete_markers {
+                let meta = FileMeta {
+                    path: Path::parse(&marker.key)?,
+                    size: 0,
+                    etag: None,
+                    content_encoding: None,
+                    storage_class: None,
+                    restore_status: None,
+                    local_version: None,
+                };
+                versions.push((
+                    marker.last_modified,
+                    FileVersion {
+                        meta,
+                        version_id: marker.version_id,
+                        is_latest: marker.is_latest,
+                        is_delete_marker: true,
+                    },
+                ));
+            }
+
+            match (
+                response.is_truncated,
+                response.next_key_marker,
+                response.next_version_id_marker,
+            ) {
+                (true, Some(key), Some(version_id)) => markers = Some((key, version_id)),
+                _ => break,
+            }
+        }
+        // the versions and the delete markers are listed apart, put them back in order
+        versions.sort_by(|(a_modified, a), (b_modified, b)| {
+            a.meta
+                .path
+                .as_ref()
+                .cmp(b.meta.path.as_ref())
+                .then(b.is_latest.cmp(&a.is_latest))
+                .then(b_modified.cmp(a_modified))
+        });
+        Ok(versions.into_iter().map(|(_, version)| version).collect())
+    }
+
+    async fn list_delimited(&self, path: &Path) -> Result<DelimitedListing, Error> {
+        let mut listing = DelimitedListing::default();
+        let mut next_token = None::<String>;
+        loop {
+            let mut response = self
+                .list_request(path, next_token.as_deref(), None, true)
+                .await?;
+            next_token = response.next_continuation_token.take();
+
+            for content in response
+                .contents
+                .iter()
+                .filter(|content| !content.is_marker())
+            {
+                listing.files.push(FileMeta {
+                    path: Path::parse(&content.key)?,
+                    size: content.size as u64,
+                    etag: content.e_tag.clone(),
+                    content_encoding: None,
+                    storage_class: content.storage_class(),
+                    restore_status: None,
+                    local_version: None,
+                });
+            }
+            for prefix in &response.common_prefixes {
+                listing
+                    .prefixes
+                    .push(Path::parse(prefix.prefix.trim_end_matches('/'))?);
+            }
+
+            if next_token.is_none() {
+                break;
+            }
+        }
+        listing.prefixes.sort();
+        Ok(listing)
+    }
+
+    async fn remove(&self, path: &Path) -> Result<(), Error> {
+        let mut url = Url::from_str(self.as_ref().options.endpoint.as_str())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        url.set_path(path.as_ref());
+
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri(url.as_str())
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        Ok(())
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<FileMeta, Error> {
+        let headers = self.head_object(path).await?;
+        let size = self.object_size(path, &headers).await?;
+        let (etag, content_encoding) = content_headers(&headers);
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+        Ok(FileMeta {
+            path: path.clone(),
+            size,
+            etag,
+            content_encoding,
+            storage_class: header(STORAGE_CLASS_HEADER).map(str::to_string),
+            restore_status: header(RESTORE_HEADER).and_then(RestoreStatus::parse),
+            local_version: None,
+        })
+    }
+
+    /// A `HEAD` of the object at `path`, followed when there is none by a listing of the objects
+    /// under it, in which case the path is a directory.
+    async fn file_type(&self, path: &Path) -> Result<Option<FileType>, Error> {
+        match self.head_object(path).await {
+            Ok(_) => return Ok(Some(FileType::File)),
+            Err(e) if is_missing(&e) => {}
+            Err(e) => return Err(e),
+        }
+        // a single object under `path`, a directory marker included, makes it a directory
+        let response = self.list_request(path, None, Some(1), false).await?;
+        Ok((!response.contents.is_empty()).then_some(FileType::Dir))
+    }
+
+    async fn dir_stats(&self, path: &Path) -> Result<DirStats, Error> {
+        let mut stats = DirStats::default();
+        let mut continuation_token = None;
+        loop {
+            let response = self
+                .list_request(path, continuation_token.as_deref(), None, false)
+                .await?;
+            for content in response.contents.iter().filter(|c| !c.is_marker()) {
+                stats.entry_count += 1;
+                stats.total_size += content.size as u64;
+            }
+            continuation_token = response.next_continuation_token;
+            if continuation_token.is_none() {
+                return Ok(stats);
+            }
+        }
+    }
+
+    async fn read_head(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        if n == 0 {
+            // a range can't be empty, the object is only checked to exist
+            self.metadata(path).await?;
+            return Ok(Vec::new());
+        }
+        match self.read_range(path, format!("bytes=0-{}", n - 1)).await? {
+            Some((content_range, buf)) => {
+                validate_content_range(content_range.as_deref(), 0, None).map_err(S3Error::from)?;
+                Ok(buf)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn read_tail(&self, path: &Path, n: usize) -> Result<Vec<u8>, Error> {
+        if n == 0 {
+            self.metadata(path).await?;
+            return Ok(Vec::new());
+        }
+        let (content_range, buf) = match self.read_range(path, format!("bytes=-{}", n)).await? {
+            Some(read) => read,
+            None => return Ok(Vec::new()),
+        };
+        // the range is answered from where the suffix starts, it must run to the end
+        let range = content_range
+            .as_deref()
+            .map(parse_content_range)
+            .transpose()
+            .map_err(S3Error::from)?;
+        match range {
+            Some((_, end, Some(total))) if end + 1 == total => Ok(buf),
+            _ => Err(S3Error::from(HttpError::RangeMismatch {
+                requested: format!("bytes -{}", n),
+                received: content_range.unwrap_or_else(|| "the whole content".into()),
+            })
+            .into()),
+        }
+    }
+
+    async fn read_if_none_match(
+        &self,
+        path: &Path,
+        etag: &str,
+    ) -> Result<Option<(FileMeta, Vec<u8>)>, Error> {
+        let url = self.object_url(path);
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .header(IF_NONE_MATCH, etag)
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        let (etag, content_encoding) = content_headers(response.headers());
+        let buf = response
+            .collect()
+            .await
+            .map_err(S3Error::from)?
+            .to_bytes()
+            .to_vec();
+
+        Ok(Some((
+            FileMeta {
+                path: path.clone(),
+                size: buf.len() as u64,
+                etag,
+                content_encoding,
+                storage_class: None,
+                restore_status: None,
+                local_version: None,
+            },
+            buf,
+        )))
+    }
+
+    fn write_file<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> impl Future<Output = (Result<FileMeta, Error>, B)> + MaybeSend {
+        self.write_file_with(path, buf, WriteOptions::default())
+    }
+
+    async fn write_file_with_checksum<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> (Result<(FileMeta, [u8; 32]), Error>, B) {
+        let digest = sha256(buf.as_slice());
+        let options = WriteOptions {
+            sha256: Some(digest),
+            ..Default::default()
+        };
+        let (result, buf) = self.write_file_with(path, buf, options).await;
+        (result.map(|meta| (meta, digest)), buf)
+    }
+
+    /// Objects are replaced in full by a single `PUT`, there is no need for a temporary object.
+    fn write_file_atomic<B: IoBuf>(
+        &self,
+        path: &Path,
+        buf: B,
+    ) -> impl Future<Output = (Result<FileMeta, Error>, B)> + MaybeSend {
+        self.write_file(path, buf)
+    }
+
+    async fn publish<W, Fut>(&self, path: &Path, write: W) -> Result<FileMeta, Error>
+    where
+        W: FnOnce(Self::File) -> Fut + MaybeSend,
+        Fut: Future<Output = Result<Self::File, Error>> + MaybeSend,
+    {
+        // nothing is visible at `path` until the upload is completed on close, a failed write
+        // leaves it as it was
+        let file = self
+            .open_options(path, OpenOptions::default().create(true).truncate(true))
+            .await?;
+        let mut file = write(file).await?;
+        file.close_with_meta().await
+    }
+
+    async fn copy_if_source_matches(
+        &self,
+        from: &Path,
+        to: &Path,
+        expected_etag: &str,
+    ) -> Result<(), Error> {
+        self.copy_with(
+            from,
+            to,
+            &CopyOptions::default().if_source_matches(expected_etag),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn copy_range(&self, from: &Path, range: Range<u64>, to: &Path) -> Result<(), Error> {
+        let headers = self.head_object(from).await?;
+        let size = self.object_size(from, &headers).await?;
+        check_copy_range(from, &range, size)?;
+        // a part copied with `UploadPartCopy` can't be empty
+        if range.is_empty() {
+            return self.write_file(to, vec![]).await.0.map(|_| ());
+        }
+        let ranges = copy_part_ranges(range.end - range.start)
+            .map(|(start, end)| (range.start + start, range.start + end));
+        self.copy_multipart(self, from, to, &CopyOptions::default(), &headers, ranges)
+            .await?;
+        Ok(())
+    }
+
+    /// Yields the frames of the body of a `GET` of the object as they are received, nothing is
+    /// buffered.
+    async fn get_stream(
+        &self,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(self.object_url(path))
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response
+                .collect()
+                .await
+                .map(|b| b.to_bytes())
+                .unwrap_or_default();
+            return Err(read_error(
+                self.location(path).to_string(),
+                status,
+                String::from_utf8_lossy(&body).to_string(),
+            )
+            .into());
+        }
+
+        let mut body = response.into_body();
+        Ok(stream! {
+            while let Some(frame) = body.frame().await {
+                match frame.map_err(S3Error::from)?.into_data() {
+                    Ok(data) if !data.is_empty() => yield Ok(data),
+                    // trailers, or an empty frame
+                    _ => {}
+                }
+            }
+        })
+    }
+
+    /// Puts the content at once if it ends within the first part, e.g. as `size_hint` tells it
+    /// is small, and uploads it in parts otherwise, sized after `size_hint` for the upload to
+    /// fit in the 10,000 parts S3 allows: without it, parts of 5 MiB make objects of up to about
+    /// 48 GiB. A part is uploaded before more of the stream is read.
+    async fn put_stream<S>(
+        &self,
+        path: &Path,
+        stream: S,
+        size_hint: Option<u64>,
+    ) -> Result<FileMeta, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + MaybeSend,
+    {
+        let part_size = stream_part_size(size_hint);
+        let upload = MultipartUpload::new(self.clone(), path.clone());
+        let mut upload_id = None::<String>;
+        let mut parts = vec![];
+
+        let result = async {
+            let mut stream = pin!(stream);
+            let mut buf = BytesMut::new();
+            while let Some(chunk) = stream.next().await {
+                buf.put(chunk?);
+                // a part is only uploaded once the stream is known not to end with it, the last
+                // part completes the upload along with the rest of the content
+                while buf.len() > part_size {
+                    let part = buf.split_to(part_size).freeze();
+                    let upload_id = match &upload_id {
+                        Some(upload_id) => upload_id,
+                        None => upload_id.insert(upload.initiate().await?),
+                    };
+                    let part_num = parts.len();
+                    parts.push(
+                        upload
+                            .upload_part(upload_id, part_num, part.len(), Full::new(part))
+                            .await?,
+                    );
+                }
+            }
+            let last = buf.freeze();
+            match &upload_id {
+                None => upload.upload_once(last.len(), Full::new(last)).await,
+                Some(upload_id) => {
+                    let part_num = parts.len();
+                    parts.push(
+                        upload
+                            .upload_part(upload_id, part_num, last.len(), Full::new(last))
+                            .await?,
+                    );
+                    upload.complete_part(upload_id, &parts).await
+                }
+            }
+        }
+        .await;
+
+        if let Err(e) = result {
+            if let Some(upload_id) = &upload_id {
+                let _ = self.abort(path, upload_id).await;
+            }
+            return Err(e);
+        }
+        self.metadata(path).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.copy_with(from, to, &CopyOptions::default()).await?;
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.copy(from, to).await?;
+        self.remove(from).await
+    }
+
+    async fn rename_with_meta(&self, from: &Path, to: &Path) -> Result<FileMeta, Error> {
+        let (source, etag) = self
+            .copy_object(self, from, to, &CopyOptions::default())
+            .await?;
+        self.remove(from).await?;
+        // a copy by parts doesn't tell its ETag
+        let etag = match etag {
+            Some(etag) => Some(etag),
+            None => self.metadata(to).await?.etag,
+        };
+        // the copy keeps the content and the content headers of the source
+        Ok(FileMeta {
+            path: to.clone(),
+            size: self.object_size(to, &source).await?,
+            etag,
+            content_encoding: content_headers(&source).1,
+            storage_class: None,
+            restore_status: None,
+            local_version: None,
+        })
+    }
+
+    async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let header = format!("{}{}", METADATA_PREFIX, metadata_key(name)?);
+        let headers = self.head_object(path).await?;
+        Ok(headers
+            .get(header.as_str())
+            .map(|value| value.as_bytes().to_vec()))
+    }
+
+    /// Objects can't be modified in place, the object is copied onto itself with its user
+    /// metadata updated, which gives it a new `ETag`. Values must be text fit for a header.
+    async fn set_xattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+        let key = metadata_key(name)?;
+        let value = std::str::from_utf8(value)
+            .ok()
+            .filter(|value| HeaderValue::from_str(value).is_ok())
+            .ok_or_else(|| Error::Unsupported {
+                message: format!("S3 user metadata can't hold the value of \"{}\"", name),
+            })?;
+
+        let headers = self.head_object(path).await?;
+        let mut metadata = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let key = name.as_str().strip_prefix(METADATA_PREFIX)?;
+                Some((key.to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect::<BTreeMap<_, _>>();
+        metadata.insert(key, value.to_string());
+        self.copy_with(
+            path,
+            path,
+            &CopyOptions::default().replace_metadata(metadata),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn list_xattr(&self, path: &Path) -> Result<Vec<String>, Error> {
+        let headers = self.head_object(path).await?;
+        Ok(headers
+            .keys()
+            .filter(|name| *name != UPLOAD_TOKEN_HEADER)
+            .filter_map(|name| name.as_str().strip_prefix(METADATA_PREFIX))
+            .map(|key| format!("{}{}", USER_XATTR_PREFIX, key))
+            .collect())
+    }
+
+    async fn checksum(&self, path: &Path, algorithm: ChecksumAlgorithm) -> Result<Vec<u8>, Error> {
+        if self.as_ref().options.checksum {
+            if let Some(checksum) = self.stored_checksum(path, algorithm).await? {
+                return Ok(checksum);
+            }
+        }
+        digest_file(self, path, algorithm).await
+    }
+
+    #[cfg(feature = "checksum")]
+    async fn list_digest(&self, path: &Path) -> Result<[u8; 32], Error> {
+        let mut entries = vec![];
+        let mut stream = pin!(self.list(path).await?);
+        while let Some(meta) = stream.next().await {
+            entries.push(meta?);
+        }
+        listing_digest(path, entries)
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        self.location(path).to_string()
+    }
+
+    /// The key of the object, without the bucket.
+    fn native_path<'a>(&self, path: &'a Path) -> Cow<'a, OsStr> {
+        Cow::Borrowed(OsStr::new(path.as_ref()))
+    }
+
+    fn as_presignable(&self) -> Option<&dyn PresignedFs> {
+        Some(self)
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+}
+
+impl PresignedFs for AmazonS3 {
+    fn presign_get(&self, path: &Path, expires_in: Duration) -> Result<Url, Error> {
+        self.presign(Method::GET, path, expires_in)
+    }
+
+    fn presign_put(&self, path: &Path, expires_in: Duration) -> Result<Url, Error> {
+        self.presign(Method::PUT, path, expires_in)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListPrefix {
+    pub prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListContents {
+    pub key: String,
+    pub size: usize,
+    pub last_modified: DateTime<Utc>,
+    #[serde(rename = "ETag")]
+    pub e_tag: Option<String>,
+    pub storage_class: Option<String>,
+}
+
+impl ListContents {
+    /// The storage class of the object, `None` for the standard one, which a `HEAD` leaves out.
+    fn storage_class(&self) -> Option<String> {
+        self.storage_class
+            .clone()
+            .filter(|storage_class| storage_class != "STANDARD")
+    }
+
+    /// Whether the object is a directory marker, see [`ListOptions::markers`].
+    fn is_marker(&self) -> bool {
+        self.key.ends_with('/') && self.size == 0
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListResponse {
+    #[serde(default)]
+    pub contents: Vec<ListContents>,
+    #[serde(default)]
+    pub common_prefixes: Vec<ListPrefix>,
+    #[serde(default)]
+    pub next_continuation_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListVersion {
+    pub key: String,
+    pub version_id: String,
+    pub is_latest: bool,
+    pub last_modified: DateTime<Utc>,
+    #[serde(rename = "ETag")]
+    pub e_tag: Option<String>,
+    pub size: usize,
+    pub storage_class: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListDeleteMarker {
+    pub key: String,
+    pub version_id: String,
+    pub is_latest: bool,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// A page of a `ListObjectVersions`, its versions and delete markers interleaved in the XML.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListVersionsResponse {
+    #[serde(default, rename = "Version")]
+    pub versions: Vec<ListVersion>,
+    #[serde(default, rename = "DeleteMarker")]
+    pub delete_markers: Vec<ListDeleteMarker>,
+    #[serde(default)]
+    pub is_truncated: bool,
+    #[serde(default)]
+    pub next_key_marker: Option<String>,
+    #[serde(default)]
+    pub next_version_id_marker: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio-http", not(feature = "completion-based")))]
+    #[test]
+    fn base_path_prefixes_request_paths() {
+        use crate::{path::Path, remotes::aws::fs::AmazonS3Builder};
+
+        let s3 = AmazonS3Builder::new("bucket".into())
+            .endpoint("https://gateway.example.com/".into())
+            .base_path("/s3/".into())
+            .build();
+        assert_eq!(
+            s3.object_url(&Path::from("a/b")),
+            "https://gateway.example.com/s3/bucket/a/b"
+        );
+
+        let s3 = AmazonS3Builder::new("bucket".into())
+            .region("eu-west-1".into())
+            .build();
+        assert_eq!(
+            s3.object_url(&Path::from("a/b")),
+            "https://bucket.s3.eu-west-1.amazonaws.com/a/b"
+        );
+    }
+
+    #[cfg(all(feature = "tokio-http", not(feature = "completion-based")))]
+    #[tokio::test]
+    async fn builder_from_environment_variables() {
+        use std::collections::HashMap;
+
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{
+                fs::AmazonS3Builder,
+                mock::{MockS3, MOCK_ENDPOINT},
+            },
+        };
+
+        let vars = HashMap::from([
+            ("AWS_ACCESS_KEY_ID", "key"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+            ("AWS_SESSION_TOKEN", "token"),
+            ("AWS_DEFAULT_REGION", "eu-west-3"),
+            ("AWS_ENDPOINT_URL", MOCK_ENDPOINT),
+        ]);
+        let mut builder = AmazonS3Builder::new("bucket".into())
+            .with_env(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        let mock = MockS3::new();
+        mock.set_bucket_region("eu-west-3");
+        builder.client = Box::new(mock.clone());
+        let s3 = builder.build();
+
+        let path = Path::from("a");
+        s3.write_file(&path, b"hello".to_vec()).await.0.unwrap();
+        assert_eq!(s3.metadata(&path).await.unwrap().size, 5);
+
+        let headers = mock.object("bucket/a").unwrap().headers;
+        let authorization = headers["authorization"].to_str().unwrap();
+        assert!(authorization.contains("Credential=key/"));
+        assert!(authorization.contains("/eu-west-3/s3/aws4_request"));
+        assert_eq!(headers["x-amz-security-token"], "token");
+    }
+
+    #[cfg(all(feature = "tokio-http", not(feature = "completion-based")))]
+    #[tokio::test]
+    async fn instance_credential_is_fetched_with_the_data_client() {
+        use http::{Method, StatusCode};
+
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{fs::AmazonS3Builder, mock::MockS3, AwsCredential},
+        };
+
+        // one client serves both the plain HTTP metadata service and the HTTPS endpoint
+        let mock = MockS3::new();
+        mock.instance_credential(AwsCredential {
+            key_id: "instance-key".into(),
+            secret_key: "instance-secret".into(),
+            token: Some("instance-token".into()),
+        });
+        let s3 = AmazonS3Builder::new("bucket".into())
+            .endpoint("https://s3.mock".into())
+            .instance_credentials()
+            .client(mock.clone())
+            .build();
+
+        let path = Path::from("a");
+        s3.write_file(&path, b"hello".to_vec()).await.0.unwrap();
+        assert_eq!(s3.metadata(&path).await.unwrap().size, 5);
+
+        // the token and the object were put, the credential is fetched once for both requests
+        assert_eq!(mock.requests(Method::PUT), [StatusCode::OK; 2]);
+        assert_eq!(mock.requests(Method::GET), [StatusCode::OK; 2]);
+        let headers = mock.object("bucket/a").unwrap().headers;
+        let authorization = headers["authorization"].to_str().unwrap();
+        assert!(authorization.contains("Credential=instance-key/"));
+        assert_eq!(headers["x-amz-security-token"], "instance-token");
+    }
+
+    #[cfg(feature = "tokio-http")]
+    #[tokio::test]
+    async fn list_and_remove() {
+        use std::{env, pin::pin};
+
+        use futures_util::StreamExt;
+
+        use super::*;
+
+        if env::var("AWS_ACCESS_KEY_ID").is_err() {
+            eprintln!("skipping AWS s3 test");
+            return;
+        }
+        let key_id = env::var("AWS_ACCESS_KEY_ID").unwrap();
+        let secret_key = env::var("AWS_SECRET_ACCESS_KEY").unwrap();
+
+        let s3 = AmazonS3Builder::new("fusio-test".into())
+            .credential(AwsCredential {
+                key_id,
+                secret_key,
+                token: None,
+            })
+            .region("ap-southeast-1".into())
+            .sign_payload(true)
+            .build();
+
+        let path = Path::parse("test").unwrap();
         let mut stream = pin!(s3.list(&path).await.unwrap());
         while let Some(meta) = stream.next().await {
             let meta = meta.unwrap();
             s3.remove(&meta.path).await.unwrap();
         }
     }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn write_file_sets_content_type() {
+        use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let body = b"{\"answer\":42}".to_vec();
+
+        let (result, _) = s3.write_file(&Path::from("foo.json"), body.clone()).await;
+        let meta = result.unwrap();
+        assert_eq!(meta.size, body.len() as u64);
+        assert!(!meta.etag.unwrap().is_empty());
+
+        let object = mock.object("foo.json").unwrap();
+        assert_eq!(object.body, body);
+        assert_eq!(object.headers[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            object.headers[CONTENT_LENGTH],
+            body.len().to_string().as_str()
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn append_emulated_accumulates() {
+        use crate::{path::Path, remotes::aws::mock::MockS3, Error};
+
+        let mock = MockS3::new();
+        let path = Path::from("log");
+
+        let (result, _) = mock.fs().append_emulated(&path, &b"a"[..]).await;
+        assert!(matches!(result, Err(Error::Unsupported { .. })));
+
+        let mut options = mock.options();
+        options.append_emulation = true;
+        let s3 = mock.fs_with(options);
+        for line in [&b"one\n"[..], &b"two\n"[..], &b"three\n"[..]] {
+            s3.append_emulated(&path, line).await.0.unwrap();
+        }
+        assert_eq!(mock.object("log").unwrap().body, &b"one\ntwo\nthree\n"[..]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn rename_keeps_metadata() {
+        use http::header::CONTENT_TYPE;
+
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        s3.write_file(&Path::from("a.json"), b"{}".to_vec())
+            .await
+            .0
+            .unwrap();
+
+        s3.rename(&Path::from("a.json"), &Path::from("dir/b"))
+            .await
+            .unwrap();
+
+        assert!(mock.object("a.json").is_none());
+        let object = mock.object("dir/b").unwrap();
+        assert_eq!(object.body, &b"{}"[..]);
+        assert_eq!(object.headers[CONTENT_TYPE], "application/json");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn copy_keeps_metadata_and_tags() {
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, CopyOptions},
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("a", "hello");
+
+        s3.copy_with(
+            &Path::from("a"),
+            &Path::from("b"),
+            &CopyOptions::default()
+                .replace_metadata([("owner", "fusio")])
+                .replace_tags([("team", "storage"), ("tier", "hot")]),
+        )
+        .await
+        .unwrap();
+        s3.copy(&Path::from("b"), &Path::from("c")).await.unwrap();
+
+        for key in ["b", "c"] {
+            let object = mock.object(key).unwrap();
+            assert_eq!(object.body, "hello");
+            assert_eq!(object.headers["x-amz-meta-owner"], "fusio");
+            assert_eq!(object.headers["x-amz-tagging"], "team=storage&tier=hot");
+        }
+        assert!(mock.object("a").is_some());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn list_fetches_pages_as_consumed() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use http::Method;
+
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.set_list_page_size(2);
+        for name in ["a", "b", "c", "d", "e"] {
+            mock.put_object(&format!("dir/{}", name), "x");
+        }
+        let gets = || mock.requests(Method::GET).len();
+        let dir = Path::from("dir");
+
+        {
+            let mut stream = pin!(s3.list(&dir).await.unwrap());
+            assert_eq!(gets(), 0);
+            for _ in 0..2 {
+                stream.next().await.unwrap().unwrap();
+            }
+            assert_eq!(gets(), 1);
+        }
+        assert_eq!(gets(), 1);
+
+        let mut stream = pin!(s3.list(&dir).await.unwrap());
+        let mut paths = vec![];
+        while let Some(meta) = stream.next().await {
+            paths.push(meta.unwrap().path);
+        }
+        assert_eq!(
+            paths,
+            ["a", "b", "c", "d", "e"].map(|name| Path::from(format!("dir/{}", name)))
+        );
+        assert_eq!(gets(), 4);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn directory_markers_are_listed_on_request() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+
+        use crate::{
+            fs::{Fs, ListOptions},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("dir/", "");
+        mock.put_object("dir/a", "x");
+        mock.put_object("dir/sub/", "");
+        mock.put_object("dir/sub/b", "x");
+        let dir = Path::from("dir");
+        let list = |options: ListOptions| {
+            let (s3, dir) = (&s3, &dir);
+            async move {
+                let mut stream = pin!(s3.list_with(dir, options).await.unwrap());
+                let mut paths = vec![];
+                while let Some(meta) = stream.next().await {
+                    paths.push(meta.unwrap().path.to_string());
+                }
+                paths
+            }
+        };
+
+        assert_eq!(list(ListOptions::default()).await, ["dir/a", "dir/sub/b"]);
+        let mut stream = pin!(s3.list(&dir).await.unwrap());
+        let mut paths = vec![];
+        while let Some(meta) = stream.next().await {
+            paths.push(meta.unwrap().path.to_string());
+        }
+        assert_eq!(paths, ["dir/a", "dir/sub/b"]);
+        let (page, _) = s3.list_page(&dir, None, 10).await.unwrap();
+        assert_eq!(page.len(), 2);
+
+        assert_eq!(
+            list(ListOptions::default().markers(true)).await,
+            ["dir", "dir/a", "dir/sub", "dir/sub/b"]
+        );
+        assert_eq!(
+            list(ListOptions::default().markers(true).relative(true)).await,
+            ["", "a", "sub", "sub/b"]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn listings_match_regardless_of_case_on_request() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+
+        use crate::{
+            fs::{Fs, ListOptions},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("data/file", "x");
+        mock.put_object("database/x", "x");
+        mock.put_object("other/y", "x");
+        let dir = Path::from("Data");
+        let list = |options: ListOptions| {
+            let (s3, dir) = (&s3, &dir);
+            async move {
+                let mut stream = pin!(s3.list_with(dir, options).await.unwrap());
+                let mut paths = vec![];
+                while let Some(meta) = stream.next().await {
+                    paths.push(meta.unwrap().path.to_string());
+                }
+                paths
+            }
+        };
+
+        // S3 is case-sensitive
+        assert!(list(ListOptions::default()).await.is_empty());
+        assert_eq!(
+            list(ListOptions::default().case_insensitive(true)).await,
+            ["data/file"]
+        );
+        assert_eq!(
+            list(ListOptions::default().case_insensitive(true).relative(true)).await,
+            ["file"]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn expected_bucket_owner_is_signed() {
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3};
+
+        let mock = MockS3::new();
+        let mut options = mock.options();
+        options.expected_bucket_owner = Some("111122223333".into());
+        let s3 = mock.fs_with(options);
+
+        s3.write_file(&Path::from("a"), b"hello".to_vec())
+            .await
+            .0
+            .unwrap();
+
+        let headers = mock.object("a").unwrap().headers;
+        assert_eq!(headers["x-amz-expected-bucket-owner"], "111122223333");
+        let authorization = headers["authorization"].to_str().unwrap();
+        let signed_headers = authorization
+            .split(", ")
+            .find_map(|part| part.strip_prefix("SignedHeaders="))
+            .unwrap();
+        assert!(signed_headers
+            .split(';')
+            .any(|header| header == "x-amz-expected-bucket-owner"));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn canned_acls_are_signed_and_read_back() {
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, CannedAcl, Grantee, Permission, WriteOptions},
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let path = Path::from("a");
+        s3.write_file_with(
+            &path,
+            b"hello".to_vec(),
+            WriteOptions::default().acl(CannedAcl::PublicRead),
+        )
+        .await
+        .0
+        .unwrap();
+
+        let headers = mock.object("a").unwrap().headers;
+        assert_eq!(headers["x-amz-acl"], "public-read");
+        let authorization = headers["authorization"].to_str().unwrap();
+        let signed_headers = authorization
+            .split(", ")
+            .find_map(|part| part.strip_prefix("SignedHeaders="))
+            .unwrap();
+        assert!(signed_headers
+            .split(';')
+            .any(|header| header == "x-amz-acl"));
+
+        let public_read = |grantee: &Grantee, permission| {
+            permission == Permission::Read
+                && *grantee
+                    == Grantee::Group {
+                        uri: "http://acs.amazonaws.com/groups/global/AllUsers".into(),
+                    }
+        };
+        let acl = s3.get_object_acl(&path).await.unwrap();
+        assert_eq!(acl.owner.unwrap().id, "mock-owner");
+        assert!(acl
+            .grants
+            .iter()
+            .any(|grant| public_read(&grant.grantee, grant.permission)));
+
+        s3.set_object_acl(&path, CannedAcl::Private).await.unwrap();
+        let acl = s3.get_object_acl(&path).await.unwrap();
+        assert_eq!(acl.grants.len(), 1);
+        assert_eq!(acl.grants[0].permission, Permission::FullControl);
+        assert_eq!(mock.object("a").unwrap().body, "hello");
+    }
+
+    #[test]
+    fn presigned_get_signs_response_overrides() {
+        use std::time::Duration;
+
+        use http::Method;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, ResponseOverrides},
+        };
+
+        let s3 = MockS3::new().fs();
+        let path = Path::from("report.csv");
+        let url = s3
+            .presign_get_with(
+                &path,
+                Duration::from_secs(3600),
+                &ResponseOverrides::default().content_disposition("attachment"),
+            )
+            .unwrap();
+        let query = url.query_pairs().collect::<Vec<_>>();
+        assert_eq!(
+            query[0],
+            ("response-content-disposition".into(), "attachment".into())
+        );
+        assert!(query.iter().any(|(name, _)| name == "X-Amz-Signature"));
+
+        // the override changes what is signed
+        let plain = s3
+            .presign(Method::GET, &path, Duration::from_secs(3600))
+            .unwrap();
+        let signature = |url: &url::Url| {
+            url.query_pairs()
+                .find(|(name, _)| name == "X-Amz-Signature")
+                .map(|(_, value)| value.into_owned())
+        };
+        assert_ne!(signature(&url), signature(&plain));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn multipart_copy_keeps_content_and_metadata() {
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, CopyOptions},
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("a", "hello world");
+        s3.copy_with(
+            &Path::from("a"),
+            &Path::from("b.txt"),
+            &CopyOptions::default().replace_metadata([("owner", "fusio")]),
+        )
+        .await
+        .unwrap();
+
+        let source = s3.head_object(&Path::from("b.txt")).await.unwrap();
+        s3.copy_multipart(
+            &s3,
+            &Path::from("b.txt"),
+            &Path::from("c.txt"),
+            &CopyOptions::default(),
+            &source,
+            [(0, 4), (5, 9), (10, 10)].into_iter(),
+        )
+        .await
+        .unwrap();
+
+        let object = mock.object("c.txt").unwrap();
+        assert_eq!(object.body, "hello world");
+        assert_eq!(object.headers["x-amz-meta-owner"], "fusio");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn write_with_content_encoding() {
+        use http::header::CONTENT_ENCODING;
+
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, WriteOptions},
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let path = Path::from("data.csv.gz");
+        // not a valid gzip stream, fusio must upload it untouched anyway
+        let body = b"\x1f\x8bcompressed".to_vec();
+
+        let (result, _) = s3
+            .write_file_with(
+                &path,
+                body.clone(),
+                WriteOptions::default().content_encoding("gzip"),
+            )
+            .await;
+        assert_eq!(result.unwrap().content_encoding.as_deref(), Some("gzip"));
+
+        let object = mock.object("data.csv.gz").unwrap();
+        assert_eq!(object.body, body);
+        assert_eq!(object.headers[CONTENT_ENCODING], "gzip");
+
+        let meta = s3.metadata(&path).await.unwrap();
+        assert_eq!(meta.content_encoding.as_deref(), Some("gzip"));
+        let (meta, buf) = s3.read_if_none_match(&path, "").await.unwrap().unwrap();
+        assert_eq!(meta.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(buf, body);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn checksum_from_head_or_download() {
+        use http::Method;
+
+        use crate::{
+            fs::{ChecksumAlgorithm, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let mut options = mock.options();
+        options.checksum = true;
+        let s3 = mock.fs_with(options);
+        let path = Path::from("hello");
+        s3.write_file(&path, b"hello world".to_vec())
+            .await
+            .0
+            .unwrap();
+
+        let sha256 = s3.checksum(&path, ChecksumAlgorithm::Sha256).await.unwrap();
+        assert_eq!(
+            sha256
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert!(mock.requests(Method::GET).is_empty());
+
+        // S3 only stores the checksum it was uploaded with, the others are computed locally
+        let sha1 = s3.checksum(&path, ChecksumAlgorithm::Sha1).await.unwrap();
+        assert_eq!(
+            sha1.iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+            "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"
+        );
+        assert!(!mock.requests(Method::GET).is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn write_modes_of_opened_objects() {
+        use std::io;
+
+        use crate::{
+            fs::{Fs, OpenOptions},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Error, Write,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let path = Path::from("a");
+        let truncate = OpenOptions::default().create(true).truncate(true);
+
+        let mut file = s3.open_options(&path, truncate).await.unwrap();
+        file.write_all(&b"hello "[..]).await.0.unwrap();
+        file.write_all(&b"world"[..]).await.0.unwrap();
+        // nothing is uploaded before the file is closed
+        assert!(mock.object("a").is_none());
+        file.close().await.unwrap();
+        assert_eq!(mock.object("a").unwrap().body, "hello world");
+
+        // a file closed without writes is empty, like a local one
+        let mut file = s3.open_options(&path, truncate).await.unwrap();
+        file.close().await.unwrap();
+        assert!(mock.object("a").unwrap().body.is_empty());
+
+        // `create` replaces the object, existing or not, even without `truncate`
+        let create = OpenOptions::default().create(true);
+        let mut file = s3.open_options(&path, create).await.unwrap();
+        file.write_all(&b"hi"[..]).await.0.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(mock.object("a").unwrap().body, "hi");
+        let mut file = s3.open_options(&Path::from("b"), create).await.unwrap();
+        file.write_all(&b"hello"[..]).await.0.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(mock.object("b").unwrap().body, "hello");
+
+        // without it, the object must exist and can only be replaced
+        let write = OpenOptions::default().write(true);
+        assert!(matches!(
+            s3.open_options(&path, write).await,
+            Err(Error::Unsupported { .. })
+        ));
+        let mut file = s3.open_options(&path, write.truncate(true)).await.unwrap();
+        file.close().await.unwrap();
+        assert!(mock.object("a").unwrap().body.is_empty());
+        assert!(matches!(
+            s3.open_options(&Path::from("c"), write).await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+        // like a local file opened for reading only
+        let mut file = s3.open(&path).await.unwrap();
+        assert!(file.write_all(&b"hello"[..]).await.0.is_err());
+        file.close().await.unwrap();
+        assert!(mock.object("a").unwrap().body.is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn empty_bodies_declare_their_length() {
+        use http::{Method, StatusCode};
+
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, CannedAcl},
+        };
+
+        // the mock, like S3, answers `411 Length Required` to empty bodies without a length
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let (from, to) = (Path::from("empty"), Path::from("copy"));
+        mock.put_object("empty", "");
+        s3.copy(&from, &to).await.unwrap();
+        s3.set_object_acl(&to, CannedAcl::Private).await.unwrap();
+
+        assert_eq!(mock.requests(Method::PUT), [StatusCode::OK; 2]);
+        assert!(mock.object("copy").unwrap().body.is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn metadata_tells_archived_objects() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+
+        use crate::{
+            fs::{Fs, RestoreStatus},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("dir/archived", "x");
+        mock.set_header("dir/archived", "x-amz-storage-class", "GLACIER");
+        mock.set_header("dir/archived", "x-amz-restore", "ongoing-request=\"true\"");
+        mock.put_object("dir/standard", "x");
+
+        let meta = s3.metadata(&Path::from("dir/archived")).await.unwrap();
+        assert_eq!(meta.storage_class.as_deref(), Some("GLACIER"));
+        assert_eq!(meta.restore_status, Some(RestoreStatus::InProgress));
+        let meta = s3.metadata(&Path::from("dir/standard")).await.unwrap();
+        assert_eq!(meta.storage_class, None);
+        assert_eq!(meta.restore_status, None);
+
+        // listings report the storage class too, though not the restoration
+        let dir = Path::from("dir");
+        let mut stream = pin!(s3.list(&dir).await.unwrap());
+        let mut classes = vec![];
+        while let Some(meta) = stream.next().await {
+            classes.push(meta.unwrap().storage_class);
+        }
+        assert_eq!(classes, [Some("GLACIER".to_string()), None]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn chunked_responses_are_read_to_their_end() {
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3, Read};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let content = b"chunked without a length";
+        mock.put_object("object", &content[..]);
+        mock.put_object("empty", "");
+        mock.chunked();
+
+        let path = Path::from("object");
+        assert_eq!(s3.metadata(&path).await.unwrap().size, content.len() as u64);
+        assert_eq!(s3.metadata(&Path::from("empty")).await.unwrap().size, 0);
+
+        let mut file = s3.open(&path).await.unwrap();
+        assert_eq!(file.size().await.unwrap(), content.len() as u64);
+        let (result, buf) = file.read_to_end_at(Vec::new(), 0).await;
+        result.unwrap();
+        assert_eq!(buf, content);
+        let (result, buf) = file.read_to_end_at(Vec::new(), 8).await;
+        result.unwrap();
+        assert_eq!(buf, &content[8..]);
+        let (result, buf) = file.read_exact_at(vec![0; 11], 2).await;
+        result.unwrap();
+        assert_eq!(buf, &content[2..13]);
+        assert_eq!(s3.read_tail(&path, 6).await.unwrap(), b"length");
+
+        // the content past the end of a chunked response isn't made up
+        let (result, _) = file.read_exact_at(vec![0; 8], 20).await;
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn versions_are_listed_with_their_delete_markers() {
+        use tempfile::TempDir;
+
+        use crate::{
+            disk::TokioFs,
+            fs::{FileVersion, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Error,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.set_version_pages(&[
+            "<ListVersionsResult><Name>fusio-test</Name><Prefix>dir/</Prefix><IsTruncated>true</\
+             IsTruncated><NextKeyMarker>dir/a</NextKeyMarker><NextVersionIdMarker>v1</\
+             NextVersionIdMarker><DeleteMarker><Key>dir/a</Key><VersionId>v3</\
+             VersionId><IsLatest>true</IsLatest><LastModified>2024-01-03T00:00:00.000Z</\
+             LastModified></DeleteMarker><Version><Key>dir/a</Key><VersionId>v2</\
+             VersionId><IsLatest>false</IsLatest><LastModified>2024-01-02T00:00:00.000Z</\
+             LastModified><ETag>\"2\"</ETag><Size>5</Size><StorageClass>STANDARD</StorageClass></\
+             Version><Version><Key>dir/a</Key><VersionId>v1</VersionId><IsLatest>false</\
+             IsLatest><LastModified>2024-01-01T00:00:00.000Z</LastModified><ETag>\"1\"</\
+             ETag><Size>3</Size><StorageClass>GLACIER</StorageClass></Version></\
+             ListVersionsResult>",
+            "<ListVersionsResult><IsTruncated>false</IsTruncated><Version><Key>dir/b</\
+             Key><VersionId>v4</VersionId><IsLatest>true</IsLatest><LastModified>2024-01-04T00:00:\
+             00.000Z</LastModified><ETag>\"4\"</ETag><Size>7</Size><StorageClass>STANDARD</\
+             StorageClass></Version></ListVersionsResult>",
+        ]);
+
+        let versions = s3.list_versions(&Path::from("dir")).await.unwrap();
+        let summary = versions
+            .iter()
+            .map(|version: &FileVersion| {
+                (
+                    version.meta.path.as_ref(),
+                    version.version_id.as_str(),
+                    version.is_latest,
+                    version.is_delete_marker,
+                    version.meta.size,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("dir/a", "v3", true, true, 0),
+                ("dir/a", "v2", false, false, 5),
+                ("dir/a", "v1", false, false, 3),
+                ("dir/b", "v4", true, false, 7),
+            ]
+        );
+        assert_eq!(versions[1].meta.etag.as_deref(), Some("\"2\""));
+        assert_eq!(versions[2].meta.storage_class.as_deref(), Some("GLACIER"));
+        assert_eq!(versions[0].meta.etag, None);
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = Path::from_filesystem_path(tmp_dir.path()).unwrap();
+        assert!(matches!(
+            TokioFs.list_versions(&dir).await,
+            Err(Error::Unsupported { .. })
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn copies_are_conditional_on_the_source_etag() {
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, S3Error},
+            Error,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let source = mock.put_object("source", "hello");
+        let (from, to) = (Path::from("source"), Path::from("archive"));
+
+        let result = s3.copy_if_source_matches(&from, &to, "\"stale\"").await;
+        assert!(matches!(
+            result,
+            Err(Error::S3Error(S3Error::PreconditionFailed(location))) if location.contains("source")
+        ));
+        assert!(mock.object("archive").is_none());
+
+        s3.copy_if_source_matches(&from, &to, &source.etag)
+            .await
+            .unwrap();
+        assert_eq!(mock.object("archive").unwrap().body, "hello");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn objects_are_copied_across_buckets() {
+        use crate::{
+            path::Path,
+            remotes::aws::{copy::copy_source, mock::MockS3, CopyOptions},
+        };
+
+        let source_mock = MockS3::new();
+        let mut options = source_mock.options();
+        options.bucket = "source-bucket".into();
+        let source = source_mock.fs_with(options);
+        let mock = MockS3::new();
+        mock.add_bucket("source-bucket", &source_mock);
+        let s3 = mock.fs();
+
+        let from = Path::from("dir/a file+1");
+        assert_eq!(
+            copy_source(source.bucket(), &from),
+            "/source-bucket/dir/a%20file%2B1"
+        );
+        source_mock.put_object("dir/a file+1", "hello");
+        let etag = s3
+            .copy_from_bucket(&source, &from, &Path::from("copy"), &CopyOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(etag, Some(mock.object("copy").unwrap().etag));
+        assert_eq!(mock.object("copy").unwrap().body, "hello");
+        assert!(mock.object("dir/a file+1").is_none());
+
+        s3.rename_from_bucket(&source, &from, &Path::from("moved"))
+            .await
+            .unwrap();
+        assert_eq!(mock.object("moved").unwrap().body, "hello");
+        assert!(source_mock.object("dir/a file+1").is_none());
+        assert!(s3
+            .rename_from_bucket(&source, &from, &Path::from("again"))
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn shutdown_aborts_uploads_in_flight() {
+        use std::time::Duration;
+
+        use http::{Method, StatusCode};
+
+        use crate::{
+            fs::{Fs, OpenOptions},
+            path::Path,
+            remotes::aws::{fs::AmazonS3, mock::MockS3, S3Error, S3File, ShutdownPolicy},
+            Error, Write,
+        };
+
+        // writes a part, starting a multipart upload, and a byte left buffered
+        async fn start_upload(s3: &AmazonS3, path: &Path) -> S3File {
+            let options = OpenOptions::default().create(true).truncate(true);
+            let mut file = s3.open_options(path, options).await.unwrap();
+            let (result, _) = file.write_all(vec![0; 5 * 1024 * 1024 + 1]).await;
+            result.unwrap();
+            let (result, _) = file.write_all(b"x".to_vec()).await;
+            result.unwrap();
+            file.flush().await.unwrap();
+            file
+        }
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let path = Path::from("a");
+        let mut file = start_upload(&s3, &path).await;
+        assert_eq!(mock.uploads(), 1);
+        assert_eq!(mock.clients(), 1);
+
+        s3.shutdown(ShutdownPolicy::Abort).await.unwrap();
+        // no part is left stored, and the client is dropped along with its connections
+        assert_eq!(mock.uploads(), 0);
+        assert_eq!(mock.requests(Method::DELETE), [StatusCode::NO_CONTENT]);
+        assert_eq!(mock.clients(), 0);
+        assert!(matches!(
+            file.close().await,
+            Err(Error::S3Error(S3Error::ShutDown))
+        ));
+        assert!(mock.object("a").is_none());
+        // a second shutdown has nothing left to do
+        s3.shutdown(ShutdownPolicy::Abort).await.unwrap();
+        assert_eq!(mock.requests(Method::DELETE).len(), 1);
+
+        // the files left open are completed with what they buffered
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let mut file = start_upload(&s3, &path).await;
+        let options = OpenOptions::default().create(true).truncate(true);
+        let mut small = s3
+            .open_options(&Path::from("small"), options)
+            .await
+            .unwrap();
+        let (result, _) = small.write_all(b"buffered".to_vec()).await;
+        result.unwrap();
+        let mut untouched = s3
+            .open_options(&Path::from("untouched"), options)
+            .await
+            .unwrap();
+        let policy = ShutdownPolicy::Complete {
+            timeout: Duration::from_secs(10),
+        };
+        s3.shutdown(policy).await.unwrap();
+        assert_eq!(mock.object("a").unwrap().body.len(), 5 * 1024 * 1024 + 2);
+        assert_eq!(mock.object("small").unwrap().body, "buffered");
+        // a file nothing was written to isn't made an empty object
+        assert!(mock.object("untouched").is_none());
+        assert_eq!(mock.uploads(), 0);
+        assert!(mock.requests(Method::DELETE).is_empty());
+        assert_eq!(mock.clients(), 0);
+        // the files were closed by the shutdown
+        file.close().await.unwrap();
+        small.close().await.unwrap();
+        assert!(matches!(
+            untouched.close().await,
+            Err(Error::S3Error(S3Error::ShutDown))
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn copy_range_copies_server_side() {
+        use std::io;
+
+        use http::Method;
+
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3, Error};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let content = (0..300).map(|i| i as u8).collect::<Vec<_>>();
+        mock.put_object("source", content.clone());
+
+        s3.copy_range(&Path::from("source"), 100..200, &Path::from("slice"))
+            .await
+            .unwrap();
+        let copy = mock.object("slice").unwrap().body;
+        assert_eq!(copy.len(), 100);
+        assert_eq!(copy.as_ref(), &content[100..200]);
+        // copied with `UploadPartCopy`, without downloading the source
+        assert!(mock.requests(Method::GET).is_empty());
+
+        // an empty range makes an empty file
+        s3.copy_range(&Path::from("source"), 300..300, &Path::from("empty"))
+            .await
+            .unwrap();
+        assert!(mock.object("empty").unwrap().body.is_empty());
+
+        // ranges past the end of the source are refused
+        assert!(matches!(
+            s3.copy_range(&Path::from("source"), 250..301, &Path::from("past"))
+                .await,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+        assert!(mock.object("past").is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn warmup_opens_connections_reused_afterwards() {
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{fs::MAX_WARMUP_CONNECTIONS, mock::MockS3},
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("object", b"hello".to_vec());
+
+        s3.warmup(4).await.unwrap();
+        assert_eq!(mock.connections(), 4);
+        assert_eq!(mock.idle_connections(), 4);
+
+        for _ in 0..8 {
+            assert_eq!(s3.metadata(&Path::from("object")).await.unwrap().size, 5);
+        }
+        assert_eq!(mock.connections(), 4);
+        assert_eq!(mock.idle_connections(), 4);
+
+        // warming up reuses the idle connections, and opens no more than the bound
+        s3.warmup(usize::MAX).await.unwrap();
+        assert_eq!(mock.connections(), MAX_WARMUP_CONNECTIONS);
+        assert_eq!(mock.idle_connections(), MAX_WARMUP_CONNECTIONS);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn put_stream_uploads_a_part_at_a_time() {
+        use std::io;
+
+        use bytes::Bytes;
+        use futures_util::stream;
+        use http::Method;
+
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, writer::S3_PART_MINIMUM_SIZE},
+            Error,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+
+        // a content fitting in a part is put at once
+        let chunks = ["hello", ", ", "world"].map(|chunk| Ok(Bytes::from(chunk)));
+        let meta = s3
+            .put_stream(&Path::from("small"), stream::iter(chunks), Some(12))
+            .await
+            .unwrap();
+        assert_eq!(meta.size, 12);
+        assert_eq!(mock.object("small").unwrap().body.as_ref(), b"hello, world");
+        assert!(mock.requests(Method::POST).is_empty());
+
+        // a larger one is uploaded in parts of the minimum size, the last one smaller
+        let content = (0..S3_PART_MINIMUM_SIZE * 5 / 2)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        let chunks = content
+            .chunks(content.len().div_ceil(3))
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 3);
+        let meta = s3
+            .put_stream(&Path::from("large"), stream::iter(chunks), None)
+            .await
+            .unwrap();
+        assert_eq!(meta.size, content.len() as u64);
+        assert_eq!(mock.object("large").unwrap().body.as_ref(), &content[..]);
+        // the `PUT` of the small object, then the three parts
+        assert_eq!(mock.requests(Method::PUT).len(), 4);
+        assert_eq!(mock.uploads(), 0);
+
+        // an error of the stream aborts the upload, after its first part was uploaded
+        let chunks = vec![
+            Ok(Bytes::from(vec![0; S3_PART_MINIMUM_SIZE])),
+            Ok(Bytes::from(vec![0; S3_PART_MINIMUM_SIZE])),
+            Err(Error::Io(io::Error::other("broken stream"))),
+        ];
+        let result = s3
+            .put_stream(&Path::from("broken"), stream::iter(chunks), None)
+            .await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.to_string() == "broken stream"));
+        assert_eq!(mock.requests(Method::PUT).len(), 5);
+        assert!(mock.object("broken").is_none());
+        assert_eq!(mock.uploads(), 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn file_types_are_told_by_a_single_request() {
+        use http::Method;
+
+        use crate::{
+            fs::{FileType, Fs},
+            path::Path,
+            remotes::aws::mock::MockS3,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("dir/a", b"a".to_vec());
+        mock.put_object("dir/b", b"b".to_vec());
+        mock.put_object("marked/", vec![]);
+
+        assert_eq!(
+            s3.file_type(&Path::from("dir/a")).await.unwrap(),
+            Some(FileType::File)
+        );
+        assert_eq!(
+            s3.file_type(&Path::from("dir")).await.unwrap(),
+            Some(FileType::Dir)
+        );
+        assert_eq!(
+            s3.file_type(&Path::from("marked")).await.unwrap(),
+            Some(FileType::Dir)
+        );
+        assert_eq!(s3.file_type(&Path::from("missing")).await.unwrap(), None);
+        // a `HEAD` of each path, then a listing of a page for those that aren't files
+        assert_eq!(mock.requests(Method::GET).len(), 3);
+    }
 }
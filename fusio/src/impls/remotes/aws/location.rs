@@ -0,0 +1,104 @@
+use std::{fmt, str::FromStr};
+
+use percent_encoding::utf8_percent_encode;
+use url::Url;
+
+use super::{S3Error, STRICT_PATH_ENCODE_SET};
+use crate::path::Path;
+
+/// The location of an object in S3, keeping the bucket apart from the key so there is no
+/// ambiguity about where the bucket boundary is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct S3Location {
+    pub bucket: String,
+    pub key: Path,
+}
+
+impl S3Location {
+    pub fn new(bucket: impl Into<String>, key: Path) -> Self {
+        Self {
+            bucket: bucket.into(),
+            key,
+        }
+    }
+
+    /// Parses a `s3://bucket/key` URL.
+    pub fn parse(location: &str) -> Result<Self, S3Error> {
+        let invalid = || S3Error::InvalidLocation(location.to_string());
+
+        let url = Url::parse(location).map_err(|_| invalid())?;
+        if url.scheme() != "s3"
+            || url.port().is_some()
+            || !url.username().is_empty()
+            || url.password().is_some()
+            || url.query().is_some()
+            || url.fragment().is_some()
+        {
+            return Err(invalid());
+        }
+        let bucket = url.host_str().ok_or_else(invalid)?;
+        if !is_valid_bucket(bucket) {
+            return Err(invalid());
+        }
+        let key = Path::from_url_path(url.path()).map_err(|_| invalid())?;
+
+        Ok(Self::new(bucket, key))
+    }
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/userguide/bucketnamingrules.html>
+fn is_valid_bucket(bucket: &str) -> bool {
+    (3..=63).contains(&bucket.len())
+        && bucket
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-')
+        && bucket.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && bucket.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+impl FromStr for S3Location {
+    type Err = S3Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for S3Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "s3://{}/{}",
+            self.bucket,
+            utf8_percent_encode(self.key.as_ref(), &STRICT_PATH_ENCODE_SET)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::S3Location;
+    use crate::path::Path;
+
+    #[test]
+    fn parse_s3_url() {
+        let location = S3Location::parse("s3://my-bucket/a/b/c.txt").unwrap();
+        assert_eq!(location.bucket, "my-bucket");
+        assert_eq!(location.key, Path::from_iter(["a", "b", "c.txt"]));
+        assert_eq!(location.to_string(), "s3://my-bucket/a/b/c.txt");
+
+        let location: S3Location = "s3://my-bucket/with%20space".parse().unwrap();
+        assert_eq!(location.key.as_ref(), "with space");
+        assert_eq!(S3Location::parse(&location.to_string()).unwrap(), location);
+    }
+
+    #[test]
+    fn reject_malformed_url() {
+        assert!(S3Location::parse("my-bucket/a/b").is_err());
+        assert!(S3Location::parse("https://my-bucket/a/b").is_err());
+        assert!(S3Location::parse("s3:///a/b").is_err());
+        assert!(S3Location::parse("s3://My_Bucket/a/b").is_err());
+        assert!(S3Location::parse("s3://my-bucket/a//b").is_err());
+        assert!(S3Location::parse("s3://my-bucket/a/b?versionId=1").is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::{
+    header::{ETAG, IF_MATCH},
+    HeaderMap, Method, Request, StatusCode,
+};
+use http_body_util::{BodyExt, Empty};
+
+use super::{
+    copy::METADATA_PREFIX,
+    fs::{is_missing, AmazonS3},
+    options::WriteOptions,
+    S3Error,
+};
+use crate::{fs::FileMeta, path::Path, remotes::http::HttpError, Error};
+
+/// The user metadata of a lease object holding when it expires, in milliseconds since the epoch.
+const EXPIRES_KEY: &str = "lease-expires";
+/// The user metadata of a lease object naming its holder.
+const OWNER_KEY: &str = "lease-owner";
+
+/// An advisory lock shared by processes on different machines through a lease object in S3.
+///
+/// The lock is taken by writing the lease object with a conditional `PUT` that only succeeds if
+/// there is none yet, so that of the contenders a single one takes it. The lease expires after
+/// a `ttl` unless renewed with [`S3Lock::renew`], after which another process may take the lock
+/// over by replacing the lease object, again conditionally on the one it found. Releasing the
+/// lock deletes the lease object, unless it was taken over in the meantime.
+///
+/// Expiry is judged by the clock of each process, the `ttl` must be much longer than their drift.
+/// Dropping a held lock doesn't release it, it is only taken over once the lease expires.
+pub struct S3Lock {
+    fs: AmazonS3,
+    path: Path,
+    owner: String,
+    ttl: Duration,
+    /// The ETag of the lease object this lock wrote, while it holds it.
+    etag: Option<String>,
+}
+
+impl S3Lock {
+    /// A lock on the lease object at `path`, taken in the name of `owner`, e.g. the host and
+    /// process of the holder, recorded with the lease for diagnostics.
+    pub fn new(fs: AmazonS3, path: Path, owner: impl Into<String>, ttl: Duration) -> Self {
+        Self {
+            fs,
+            path,
+            owner: owner.into(),
+            ttl,
+            etag: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether this lock holds the lease, as of its last acquisition or renewal.
+    pub fn is_held(&self) -> bool {
+        self.etag.is_some()
+    }
+
+    /// Tries to take the lock, returning whether it did. It fails to while another process holds
+    /// an unexpired lease. Trying to take a lock already held renews it.
+    pub async fn try_acquire(&mut self) -> Result<bool, Error> {
+        if self.is_held() {
+            return self.renew().await.map(|()| true);
+        }
+
+        let (result, _) = self
+            .fs
+            .put_if_not_exists(&self.path, self.owner.clone().into_bytes(), self.lease())
+            .await;
+        if let Some(meta) = result? {
+            return Ok(self.hold(meta));
+        }
+
+        let headers = match self.fs.head_object(&self.path).await {
+            Ok(headers) => headers,
+            // released in the meantime, the next try may take it
+            Err(e) if is_missing(&e) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if !self.expired(&headers)? {
+            return Ok(false);
+        }
+        let etag = headers
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .ok_or_else(|| Error::Other("missing etag header".into()))?;
+        // of the processes taking the expired lease over, the first to replace it wins
+        let (result, _) = self
+            .fs
+            .write_file_with(
+                &self.path,
+                self.owner.clone().into_bytes(),
+                self.lease().if_match(etag),
+            )
+            .await;
+        match result {
+            Ok(meta) => Ok(self.hold(meta)),
+            Err(Error::S3Error(S3Error::PreconditionFailed(_))) => Ok(false),
+            Err(e) if is_missing(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Extends the lease by another `ttl`. Fails if the lock isn't held, or if its lease expired
+    /// and was taken over, after which the lock is no longer held.
+    pub async fn renew(&mut self) -> Result<(), Error> {
+        let etag = self
+            .etag
+            .take()
+            .ok_or_else(|| Error::Other(format!("the lock \"{}\" isn't held", self.path).into()))?;
+        let (result, _) = self
+            .fs
+            .write_file_with(
+                &self.path,
+                self.owner.clone().into_bytes(),
+                self.lease().if_match(etag),
+            )
+            .await;
+        self.hold(result?);
+        Ok(())
+    }
+
+    /// Releases the lock by deleting the lease object, leaving it be if it was taken over.
+    pub async fn release(&mut self) -> Result<(), Error> {
+        let etag = match self.etag.take() {
+            Some(etag) => etag,
+            None => return Ok(()),
+        };
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri(self.fs.object_url(&self.path))
+            .header(IF_MATCH, etag)
+            .body(Empty::<Bytes>::new())
+            .map_err(|e| S3Error::from(HttpError::from(e)))?;
+        let response = self.fs.send(request).await?;
+
+        // the lease was taken over, or removed, it isn't this lock's to delete anymore
+        if matches!(
+            response.status(),
+            StatusCode::PRECONDITION_FAILED | StatusCode::NOT_FOUND
+        ) {
+            return Ok(());
+        }
+        if !response.status().is_success() {
+            return Err(S3Error::from(HttpError::HttpNotSuccess {
+                status: response.status(),
+                body: String::from_utf8_lossy(
+                    &response
+                        .collect()
+                        .await
+                        .map_err(|e| Error::Other(e.into()))?
+                        .to_bytes(),
+                )
+                .to_string(),
+            })
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// The options of a write of the lease object, expiring a `ttl` from now.
+    fn lease(&self) -> WriteOptions {
+        let expires = now() + self.ttl;
+        WriteOptions::default()
+            .metadata(OWNER_KEY, self.owner.clone())
+            .metadata(EXPIRES_KEY, expires.as_millis().to_string())
+    }
+
+    fn hold(&mut self, meta: FileMeta) -> bool {
+        self.etag = meta.etag;
+        self.is_held()
+    }
+
+    /// Whether the lease object answered with `headers` expired.
+    fn expired(&self, headers: &HeaderMap) -> Result<bool, Error> {
+        let expires = headers
+            .get(format!("{}{}", METADATA_PREFIX, EXPIRES_KEY))
+            .and_then(|expires| expires.to_str().ok())
+            .and_then(|expires| expires.parse::<u64>().ok())
+            .ok_or_else(|| {
+                Error::Other(format!("\"{}\" isn't the object of a lease", self.path).into())
+            })?;
+        Ok(Duration::from_millis(expires) <= now())
+    }
+}
+
+/// The time elapsed since the epoch.
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn contenders_for_a_lease_get_a_single_holder() {
+        use std::time::Duration;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, S3Error, S3Lock},
+            Error,
+        };
+
+        let mock = MockS3::new();
+        let path = Path::from("locks/job");
+        let ttl = Duration::from_secs(60);
+        let mut first = S3Lock::new(mock.fs(), path.clone(), "first", ttl);
+        let mut second = S3Lock::new(mock.fs(), path.clone(), "second", ttl);
+
+        let (first_took, second_took) = tokio::join!(first.try_acquire(), second.try_acquire());
+        assert!(first_took.unwrap() ^ second_took.unwrap());
+        let (holder, other) = match first.is_held() {
+            true => (&mut first, &mut second),
+            false => (&mut second, &mut first),
+        };
+        assert!(!other.try_acquire().await.unwrap());
+        assert!(other.renew().await.is_err());
+        holder.renew().await.unwrap();
+
+        holder.release().await.unwrap();
+        assert!(mock.object("locks/job").is_none());
+        assert!(other.try_acquire().await.unwrap());
+        other.release().await.unwrap();
+
+        // an expired lease is taken over, and can't be renewed or released by its former holder
+        let mut expired = S3Lock::new(mock.fs(), path.clone(), "expired", Duration::ZERO);
+        assert!(expired.try_acquire().await.unwrap());
+        let mut taker = S3Lock::new(mock.fs(), path.clone(), "taker", ttl);
+        assert!(taker.try_acquire().await.unwrap());
+        assert!(matches!(
+            expired.renew().await,
+            Err(Error::S3Error(S3Error::PreconditionFailed(_)))
+        ));
+        assert!(!expired.is_held());
+        expired.release().await.unwrap();
+        let lease = mock.object("locks/job").unwrap();
+        assert_eq!(lease.body, "taker");
+        assert!(taker.is_held());
+    }
+}
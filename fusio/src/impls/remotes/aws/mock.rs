@@ -0,0 +1,1045 @@
+//! An in-memory S3 answering the requests of [`AmazonS3`], so that the S3 backend can be tested
+//! without network access or credentials.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    ops::Bound,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    vec,
+};
+
+use bytes::Bytes;
+use futures_util::stream;
+use http::{
+    header::{
+        AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, HOST,
+        IF_MATCH, IF_NONE_MATCH, LOCATION, RANGE,
+    },
+    HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri,
+};
+use http_body::{Body, Frame};
+use http_body_util::{BodyExt, Full, StreamBody};
+use percent_encoding::percent_decode_str;
+use quick_xml::escape::escape;
+use ring::digest;
+use serde::Deserialize;
+use url::form_urlencoded;
+
+use super::{
+    acl::ACL_HEADER,
+    credential::AwsCredential,
+    fs::{AmazonS3, AmazonS3Inner},
+    options::S3Options,
+    redirect::{BUCKET_REGION_HEADER, DEFAULT_MAX_REDIRECTS},
+    CHECKSUM_HEADER,
+};
+use crate::{
+    error::BoxedError,
+    remotes::{
+        encoding::base64_encode,
+        http::{DynHttpClient, HttpClient, HttpError},
+    },
+};
+
+pub(crate) const MOCK_ENDPOINT: &str = "http://fusio-test.s3.mock";
+
+const COPY_SOURCE: &str = "x-amz-copy-source";
+const COPY_SOURCE_RANGE: &str = "x-amz-copy-source-range";
+const COPY_SOURCE_IF_MATCH: &str = "x-amz-copy-source-if-match";
+const METADATA_DIRECTIVE: &str = "x-amz-metadata-directive";
+const TAGGING_DIRECTIVE: &str = "x-amz-tagging-directive";
+const TAGGING: &str = "x-amz-tagging";
+const OWNER_ID: &str = "mock-owner";
+const METADATA_TOKEN_HEADER: &str = "x-aws-ec2-metadata-token";
+const METADATA_TOKEN: &str = "mock-metadata-token";
+const INSTANCE_ROLE: &str = "mock-role";
+
+#[derive(Debug, Clone)]
+pub(crate) struct MockObject {
+    pub(crate) body: Bytes,
+    pub(crate) etag: String,
+    /// The headers of the request that wrote the object.
+    pub(crate) headers: HeaderMap,
+}
+
+/// A multipart upload in progress, with the headers it was initiated with.
+struct Upload {
+    headers: HeaderMap,
+    parts: BTreeMap<usize, Bytes>,
+}
+
+#[derive(Default)]
+struct State {
+    objects: BTreeMap<String, MockObject>,
+    uploads: HashMap<String, Upload>,
+    requests: Vec<(Method, String, StatusCode)>,
+    version: u64,
+    /// How many keys a page of a listing holds at most, 1000 like S3 by default.
+    list_page_size: Option<usize>,
+    /// Keys whose requests are redirected, with the location they are redirected to.
+    redirects: HashMap<String, String>,
+    /// Whether the parts uploaded are corrupted on their way.
+    corrupt_parts: bool,
+    /// The credential of the instance, served by the metadata service when set.
+    instance_credential: Option<AwsCredential>,
+    /// Whether responses are sent in chunks without a `Content-Length`.
+    chunked: bool,
+    /// The region of the bucket, requests signed for another are redirected to it when set.
+    bucket_region: Option<String>,
+    /// The pages of the listings of versions, as the mock doesn't keep versions itself.
+    version_pages: Vec<String>,
+    /// The page the next listing of versions with a key marker is answered with.
+    next_version_page: usize,
+    /// The mocks of the other buckets copies may take their source from, by name.
+    buckets: HashMap<String, MockS3>,
+    /// How many of the next requests for a page of a listing after the first one fail.
+    failing_pages: usize,
+    /// How many bytes of objects were sent in the responses to `GET`s.
+    bytes_served: u64,
+    /// How many connections the client opened, as a client keeping idle connections in a pool
+    /// would: a request takes an idle one if any, and the response gives it back once dropped.
+    connections: usize,
+    idle_connections: usize,
+}
+
+impl State {
+    fn next_etag(&mut self) -> String {
+        self.version += 1;
+        format!("\"{:x}\"", self.version)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct MockS3 {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockS3 {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an [`AmazonS3`] whose requests are all answered by this mock.
+    pub(crate) fn fs(&self) -> AmazonS3 {
+        self.fs_with(self.options())
+    }
+
+    /// Returns the [`S3Options`] of [`MockS3::fs`], to be adjusted and passed to
+    /// [`MockS3::fs_with`].
+    pub(crate) fn options(&self) -> S3Options {
+        S3Options {
+            endpoint: MOCK_ENDPOINT.into(),
+            bucket: "fusio-test".into(),
+            region: "us-east-1".into(),
+            credential: Some(AwsCredential {
+                key_id: "key".into(),
+                secret_key: "secret".into(),
+                token: None,
+            }),
+            instance_credentials: None,
+            sign_payload: false,
+            sign_payload_threshold: None,
+            checksum: false,
+            append_emulation: false,
+            expected_bucket_owner: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+
+    pub(crate) fn fs_with(&self, options: S3Options) -> AmazonS3 {
+        AmazonS3 {
+            inner: Arc::new(AmazonS3Inner::new(
+                options,
+                Box::new(self.clone()) as Box<dyn DynHttpClient>,
+            )),
+        }
+    }
+
+    pub(crate) fn put_object(&self, key: &str, body: impl Into<Bytes>) -> MockObject {
+        let mut state = self.state.lock().unwrap();
+        let object = MockObject {
+            body: body.into(),
+            etag: state.next_etag(),
+            headers: HeaderMap::new(),
+        };
+        state.objects.insert(key.into(), object.clone());
+        object
+    }
+
+    /// Sets the header `name` of the object at `key`, e.g. `x-amz-storage-class` to archive it.
+    pub(crate) fn set_header(&self, key: &str, name: &'static str, value: &str) {
+        let mut state = self.state.lock().unwrap();
+        let object = state.objects.get_mut(key).unwrap();
+        object
+            .headers
+            .insert(name, HeaderValue::from_str(value).unwrap());
+    }
+
+    /// Lists at most `page_size` keys per request, so that listings take several pages.
+    pub(crate) fn set_list_page_size(&self, page_size: usize) {
+        self.state.lock().unwrap().list_page_size = Some(page_size);
+    }
+
+    /// Answers the requests for `key` with a `307 Temporary Redirect` to `location`.
+    pub(crate) fn redirect(&self, key: &str, location: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .redirects
+            .insert(key.into(), location.into());
+    }
+
+    /// Flips a bit of every part uploaded from now on before it is received, as if it was
+    /// corrupted on the wire.
+    pub(crate) fn corrupt_parts(&self) {
+        self.state.lock().unwrap().corrupt_parts = true;
+    }
+
+    /// Also answers the requests of the EC2 instance metadata service, handing out `credential`
+    /// as the one of the instance.
+    #[cfg(all(feature = "tokio-http", not(feature = "completion-based")))]
+    pub(crate) fn instance_credential(&self, credential: AwsCredential) {
+        self.state.lock().unwrap().instance_credential = Some(credential);
+    }
+
+    /// Puts the bucket in `region`, answering the requests signed for another region with a
+    /// `301 Moved Permanently` telling it, like S3 does.
+    pub(crate) fn set_bucket_region(&self, region: &str) {
+        self.state.lock().unwrap().bucket_region = Some(region.into());
+    }
+
+    /// Forgets the multipart uploads in progress, as if they expired or were aborted by a
+    /// lifecycle rule.
+    pub(crate) fn expire_uploads(&self) {
+        self.state.lock().unwrap().uploads.clear();
+    }
+
+    /// Sends the responses from now on in chunks without a `Content-Length`, like the
+    /// `Transfer-Encoding: chunked` responses of some S3-compatible servers.
+    pub(crate) fn chunked(&self) {
+        self.state.lock().unwrap().chunked = true;
+    }
+
+    /// Answers the listings of versions with the `ListVersionsResult` documents of `pages`, the
+    /// first one without a key marker and the following ones in turn.
+    pub(crate) fn set_version_pages(&self, pages: &[&str]) {
+        self.state.lock().unwrap().version_pages =
+            pages.iter().map(|page| page.to_string()).collect();
+    }
+
+    /// Serves the copies whose source is in `bucket` from the objects of `mock`, as if both
+    /// buckets were in the same S3.
+    pub(crate) fn add_bucket(&self, bucket: &str, mock: &MockS3) {
+        self.state
+            .lock()
+            .unwrap()
+            .buckets
+            .insert(bucket.into(), mock.clone());
+    }
+
+    /// Answers the next `count` requests for a page of a listing, other than its first page,
+    /// with a `503 Slow Down`, as S3 throttling the listing would.
+    pub(crate) fn fail_next_pages(&self, count: usize) {
+        self.state.lock().unwrap().failing_pages = count;
+    }
+
+    /// Returns how many bytes of objects were sent in the responses to `GET`s so far, e.g. to
+    /// check that a ranged read didn't download the whole object.
+    #[cfg(feature = "encryption")]
+    pub(crate) fn bytes_served(&self) -> u64 {
+        self.state.lock().unwrap().bytes_served
+    }
+
+    /// Returns how many multipart uploads are in progress, neither completed nor aborted.
+    pub(crate) fn uploads(&self) -> usize {
+        self.state.lock().unwrap().uploads.len()
+    }
+
+    /// Returns how many clones of this mock there are besides itself, e.g. the clients of the
+    /// file systems it answers, to check that they were dropped.
+    pub(crate) fn clients(&self) -> usize {
+        Arc::strong_count(&self.state) - 1
+    }
+
+    /// Returns how many connections were opened to the mock so far.
+    pub(crate) fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    /// Returns how many of the connections opened are idle, in the pool of the client.
+    pub(crate) fn idle_connections(&self) -> usize {
+        self.state.lock().unwrap().idle_connections
+    }
+
+    pub(crate) fn object(&self, key: &str) -> Option<MockObject> {
+        self.state.lock().unwrap().objects.get(key).cloned()
+    }
+
+    /// Returns the status codes of the requests with `method` received so far, in order.
+    pub(crate) fn requests(&self, method: Method) -> Vec<StatusCode> {
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|(m, _, _)| *m == method)
+            .map(|(_, _, status)| *status)
+            .collect()
+    }
+
+    fn handle(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) -> Response<Full<Bytes>> {
+        let mut state = self.state.lock().unwrap();
+        let key = percent_decode_str(uri.path().trim_start_matches('/'))
+            .decode_utf8_lossy()
+            .into_owned();
+        let query = uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let body = match state.corrupt_parts && query.contains_key("partNumber") {
+            true => corrupt(body),
+            false => body,
+        };
+
+        if let Some(response) = instance_metadata(&state, method, &key, headers)
+            .or_else(|| intercept(&state, &key, method, uri, headers, &body))
+        {
+            state
+                .requests
+                .push((method.clone(), key, response.status()));
+            return response;
+        }
+
+        let response = match *method {
+            Method::GET if query.contains_key("continuation-token") && state.failing_pages > 0 => {
+                state.failing_pages -= 1;
+                response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    &[],
+                    "<Error><Code>SlowDown</Code><Message>Please reduce your request \
+                     rate.</Message></Error>",
+                )
+            }
+            Method::GET if query.contains_key("list-type") => {
+                let prefix = query.get("prefix").map(String::as_str).unwrap_or_default();
+                // the continuation token is the last key of the previous page
+                let start = match query.get("continuation-token") {
+                    Some(token) => Bound::Excluded(token.clone()),
+                    None => Bound::Included(prefix.to_string()),
+                };
+                let max_keys = query
+                    .get("max-keys")
+                    .map(|max_keys| max_keys.parse::<usize>().unwrap());
+                let page_size = state
+                    .list_page_size
+                    .unwrap_or(1000)
+                    .min(max_keys.unwrap_or(usize::MAX));
+                let delimiter = query.get("delimiter").map(String::as_str);
+                // a token ending with the delimiter is a common prefix, whose keys are all listed
+                let rolled_up = query
+                    .get("continuation-token")
+                    .filter(|token| delimiter.is_some_and(|delimiter| token.ends_with(delimiter)));
+                let mut keys = state
+                    .objects
+                    .range((start, Bound::Unbounded))
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .filter(|(key, _)| !rolled_up.is_some_and(|token| key.starts_with(token)))
+                    .peekable();
+                let mut xml = String::from("<ListBucketResult>");
+                let mut last_key = None::<String>;
+                let mut listed = 0;
+                while listed < page_size {
+                    let Some((key, object)) = keys.next() else {
+                        break;
+                    };
+                    listed += 1;
+                    let common_prefix = delimiter.and_then(|delimiter| {
+                        let rest = &key[prefix.len()..];
+                        rest.find(delimiter)
+                            .map(|end| key[..prefix.len() + end + delimiter.len()].to_string())
+                    });
+                    if let Some(common_prefix) = common_prefix {
+                        xml.push_str(&format!(
+                            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                            escape(&common_prefix)
+                        ));
+                        while keys
+                            .next_if(|(key, _)| key.starts_with(&common_prefix))
+                            .is_some()
+                        {}
+                        last_key = Some(common_prefix);
+                        continue;
+                    }
+                    xml.push_str(&format!(
+                        "<Contents><Key>{}</Key><Size>{}</Size><LastModified>2024-01-01T00:00:00.\
+                         000Z</LastModified><ETag>{}</ETag><StorageClass>{}</StorageClass></\
+                         Contents>",
+                        escape(key),
+                        object.body.len(),
+                        escape(&object.etag),
+                        object
+                            .headers
+                            .get("x-amz-storage-class")
+                            .map_or("STANDARD", |class| class.to_str().unwrap()),
+                    ));
+                    last_key = Some(key.clone());
+                }
+                if let (Some(key), Some(_)) = (last_key, keys.peek()) {
+                    xml.push_str(&format!(
+                        "<NextContinuationToken>{}</NextContinuationToken>",
+                        escape(&key)
+                    ));
+                }
+                xml.push_str("</ListBucketResult>");
+                response(StatusCode::OK, &[], xml)
+            }
+            Method::GET if query.contains_key("versions") => {
+                let page = match query.contains_key("key-marker") {
+                    true => state.next_version_page,
+                    false => 0,
+                };
+                state.next_version_page = page + 1;
+                let xml = state
+                    .version_pages
+                    .get(page)
+                    .cloned()
+                    .unwrap_or_else(|| "<ListVersionsResult/>".to_string());
+                response(StatusCode::OK, &[], xml)
+            }
+            Method::GET if query.contains_key("acl") => match state.objects.get(&key) {
+                None => not_found(method),
+                Some(object) => response(StatusCode::OK, &[], acl_policy(&object.headers)),
+            },
+            Method::PUT if query.contains_key("acl") => match state.objects.get_mut(&key) {
+                None => not_found(method),
+                Some(object) => {
+                    object
+                        .headers
+                        .insert(ACL_HEADER, headers[ACL_HEADER].clone());
+                    response(StatusCode::OK, &[], Bytes::new())
+                }
+            },
+            Method::GET | Method::HEAD => match state.objects.get(&key) {
+                None => not_found(method),
+                Some(object)
+                    if headers
+                        .get(IF_NONE_MATCH)
+                        .is_some_and(|etag| etag.as_bytes() == object.etag.as_bytes()) =>
+                {
+                    response(
+                        StatusCode::NOT_MODIFIED,
+                        &[(ETAG, object.etag.clone())],
+                        Bytes::new(),
+                    )
+                }
+                Some(object) => {
+                    let mut response_headers = vec![(ETAG, object.etag.clone())];
+                    for (name, value) in object.headers.iter() {
+                        if *name == CONTENT_TYPE
+                            || *name == CONTENT_ENCODING
+                            || name.as_str().starts_with("x-amz-meta-")
+                            || matches!(name.as_str(), "x-amz-storage-class" | "x-amz-restore")
+                            || (name.as_str().starts_with("x-amz-checksum-")
+                                && headers.contains_key("x-amz-checksum-mode"))
+                        {
+                            response_headers
+                                .push((name.clone(), value.to_str().unwrap().to_string()));
+                        }
+                    }
+                    let range = headers
+                        .get(RANGE)
+                        .map(|range| parse_range(range.to_str().unwrap(), object.body.len()));
+                    let (status, body) = match range {
+                        Some(Some((start, end))) => {
+                            response_headers.push((
+                                CONTENT_RANGE,
+                                format!("bytes {}-{}/{}", start, end - 1, object.body.len()),
+                            ));
+                            (StatusCode::PARTIAL_CONTENT, object.body.slice(start..end))
+                        }
+                        Some(None) => (StatusCode::RANGE_NOT_SATISFIABLE, Bytes::new()),
+                        None => (StatusCode::OK, object.body.clone()),
+                    };
+                    response_headers.push((CONTENT_LENGTH, body.len().to_string()));
+                    let body = if *method == Method::HEAD {
+                        Bytes::new()
+                    } else {
+                        body
+                    };
+                    state.bytes_served += body.len() as u64;
+                    response(status, &response_headers, body)
+                }
+            },
+            Method::PUT => match (query.get("uploadId"), query.get("partNumber")) {
+                (Some(upload_id), Some(part)) => {
+                    let part = part.parse::<usize>().unwrap();
+                    let etag = state.next_etag();
+                    // an `UploadPartCopy` takes its content from a range of another object
+                    let (body, xml) = match headers.get(COPY_SOURCE) {
+                        Some(source) => {
+                            let source = copy_source(&state, source).unwrap().body;
+                            let range = headers[COPY_SOURCE_RANGE].to_str().unwrap();
+                            let (start, end) = parse_range(range, source.len()).unwrap();
+                            (
+                                source.slice(start..end),
+                                format!(
+                                    "<CopyPartResult><ETag>{}</ETag></CopyPartResult>",
+                                    escape(&etag)
+                                ),
+                            )
+                        }
+                        None => (body, String::new()),
+                    };
+                    match state.uploads.get_mut(upload_id) {
+                        Some(upload) => {
+                            upload.parts.insert(part, body);
+                            response(StatusCode::OK, &[(ETAG, etag)], xml)
+                        }
+                        None => no_such_upload(),
+                    }
+                }
+                _ => match headers.get(COPY_SOURCE) {
+                    Some(source) => {
+                        let source = copy_source(&state, source);
+                        match source {
+                            Some(source) => {
+                                let object = MockObject {
+                                    etag: state.next_etag(),
+                                    headers: copied_headers(&source.headers, headers),
+                                    body: source.body,
+                                };
+                                let xml = format!(
+                                    "<CopyObjectResult><ETag>{}</ETag></CopyObjectResult>",
+                                    escape(&object.etag)
+                                );
+                                state.objects.insert(key.clone(), object);
+                                response(StatusCode::OK, &[], xml)
+                            }
+                            None => not_found(method),
+                        }
+                    }
+                    None => {
+                        let etag = state.next_etag();
+                        state.objects.insert(
+                            key.clone(),
+                            MockObject {
+                                body,
+                                etag: etag.clone(),
+                                headers: headers.clone(),
+                            },
+                        );
+                        response(StatusCode::OK, &[(ETAG, etag)], Bytes::new())
+                    }
+                },
+            },
+            Method::POST if query.contains_key("uploads") => {
+                let upload_id = state.next_etag().trim_matches('"').to_string();
+                state.uploads.insert(
+                    upload_id.clone(),
+                    Upload {
+                        headers: headers.clone(),
+                        parts: BTreeMap::new(),
+                    },
+                );
+                response(
+                    StatusCode::OK,
+                    &[],
+                    format!(
+                        "<InitiateMultipartUploadResult><UploadId>{}</UploadId></\
+                         InitiateMultipartUploadResult>",
+                        upload_id
+                    ),
+                )
+            }
+            Method::POST if query.contains_key("uploadId") => {
+                let completed: CompleteMultipartUpload =
+                    quick_xml::de::from_reader(body.as_ref()).unwrap();
+                let part_numbers = completed
+                    .part
+                    .iter()
+                    .map(|part| part.part_number)
+                    .collect::<Vec<_>>();
+                match state.uploads.remove(&query["uploadId"]) {
+                    // like S3, which fails the whole completion
+                    Some(_) if !part_numbers.windows(2).all(|pair| pair[0] < pair[1]) => response(
+                        StatusCode::BAD_REQUEST,
+                        &[],
+                        "<Error><Code>InvalidPartOrder</Code><Message>The list of parts was not \
+                         in ascending order.</Message></Error>",
+                    ),
+                    // the checksums listed must be the ones the parts were uploaded with
+                    Some(upload)
+                        if !completed.part.iter().all(|part| {
+                            part.checksum_sha256.as_ref().is_none_or(|checksum| {
+                                upload
+                                    .parts
+                                    .get(&part.part_number)
+                                    .is_some_and(|body| *checksum == sha256(body))
+                            })
+                        }) =>
+                    {
+                        response(
+                            StatusCode::BAD_REQUEST,
+                            &[],
+                            "<Error><Code>InvalidPart</Code><Message>The checksum of a part \
+                             doesn't match.</Message></Error>",
+                        )
+                    }
+                    Some(mut upload) => {
+                        let body = part_numbers
+                            .iter()
+                            .flat_map(|part_number| upload.parts.remove(part_number))
+                            .flatten()
+                            .collect::<Bytes>();
+                        let etag = state.next_etag();
+                        state.objects.insert(
+                            key.clone(),
+                            MockObject {
+                                body,
+                                etag: etag.clone(),
+                                headers: upload.headers,
+                            },
+                        );
+                        response(
+                            StatusCode::OK,
+                            &[],
+                            format!(
+                                "<CompleteMultipartUploadResult><ETag>{}</ETag></\
+                                 CompleteMultipartUploadResult>",
+                                escape(&etag)
+                            ),
+                        )
+                    }
+                    None => no_such_upload(),
+                }
+            }
+            Method::DELETE if query.contains_key("uploadId") => {
+                match state.uploads.remove(&query["uploadId"]) {
+                    Some(_) => response(StatusCode::NO_CONTENT, &[], Bytes::new()),
+                    None => no_such_upload(),
+                }
+            }
+            Method::DELETE => {
+                state.objects.remove(&key);
+                response(StatusCode::NO_CONTENT, &[], Bytes::new())
+            }
+            _ => response(StatusCode::METHOD_NOT_ALLOWED, &[], Bytes::new()),
+        };
+
+        state
+            .requests
+            .push((method.clone(), key, response.status()));
+        response
+    }
+}
+
+/// Answers requests signed for another host than the one they are sent to, requests whose body
+/// doesn't match their checksum, and requests to redirected keys, before they reach the objects.
+fn intercept(
+    state: &State,
+    key: &str,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Option<Response<Full<Bytes>>> {
+    // clients only send the length of non-empty bodies by themselves
+    if matches!(*method, Method::PUT | Method::POST)
+        && body.is_empty()
+        && !headers.contains_key(CONTENT_LENGTH)
+    {
+        return Some(response(
+            StatusCode::LENGTH_REQUIRED,
+            &[],
+            "<Error><Code>MissingContentLength</Code><Message>You must provide the Content-Length \
+             HTTP header.</Message></Error>",
+        ));
+    }
+    let region = state.bucket_region.as_deref().unwrap_or("us-east-1");
+    if signed_region(headers).is_some_and(|signed| signed != region) {
+        let body = match *method {
+            Method::HEAD => "",
+            _ => {
+                "<Error><Code>PermanentRedirect</Code><Message>The bucket you are attempting to \
+                 access must be addressed using the specified endpoint.</Message></Error>"
+            }
+        };
+        return Some(response(
+            StatusCode::MOVED_PERMANENTLY,
+            &[(HeaderName::from_static(BUCKET_REGION_HEADER), region.into())],
+            body,
+        ));
+    }
+    // a `HEAD` of the bucket itself
+    if key.is_empty() && *method == Method::HEAD {
+        return Some(response(
+            StatusCode::OK,
+            &[(HeaderName::from_static(BUCKET_REGION_HEADER), region.into())],
+            Bytes::new(),
+        ));
+    }
+    let host = headers.get(HOST).and_then(|host| host.to_str().ok());
+    if host.is_some() && host != uri.authority().map(|authority| authority.as_str()) {
+        return Some(response(
+            StatusCode::FORBIDDEN,
+            &[],
+            "<Error><Code>SignatureDoesNotMatch</Code><Message>The request signature we \
+             calculated does not match the signature you provided.</Message></Error>",
+        ));
+    }
+    if headers
+        .get(CHECKSUM_HEADER)
+        .is_some_and(|checksum| checksum.as_bytes() != sha256(body).as_bytes())
+    {
+        return Some(response(
+            StatusCode::BAD_REQUEST,
+            &[],
+            "<Error><Code>BadDigest</Code><Message>The SHA256 you specified did not match the \
+             calculated checksum.</Message></Error>",
+        ));
+    }
+    // the condition on the source of a copy
+    if let (Some(source), Some(etag)) =
+        (headers.get(COPY_SOURCE), headers.get(COPY_SOURCE_IF_MATCH))
+    {
+        if copy_source(state, source)
+            .is_some_and(|source| etag.as_bytes() != source.etag.as_bytes())
+        {
+            return Some(precondition_failed());
+        }
+    }
+    // the conditions of writes and deletions, those of reads are answered with the objects
+    if matches!(*method, Method::PUT | Method::DELETE) {
+        let object = state.objects.get(key);
+        if let Some(etag) = headers.get(IF_MATCH) {
+            match object {
+                None => return Some(not_found(method)),
+                Some(object) if etag.as_bytes() != object.etag.as_bytes() => {
+                    return Some(precondition_failed())
+                }
+                Some(_) => {}
+            }
+        }
+        if headers
+            .get(IF_NONE_MATCH)
+            .is_some_and(|etag| etag.as_bytes() == b"*")
+            && object.is_some()
+        {
+            return Some(precondition_failed());
+        }
+    }
+    state.redirects.get(key).map(|location| {
+        response(
+            StatusCode::TEMPORARY_REDIRECT,
+            &[(LOCATION, location.clone())],
+            Bytes::new(),
+        )
+    })
+}
+
+/// Answers the requests of the instance metadata service for the credential of the instance,
+/// with a session token required like IMDSv2.
+fn instance_metadata(
+    state: &State,
+    method: &Method,
+    key: &str,
+    headers: &HeaderMap,
+) -> Option<Response<Full<Bytes>>> {
+    let credential = state.instance_credential.as_ref()?;
+    let path = key.strip_prefix("latest/")?;
+    if *method == Method::PUT && path == "api/token" {
+        return Some(response(StatusCode::OK, &[], METADATA_TOKEN));
+    }
+    let role = path.strip_prefix("meta-data/iam/security-credentials/")?;
+    if headers
+        .get(METADATA_TOKEN_HEADER)
+        .is_none_or(|token| token != METADATA_TOKEN)
+    {
+        return Some(response(StatusCode::UNAUTHORIZED, &[], ""));
+    }
+    Some(match role {
+        "" => response(StatusCode::OK, &[], INSTANCE_ROLE),
+        INSTANCE_ROLE => response(
+            StatusCode::OK,
+            &[],
+            format!(
+                r#"{{"AccessKeyId":"{}","SecretAccessKey":"{}","Token":"{}","Expiration":"{}"}}"#,
+                credential.key_id,
+                credential.secret_key,
+                credential.token.as_deref().unwrap_or_default(),
+                (chrono::Utc::now() + chrono::Duration::hours(6)).to_rfc3339(),
+            ),
+        ),
+        _ => response(StatusCode::NOT_FOUND, &[], ""),
+    })
+}
+
+fn corrupt(body: Bytes) -> Bytes {
+    let mut body = body.to_vec();
+    if let Some(byte) = body.first_mut() {
+        *byte ^= 1;
+    }
+    body.into()
+}
+
+/// Returns the base64 encoded SHA256 of `body`, as S3 checksums are sent.
+fn sha256(body: &[u8]) -> String {
+    base64_encode(digest::digest(&digest::SHA256, body).as_ref())
+}
+
+fn response(
+    status: StatusCode,
+    headers: &[(HeaderName, String)],
+    body: impl Into<Bytes>,
+) -> Response<Full<Bytes>> {
+    let mut builder = Response::builder().status(status);
+    for (name, value) in headers {
+        builder = builder.header(name, value);
+    }
+    builder.body(Full::new(body.into())).unwrap()
+}
+
+/// Returns the region the request with `headers` was signed for, from the scope of its credential.
+fn signed_region(headers: &HeaderMap) -> Option<&str> {
+    let authorization = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let scope = authorization.split("Credential=").nth(1)?;
+    scope.split(['/', ',']).nth(2)
+}
+
+/// Returns the object named by an `x-amz-copy-source` header.
+fn copy_source(state: &State, source: &HeaderValue) -> Option<MockObject> {
+    let source = percent_decode_str(source.to_str().unwrap())
+        .decode_utf8_lossy()
+        .into_owned();
+    let (bucket, key) = source.trim_start_matches('/').split_once('/')?;
+    match state.buckets.get(bucket) {
+        Some(mock) => mock.object(key),
+        None => state.objects.get(key).cloned(),
+    }
+}
+
+/// Returns the headers of an object copied from one with `source` headers by a request with
+/// `headers`, following its metadata and tagging directives.
+fn copied_headers(source: &HeaderMap, headers: &HeaderMap) -> HeaderMap {
+    let replace = |directive: &str| {
+        headers
+            .get(directive)
+            .is_some_and(|directive| directive == "REPLACE")
+    };
+
+    let mut copied = match replace(METADATA_DIRECTIVE) {
+        true => headers.clone(),
+        false => source.clone(),
+    };
+    let tagging = match replace(TAGGING_DIRECTIVE) {
+        true => headers.get(TAGGING),
+        false => source.get(TAGGING),
+    };
+    copied.remove(TAGGING);
+    if let Some(tagging) = tagging {
+        copied.insert(TAGGING, tagging.clone());
+    }
+    copied
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompleteMultipartUpload {
+    #[serde(default)]
+    part: Vec<CompletedPart>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompletedPart {
+    part_number: usize,
+    #[serde(rename = "ChecksumSHA256")]
+    checksum_sha256: Option<String>,
+}
+
+/// Builds the `AccessControlPolicy` of an object from the canned ACL it was given, the owner
+/// always has full control.
+fn acl_policy(headers: &HeaderMap) -> String {
+    let mut grants = vec![(
+        format!("<ID>{}</ID>", OWNER_ID),
+        "CanonicalUser",
+        "FULL_CONTROL",
+    )];
+    let group = |name: &str| format!("<URI>http://acs.amazonaws.com/groups/global/{}</URI>", name);
+    match headers.get(ACL_HEADER).map(|acl| acl.to_str().unwrap()) {
+        Some("public-read") => grants.push((group("AllUsers"), "Group", "READ")),
+        Some("public-read-write") => {
+            grants.push((group("AllUsers"), "Group", "READ"));
+            grants.push((group("AllUsers"), "Group", "WRITE"));
+        }
+        Some("authenticated-read") => grants.push((group("AuthenticatedUsers"), "Group", "READ")),
+        _ => {}
+    }
+
+    let mut xml = format!(
+        "<AccessControlPolicy><Owner><ID>{}</ID></Owner><AccessControlList>",
+        OWNER_ID
+    );
+    for (grantee, kind, permission) in grants {
+        xml.push_str(&format!(
+            "<Grant><Grantee xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+             xsi:type=\"{}\">{}</Grantee><Permission>{}</Permission></Grant>",
+            kind, grantee, permission
+        ));
+    }
+    xml.push_str("</AccessControlList></AccessControlPolicy>");
+    xml
+}
+
+fn precondition_failed() -> Response<Full<Bytes>> {
+    response(
+        StatusCode::PRECONDITION_FAILED,
+        &[],
+        "<Error><Code>PreconditionFailed</Code><Message>At least one of the pre-conditions you \
+         specified did not hold</Message></Error>",
+    )
+}
+
+fn no_such_upload() -> Response<Full<Bytes>> {
+    response(
+        StatusCode::NOT_FOUND,
+        &[],
+        "<Error><Code>NoSuchUpload</Code><Message>The specified upload does not \
+         exist.</Message></Error>",
+    )
+}
+
+fn not_found(method: &Method) -> Response<Full<Bytes>> {
+    let body = match *method {
+        Method::HEAD => Bytes::new(),
+        _ => Bytes::from(
+            "<Error><Code>NoSuchKey</Code><Message>The specified key does not \
+             exist.</Message></Error>",
+        ),
+    };
+    response(StatusCode::NOT_FOUND, &[], body)
+}
+
+/// Returns the half-open byte range of a `Range` header, `None` when it can't be satisfied.
+fn parse_range(range: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    if start.is_empty() {
+        // a suffix range of the last bytes
+        let suffix = end.parse::<usize>().ok()?.min(len);
+        return (suffix > 0).then_some((len - suffix, len));
+    }
+    let start = start.parse::<usize>().ok()?;
+    let end = match end {
+        "" => len,
+        end => (end.parse::<usize>().ok()? + 1).min(len),
+    };
+    (start < end).then_some((start, end))
+}
+
+/// The size of the chunks of chunked responses.
+const CHUNK_SIZE: usize = 4;
+
+/// The body of a response, in one chunk or several, holding the connection it is received on.
+pub(crate) struct MockBody {
+    frames: StreamBody<stream::Iter<vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>,
+    _connection: Connection,
+}
+
+impl Body for MockBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        Pin::new(&mut self.get_mut().frames).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.frames.is_end_stream()
+    }
+}
+
+/// A connection of the client to the mock, back in the pool once dropped. It doesn't keep the
+/// mock alive, for [`MockS3::clients`] to count the clients only.
+struct Connection {
+    state: Weak<Mutex<State>>,
+}
+
+impl Connection {
+    /// Takes an idle connection, or opens one if there is none.
+    fn take(state: &Arc<Mutex<State>>) -> Self {
+        let mut guard = state.lock().unwrap();
+        match guard.idle_connections {
+            0 => guard.connections += 1,
+            _ => guard.idle_connections -= 1,
+        }
+        Self {
+            state: Arc::downgrade(state),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade() {
+            state.lock().unwrap().idle_connections += 1;
+        }
+    }
+}
+
+impl HttpClient for MockS3 {
+    type RespBody = MockBody;
+
+    async fn send_request<B>(&self, request: Request<B>) -> Result<Response<MockBody>, HttpError>
+    where
+        B: Body + Send + crate::MaybeSync + 'static,
+        B::Data: Into<Bytes>,
+        B::Error: Into<BoxedError>,
+    {
+        let connection = Connection::take(&self.state);
+        let (parts, body) = request.into_parts();
+        let body = body
+            .map_frame(|frame| frame.map_data(Into::<Bytes>::into))
+            .collect()
+            .await
+            .map_err(|e| HttpError::from(e.into() as BoxedError))?
+            .to_bytes();
+
+        let (mut parts, body) = self
+            .handle(&parts.method, &parts.uri, &parts.headers, body)
+            .into_parts();
+        let body = body.collect().await.unwrap().to_bytes();
+        let chunks = match self.state.lock().unwrap().chunked {
+            true => {
+                parts.headers.remove(CONTENT_LENGTH);
+                body.chunks(CHUNK_SIZE)
+                    .map(|chunk| Ok(Frame::data(body.slice_ref(chunk))))
+                    .collect()
+            }
+            false => vec![Ok(Frame::data(body))],
+        };
+        Ok(Response::from_parts(
+            parts,
+            MockBody {
+                frames: StreamBody::new(stream::iter(chunks)),
+                _connection: connection,
+            },
+        ))
+    }
+}
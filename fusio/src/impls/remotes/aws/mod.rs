@@ -1,15 +1,37 @@
+mod acl;
+mod copy;
 pub mod credential;
+mod endpoint;
+mod env;
 mod error;
 #[cfg(feature = "fs")]
 pub mod fs;
+mod location;
+#[cfg(feature = "fs")]
+mod lock;
+#[cfg(all(test, feature = "fs"))]
+pub(crate) mod mock;
 pub(crate) mod multipart_upload;
 pub(crate) mod options;
+mod prefetch;
+mod redirect;
+mod restore;
 mod s3;
 pub(crate) mod sign;
 pub(crate) mod writer;
 
+pub use acl::{CannedAcl, Grant, Grantee, ObjectAcl, Owner, Permission};
+pub use copy::CopyOptions;
 pub use credential::AwsCredential;
+pub use endpoint::SigningScope;
 pub use error::S3Error;
+pub use location::S3Location;
+#[cfg(feature = "fs")]
+pub use lock::S3Lock;
+pub use multipart_upload::multipart_etag;
+pub use options::{ResponseOverrides, ShutdownPolicy, WriteOptions};
+pub use prefetch::S3PrefetchReader;
+pub use restore::{RestoreStatus, RestoreTier};
 pub use s3::S3File;
 use serde::Deserialize;
 
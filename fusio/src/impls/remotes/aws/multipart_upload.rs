@@ -1,7 +1,13 @@
+use std::{
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use bytes::{Buf, Bytes};
 use http::{
     header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG},
-    Method, Request, Response,
+    HeaderMap, Method, Request, Response, StatusCode,
 };
 use http_body::Body;
 use http_body_util::{BodyExt, Empty, Full};
@@ -12,24 +18,65 @@ use super::fs::AmazonS3;
 use crate::{
     path::Path,
     remotes::{
-        aws::{sign::Sign, S3Error, S3ResponseError, STRICT_PATH_ENCODE_SET},
-        http::{BoxBody, HttpClient},
+        aws::{
+            copy::{COPY_SOURCE_HEADER, COPY_SOURCE_IF_MATCH_HEADER, COPY_SOURCE_RANGE_HEADER},
+            credential::hex_encode,
+            sign::body_checksum,
+            S3Error, S3ResponseError, CHECKSUM_HEADER, STRICT_PATH_ENCODE_SET,
+        },
+        http::BoxBody,
         serde::{
             CompleteMultipartUploadRequest, CompleteMultipartUploadRequestPart,
-            InitiateMultipartUploadResult, MultipartPart,
+            CompleteMultipartUploadResult, CopyObjectResult, InitiateMultipartUploadResult,
+            MultipartPart,
         },
     },
     Error,
 };
 
+/// Stores the token of the upload that wrote an object in its user metadata.
+pub(crate) const UPLOAD_TOKEN_HEADER: &str = "x-amz-meta-fusio-upload-token";
+
+/// Tells S3 which checksum the parts of an upload are sent with, for it to expect them on
+/// completion.
+const CHECKSUM_ALGORITHM_HEADER: &str = "x-amz-checksum-algorithm";
+
+/// Returns a token unique to this process and call, identifying an upload across retries.
+fn upload_token() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Writes an object with a single `PUT` or a multipart upload.
+///
+/// A plain `PUT` is idempotent by key, retrying it just writes the same object again. Completing
+/// a multipart upload is not: once an attempt succeeded, S3 forgets the upload and answers a retry
+/// with `NoSuchUpload`, even if the response of the first attempt was lost. The object is tagged
+/// with a token of the upload in its metadata, so that such a retry can tell its own completed
+/// upload apart from a failure.
 pub(crate) struct MultipartUpload {
     fs: AmazonS3,
     path: Path,
+    token: String,
 }
 
 impl MultipartUpload {
     pub fn new(fs: AmazonS3, path: Path) -> Self {
-        Self { fs, path }
+        Self {
+            fs,
+            path,
+            token: upload_token(),
+        }
     }
 
     async fn check_response(response: Response<BoxBody>) -> Result<Response<BoxBody>, Error> {
@@ -48,26 +95,80 @@ impl MultipartUpload {
         Ok(response)
     }
 
-    async fn send_request<B>(&self, mut request: Request<B>) -> Result<Response<BoxBody>, Error>
+    /// Checks the response to a request of the upload `upload_id`, failing with
+    /// [`S3Error::NoSuchUpload`] when S3 no longer knows it, e.g. as it expired or was aborted
+    /// by a lifecycle rule. Retrying can't succeed then, the upload has to be restarted.
+    async fn check_upload_response(
+        &self,
+        upload_id: &str,
+        response: Response<BoxBody>,
+    ) -> Result<Response<BoxBody>, Error> {
+        if response.status() != StatusCode::NOT_FOUND {
+            return Self::check_response(response).await;
+        }
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(S3Error::from)?
+            .to_bytes();
+        let error: S3ResponseError = quick_xml::de::from_reader(body.as_ref()).unwrap_or_default();
+        if error.code == "NoSuchUpload" {
+            return Err(S3Error::NoSuchUpload {
+                path: self.fs.location(&self.path).to_string(),
+                upload_id: upload_id.to_string(),
+            }
+            .into());
+        }
+        Err(Error::Other(
+            format!(
+                "failed to write to S3, HTTP status: {} content: {}",
+                StatusCode::NOT_FOUND,
+                String::from_utf8_lossy(&body)
+            )
+            .into(),
+        ))
+    }
+
+    async fn send_request<B>(&self, request: Request<B>) -> Result<Response<BoxBody>, Error>
     where
         B: Body<Data = Bytes> + Clone + Unpin + Send + Sync + 'static,
         B::Error: std::error::Error + Send + Sync + 'static,
     {
-        request
-            .sign(&self.fs.as_ref().options)
-            .await
-            .map_err(|e| Error::S3Error(S3Error::from(e)))?;
-        let response = self
-            .fs
-            .as_ref()
-            .client
-            .send_request(request)
-            .await
-            .map_err(|e| Error::S3Error(S3Error::from(e)))?;
+        let response = self.send_request_unchecked(request).await?;
         Self::check_response(response).await
     }
 
-    pub(crate) async fn upload_once<B>(&self, size: usize, body: B) -> Result<(), Error>
+    async fn send_request_unchecked<B>(
+        &self,
+        request: Request<B>,
+    ) -> Result<Response<BoxBody>, Error>
+    where
+        B: Body<Data = Bytes> + Clone + Unpin + Send + Sync + 'static,
+        B::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.fs.send(request).await.map_err(Error::S3Error)
+    }
+
+    /// Whether the object was written by this upload, i.e. an earlier attempt to complete it
+    /// succeeded.
+    async fn completed(&self) -> Result<bool, Error> {
+        let request = Request::builder()
+            .uri(self.fs.object_url(&self.path))
+            .method(Method::HEAD)
+            .body(Empty::new())
+            .map_err(|e| Error::Other(e.into()))?;
+        let response = self.send_request_unchecked(request).await?;
+
+        Ok(response.status().is_success()
+            && response
+                .headers()
+                .get(UPLOAD_TOKEN_HEADER)
+                .is_some_and(|token| token.as_bytes() == self.token.as_bytes()))
+    }
+
+    /// Puts the object at once, returning its ETag.
+    pub(crate) async fn upload_once<B>(&self, size: usize, body: B) -> Result<Option<String>, Error>
     where
         B: Body<Data = Bytes> + Clone + Unpin + Send + Sync + 'static,
         B::Error: std::error::Error + Send + Sync + 'static,
@@ -81,22 +182,40 @@ impl MultipartUpload {
             .uri(url)
             .method(Method::PUT)
             .header(CONTENT_LENGTH, size)
+            .header(UPLOAD_TOKEN_HEADER, &self.token)
             .body(body)
             .map_err(|e| Error::Other(e.into()))?;
-        let _ = self.send_request(request).await?;
+        let response = self.send_request(request).await?;
 
-        Ok(())
+        Ok(response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(String::from))
     }
 
     pub(crate) async fn initiate(&self) -> Result<String, Error> {
+        self.initiate_with(HeaderMap::new()).await
+    }
+
+    /// Initiates the upload with additional `headers`, e.g. the user metadata of the object.
+    pub(crate) async fn initiate_with(&self, headers: HeaderMap) -> Result<String, Error> {
         let url = format!(
             "{}/{}?uploads",
             self.fs.as_ref().options.endpoint,
             utf8_percent_encode(self.path.as_ref(), &STRICT_PATH_ENCODE_SET)
         );
-        let request = Request::builder()
+        let mut builder = Request::builder()
             .uri(url)
             .method(Method::POST)
+            .header(UPLOAD_TOKEN_HEADER, &self.token);
+        if self.fs.as_ref().options.checksum {
+            builder = builder.header(CHECKSUM_ALGORITHM_HEADER, "SHA256");
+        }
+        if let Some(request_headers) = builder.headers_mut() {
+            request_headers.extend(headers);
+        }
+        let request = builder
             .body(Empty::new())
             .map_err(|e| Error::Other(e.into()))?;
         let response = self.send_request(request).await?;
@@ -109,10 +228,18 @@ impl MultipartUpload {
                 .reader(),
         )
         .map_err(S3Error::from)?;
+        self.fs
+            .as_ref()
+            .uploads
+            .lock()
+            .unwrap()
+            .insert(result.upload_id.clone(), self.path.clone());
 
         Ok(result.upload_id)
     }
 
+    /// Uploads `body` as part `part_num`. With checksums enabled, the part is sent with its
+    /// SHA256 for S3 to reject it if it was corrupted on the way.
     pub(crate) async fn upload_part<B>(
         &self,
         upload_id: &str,
@@ -131,13 +258,20 @@ impl MultipartUpload {
             part_num + 1,
             utf8_percent_encode(upload_id, &STRICT_PATH_ENCODE_SET),
         );
-        let request = Request::builder()
+        let checksum = match self.fs.as_ref().options.checksum {
+            true => Some(body_checksum(body.clone()).await.map_err(S3Error::from)?),
+            false => None,
+        };
+        let mut builder = Request::builder()
             .uri(url)
             .method(Method::PUT)
-            .header(CONTENT_LENGTH, size)
-            .body(body)
-            .map_err(|e| Error::Other(e.into()))?;
-        let response = self.send_request(request).await?;
+            .header(CONTENT_LENGTH, size);
+        if let Some(checksum) = &checksum {
+            builder = builder.header(CHECKSUM_HEADER, checksum);
+        }
+        let request = builder.body(body).map_err(|e| Error::Other(e.into()))?;
+        let response = self.send_request_unchecked(request).await?;
+        let response = self.check_upload_response(upload_id, response).await?;
         let etag = response
             .headers()
             .get(ETAG)
@@ -148,30 +282,96 @@ impl MultipartUpload {
         Ok(MultipartPart {
             part_num,
             etag: etag.to_string(),
+            checksum,
         })
     }
 
+    /// Uploads the inclusive byte `range` of the object at `source`, an `x-amz-copy-source`, as
+    /// part `part_num` with `UploadPartCopy`, only if the source has the ETag `if_match` when
+    /// given.
+    pub(crate) async fn upload_part_copy(
+        &self,
+        upload_id: &str,
+        part_num: usize,
+        source: &str,
+        if_match: Option<&str>,
+        (start, end): (u64, u64),
+    ) -> Result<MultipartPart, Error> {
+        let url = format!(
+            "{}/{}?partNumber={}&uploadId={}",
+            self.fs.as_ref().options.endpoint,
+            utf8_percent_encode(self.path.as_ref(), &STRICT_PATH_ENCODE_SET),
+            part_num + 1,
+            utf8_percent_encode(upload_id, &STRICT_PATH_ENCODE_SET),
+        );
+        let mut builder = Request::builder()
+            .uri(url)
+            .method(Method::PUT)
+            .header(COPY_SOURCE_HEADER, source)
+            .header(COPY_SOURCE_RANGE_HEADER, format!("bytes={}-{}", start, end));
+        if let Some(etag) = if_match {
+            builder = builder.header(COPY_SOURCE_IF_MATCH_HEADER, etag);
+        }
+        let request = builder
+            .body(Empty::new())
+            .map_err(|e| Error::Other(e.into()))?;
+        let response = self.send_request_unchecked(request).await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(S3Error::PreconditionFailed(source.to_string()).into());
+        }
+        let response = self.check_upload_response(upload_id, response).await?;
+        // `CopyPartResult` has the shape of `CopyObjectResult`, a failure reported in the body of a
+        // 200 response has no ETag
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(S3Error::from)?
+            .to_bytes();
+        let result: CopyObjectResult =
+            quick_xml::de::from_reader(body.as_ref()).map_err(S3Error::from)?;
+        let etag = result.etag.ok_or_else(|| {
+            Error::Other(
+                format!(
+                    "failed to copy part to S3, content: {}",
+                    String::from_utf8_lossy(&body)
+                )
+                .into(),
+            )
+        })?;
+
+        Ok(MultipartPart {
+            part_num,
+            etag,
+            checksum: None,
+        })
+    }
+
+    /// Completes the upload from its `parts`, returning the ETag of the object, unknown if an
+    /// earlier attempt completed it.
     pub(crate) async fn complete_part(
         &self,
         upload_id: &str,
         parts: &[MultipartPart],
-    ) -> Result<(), Error> {
+    ) -> Result<Option<String>, Error> {
         let url = format!(
             "{}/{}?uploadId={}",
             self.fs.as_ref().options.endpoint,
             utf8_percent_encode(self.path.as_ref(), &STRICT_PATH_ENCODE_SET),
             utf8_percent_encode(upload_id, &STRICT_PATH_ENCODE_SET),
         );
-        let content = quick_xml::se::to_string(&CompleteMultipartUploadRequest {
-            part: parts
-                .iter()
-                .map(|p| CompleteMultipartUploadRequestPart {
-                    part_number: p.part_num + 1,
-                    etag: p.etag.to_owned(),
-                })
-                .collect_vec(),
-        })
-        .map_err(S3Error::from)?;
+        let mut part = parts
+            .iter()
+            .map(|p| CompleteMultipartUploadRequestPart {
+                part_number: p.part_num + 1,
+                etag: p.etag.to_owned(),
+                checksum_sha256: p.checksum.clone(),
+            })
+            .collect_vec();
+        // S3 requires the parts in ascending order, whichever order their uploads completed in
+        part.sort_by_key(|p| p.part_number);
+        let content = quick_xml::se::to_string(&CompleteMultipartUploadRequest { part })
+            .map_err(S3Error::from)?;
 
         let request = Request::builder()
             .uri(url)
@@ -180,24 +380,238 @@ impl MultipartUpload {
             .header(CONTENT_TYPE, "application/xml")
             .body(Full::new(Bytes::from(content)))
             .map_err(|e| Error::Other(e.into()))?;
-        let response = self.send_request(request).await?;
+        let response = self.send_request_unchecked(request).await?;
+        // a retry of a completion whose response was lost finds the upload gone
+        if response.status() == StatusCode::NOT_FOUND && self.completed().await? {
+            self.forget(upload_id);
+            return Ok(None);
+        }
+        let response = self.check_upload_response(upload_id, response).await?;
         // still check if there is any error because S3 might return error for status code 200
         // https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html#API_CompleteMultipartUpload_Example_4
         let (parts, body) = response.into_parts();
-        let maybe_error: S3ResponseError = quick_xml::de::from_reader(
-            body.collect()
-                .await
-                .map_err(S3Error::from)?
-                .aggregate()
-                .reader(),
-        )
-        .map_err(S3Error::from)?;
+        let body = body.collect().await.map_err(S3Error::from)?.to_bytes();
+        let maybe_error: S3ResponseError =
+            quick_xml::de::from_reader(body.as_ref()).map_err(S3Error::from)?;
         if !maybe_error.code.is_empty() {
             return Err(Error::Other(
                 format!("{:#?}, {:?}", parts, maybe_error).into(),
             ));
         }
+        let result: CompleteMultipartUploadResult =
+            quick_xml::de::from_reader(body.as_ref()).map_err(S3Error::from)?;
+        self.forget(upload_id);
+
+        Ok(result.etag)
+    }
+
+    /// Forgets the upload `upload_id` once completed, it is no longer aborted on shutdown.
+    fn forget(&self, upload_id: &str) {
+        self.fs.as_ref().uploads.lock().unwrap().remove(upload_id);
+    }
+}
+
+/// The ETag S3 gives an object completed from parts with the MD5 digests `part_md5s`, in the
+/// order of their part numbers: the MD5 of their concatenation followed by the number of parts,
+/// quoted like [`FileMeta::etag`](crate::fs::FileMeta::etag). Comparing it with the ETag of the
+/// completed object verifies that it holds what was uploaded.
+pub fn multipart_etag(part_md5s: impl IntoIterator<Item = [u8; 16]>) -> String {
+    use md5::{Digest, Md5};
+
+    let mut hasher = Md5::new();
+    let mut parts = 0;
+    for md5 in part_md5s {
+        hasher.update(md5);
+        parts += 1;
+    }
+    format!("\"{}-{}\"", hex_encode(&hasher.finalize()), parts)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn retried_completion_is_idempotent() {
+        use bytes::Bytes;
+        use http::{Method, StatusCode};
+        use http_body_util::Full;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, multipart_upload::MultipartUpload},
+        };
+
+        let mock = MockS3::new();
+        let upload = MultipartUpload::new(mock.fs(), Path::from("a"));
+        let upload_id = upload.initiate().await.unwrap();
+        let part = upload
+            .upload_part(&upload_id, 0, 5, Full::new(Bytes::from("hello")))
+            .await
+            .unwrap();
+        let parts = [part];
+
+        upload.complete_part(&upload_id, &parts).await.unwrap();
+        upload.complete_part(&upload_id, &parts).await.unwrap();
+
+        assert_eq!(
+            mock.requests(Method::POST)[1..],
+            [StatusCode::OK, StatusCode::NOT_FOUND]
+        );
+        assert_eq!(mock.object("a").unwrap().body, "hello");
 
-        Ok(())
+        let other = MultipartUpload::new(mock.fs(), Path::from("a"));
+        assert!(other.complete_part(&upload_id, &parts).await.is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn parts_completed_out_of_order_are_listed_in_order() {
+        use bytes::Bytes;
+        use futures_util::{stream::FuturesUnordered, StreamExt};
+        use http_body_util::Full;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, multipart_upload::MultipartUpload},
+        };
+
+        let mock = MockS3::new();
+        let upload = MultipartUpload::new(mock.fs(), Path::from("a"));
+        let upload_id = upload.initiate().await.unwrap();
+        let contents = ["first,", "second,", "third,", "fourth"];
+
+        // the later a part is started, the sooner its upload completes
+        let mut uploads = contents
+            .iter()
+            .enumerate()
+            .map(|(part_num, content)| {
+                let (upload, upload_id) = (&upload, &upload_id);
+                async move {
+                    let delay = (contents.len() - part_num) as u64 * 10;
+                    tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                    upload
+                        .upload_part(
+                            upload_id,
+                            part_num,
+                            content.len(),
+                            Full::new(Bytes::from(*content)),
+                        )
+                        .await
+                }
+            })
+            .collect::<FuturesUnordered<_>>();
+        let mut parts = vec![];
+        while let Some(part) = uploads.next().await {
+            parts.push(part.unwrap());
+        }
+        assert_eq!(
+            parts.iter().map(|part| part.part_num).collect::<Vec<_>>(),
+            [3, 2, 1, 0]
+        );
+
+        upload.complete_part(&upload_id, &parts).await.unwrap();
+        assert_eq!(mock.object("a").unwrap().body, contents.concat());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn corrupted_parts_are_rejected_by_their_checksum() {
+        use bytes::Bytes;
+        use http_body_util::Full;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, multipart_upload::MultipartUpload},
+        };
+
+        let mock = MockS3::new();
+        let mut options = mock.options();
+        options.checksum = true;
+        let upload = MultipartUpload::new(mock.fs_with(options), Path::from("a"));
+        let upload_id = upload.initiate().await.unwrap();
+
+        let part = upload
+            .upload_part(&upload_id, 0, 6, Full::new(Bytes::from("hello,")))
+            .await
+            .unwrap();
+        assert!(part.checksum.is_some());
+
+        mock.corrupt_parts();
+        let error = upload
+            .upload_part(&upload_id, 1, 5, Full::new(Bytes::from("world")))
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("BadDigest"));
+
+        // the checksums of the parts are listed on completion
+        upload.complete_part(&upload_id, &[part]).await.unwrap();
+        assert_eq!(mock.object("a").unwrap().body, "hello,");
+
+        // without checksums the corruption goes unnoticed
+        let upload = MultipartUpload::new(mock.fs(), Path::from("b"));
+        let upload_id = upload.initiate().await.unwrap();
+        let part = upload
+            .upload_part(&upload_id, 0, 5, Full::new(Bytes::from("hello")))
+            .await
+            .unwrap();
+        upload.complete_part(&upload_id, &[part]).await.unwrap();
+        assert_ne!(mock.object("b").unwrap().body, "hello");
+    }
+
+    #[test]
+    fn multipart_etags_hash_the_digests_of_parts() {
+        use md5::{Digest, Md5};
+
+        use super::multipart_etag;
+
+        let parts = [vec![b'a'; 5 * 1024 * 1024], vec![b'b'; 1024]];
+        let md5s = parts.iter().map(|part| <[u8; 16]>::from(Md5::digest(part)));
+        assert_eq!(
+            multipart_etag(md5s),
+            "\"16329fb6004d64a4fbc5bbb983fa0528-2\""
+        );
+        // a single part still counts as a multipart upload, unlike the plain MD5 of a `PutObject`
+        assert_eq!(
+            multipart_etag([<[u8; 16]>::from(Md5::digest(b"x"))]),
+            "\"9affad555af89da9b0bfcd5e45bc93da-1\""
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn vanished_uploads_are_told_apart() {
+        use bytes::Bytes;
+        use http_body_util::Full;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{mock::MockS3, multipart_upload::MultipartUpload, S3Error},
+            Error,
+        };
+
+        let mock = MockS3::new();
+        let upload = MultipartUpload::new(mock.fs(), Path::from("a"));
+        let upload_id = upload.initiate().await.unwrap();
+        let part = upload
+            .upload_part(&upload_id, 0, 5, Full::new(Bytes::from("hello")))
+            .await
+            .unwrap();
+        // as if a lifecycle rule aborted it
+        mock.expire_uploads();
+
+        let err = upload.complete_part(&upload_id, &[part]).await.unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::S3Error(S3Error::NoSuchUpload { upload_id: id, .. }) if *id == upload_id
+        ));
+        assert!(err.to_string().contains("restart the upload"));
+        let err = upload
+            .upload_part(&upload_id, 1, 5, Full::new(Bytes::from("world")))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::S3Error(S3Error::NoSuchUpload { .. })));
+        assert!(mock.object("a").is_none());
     }
 }
@@ -1,9 +1,149 @@
-use super::credential::AwsCredential;
+use std::time::Duration;
+
+use super::{
+    acl::CannedAcl,
+    credential::{AwsCredential, InstanceCredentialProvider},
+};
 
 pub(crate) struct S3Options {
     pub(crate) endpoint: String,
+    pub(crate) bucket: String,
     pub(crate) region: String,
     pub(crate) credential: Option<AwsCredential>,
+    /// Where the credential comes from when none is given.
+    pub(crate) instance_credentials: Option<InstanceCredentialProvider>,
     pub(crate) sign_payload: bool,
+    /// The size above which bodies are sent unsigned, signing the smaller ones, in place of
+    /// `sign_payload` when set.
+    pub(crate) sign_payload_threshold: Option<u64>,
     pub(crate) checksum: bool,
+    pub(crate) append_emulation: bool,
+    /// The account expected to own the bucket, sent with every request.
+    pub(crate) expected_bucket_owner: Option<String>,
+    /// How many redirects a request follows at most.
+    pub(crate) max_redirects: usize,
+}
+
+/// What [`AmazonS3::shutdown`](super::fs::AmazonS3::shutdown) does with the multipart uploads in
+/// flight, those of the files opened for writing and not closed yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Aborts them right away.
+    #[default]
+    Abort,
+    /// Closes the files written to, uploading what they buffered and completing their uploads,
+    /// for up to `timeout`, then aborts the ones left. Without the timer of tokio or monoio, the
+    /// timeout isn't enforced.
+    Complete { timeout: Duration },
+}
+
+/// Options of a single write of an object, see
+/// [`AmazonS3::write_file_with`](super::fs::AmazonS3::write_file_with).
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    pub(crate) content_encoding: Option<String>,
+    pub(crate) acl: Option<CannedAcl>,
+    pub(crate) if_not_exists: bool,
+    pub(crate) if_match: Option<String>,
+    pub(crate) metadata: Vec<(String, String)>,
+    pub(crate) sign_payload: Option<bool>,
+    /// The SHA-256 digest of the content, sent as its `x-amz-checksum-sha256`.
+    pub(crate) sha256: Option<[u8; 32]>,
+}
+
+impl WriteOptions {
+    /// Declares the content as encoded with `content_encoding`, e.g. `gzip` for data compressed
+    /// beforehand. The content is uploaded as is, fusio doesn't encode it.
+    pub fn content_encoding(mut self, content_encoding: impl Into<String>) -> Self {
+        self.content_encoding = Some(content_encoding.into());
+        self
+    }
+
+    /// Applies the canned `acl` to the written object, e.g. [`CannedAcl::PublicRead`] to publish
+    /// it.
+    pub fn acl(mut self, acl: CannedAcl) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    /// Only writes the object if there is none at its path yet (`If-None-Match: *`), failing
+    /// with [`S3Error::PreconditionFailed`](super::S3Error::PreconditionFailed) otherwise.
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    /// Only replaces the object if its ETag is still `etag` (`If-Match`), failing with
+    /// [`S3Error::PreconditionFailed`](super::S3Error::PreconditionFailed) otherwise.
+    pub fn if_match(mut self, etag: impl Into<String>) -> Self {
+        self.if_match = Some(etag.into());
+        self
+    }
+
+    /// Stores `value` as the user metadata `name` of the object, sent as `x-amz-meta-{name}`.
+    pub fn metadata(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((name.into(), value.into()));
+        self
+    }
+
+    /// Signs the content of this write, or sends it as `UNSIGNED-PAYLOAD`, whatever the file
+    /// system is configured with.
+    pub fn sign_payload(mut self, sign_payload: bool) -> Self {
+        self.sign_payload = Some(sign_payload);
+        self
+    }
+}
+
+/// Headers the response to a presigned `GET` is sent with in place of the ones stored with the
+/// object, e.g. to download it as an attachment, see
+/// [`AmazonS3::presign_get_with`](super::fs::AmazonS3::presign_get_with). They are passed as
+/// `response-*` query parameters, which are signed with the rest of the URL.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseOverrides {
+    params: Vec<(&'static str, String)>,
+}
+
+impl ResponseOverrides {
+    pub fn content_type(self, content_type: impl Into<String>) -> Self {
+        self.set("response-content-type", content_type.into())
+    }
+
+    /// e.g. `attachment; filename="report.csv"` for browsers to download the object.
+    pub fn content_disposition(self, content_disposition: impl Into<String>) -> Self {
+        self.set("response-content-disposition", content_disposition.into())
+    }
+
+    pub fn content_encoding(self, content_encoding: impl Into<String>) -> Self {
+        self.set("response-content-encoding", content_encoding.into())
+    }
+
+    pub fn content_language(self, content_language: impl Into<String>) -> Self {
+        self.set("response-content-language", content_language.into())
+    }
+
+    pub fn cache_control(self, cache_control: impl Into<String>) -> Self {
+        self.set("response-cache-control", cache_control.into())
+    }
+
+    /// An HTTP date, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`.
+    pub fn expires(self, expires: impl Into<String>) -> Self {
+        self.set("response-expires", expires.into())
+    }
+
+    fn set(mut self, name: &'static str, value: String) -> Self {
+        self.params.retain(|(param, _)| *param != name);
+        self.params.push((name, value));
+        self
+    }
+
+    /// Appends the overrides to the query of `url`, before it is signed.
+    pub(crate) fn apply(&self, url: &mut url::Url) {
+        if self.params.is_empty() {
+            return;
+        }
+        let mut pairs = url.query_pairs_mut();
+        for (name, value) in &self.params {
+            pairs.append_pair(name, value);
+        }
+    }
 }
@@ -0,0 +1,200 @@
+use std::{collections::VecDeque, io, pin::Pin, task::Poll};
+
+use bytes::Bytes;
+use futures_util::{future::poll_fn, stream::FuturesOrdered, StreamExt};
+
+use super::{fs::AmazonS3, s3::S3File};
+use crate::{dynamic::MaybeSendFuture, path::Path, Error, IoBufMut, Read};
+
+type Fetch = Pin<Box<dyn MaybeSendFuture<Output = Result<(u64, Bytes), Error>>>>;
+
+/// A reader of an S3 object for sequential scans. The object is fetched in blocks of
+/// `block_size` bytes, up to `depth` blocks ahead of the one being read are requested while the
+/// caller consumes it, hiding the latency of S3.
+///
+/// Reading at another position than the one following the last read drops the blocks fetched
+/// ahead. Local files have no latency to hide, read them directly.
+pub struct S3PrefetchReader {
+    fs: AmazonS3,
+    path: Path,
+    size: u64,
+    block_size: u64,
+    depth: usize,
+    /// Blocks fetched ahead, contiguous and followed by the ones still `pending`.
+    ready: VecDeque<Result<(u64, Bytes), Error>>,
+    pending: FuturesOrdered<Fetch>,
+    /// The position of the next block to fetch.
+    next_fetch: u64,
+}
+
+unsafe impl Sync for S3PrefetchReader {}
+
+impl S3PrefetchReader {
+    pub(crate) fn new(
+        fs: AmazonS3,
+        path: Path,
+        size: u64,
+        block_size: usize,
+        depth: usize,
+    ) -> Self {
+        Self {
+            fs,
+            path,
+            size,
+            block_size: block_size.max(1) as u64,
+            depth,
+            ready: VecDeque::new(),
+            pending: FuturesOrdered::new(),
+            next_fetch: 0,
+        }
+    }
+
+    /// Drops the blocks fetched ahead, fetching from `pos` next.
+    fn reset(&mut self, pos: u64) {
+        self.ready.clear();
+        self.pending = FuturesOrdered::new();
+        self.next_fetch = pos;
+    }
+
+    /// Requests blocks until `depth` of them follow the one being read.
+    fn prefetch(&mut self) {
+        while self.ready.len() + self.pending.len() <= self.depth && self.next_fetch < self.size {
+            let start = self.next_fetch;
+            let len = self.block_size.min(self.size - start);
+            self.next_fetch += len;
+
+            let mut file = S3File::new(self.fs.clone(), self.path.clone());
+            self.pending.push_back(Box::pin(async move {
+                let (result, buf) = file.read_exact_at(vec![0; len as usize], start).await;
+                result.map(|_| (start, Bytes::from(buf)))
+            }));
+        }
+    }
+
+    /// Polls the pending fetches once, so that their requests are sent before the caller comes
+    /// back for the blocks.
+    async fn poll_pending(&mut self) {
+        poll_fn(|cx| {
+            while let Poll::Ready(Some(block)) = self.pending.poll_next_unpin(cx) {
+                self.ready.push_back(block);
+            }
+            Poll::Ready(())
+        })
+        .await
+    }
+}
+
+impl Read for S3PrefetchReader {
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let len = buf.as_slice().len();
+        if pos + len as u64 > self.size {
+            return (
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "read of {} bytes at {} past the end of {} ({} bytes)",
+                        len, pos, self.path, self.size
+                    ),
+                )
+                .into()),
+                buf,
+            );
+        }
+
+        let mut filled = 0;
+        while filled < len {
+            let at = pos + filled as u64;
+            let (start, bytes) = match self.ready.front() {
+                Some(Ok((start, bytes))) if (*start..start + bytes.len() as u64).contains(&at) => {
+                    (*start, bytes.clone())
+                }
+                Some(Ok((start, bytes))) if at >= start + bytes.len() as u64 => {
+                    self.ready.pop_front();
+                    continue;
+                }
+                Some(Ok(_)) => {
+                    self.reset(at);
+                    continue;
+                }
+                Some(Err(_)) => {
+                    let Some(Err(e)) = self.ready.pop_front() else {
+                        unreachable!()
+                    };
+                    self.reset(at);
+                    return (Err(e), buf);
+                }
+                None => {
+                    if self.pending.is_empty() || at >= self.next_fetch {
+                        self.reset(at);
+                        self.prefetch();
+                    }
+                    match self.pending.next().await {
+                        Some(block) => self.ready.push_back(block),
+                        None => unreachable!("a block of the object is always pending"),
+                    }
+                    continue;
+                }
+            };
+
+            let offset = (at - start) as usize;
+            let n = (bytes.len() - offset).min(len - filled);
+            buf.as_slice_mut()[filled..filled + n].copy_from_slice(&bytes[offset..offset + n]);
+            filled += n;
+        }
+
+        self.prefetch();
+        self.poll_pending().await;
+        (Ok(()), buf)
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        S3File::new(self.fs.clone(), self.path.clone())
+            .read_to_end_at(buf, pos)
+            .await
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        Ok(self.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn sequential_reads_prefetch_blocks() {
+        use http::Method;
+
+        use crate::{path::Path, remotes::aws::mock::MockS3, Read};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("a", "0123456789");
+
+        let mut reader = s3.open_prefetching(&Path::from("a"), 4, 2).await.unwrap();
+        let gets = || mock.requests(Method::GET).len();
+
+        let (result, buf) = reader.read_exact_at(vec![0; 3], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"012");
+        // the block read and the two following ones were requested together
+        assert_eq!(gets(), 3);
+
+        let mut content = buf;
+        for (pos, len) in [(3, 4), (7, 3)] {
+            let (result, buf) = reader.read_exact_at(vec![0; len], pos).await;
+            result.unwrap();
+            content.extend(buf);
+        }
+        assert_eq!(content, b"0123456789");
+        assert_eq!(gets(), 3);
+
+        let (result, buf) = reader.read_exact_at(vec![0; 2], 1).await;
+        result.unwrap();
+        assert_eq!(buf, b"12");
+        assert_eq!(gets(), 6);
+
+        let (result, _) = reader.read_exact_at(vec![0; 2], 9).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use bytes::Bytes;
+use http::{header::LOCATION, Request, Response, StatusCode, Uri};
+use http_body::Body;
+use url::Url;
+
+use super::{options::S3Options, sign::Sign, S3Error};
+use crate::remotes::http::{declare_empty_body, HttpClient};
+
+/// How many redirects a request follows by default before failing.
+#[allow(unused)]
+pub(crate) const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Tells the region of the bucket, in particular on the responses to requests signed for another.
+pub(crate) const BUCKET_REGION_HEADER: &str = "x-amz-bucket-region";
+
+/// Signs `request` and sends it with `client`, following `301`, `302`, `307` and `308` redirects
+/// up to [`S3Options::max_redirects`] hops. Without a credential in the options, the one of the
+/// instance is fetched, or refreshed, with the same `client` first. The request is signed again for
+/// every hop, as the signature covers the host and path it is sent to. A redirect to a URL already
+/// visited fails right away rather than after exhausting the hops, and one to the bucket in another
+/// region fails with [`S3Error::WrongRegion`].
+pub(crate) async fn send_following_redirects<C, B>(
+    client: &C,
+    options: &S3Options,
+    mut request: Request<B>,
+) -> Result<Response<C::RespBody>, S3Error>
+where
+    C: HttpClient,
+    B: Body<Data = Bytes> + Clone + Unpin + Send + Sync + 'static,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    declare_empty_body(&mut request);
+    let instance_credential = match (&options.credential, &options.instance_credentials) {
+        (None, Some(provider)) => Some(provider.credential(client).await?),
+        _ => None,
+    };
+    let credential = options
+        .credential
+        .as_ref()
+        .or(instance_credential.as_deref());
+    let mut visited = Vec::new();
+    loop {
+        request.sign(options, credential).await?;
+        let response = client.send_request(clone_request(&request)).await?;
+        let location = match redirect_location(request.uri(), &response) {
+            Some(location) => location,
+            None => {
+                check_region(options, &response)?;
+                return Ok(response);
+            }
+        };
+
+        visited.push(request.uri().clone());
+        if visited.contains(&location) {
+            return Err(S3Error::RedirectLoop(location.to_string()));
+        }
+        if visited.len() > options.max_redirects {
+            return Err(S3Error::TooManyRedirects {
+                url: location.to_string(),
+                max: options.max_redirects,
+            });
+        }
+        *request.uri_mut() = location;
+    }
+}
+
+/// Returns where `response` redirects a request to `uri`, resolving relative locations against
+/// it, or `None` if it isn't a redirect this client follows.
+fn redirect_location<B>(uri: &Uri, response: &Response<B>) -> Option<Uri> {
+    if !matches!(
+        response.status(),
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    ) {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let location = Url::parse(&uri.to_string()).ok()?.join(location).ok()?;
+    location.as_str().parse().ok()
+}
+
+/// Fails if `response` tells that the bucket is in another region than the one of `options`, as
+/// S3 answers a request signed for the wrong region with a `301 Moved Permanently` without a
+/// `Location`, or a `400 Bad Request`. Their only explanation is the [`BUCKET_REGION_HEADER`], the
+/// response to a `HEAD` having no body.
+fn check_region<B>(options: &S3Options, response: &Response<B>) -> Result<(), S3Error> {
+    if !matches!(
+        response.status(),
+        StatusCode::MOVED_PERMANENTLY | StatusCode::BAD_REQUEST
+    ) {
+        return Ok(());
+    }
+    match response
+        .headers()
+        .get(BUCKET_REGION_HEADER)
+        .and_then(|region| region.to_str().ok())
+    {
+        Some(region) if region != options.region => Err(S3Error::WrongRegion {
+            bucket: options.bucket.clone(),
+            region: region.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn clone_request<B: Clone>(request: &Request<B>) -> Request<B> {
+    let mut clone = Request::new(request.body().clone());
+    *clone.method_mut() = request.method().clone();
+    *clone.uri_mut() = request.uri().clone();
+    *clone.version_mut() = request.version();
+    *clone.headers_mut() = request.headers().clone();
+    *clone.extensions_mut() = request.extensions().clone();
+    clone
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn redirect_chains_are_followed_up_to_the_limit() {
+        use bytes::Bytes;
+        use http::{Method, Request, StatusCode};
+        use http_body_util::{BodyExt, Empty};
+
+        use super::send_following_redirects;
+        use crate::remotes::aws::{mock::MockS3, S3Error};
+
+        let get = |url: &str| Request::get(url).body(Empty::<Bytes>::new()).unwrap();
+
+        // the second hop changes host and is rejected unless signed again
+        let mock = MockS3::new();
+        mock.redirect("a", "http://mirror.s3.mock/b");
+        mock.redirect("b", "/c");
+        mock.put_object("c", "hello");
+        let response =
+            send_following_redirects(&mock, &mock.options(), get("http://fusio-test.s3.mock/a"))
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+        assert_eq!(
+            mock.requests(Method::GET),
+            [
+                StatusCode::TEMPORARY_REDIRECT,
+                StatusCode::TEMPORARY_REDIRECT,
+                StatusCode::OK
+            ]
+        );
+
+        let mut options = mock.options();
+        options.max_redirects = 1;
+        let result =
+            send_following_redirects(&mock, &options, get("http://fusio-test.s3.mock/a")).await;
+        assert!(matches!(
+            result,
+            Err(S3Error::TooManyRedirects { max: 1, .. })
+        ));
+
+        mock.redirect("c", "http://fusio-test.s3.mock/a");
+        let result =
+            send_following_redirects(&mock, &mock.options(), get("http://fusio-test.s3.mock/a"))
+                .await;
+        assert!(matches!(result, Err(S3Error::RedirectLoop(_))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn wrong_region_is_told_by_the_response_header() {
+        use http::{Method, StatusCode};
+
+        use crate::{
+            fs::Fs,
+            path::Path,
+            remotes::aws::{mock::MockS3, S3Error},
+            Error,
+        };
+
+        let mock = MockS3::new();
+        mock.put_object("object", "hello");
+        mock.set_bucket_region("eu-west-2");
+        let s3 = mock.fs();
+
+        // the `HEAD` is answered without a body, the region is all it tells
+        let result = s3.metadata(&Path::from("object")).await;
+        assert!(matches!(
+            result,
+            Err(Error::S3Error(S3Error::WrongRegion { bucket, region }))
+                if bucket == "fusio-test" && region == "eu-west-2"
+        ));
+        assert_eq!(mock.requests(Method::HEAD), [StatusCode::MOVED_PERMANENTLY]);
+
+        let region = s3.bucket_region().await.unwrap();
+        assert_eq!(region, "eu-west-2");
+        let mut options = mock.options();
+        options.region = region;
+        let s3 = mock.fs_with(options);
+        assert_eq!(s3.metadata(&Path::from("object")).await.unwrap().size, 5);
+        assert_eq!(s3.bucket_region().await.unwrap(), "eu-west-2");
+    }
+}
@@ -0,0 +1,134 @@
+use http::StatusCode;
+
+use super::{S3Error, S3ResponseError};
+pub use crate::fs::RestoreStatus;
+use crate::remotes::{
+    http::HttpError,
+    serde::{GlacierJobParameters, RestoreRequest},
+};
+
+pub(crate) const RESTORE_HEADER: &str = "x-amz-restore";
+
+/// How fast, and at which cost, an archived object is restored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreTier {
+    /// Within minutes, not available for Deep Archive.
+    Expedited,
+    /// Within hours.
+    #[default]
+    Standard,
+    /// Within hours to days, the cheapest tier.
+    Bulk,
+}
+
+impl RestoreTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestoreTier::Expedited => "Expedited",
+            RestoreTier::Standard => "Standard",
+            RestoreTier::Bulk => "Bulk",
+        }
+    }
+}
+
+impl RestoreStatus {
+    /// Parses the value of the `x-amz-restore` header, e.g. `ongoing-request="false",
+    /// expiry-date="Fri, 21 Dec 2012 00:00:00 GMT"`.
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut ongoing = None;
+        let mut expiry_date = None;
+
+        let mut rest = header.trim();
+        while !rest.is_empty() {
+            let (key, value) = rest.split_once('=')?;
+            let value = value.trim_start().strip_prefix('"')?;
+            let (value, tail) = value.split_once('"')?;
+            match key.trim() {
+                "ongoing-request" => ongoing = Some(value.parse::<bool>().ok()?),
+                "expiry-date" => expiry_date = Some(value.to_string()),
+                _ => {}
+            }
+            rest = tail.trim_start().trim_start_matches(',').trim_start();
+        }
+
+        Some(match ongoing? {
+            true => RestoreStatus::InProgress,
+            false => RestoreStatus::Restored { expiry_date },
+        })
+    }
+}
+
+pub(crate) fn restore_request(days: u32, tier: RestoreTier) -> Result<String, S3Error> {
+    Ok(quick_xml::se::to_string(&RestoreRequest {
+        days,
+        glacier_job_parameters: GlacierJobParameters {
+            tier: tier.as_str(),
+        },
+    })?)
+}
+
+/// Builds the error of a failed read of `location`, telling archived objects that still have to
+/// be restored apart from other failures.
+pub(crate) fn read_error(location: String, status: StatusCode, body: String) -> S3Error {
+    if status == StatusCode::FORBIDDEN {
+        if let Ok(error) = quick_xml::de::from_str::<S3ResponseError>(&body) {
+            if error.code == "InvalidObjectState" {
+                return S3Error::ObjectArchived(location);
+            }
+        }
+    }
+    S3Error::from(HttpError::HttpNotSuccess { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn restore_request_xml() {
+        use super::{restore_request, RestoreTier};
+
+        assert_eq!(
+            restore_request(7, RestoreTier::Bulk).unwrap(),
+            "<RestoreRequest><Days>7</Days><GlacierJobParameters><Tier>Bulk</Tier></\
+             GlacierJobParameters></RestoreRequest>"
+        );
+    }
+
+    #[test]
+    fn parse_restore_status() {
+        use super::RestoreStatus;
+
+        assert_eq!(
+            RestoreStatus::parse("ongoing-request=\"true\""),
+            Some(RestoreStatus::InProgress)
+        );
+        assert_eq!(
+            RestoreStatus::parse(
+                "ongoing-request=\"false\", expiry-date=\"Fri, 21 Dec 2012 00:00:00 GMT\""
+            ),
+            Some(RestoreStatus::Restored {
+                expiry_date: Some("Fri, 21 Dec 2012 00:00:00 GMT".into())
+            })
+        );
+        assert_eq!(RestoreStatus::parse("ongoing-request=\"maybe\""), None);
+        assert_eq!(RestoreStatus::parse("expiry-date=\"Fri\""), None);
+    }
+
+    #[test]
+    fn archived_read_error() {
+        use http::StatusCode;
+
+        use super::read_error;
+        use crate::remotes::aws::S3Error;
+
+        let body = "<Error><Code>InvalidObjectState</Code><Message>The operation is not valid for \
+                    the object's storage class</Message></Error>";
+        assert!(matches!(
+            read_error("s3://bucket/a".into(), StatusCode::FORBIDDEN, body.into()),
+            S3Error::ObjectArchived(location) if location == "s3://bucket/a"
+        ));
+        assert!(matches!(
+            read_error("s3://bucket/a".into(), StatusCode::FORBIDDEN, "".into()),
+            S3Error::HttpError(_)
+        ));
+    }
+}
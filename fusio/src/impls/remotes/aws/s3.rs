@@ -1,53 +1,113 @@
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 use bytes::Buf;
+use futures_util::lock::Mutex;
 use http::{
-    header::{CONTENT_LENGTH, RANGE},
+    header::{CONTENT_RANGE, RANGE},
     request::Builder,
-    Method, Request,
+    Method, Request, StatusCode,
 };
 use http_body_util::{BodyExt, Empty};
-use percent_encoding::utf8_percent_encode;
 
-use super::{fs::AmazonS3, sign::Sign, S3Error, STRICT_PATH_ENCODE_SET};
+use super::{fs::AmazonS3, restore::read_error, S3Error, S3Location};
 use crate::{
     buf::IoBufMut,
+    fs::{FileMeta, Fs},
     path::Path,
     remotes::{
         aws::{multipart_upload::MultipartUpload, writer::S3Writer},
-        http::{HttpClient, HttpError},
+        http::{range::validate_content_range, HttpError},
     },
     Error, IoBuf, Read, Write,
 };
 
+/// The writer of a file opened for writing, shared with its file system for
+/// [`AmazonS3::shutdown`] to complete it. `None` once closed, until written again.
+pub(super) type SharedWriter = Arc<Mutex<Option<S3Writer>>>;
+
+/// An object of S3. Objects are written whole: the writes of a handle opened for writing are
+/// buffered and uploaded, at once or by parts, replacing the object on [`Write::close`].
 pub struct S3File {
     fs: AmazonS3,
     path: Path,
-    writer: Option<S3Writer>,
+    writable: bool,
+    writer: SharedWriter,
+    /// The size of the object, when known on open.
+    len: Option<u64>,
 }
 
 impl S3File {
+    /// A handle to read the object at `path`, writes fail.
     pub(crate) fn new(fs: AmazonS3, path: Path) -> Self {
         Self {
             fs,
             path,
-            writer: None,
+            writable: false,
+            writer: Arc::new(Mutex::new(None)),
+            len: None,
         }
     }
 
-    fn build_request(&self, method: Method) -> Builder {
-        let url = format!(
-            "{}/{}",
-            self.fs.as_ref().options.endpoint,
-            utf8_percent_encode(self.path.as_ref(), &STRICT_PATH_ENCODE_SET)
-        );
+    /// A handle to read the object at `path` of `len` bytes, which isn't asked for again.
+    pub(crate) fn with_len(fs: AmazonS3, path: Path, len: u64) -> Self {
+        let mut file = Self::new(fs, path);
+        file.len = Some(len);
+        file
+    }
+
+    /// Fails a read of `len` bytes at `pos` past the known end of the object.
+    fn check_within_len(&self, pos: u64, len: u64) -> Result<(), Error> {
+        match self.len {
+            Some(size) if pos.saturating_add(len) > size => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "read of {} bytes at {} past the end of \"{}\" at {}",
+                    len, pos, self.path, size
+                ),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// A handle whose writes replace the object at `path` once closed, with an empty one if
+    /// nothing was written.
+    pub(crate) fn writable(fs: AmazonS3, path: Path) -> Self {
+        let mut file = Self::new(fs, path);
+        file.writable = true;
+        file.writer = Arc::new(Mutex::new(Some(file.new_writer())));
+        file.fs.register_writer(&file.writer);
+        file
+    }
+
+    fn new_writer(&self) -> S3Writer {
+        S3Writer::new(Arc::new(MultipartUpload::new(
+            self.fs.clone(),
+            self.path.clone(),
+        )))
+    }
 
-        Request::builder().method(method).uri(url)
+    pub fn location(&self) -> S3Location {
+        self.fs.location(&self.path)
+    }
+
+    fn build_request(&self, method: Method) -> Builder {
+        Request::builder()
+            .method(method)
+            .uri(self.fs.object_url(&self.path))
     }
 }
 
 impl Read for S3File {
     async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        // a range can't be empty, `bytes=5-4` is invalid
+        if buf.as_slice().is_empty() {
+            return (Ok(()), buf);
+        }
+        if let Err(e) = self.check_within_len(pos, buf.as_slice().len() as u64) {
+            return (Err(e), buf);
+        }
+
         let request = self
             .build_request(Method::GET)
             .header(
@@ -57,36 +117,22 @@ impl Read for S3File {
             .body(Empty::new())
             .map_err(|e| S3Error::from(HttpError::from(e)));
 
-        let mut request = match request {
+        let request = match request {
             Ok(request) => request,
             Err(e) => return (Err(e.into()), buf),
         };
 
-        if let Err(e) = request
-            .sign(&self.fs.as_ref().options)
-            .await
-            .map_err(S3Error::from)
-        {
-            return (Err(e.into()), buf);
-        }
-
-        let response = match self
-            .fs
-            .as_ref()
-            .client
-            .send_request(request)
-            .await
-            .map_err(S3Error::from)
-        {
+        let response = match self.fs.send(request).await {
             Ok(response) => response,
             Err(e) => return (Err(e.into()), buf),
         };
 
         if !response.status().is_success() {
             return (
-                Err(S3Error::from(HttpError::HttpNotSuccess {
-                    status: response.status(),
-                    body: String::from_utf8_lossy(
+                Err(read_error(
+                    self.location().to_string(),
+                    response.status(),
+                    String::from_utf8_lossy(
                         &response
                             .into_body()
                             .collect()
@@ -95,11 +141,21 @@ impl Read for S3File {
                             .unwrap_or_default(),
                     )
                     .to_string(),
-                })
+                )
                 .into()),
                 buf,
             );
         } else {
+            if let Err(e) = validate_content_range(
+                response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|range| range.to_str().ok()),
+                pos,
+                Some(pos + buf.as_slice().len() as u64 - 1),
+            ) {
+                return (Err(S3Error::from(e).into()), buf);
+            }
             match response.into_body().collect().await.map_err(S3Error::from) {
                 Ok(body) => {
                     if let Err(e) = std::io::Read::read_exact(
@@ -117,7 +173,11 @@ impl Read for S3File {
     }
 
     async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
-        let mut request = match self
+        if self.len.is_some_and(|len| pos >= len) {
+            buf.clear();
+            return (Ok(()), buf);
+        }
+        let request = match self
             .build_request(Method::GET)
             .header(RANGE, format!("bytes={}-", pos))
             .body(Empty::new())
@@ -127,31 +187,23 @@ impl Read for S3File {
             Ok(request) => request,
         };
 
-        if let Err(e) = request
-            .sign(&self.fs.as_ref().options)
-            .await
-            .map_err(S3Error::from)
-        {
-            return (Err(e.into()), buf);
-        }
-
-        let response = match self
-            .fs
-            .as_ref()
-            .client
-            .send_request(request)
-            .await
-            .map_err(S3Error::from)
-        {
+        let response = match self.fs.send(request).await {
             Ok(response) => response,
             Err(e) => return (Err(e.into()), buf),
         };
 
+        // S3 answers ranges starting at or past the end of the object with a 416, reading there
+        // yields nothing like it does from a local file
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            buf.clear();
+            return (Ok(()), buf);
+        }
         if !response.status().is_success() {
             return (
-                Err(S3Error::from(HttpError::HttpNotSuccess {
-                    status: response.status(),
-                    body: String::from_utf8_lossy(
+                Err(read_error(
+                    self.location().to_string(),
+                    response.status(),
+                    String::from_utf8_lossy(
                         &response
                             .into_body()
                             .collect()
@@ -160,11 +212,21 @@ impl Read for S3File {
                             .unwrap_or_default(),
                     )
                     .to_string(),
-                })
+                )
                 .into()),
                 buf,
             );
         } else {
+            if let Err(e) = validate_content_range(
+                response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|range| range.to_str().ok()),
+                pos,
+                None,
+            ) {
+                return (Err(S3Error::from(e).into()), buf);
+            }
             match response.into_body().collect().await.map_err(S3Error::from) {
                 Ok(body) => {
                     let mut body = body.to_bytes();
@@ -178,77 +240,75 @@ impl Read for S3File {
     }
 
     async fn size(&self) -> Result<u64, Error> {
-        let mut request = self
-            .build_request(Method::HEAD)
-            .body(Empty::new())
-            .map_err(|e| S3Error::from(HttpError::from(e)))?;
-        request
-            .sign(&self.fs.as_ref().options)
-            .await
-            .map_err(S3Error::from)?;
-
-        let response = self
-            .fs
-            .as_ref()
-            .client
-            .send_request(request)
-            .await
-            .map_err(S3Error::from)?;
-
-        if !response.status().is_success() {
-            Err(S3Error::from(HttpError::HttpNotSuccess {
-                status: response.status(),
-                body: String::from_utf8_lossy(
-                    &response
-                        .into_body()
-                        .collect()
-                        .await
-                        .map_err(S3Error::from)?
-                        .to_bytes(),
-                )
-                .to_string(),
-            })
-            .into())
-        } else {
-            let size = response
-                .headers()
-                .get(CONTENT_LENGTH)
-                .ok_or_else(|| Error::Other("missing content-length header".into()))?
-                .to_str()
-                .map_err(|e| Error::Other(e.into()))?
-                .parse::<u64>()
-                .map_err(|e| Error::Other(e.into()))?;
-            Ok(size)
+        if let Some(len) = self.len {
+            return Ok(len);
         }
+        Ok(self.fs.metadata(&self.path).await?.size)
     }
 }
 
 impl Write for S3File {
     async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
-        self.writer
-            .get_or_insert_with(|| {
-                S3Writer::new(Arc::new(MultipartUpload::new(
-                    self.fs.clone(),
-                    self.path.clone(),
-                )))
-            })
+        if !self.writable {
+            return (
+                Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "\"{}\" was opened without `OpenOptions::write`, it can't be written",
+                        self.path
+                    ),
+                )
+                .into()),
+                buf,
+            );
+        }
+        // writes after a close start a new upload, replacing the object again
+        let mut writer = self.writer.lock().await;
+        writer
+            .get_or_insert_with(|| self.new_writer())
             .write_all(buf)
             .await
     }
 
+    /// Uploads what was written so far as parts of a multipart upload, the object is only
+    /// written on [`S3File::close`].
     async fn flush(&mut self) -> Result<(), Error> {
-        if let Some(writer) = self.writer.as_mut() {
+        if let Some(writer) = self.writer.lock().await.as_mut() {
             writer.flush().await?;
         }
         Ok(())
     }
 
+    /// Completes the upload. A file whose upload [`AmazonS3::shutdown`] completed has nothing
+    /// left to do.
     async fn close(&mut self) -> Result<(), Error> {
-        if let Some(mut writer) = self.writer.take() {
+        let writer = self.writer.lock().await.take();
+        if let Some(mut writer) = writer {
             writer.close().await?;
         }
         Ok(())
     }
+
+    /// Closes the file, returning the size and ETag of the object written from the response of
+    /// the upload. The object is only asked for if nothing was written since it was opened, or
+    /// if a retry of the upload lost its ETag.
+    async fn close_with_meta(&mut self) -> Result<FileMeta, Error> {
+        let Some(mut writer) = self.writer.lock().await.take() else {
+            return self.fs.metadata(&self.path).await;
+        };
+        match writer.close_with_etag().await? {
+            Some(etag) => Ok(FileMeta {
+                path: self.path.clone(),
+                size: writer.written(),
+                etag: Some(etag),
+                content_encoding: None,
+                storage_class: None,
+                restore_status: None,
+                local_version: None,
+            }),
+            None => self.fs.metadata(&self.path).await,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -264,9 +324,10 @@ mod tests {
                     credential::AwsCredential,
                     fs::{AmazonS3, AmazonS3Inner},
                     options::S3Options,
+                    redirect::DEFAULT_MAX_REDIRECTS,
                     s3::S3File,
                 },
-                http::{tokio::TokioClient, DynHttpClient, HttpClient},
+                http::{tokio::TokioClient, DynHttpClient},
             },
             Read, Write,
         };
@@ -282,24 +343,30 @@ mod tests {
         let region = "ap-southeast-1";
         let options = S3Options {
             endpoint: "https://fusio-test.s3.ap-southeast-1.amazonaws.com".into(),
+            bucket: "fusio-test".into(),
             credential: Some(AwsCredential {
                 key_id,
                 secret_key,
                 token: None,
             }),
+            instance_credentials: None,
             region: region.into(),
             sign_payload: true,
+            sign_payload_threshold: None,
             checksum: false,
+            append_emulation: false,
+            expected_bucket_owner: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
         };
 
         let s3 = AmazonS3 {
-            inner: Arc::new(AmazonS3Inner {
+            inner: Arc::new(AmazonS3Inner::new(
                 options,
-                client: Box::new(client) as Box<dyn DynHttpClient>,
-            }),
+                Box::new(client) as Box<dyn DynHttpClient>,
+            )),
         };
 
-        let mut s3 = S3File::new(s3, "read-write.txt".into());
+        let mut s3 = S3File::writable(s3, "read-write.txt".into());
 
         let (result, _) = s3
             .write_all(&b"The answer of life, universe and everthing"[..])
@@ -313,4 +380,105 @@ mod tests {
         result.unwrap();
         assert_eq!(buf, b"The answer of life, universe and everthing");
     }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn empty_ranges_skip_requests() {
+        use http::Method;
+
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3, Read};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("empty", "");
+        mock.put_object("a", "hello");
+
+        for (key, pos) in [("empty", 0), ("a", 5), ("a", 2)] {
+            let mut file = s3.open(&Path::from(key)).await.unwrap();
+            let (result, buf) = file.read_exact_at(vec![], pos).await;
+            result.unwrap();
+            assert!(buf.is_empty());
+        }
+        assert!(mock.requests(Method::GET).is_empty());
+
+        for (key, pos) in [("empty", 0), ("a", 5)] {
+            let mut file = s3.open(&Path::from(key)).await.unwrap();
+            let (result, buf) = file.read_to_end_at(vec![1], pos).await;
+            result.unwrap();
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn close_with_meta_returns_what_was_written() {
+        use http::Method;
+
+        use crate::{
+            fs::{Fs, OpenOptions},
+            path::Path,
+            remotes::aws::mock::MockS3,
+            Write,
+        };
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        let options = OpenOptions::default()
+            .create(true)
+            .write(true)
+            .truncate(true);
+
+        // put at once, and uploaded by parts
+        for (key, len) in [("small", 5), ("large", 6 * 1024 * 1024 + 4)] {
+            let path = Path::from(key);
+            let mut file = s3.open_options(&path, options).await.unwrap();
+            let content = vec![7u8; len];
+            let (result, _) = file.write_all(content[..len - 4].to_vec()).await;
+            result.unwrap();
+            let (result, _) = file.write_all(content[len - 4..].to_vec()).await;
+            result.unwrap();
+
+            let meta = file.close_with_meta().await.unwrap();
+            assert_eq!(meta.path, path);
+            assert_eq!(meta.size, len as u64);
+            let etag = meta.etag.unwrap();
+            assert!(!etag.is_empty());
+            assert_eq!(etag, mock.object(key).unwrap().etag);
+        }
+        // told by the responses of the uploads
+        assert!(mock.requests(Method::HEAD).is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn known_lengths_spare_head_requests() {
+        use std::io;
+
+        use http::Method;
+
+        use crate::{fs::Fs, path::Path, remotes::aws::mock::MockS3, Error, Read};
+
+        let mock = MockS3::new();
+        let s3 = mock.fs();
+        mock.put_object("object", "0123456789");
+
+        let mut file = s3.open_with_len(&Path::from("object"), 10).await.unwrap();
+        assert_eq!(file.size().await.unwrap(), 10);
+        let (result, buf) = file.read_exact_at(vec![0; 4], 3).await;
+        result.unwrap();
+        assert_eq!(buf, b"3456");
+        let (result, buf) = file.read_to_end_at(Vec::new(), 7).await;
+        result.unwrap();
+        assert_eq!(buf, b"789");
+        assert_eq!(mock.requests(Method::GET).len(), 2);
+
+        // reads past the declared length fail without a request
+        let (result, _) = file.read_exact_at(vec![0; 4], 8).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+        let (result, buf) = file.read_to_end_at(vec![1], 10).await;
+        result.unwrap();
+        assert!(buf.is_empty());
+        assert_eq!(mock.requests(Method::GET).len(), 2);
+        assert!(mock.requests(Method::HEAD).is_empty());
+    }
 }
@@ -1,17 +1,55 @@
-use base64::{prelude::BASE64_STANDARD, Engine};
 use bytes::Bytes;
 use http::Request;
 use http_body::Body;
 use http_body_util::BodyExt;
 use ring::digest::{self, Context};
 
-use super::{credential::AuthorizeError, options::S3Options, CHECKSUM_HEADER};
-use crate::remotes::aws::credential::AwsAuthorizer;
+use super::{
+    credential::{AuthorizeError, AwsCredential},
+    options::S3Options,
+    CHECKSUM_HEADER,
+};
+use crate::remotes::{aws::credential::AwsAuthorizer, encoding::base64_encode};
 
+/// Makes S3 reject requests to a bucket owned by another account than the given one.
+const EXPECTED_BUCKET_OWNER_HEADER: &str = "x-amz-expected-bucket-owner";
+
+/// Overrides whether the payload of a request is signed, as a request extension.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SignPayload(pub(crate) bool);
+
+/// Returns the base64 encoded SHA256 digest of `body`, the value of its `x-amz-checksum-sha256`.
+/// It takes a clone of the body, owned by the future, rather than borrowing it across the await.
+pub(crate) async fn body_checksum<B>(body: B) -> Result<String, AuthorizeError>
+where
+    B: Body<Data = Bytes> + Clone + Unpin,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut sha256 = Context::new(&digest::SHA256);
+    sha256.update(
+        &body
+            .collect()
+            .await
+            .map_err(|e| AuthorizeError::SignHashFailed(e.into()))?
+            .to_bytes(),
+    );
+    Ok(base64_encode(sha256.finish().as_ref()))
+}
+
+/// Signs requests with `credential`, resolved from the [`S3Options`] by the sender, or leaves
+/// them anonymous without one.
 pub(crate) trait Sign {
-    async fn checksum(&mut self, options: &S3Options) -> Result<(), AuthorizeError>;
+    async fn checksum(
+        &mut self,
+        options: &S3Options,
+        credential: Option<&AwsCredential>,
+    ) -> Result<(), AuthorizeError>;
 
-    async fn sign(&mut self, options: &S3Options) -> Result<(), AuthorizeError>;
+    async fn sign(
+        &mut self,
+        options: &S3Options,
+        credential: Option<&AwsCredential>,
+    ) -> Result<(), AuthorizeError>;
 }
 
 impl<B> Sign for Request<B>
@@ -19,45 +57,104 @@ where
     B: Body<Data = Bytes> + Clone + Unpin,
     B::Error: std::error::Error + Send + Sync + 'static,
 {
-    async fn checksum(&mut self, options: &S3Options) -> Result<(), AuthorizeError> {
-        if options.credential.is_some() && options.checksum {
-            let mut sha256 = Context::new(&digest::SHA256);
-            sha256.update(
-                &self
-                    .body()
-                    .clone()
-                    .collect()
-                    .await
-                    .map_err(|e| AuthorizeError::SignHashFailed(e.into()))?
-                    .to_bytes(),
-            );
-            let payload_sha256 = sha256.finish();
-            self.headers_mut().insert(
-                CHECKSUM_HEADER,
-                BASE64_STANDARD.encode(payload_sha256).parse().unwrap(),
-            );
+    async fn checksum(
+        &mut self,
+        options: &S3Options,
+        credential: Option<&AwsCredential>,
+    ) -> Result<(), AuthorizeError> {
+        // multipart uploads set the checksums of their parts themselves, to list them on completion
+        if credential.is_some() && options.checksum && !self.headers().contains_key(CHECKSUM_HEADER)
+        {
+            let checksum = body_checksum(self.body().clone()).await?;
+            self.headers_mut()
+                .insert(CHECKSUM_HEADER, checksum.parse()?);
         }
         Ok(())
     }
 
-    async fn sign(&mut self, options: &S3Options) -> Result<(), AuthorizeError> {
-        self.checksum(options).await?;
+    async fn sign(
+        &mut self,
+        options: &S3Options,
+        credential: Option<&AwsCredential>,
+    ) -> Result<(), AuthorizeError> {
+        self.checksum(options, credential).await?;
+
+        if let Some(owner) = &options.expected_bucket_owner {
+            self.headers_mut()
+                .insert(EXPECTED_BUCKET_OWNER_HEADER, owner.parse()?);
+        }
 
-        let credential = if let Some(credential) = options.credential.as_ref() {
+        let credential = if let Some(credential) = credential {
             credential
         } else {
             return Ok(());
         };
 
-        let authorizer = AwsAuthorizer::new(credential, "s3", &options.region).with_sign_payload(
-            if options.checksum {
-                false
-            } else {
-                options.sign_payload
+        let sign_payload = match self.extensions().get::<SignPayload>() {
+            Some(SignPayload(sign_payload)) => *sign_payload,
+            None if options.checksum => false,
+            None => match options.sign_payload_threshold {
+                Some(threshold) => self
+                    .body()
+                    .size_hint()
+                    .exact()
+                    .is_some_and(|size| size <= threshold),
+                None => options.sign_payload,
             },
-        );
+        };
+        let authorizer =
+            AwsAuthorizer::new(credential, "s3", &options.region).with_sign_payload(sign_payload);
         authorizer.authorize(self).await?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn payloads_above_the_threshold_are_unsigned() {
+        use bytes::Bytes;
+        use http::{Method, Request};
+        use http_body_util::Full;
+
+        use super::{Sign, SignPayload};
+        use crate::remotes::aws::{credential::hex_encode, mock::MockS3};
+
+        const HASH_HEADER: &str = "x-amz-content-sha256";
+
+        let mut options = MockS3::new().options();
+        options.sign_payload_threshold = Some(8);
+        let credential = options.credential.clone().unwrap();
+        let request = |body: &'static [u8]| {
+            Request::builder()
+                .method(Method::PUT)
+                .uri("https://fusio-test.s3.us-east-1.amazonaws.com/object")
+                .body(Full::new(Bytes::from_static(body)))
+                .unwrap()
+        };
+
+        let mut small = request(b"small");
+        small.sign(&options, Some(&credential)).await.unwrap();
+        let digest = ring::digest::digest(&ring::digest::SHA256, b"small");
+        assert_eq!(
+            small.headers()[HASH_HEADER],
+            hex_encode(digest.as_ref()).as_str()
+        );
+
+        let mut large = request(b"larger than the threshold");
+        large.sign(&options, Some(&credential)).await.unwrap();
+        assert_eq!(large.headers()[HASH_HEADER], "UNSIGNED-PAYLOAD");
+
+        // overridden per request
+        let mut large = request(b"larger than the threshold");
+        large.extensions_mut().insert(SignPayload(true));
+        large.sign(&options, Some(&credential)).await.unwrap();
+        assert_ne!(large.headers()[HASH_HEADER], "UNSIGNED-PAYLOAD");
+        let mut small = request(b"small");
+        small.extensions_mut().insert(SignPayload(false));
+        small.sign(&options, Some(&credential)).await.unwrap();
+        assert_eq!(small.headers()[HASH_HEADER], "UNSIGNED-PAYLOAD");
+    }
+}
@@ -1,7 +1,7 @@
 use std::{mem, pin::Pin, sync::Arc};
 
 use bytes::{BufMut, BytesMut};
-use futures_util::{stream::FuturesOrdered, StreamExt};
+use futures_util::{stream::FuturesUnordered, StreamExt};
 use http_body_util::Full;
 
 use crate::{
@@ -10,15 +10,20 @@ use crate::{
     Error, IoBuf, Write,
 };
 
-const S3_PART_MINIMUM_SIZE: usize = 5 * 1024 * 1024;
+pub(crate) const S3_PART_MINIMUM_SIZE: usize = 5 * 1024 * 1024;
 
 pub struct S3Writer {
     inner: Arc<MultipartUpload>,
     upload_id: Option<Arc<String>>,
     next_part_numer: usize,
     buf: BytesMut,
+    /// The parts uploaded so far, in the order their uploads completed.
+    parts: Vec<MultipartPart>,
+    /// The number of bytes written so far.
+    written: u64,
 
-    handlers: FuturesOrdered<Pin<Box<dyn MaybeSendFuture<Output = Result<MultipartPart, Error>>>>>,
+    handlers:
+        FuturesUnordered<Pin<Box<dyn MaybeSendFuture<Output = Result<MultipartPart, Error>>>>>,
 }
 
 unsafe impl Sync for S3Writer {}
@@ -30,10 +35,34 @@ impl S3Writer {
             upload_id: None,
             next_part_numer: 0,
             buf: BytesMut::with_capacity(S3_PART_MINIMUM_SIZE),
-            handlers: FuturesOrdered::new(),
+            parts: Vec::new(),
+            written: 0,
+            handlers: FuturesUnordered::new(),
         }
     }
 
+    /// The number of bytes written so far.
+    pub(crate) fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Completes the upload like [`S3Writer::close`], returning the ETag of the object, unknown
+    /// if a retry found the upload completed by an earlier attempt.
+    pub(crate) async fn close_with_etag(&mut self) -> Result<Option<String>, Error> {
+        let Some(upload_id) = self.upload_id.clone() else {
+            let bytes = mem::replace(&mut self.buf, BytesMut::new()).freeze();
+            return self.inner.upload_once(bytes.len(), Full::new(bytes)).await;
+        };
+        if !self.buf.is_empty() {
+            self.upload_part(BytesMut::new).await?;
+        }
+        while let Some(handle) = self.handlers.next().await {
+            self.parts.push(handle?);
+        }
+        assert_eq!(self.next_part_numer, self.parts.len());
+        self.inner.complete_part(&upload_id, &self.parts).await
+    }
+
     async fn upload_part<F>(&mut self, fn_bytes_init: F) -> Result<(), Error>
     where
         F: FnOnce() -> BytesMut,
@@ -51,7 +80,7 @@ impl S3Writer {
 
         let upload = self.inner.clone();
         let bytes = mem::replace(&mut self.buf, fn_bytes_init()).freeze();
-        self.handlers.push_back(Box::pin(async move {
+        self.handlers.push(Box::pin(async move {
             upload
                 .upload_part(&upload_id, part_num, bytes.len(), Full::new(bytes))
                 .await
@@ -72,38 +101,30 @@ impl Write for S3Writer {
             }
         }
         self.buf.put(buf.as_slice());
+        self.written += buf.bytes_init() as u64;
 
         (Ok(()), buf)
     }
 
+    /// Uploads the buffered content as a part if it is large enough to be one and waits for the
+    /// parts in flight, making them durable as parts of the upload. The object is only written
+    /// by [`S3Writer::close`], which completes the upload: until then it can't be read, and the
+    /// last bytes, too few to make a part of 5 MiB, stay buffered.
     async fn flush(&mut self) -> Result<(), Error> {
-        if self.buf.len() > S3_PART_MINIMUM_SIZE {
+        if self.buf.len() >= S3_PART_MINIMUM_SIZE {
             self.upload_part(BytesMut::new).await?;
         }
+        while let Some(handle) = self.handlers.next().await {
+            self.parts.push(handle?);
+        }
 
         Ok(())
     }
 
+    /// Completes the upload, or puts the object at once if it was too small for a part, empty
+    /// if nothing was written.
     async fn close(&mut self) -> Result<(), Error> {
-        let Some(upload_id) = self.upload_id.clone() else {
-            if !self.buf.is_empty() {
-                let bytes = mem::replace(&mut self.buf, BytesMut::new()).freeze();
-
-                self.inner
-                    .upload_once(bytes.len(), Full::new(bytes))
-                    .await?;
-            }
-            return Ok(());
-        };
-        if !self.buf.is_empty() {
-            self.upload_part(BytesMut::new).await?;
-        }
-        let mut parts = Vec::with_capacity(self.handlers.len());
-        while let Some(handle) = self.handlers.next().await {
-            parts.push(handle?);
-        }
-        assert_eq!(self.next_part_numer, parts.len());
-        self.inner.complete_part(&upload_id, &parts).await?;
+        self.close_with_etag().await?;
 
         Ok(())
     }
@@ -129,6 +150,7 @@ mod tests {
                     fs::{AmazonS3, AmazonS3Inner},
                     multipart_upload::MultipartUpload,
                     options::S3Options,
+                    redirect::DEFAULT_MAX_REDIRECTS,
                     writer::S3Writer,
                     AwsCredential,
                 },
@@ -140,22 +162,28 @@ mod tests {
         let region = "ap-southeast-2";
         let options = S3Options {
             endpoint: "endpoint".into(),
+            bucket: "fusio-test".into(),
             credential: Some(AwsCredential {
                 key_id: "key".to_string(),
                 secret_key: "secret_key".to_string(),
                 token: None,
             }),
+            instance_credentials: None,
             region: region.into(),
             sign_payload: true,
+            sign_payload_threshold: None,
             checksum: false,
+            append_emulation: false,
+            expected_bucket_owner: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
         };
         let client = crate::impls::remotes::http::tokio::TokioClient::new();
 
         let s3 = AmazonS3 {
-            inner: Arc::new(AmazonS3Inner {
+            inner: Arc::new(AmazonS3Inner::new(
                 options,
-                client: Box::new(client) as Box<dyn DynHttpClient>,
-            }),
+                Box::new(client) as Box<dyn DynHttpClient>,
+            )),
         };
 
         let upload = MultipartUpload::new(s3, "read-write.txt".into());
@@ -167,4 +195,46 @@ mod tests {
         result.unwrap();
         writer.close().await.unwrap();
     }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn flush_uploads_parts_close_completes() {
+        use std::sync::Arc;
+
+        use bytes::Bytes;
+        use http::Method;
+
+        use crate::{
+            path::Path,
+            remotes::aws::{
+                mock::MockS3,
+                multipart_upload::MultipartUpload,
+                writer::{S3Writer, S3_PART_MINIMUM_SIZE},
+            },
+            Write,
+        };
+
+        let mock = MockS3::new();
+        let upload = MultipartUpload::new(mock.fs(), Path::from("a"));
+        let mut writer = S3Writer::new(Arc::new(upload));
+
+        let part = Bytes::from(vec![b'x'; S3_PART_MINIMUM_SIZE]);
+        let (result, _) = writer.write_all(part.clone()).await;
+        result.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(mock.requests(Method::PUT).len(), 1);
+        assert!(mock.object("a").is_none());
+
+        let (result, _) = writer.write_all(Bytes::from("tail")).await;
+        result.unwrap();
+        writer.flush().await.unwrap();
+        // too small to be a part, it is uploaded as the last one on close
+        assert_eq!(mock.requests(Method::PUT).len(), 1);
+
+        writer.close().await.unwrap();
+        assert_eq!(mock.requests(Method::PUT).len(), 2);
+        let object = mock.object("a").unwrap();
+        assert_eq!(object.body.len(), S3_PART_MINIMUM_SIZE + 4);
+        assert!(object.body.ends_with(b"tail"));
+    }
 }
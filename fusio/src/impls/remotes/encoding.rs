@@ -0,0 +1,74 @@
+//! Base64 as used by the signing and checksum headers of the remote stores.
+//!
+//! With the `simd` feature the encoding goes through `base64-simd`, which picks the widest
+//! instruction set of the CPU at runtime.
+
+/// Encodes `bytes` in the standard, padded alphabet of RFC 4648.
+#[cfg(not(feature = "simd"))]
+pub fn base64_encode(bytes: &[u8]) -> String {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+
+    BASE64_STANDARD.encode(bytes)
+}
+
+/// Encodes `bytes` in the standard, padded alphabet of RFC 4648.
+#[cfg(feature = "simd")]
+pub fn base64_encode(bytes: &[u8]) -> String {
+    base64_simd::STANDARD.encode_to_string(bytes)
+}
+
+/// Decodes `encoded` from the standard, padded alphabet of RFC 4648, `None` if it isn't valid.
+#[cfg(not(feature = "simd"))]
+pub fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+
+    BASE64_STANDARD.decode(encoded).ok()
+}
+
+/// Decodes `encoded` from the standard, padded alphabet of RFC 4648, `None` if it isn't valid.
+#[cfg(feature = "simd")]
+pub fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    base64_simd::STANDARD.decode_to_vec(encoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn rfc4648_vectors() {
+        use super::{base64_decode, base64_encode};
+
+        for (decoded, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64_encode(decoded.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), decoded.as_bytes());
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        use rand::RngCore;
+
+        use super::{base64_decode, base64_encode};
+
+        // every length modulo 3 pads differently, and the SIMD paths switch on block sizes
+        for len in (0..=70).chain([1023, 1024, 1025, 64 * 1024 + 1]) {
+            let mut bytes = vec![0u8; len];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let encoded = base64_encode(&bytes);
+            assert_eq!(encoded.len(), len.div_ceil(3) * 4);
+            assert_eq!(base64_decode(&encoded).unwrap(), bytes);
+        }
+
+        assert_eq!(base64_decode("Zg="), None);
+        assert_eq!(base64_decode("Zg"), None);
+        assert_eq!(base64_decode("Z==="), None);
+        assert_eq!(base64_decode("Zm9v!"), None);
+    }
+}
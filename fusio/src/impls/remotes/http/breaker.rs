@@ -0,0 +1,278 @@
+//! Protections of the backends, and of the callers, against hammering a store that is failing:
+//! a circuit breaker failing requests fast once a host keeps failing, and a budget bounding how
+//! often failed operations are retried.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::{Request, Response, StatusCode};
+use http_body::Body;
+
+use super::{HttpClient, HttpError};
+use crate::{error::BoxedError, MaybeSync};
+
+/// How many consecutive failures of a host trip its circuit by default.
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long a tripped circuit fails requests by default.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// An [`HttpClient`] sending requests with another one, until a host fails enough requests in a
+/// row: its circuit then trips, and requests to it fail right away with
+/// [`HttpError::CircuitOpen`] for a cooldown, sparing the host while it recovers. A request
+/// failing, with a transport error, a `5xx` or a `429 Too Many Requests`, after the cooldown
+/// trips the circuit again, one succeeding closes it.
+///
+/// Circuits are kept per authority, so a breaker can be shared by the file systems of several
+/// hosts, e.g. passed to [`AmazonS3Builder::client`](crate::remotes::aws::fs::AmazonS3Builder).
+pub struct CircuitBreaker<C> {
+    client: C,
+    failure_threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Default)]
+struct HostState {
+    /// The failures in a row of the latest requests.
+    failures: u32,
+    /// Until when requests fail fast, if the circuit is tripped.
+    open_until: Option<Instant>,
+}
+
+impl<C: HttpClient> CircuitBreaker<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Trips the circuit of a host after `failure_threshold` failures in a row, 5 by default.
+    pub fn failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    /// Fails the requests to a host for `cooldown` once its circuit trips, 30 seconds by
+    /// default.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// How long requests to `host` still fail fast, `None` if its circuit is closed.
+    fn open_for(&self, host: &str) -> Option<Duration> {
+        let hosts = self.hosts.lock().unwrap();
+        let open_until = hosts.get(host)?.open_until?;
+        open_until.checked_duration_since(Instant::now())
+    }
+
+    fn record(&self, host: &str, failed: bool) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if !failed {
+            hosts.remove(host);
+            return;
+        }
+        let state = hosts.entry(host.to_string()).or_default();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for CircuitBreaker<C> {
+    type RespBody = C::RespBody;
+
+    async fn send_request<B>(
+        &self,
+        request: Request<B>,
+    ) -> Result<Response<Self::RespBody>, HttpError>
+    where
+        B: Body + Send + MaybeSync + 'static,
+        B::Data: Into<Bytes>,
+        B::Error: Into<BoxedError>,
+    {
+        let host = request
+            .uri()
+            .authority()
+            .map(|authority| authority.to_string())
+            .unwrap_or_default();
+        if let Some(retry_after) = self.open_for(&host) {
+            return Err(HttpError::CircuitOpen { host, retry_after });
+        }
+
+        let result = self.client.send_request(request).await;
+        let failed = match &result {
+            Ok(response) => {
+                response.status().is_server_error()
+                    || response.status() == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(_) => true,
+        };
+        self.record(&host, failed);
+        result
+    }
+}
+
+/// A budget of retries shared by the callers retrying failed operations, `max_retries` of them
+/// per `window`, so that retries can't multiply the load of a failing store. fusio doesn't retry
+/// requests itself. The budget is a token bucket: it starts full and refills continuously, a
+/// burst of retries can spend it all at once.
+pub struct RetryBudget {
+    max_retries: u32,
+    window: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RetryBudget {
+    pub fn new(max_retries: u32, window: Duration) -> Self {
+        Self {
+            max_retries,
+            window,
+            bucket: Mutex::new(Bucket {
+                tokens: max_retries as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a retry from the budget, `false` if it is spent, in which case the operation should
+    /// fail rather than be retried.
+    pub fn try_retry(&self) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        let refill = match self.window.is_zero() {
+            true => f64::INFINITY,
+            false => elapsed / self.window.as_secs_f64() * self.max_retries as f64,
+        };
+        bucket.tokens = (bucket.tokens + refill).min(self.max_retries as f64);
+        bucket.refilled_at = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn tripped_circuits_fail_fast_until_the_cooldown() {
+        use std::{
+            sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+            time::Duration,
+        };
+
+        use bytes::Bytes;
+        use http::{Request, Response, StatusCode};
+        use http_body::Body;
+        use http_body_util::{Empty, Full};
+
+        use super::CircuitBreaker;
+        use crate::{
+            error::BoxedError,
+            remotes::http::{HttpClient, HttpError},
+            MaybeSync,
+        };
+
+        /// Answers every request with `status`, counting them.
+        struct Backend {
+            status: AtomicU32,
+            requests: AtomicUsize,
+        }
+
+        impl HttpClient for &Backend {
+            type RespBody = Full<Bytes>;
+
+            async fn send_request<B>(
+                &self,
+                _: Request<B>,
+            ) -> Result<Response<Full<Bytes>>, HttpError>
+            where
+                B: Body + Send + MaybeSync + 'static,
+                B::Data: Into<Bytes>,
+                B::Error: Into<BoxedError>,
+            {
+                self.requests.fetch_add(1, Ordering::SeqCst);
+                Ok(Response::builder()
+                    .status(self.status.load(Ordering::SeqCst) as u16)
+                    .body(Full::new(Bytes::new()))
+                    .unwrap())
+            }
+        }
+
+        let backend = Backend {
+            status: AtomicU32::new(503),
+            requests: AtomicUsize::new(0),
+        };
+        let breaker = CircuitBreaker::new(&backend)
+            .failure_threshold(3)
+            .cooldown(Duration::from_millis(100));
+        let send = |host: &str| {
+            breaker.send_request(
+                Request::get(format!("http://{}/object", host))
+                    .body(Empty::<Bytes>::new())
+                    .unwrap(),
+            )
+        };
+
+        for _ in 0..3 {
+            let response = send("failing").await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+        // the circuit is open, requests don't reach the backend
+        for _ in 0..3 {
+            assert!(matches!(
+                send("failing").await,
+                Err(HttpError::CircuitOpen { host, .. }) if host == "failing"
+            ));
+        }
+        assert_eq!(backend.requests.load(Ordering::SeqCst), 3);
+        // other hosts have their own circuits
+        send("other").await.unwrap();
+        assert_eq!(backend.requests.load(Ordering::SeqCst), 4);
+
+        // after the cooldown a failure trips it again, a success closes it
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        send("failing").await.unwrap();
+        assert!(send("failing").await.is_err());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        backend.status.store(200, Ordering::SeqCst);
+        for _ in 0..3 {
+            assert_eq!(send("failing").await.unwrap().status(), StatusCode::OK);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn retry_budgets_refill_over_their_window() {
+        use std::time::Duration;
+
+        use super::RetryBudget;
+
+        let budget = RetryBudget::new(2, Duration::from_millis(100));
+        assert!(budget.try_retry());
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+    }
+}
@@ -10,6 +10,15 @@ pub enum HttpError {
         status: http::StatusCode,
         body: String,
     },
+    #[error("invalid Content-Range header: {0}")]
+    InvalidContentRange(String),
+    #[error("requested range {requested} but received {received}")]
+    RangeMismatch { requested: String, received: String },
+    #[error("the circuit of {host} is open after repeated failures, retry in {retry_after:?}")]
+    CircuitOpen {
+        host: String,
+        retry_after: std::time::Duration,
+    },
     #[error(transparent)]
     Http(#[from] http::Error),
     #[cfg(feature = "tokio-http")]
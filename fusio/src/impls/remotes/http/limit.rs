@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    sync::Mutex,
+    task::{Poll, Waker},
+};
+
+use bytes::Bytes;
+use http::{Request, Response};
+use http_body::Body;
+
+use super::{HttpClient, HttpError};
+use crate::{error::BoxedError, MaybeSync};
+
+/// An [`HttpClient`] sending at most `max_per_host` requests at once to each host with another
+/// one, the requests beyond waiting for one in flight to be answered, e.g. to keep under the
+/// request rates S3 allows per prefix. A request counts as in flight until its response headers
+/// are received.
+pub struct ConcurrencyLimit<C> {
+    client: C,
+    max_per_host: usize,
+    hosts: Mutex<HashMap<String, Host>>,
+}
+
+#[derive(Default)]
+struct Host {
+    in_flight: usize,
+    /// The requests waiting for a request in flight to be answered, all woken when one is.
+    waiters: Vec<Waker>,
+}
+
+impl<C: HttpClient> ConcurrencyLimit<C> {
+    pub fn new(client: C, max_per_host: usize) -> Self {
+        Self {
+            client,
+            max_per_host: max_per_host.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_per_host(&self) -> usize {
+        self.max_per_host
+    }
+
+    /// The number of requests to `host`, an authority like `bucket.s3.amazonaws.com`, in flight.
+    pub fn in_flight(&self, host: &str) -> usize {
+        self.hosts
+            .lock()
+            .unwrap()
+            .get(host)
+            .map_or(0, |host| host.in_flight)
+    }
+
+    async fn acquire<'a>(&'a self, host: &'a str) -> Permit<'a, C> {
+        poll_fn(|cx| {
+            let mut hosts = self.hosts.lock().unwrap();
+            let state = hosts.entry(host.to_string()).or_default();
+            if state.in_flight < self.max_per_host {
+                state.in_flight += 1;
+                return Poll::Ready(());
+            }
+            if !state
+                .waiters
+                .iter()
+                .any(|waker| waker.will_wake(cx.waker()))
+            {
+                state.waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await;
+        Permit { limit: self, host }
+    }
+}
+
+/// A request in flight, released when dropped, even if the request is cancelled.
+struct Permit<'a, C> {
+    limit: &'a ConcurrencyLimit<C>,
+    host: &'a str,
+}
+
+impl<C> Drop for Permit<'_, C> {
+    fn drop(&mut self) {
+        let mut hosts = self.limit.hosts.lock().unwrap();
+        if let Some(state) = hosts.get_mut(self.host) {
+            state.in_flight -= 1;
+            for waker in state.waiters.drain(..) {
+                waker.wake();
+            }
+            if state.in_flight == 0 {
+                hosts.remove(self.host);
+            }
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for ConcurrencyLimit<C> {
+    type RespBody = C::RespBody;
+
+    async fn send_request<B>(
+        &self,
+        request: Request<B>,
+    ) -> Result<Response<Self::RespBody>, HttpError>
+    where
+        B: Body + Send + MaybeSync + 'static,
+        B::Data: Into<Bytes>,
+        B::Error: Into<BoxedError>,
+    {
+        let host = request
+            .uri()
+            .authority()
+            .map(|authority| authority.to_string())
+            .unwrap_or_default();
+        let _permit = self.acquire(&host).await;
+        self.client.send_request(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn requests_beyond_the_limit_wait() {
+        use std::{
+            sync::atomic::{AtomicUsize, Ordering},
+            time::Duration,
+        };
+
+        use bytes::Bytes;
+        use http::{Request, Response};
+        use http_body::Body;
+        use http_body_util::{Empty, Full};
+
+        use super::ConcurrencyLimit;
+        use crate::{
+            error::BoxedError,
+            remotes::http::{HttpClient, HttpError},
+            MaybeSync,
+        };
+
+        /// Answers every request after a while, counting the requests received.
+        struct Backend {
+            received: AtomicUsize,
+        }
+
+        impl HttpClient for &Backend {
+            type RespBody = Full<Bytes>;
+
+            async fn send_request<B>(
+                &self,
+                _: Request<B>,
+            ) -> Result<Response<Full<Bytes>>, HttpError>
+            where
+                B: Body + Send + MaybeSync + 'static,
+                B::Data: Into<Bytes>,
+                B::Error: Into<BoxedError>,
+            {
+                self.received.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(Response::new(Full::new(Bytes::new())))
+            }
+        }
+
+        let backend = Backend {
+            received: AtomicUsize::new(0),
+        };
+        let limit = ConcurrencyLimit::new(&backend, 2);
+        assert_eq!(limit.max_per_host(), 2);
+        let send = || {
+            limit.send_request(
+                Request::get("http://host/object")
+                    .body(Empty::<Bytes>::new())
+                    .unwrap(),
+            )
+        };
+
+        let (first, second, third, ()) = tokio::join!(send(), send(), send(), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            // the third request waits for one of the first two
+            assert_eq!(backend.received.load(Ordering::SeqCst), 2);
+            assert_eq!(limit.in_flight("host"), 2);
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(backend.received.load(Ordering::SeqCst), 3);
+            assert_eq!(limit.in_flight("host"), 1);
+        });
+        for response in [first, second, third] {
+            response.unwrap();
+        }
+        assert_eq!(limit.in_flight("host"), 0);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::path::Path;
+
+/// Guesses the `Content-Type` of the file at `path` from its extension, falling back to
+/// `application/octet-stream`.
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("txt" | "log") => "text/plain",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("parquet") => "application/vnd.apache.parquet",
+        Some("avro") => "application/avro",
+        Some("pdf") => "application/pdf",
+        Some("gz") => "application/gzip",
+        Some("zip") => "application/zip",
+        Some("tar") => "application/x-tar",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::content_type;
+    use crate::path::Path;
+
+    #[test]
+    fn guess_content_type() {
+        assert_eq!(content_type(&Path::from("a/foo.json")), "application/json");
+        assert_eq!(content_type(&Path::from("foo.JPG")), "image/jpeg");
+        assert_eq!(
+            content_type(&Path::from("foo.unknown")),
+            "application/octet-stream"
+        );
+        assert_eq!(content_type(&Path::from("foo")), "application/octet-stream");
+    }
+}
@@ -1,15 +1,21 @@
+mod breaker;
 mod error;
+mod limit;
+pub(crate) mod mime;
+pub(crate) mod range;
 #[cfg(all(feature = "tokio-http", not(feature = "completion-based")))]
 pub mod tokio;
 
 use std::{future::Future, pin::Pin};
 
+pub use breaker::{CircuitBreaker, RetryBudget};
 use bytes::Bytes;
 pub use error::HttpError;
 use futures_core::Stream;
-use http::{Request, Response};
+use http::{header::CONTENT_LENGTH, Method, Request, Response};
 use http_body::Body;
 use http_body_util::BodyExt;
+pub use limit::ConcurrencyLimit;
 
 use crate::{dynamic::MaybeSendFuture, error::BoxedError, MaybeSend, MaybeSync};
 
@@ -26,6 +32,22 @@ pub trait HttpClient: MaybeSend + MaybeSync {
         B::Error: Into<BoxedError>;
 }
 
+/// Declares `Content-Length: 0` on a `PUT`, `POST` or `PATCH` whose body is known to be empty,
+/// which HTTP clients leave out. Servers such as S3 and the EC2 metadata service may require it
+/// and answer `411 Length Required` without.
+pub(crate) fn declare_empty_body<B: Body>(request: &mut Request<B>) {
+    if matches!(
+        *request.method(),
+        Method::PUT | Method::POST | Method::PATCH
+    ) && request.body().size_hint().exact() == Some(0)
+        && !request.headers().contains_key(CONTENT_LENGTH)
+    {
+        request
+            .headers_mut()
+            .insert(CONTENT_LENGTH, http::HeaderValue::from_static("0"));
+    }
+}
+
 pub trait MaybeSendStream: Stream + Unpin + MaybeSend {}
 
 #[cfg(not(feature = "no-send"))]
@@ -67,7 +89,7 @@ where
     }
 }
 
-impl HttpClient for Box<dyn DynHttpClient> {
+impl<'a> HttpClient for Box<dyn DynHttpClient + 'a> {
     type RespBody = BoxBody;
 
     async fn send_request<B>(
@@ -91,3 +113,33 @@ impl HttpClient for Box<dyn DynHttpClient> {
         Ok(response)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn empty_bodies_declare_a_zero_length() {
+        use bytes::Bytes;
+        use http::{header::CONTENT_LENGTH, Request};
+        use http_body_util::{Empty, Full};
+
+        use super::declare_empty_body;
+
+        let mut put = Request::put("http://localhost/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        declare_empty_body(&mut put);
+        assert_eq!(put.headers()[CONTENT_LENGTH], "0");
+
+        let mut put = Request::put("http://localhost/a")
+            .body(Full::new(Bytes::from_static(b"hello")))
+            .unwrap();
+        declare_empty_body(&mut put);
+        assert!(!put.headers().contains_key(CONTENT_LENGTH));
+
+        let mut get = Request::get("http://localhost/a")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        declare_empty_body(&mut get);
+        assert!(!get.headers().contains_key(CONTENT_LENGTH));
+    }
+}
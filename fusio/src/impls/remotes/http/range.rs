@@ -0,0 +1,107 @@
+use super::HttpError;
+
+/// Parses a `Content-Range: bytes <start>-<end>/<total>` header into `(start, end, total)`,
+/// `end` being inclusive and `total` `None` when the server reports it as unknown (`*`).
+pub(crate) fn parse_content_range(value: &str) -> Result<(u64, u64, Option<u64>), HttpError> {
+    let invalid = || HttpError::InvalidContentRange(value.to_string());
+
+    let (range, total) = value
+        .trim()
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split_once('/'))
+        .ok_or_else(invalid)?;
+    let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+    let start = start.parse::<u64>().map_err(|_| invalid())?;
+    let end = end.parse::<u64>().map_err(|_| invalid())?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse::<u64>().map_err(|_| invalid())?),
+    };
+
+    if start > end || total.is_some_and(|total| end >= total) {
+        return Err(invalid());
+    }
+    Ok((start, end, total))
+}
+
+/// Checks that a `Content-Range` header answers the requested range starting at `start` and,
+/// when given, ending at the inclusive `end`. A missing or different range means that the server
+/// ignored the requested one.
+pub(crate) fn validate_content_range(
+    value: Option<&str>,
+    start: u64,
+    end: Option<u64>,
+) -> Result<(u64, u64, Option<u64>), HttpError> {
+    let value = value.ok_or_else(|| HttpError::RangeMismatch {
+        requested: format_range(start, end),
+        received: "the whole content".into(),
+    })?;
+    let range = parse_content_range(value)?;
+
+    let mismatch = match end {
+        Some(end) => range.0 != start || range.1 != end,
+        None => range.0 != start,
+    };
+    if mismatch {
+        return Err(HttpError::RangeMismatch {
+            requested: format_range(start, end),
+            received: value.to_string(),
+        });
+    }
+    Ok(range)
+}
+
+fn format_range(start: u64, end: Option<u64>) -> String {
+    match end {
+        Some(end) => format!("bytes {}-{}", start, end),
+        None => format!("bytes {}-", start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_content_range() {
+        use super::parse_content_range;
+
+        assert_eq!(
+            parse_content_range("bytes 0-99/200").unwrap(),
+            (0, 99, Some(200))
+        );
+        assert_eq!(parse_content_range("bytes 5-5/*").unwrap(), (5, 5, None));
+
+        for malformed in [
+            "",
+            "bytes",
+            "bytes 0-99",
+            "items 0-99/200",
+            "bytes */200",
+            "bytes 10-5/200",
+            "bytes 0-200/200",
+            "bytes a-b/c",
+            "bytes 0-99/x",
+        ] {
+            assert!(parse_content_range(malformed).is_err(), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn validate_content_range() {
+        use super::validate_content_range;
+        use crate::remotes::http::HttpError;
+
+        assert_eq!(
+            validate_content_range(Some("bytes 10-19/100"), 10, Some(19)).unwrap(),
+            (10, 19, Some(100))
+        );
+        assert!(validate_content_range(Some("bytes 10-99/100"), 10, None).is_ok());
+        assert!(matches!(
+            validate_content_range(Some("bytes 0-99/100"), 10, Some(19)),
+            Err(HttpError::RangeMismatch { .. })
+        ));
+        assert!(matches!(
+            validate_content_range(None, 10, Some(19)),
+            Err(HttpError::RangeMismatch { .. })
+        ));
+    }
+}
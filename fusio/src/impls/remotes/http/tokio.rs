@@ -5,6 +5,9 @@ use http_body::Body;
 use super::{HttpClient, HttpError};
 use crate::{error::BoxedError, MaybeSend, MaybeSync};
 
+/// Clones share the pool of connections, kept per scheme and authority, so that one client can
+/// send the requests of several file systems and the fetches of their credentials.
+#[derive(Clone)]
 pub struct TokioClient {
     client: reqwest::Client,
 }
@@ -16,9 +19,14 @@ impl Default for TokioClient {
 }
 
 impl TokioClient {
+    /// Redirects are returned rather than followed, for the S3 client to sign the requests it
+    /// sends to their locations again.
     pub fn new() -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("the TLS backend failed to initialize"),
         }
     }
 }
@@ -1,5 +1,8 @@
 #[cfg(feature = "aws")]
 pub mod aws;
+#[cfg(feature = "aws")]
+#[doc(hidden)]
+pub mod encoding;
 #[cfg(feature = "http")]
 pub mod http;
 #[cfg(feature = "aws")]
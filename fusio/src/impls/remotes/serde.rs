@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 pub(crate) struct MultipartPart {
     pub part_num: usize,
     pub etag: String,
+    /// The SHA256 checksum the part was uploaded with, if any.
+    pub checksum: Option<String>,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -24,4 +26,72 @@ pub struct CompleteMultipartUploadRequestPart {
     pub part_number: usize,
     #[serde(rename = "ETag")]
     pub etag: String,
+    #[serde(rename = "ChecksumSHA256", skip_serializing_if = "Option::is_none")]
+    pub checksum_sha256: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct CompleteMultipartUploadResult {
+    #[serde(rename = "ETag")]
+    pub etag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename = "RestoreRequest", rename_all = "PascalCase")]
+pub struct RestoreRequest {
+    pub days: u32,
+    pub glacier_job_parameters: GlacierJobParameters,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GlacierJobParameters {
+    pub tier: &'static str,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct CopyObjectResult {
+    #[serde(rename = "ETag")]
+    pub etag: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AccessControlPolicy {
+    pub owner: Option<AclOwner>,
+    pub access_control_list: AccessControlList,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AclOwner {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub display_name: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AccessControlList {
+    pub grant: Vec<AclGrant>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AclGrant {
+    pub grantee: AclGrantee,
+    pub permission: String,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AclGrantee {
+    #[serde(rename = "ID")]
+    pub id: Option<String>,
+    pub display_name: Option<String>,
+    #[serde(rename = "URI")]
+    pub uri: Option<String>,
+    pub email_address: Option<String>,
 }
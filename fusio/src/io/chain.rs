@@ -0,0 +1,152 @@
+use std::io;
+
+use crate::{Error, IoBufMut, Read};
+
+/// A [`Read`] over several others one after the other, e.g. the part files of a dataset split on
+/// write, as if they were a single file. Positions are in the whole chain, a read spanning the
+/// end of a reader goes on at the start of the next one.
+pub struct ChainReader<R> {
+    readers: Vec<R>,
+}
+
+impl<R: Read> ChainReader<R> {
+    pub fn new(readers: impl IntoIterator<Item = R>) -> Self {
+        Self {
+            readers: readers.into_iter().collect(),
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<R> {
+        self.readers
+    }
+
+    /// The positions in the chain the readers start at, followed by the size of the chain.
+    async fn offsets(&self) -> Result<Vec<u64>, Error> {
+        let mut offsets = Vec::with_capacity(self.readers.len() + 1);
+        let mut offset = 0;
+        offsets.push(offset);
+        for reader in &self.readers {
+            offset += reader.size().await?;
+            offsets.push(offset);
+        }
+        Ok(offsets)
+    }
+}
+
+impl<R: Read> Read for ChainReader<R> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let offsets = match self.offsets().await {
+            Ok(offsets) => offsets,
+            Err(e) => return (Err(e), buf),
+        };
+        let len = buf.as_slice().len() as u64;
+        let size = offsets[self.readers.len()];
+        if pos.saturating_add(len) > size {
+            return (
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("read of {} bytes at {} past the end at {}", len, pos, size),
+                )
+                .into()),
+                buf,
+            );
+        }
+        if len == 0 {
+            return (Ok(()), buf);
+        }
+
+        // the last reader starting at or before `pos`, which skips the empty ones
+        let first = offsets.partition_point(|offset| *offset <= pos) - 1;
+        if pos + len <= offsets[first + 1] {
+            return self.readers[first]
+                .read_exact_at(buf, pos - offsets[first])
+                .await;
+        }
+        let mut filled = 0;
+        for (i, reader) in self.readers.iter_mut().enumerate().skip(first) {
+            if filled == len {
+                break;
+            }
+            let start = pos + filled - offsets[i];
+            let part_len = (offsets[i + 1] - offsets[i] - start).min(len - filled);
+            if part_len == 0 {
+                continue;
+            }
+            let (result, part) = reader
+                .read_exact_at(vec![0; part_len as usize], start)
+                .await;
+            if let Err(e) = result {
+                return (Err(e), buf);
+            }
+            buf.as_slice_mut()[filled as usize..(filled + part_len) as usize]
+                .copy_from_slice(&part);
+            filled += part_len;
+        }
+        (Ok(()), buf)
+    }
+
+    /// Appends what follows `pos` in the chain to `buf`.
+    async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let offsets = match self.offsets().await {
+            Ok(offsets) => offsets,
+            Err(e) => return (Err(e), buf),
+        };
+        for (i, reader) in self.readers.iter_mut().enumerate() {
+            if offsets[i + 1] <= pos {
+                continue;
+            }
+            let (result, part) = reader
+                .read_to_end_at(Vec::new(), pos.saturating_sub(offsets[i]))
+                .await;
+            if let Err(e) = result {
+                return (Err(e), buf);
+            }
+            buf.extend_from_slice(&part);
+        }
+        (Ok(()), buf)
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        let mut size = 0;
+        for reader in &self.readers {
+            size += reader.size().await?;
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn reads_span_the_boundaries_of_readers() {
+        use std::io::{self, Cursor};
+
+        use crate::{io::ChainReader, Error, Read};
+
+        let parts = ["0123", "", "45", "6789"];
+        let mut chain = ChainReader::new(parts.map(|part| Cursor::new(part.as_bytes())));
+        assert_eq!(chain.size().await.unwrap(), 10);
+
+        let (result, buf) = chain.read_exact_at(vec![0; 4], 2).await;
+        result.unwrap();
+        assert_eq!(buf, b"2345");
+        let (result, buf) = chain.read_exact_at(vec![0; 10], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"0123456789");
+        // within a single reader, and at the start of one after an empty one
+        let (result, buf) = chain.read_exact_at(vec![0; 2], 4).await;
+        result.unwrap();
+        assert_eq!(buf, b"45");
+
+        let (result, buf) = chain.read_to_end_at(b">".to_vec(), 3).await;
+        result.unwrap();
+        assert_eq!(buf, b">3456789");
+        let (result, buf) = chain.read_to_end_at(Vec::new(), 10).await;
+        result.unwrap();
+        assert!(buf.is_empty());
+
+        let (result, _) = chain.read_exact_at(vec![0; 3], 8).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+}
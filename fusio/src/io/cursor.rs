@@ -0,0 +1,125 @@
+//! In-memory [`Read`] and [`Write`] implementations, e.g. to test serialization against the
+//! fusio traits without a backend.
+
+use std::io::Cursor;
+
+use crate::{Error, IoBuf, IoBufMut, MaybeSend, MaybeSync, Read, Write};
+
+/// Reads are positioned, the position of the cursor is neither used nor moved.
+impl<T> Read for Cursor<T>
+where
+    T: AsRef<[u8]> + MaybeSend + MaybeSync,
+{
+    async fn read_exact_at<B: IoBufMut>(&mut self, mut buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let data = self.get_ref().as_ref();
+        let len = buf.as_slice().len();
+        match usize::try_from(pos)
+            .ok()
+            .and_then(|pos| data.get(pos..pos.checked_add(len)?))
+        {
+            Some(read) => {
+                buf.as_slice_mut().copy_from_slice(read);
+                (Ok(()), buf)
+            }
+            None => (
+                Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+                buf,
+            ),
+        }
+    }
+
+    async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let data = self.get_ref().as_ref();
+        let pos = usize::try_from(pos).unwrap_or(usize::MAX).min(data.len());
+        buf.extend_from_slice(&data[pos..]);
+        (Ok(()), buf)
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        Ok(self.get_ref().as_ref().len() as u64)
+    }
+}
+
+/// Writes are appended, the cursor is moved to the end.
+impl Write for Cursor<Vec<u8>> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        self.get_mut().extend_from_slice(buf.as_slice());
+        self.set_position(self.get_ref().len() as u64);
+        (Ok(()), buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Writes are appended, the cursor is moved to the end.
+#[cfg(feature = "bytes")]
+impl Write for Cursor<bytes::BytesMut> {
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<(), Error>, B) {
+        self.get_mut().extend_from_slice(buf.as_slice());
+        self.set_position(self.get_ref().len() as u64);
+        (Ok(()), buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "bytes"))]
+    #[tokio::test]
+    async fn serialize_then_read_back() {
+        use std::io::{self, Cursor};
+
+        use bytes::BytesMut;
+
+        use crate::{Error, Read, Write};
+
+        let records: [(u32, &str); 3] = [(1, "one"), (20, "twenty"), (300, "three hundred")];
+
+        let mut writer = Cursor::new(BytesMut::new());
+        for (id, name) in records {
+            let (result, _) = writer.write_all(id.to_le_bytes().to_vec()).await;
+            result.unwrap();
+            let (result, _) = writer
+                .write_all((name.len() as u32).to_le_bytes().to_vec())
+                .await;
+            result.unwrap();
+            let (result, _) = writer.write_all(name.as_bytes().to_vec()).await;
+            result.unwrap();
+        }
+        writer.close().await.unwrap();
+        assert_eq!(writer.position(), 46);
+
+        let mut reader = Cursor::new(writer.into_inner().freeze());
+        assert_eq!(reader.size().await.unwrap(), 46);
+        let mut pos = 0;
+        for (id, name) in records {
+            let (result, header) = reader.read_exact_at(vec![0; 8], pos).await;
+            result.unwrap();
+            assert_eq!(u32::from_le_bytes(header[..4].try_into().unwrap()), id);
+            let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+            let (result, read) = reader.read_exact_at(vec![0; len as usize], pos + 8).await;
+            result.unwrap();
+            assert_eq!(read, name.as_bytes());
+            pos += 8 + len as u64;
+        }
+
+        let (result, rest) = reader.read_to_end_at(vec![], 39).await;
+        result.unwrap();
+        assert_eq!(rest, b"hundred");
+        let (result, _) = reader.read_exact_at(vec![0; 1], pos).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+}
@@ -0,0 +1,170 @@
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+
+use crate::{Error, Read};
+
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Splits the content of a [`Read`] into lines, e.g. of logs or JSON Lines, reading it block by
+/// block from the start. Lines are returned without their delimiter, the last one may not end
+/// with it.
+pub struct LineReader<R> {
+    reader: R,
+    pos: u64,
+    size: Option<u64>,
+    delimiter: u8,
+    max_line_length: Option<usize>,
+    block_size: usize,
+    /// Read but not yet returned, holding no delimiter before `scanned`.
+    pending: BytesMut,
+    scanned: usize,
+    block: Vec<u8>,
+}
+
+impl<R: Read> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pos: 0,
+            size: None,
+            delimiter: b'\n',
+            max_line_length: None,
+            block_size: DEFAULT_BLOCK_SIZE,
+            pending: BytesMut::new(),
+            scanned: 0,
+            block: vec![],
+        }
+    }
+
+    /// Splits lines on `delimiter` instead of `\n`.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Fails on lines longer than `max_line_length` bytes instead of buffering them whole, e.g.
+    /// to bound the memory used by a corrupted file.
+    pub fn max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = Some(max_line_length);
+        self
+    }
+
+    /// Reads `block_size` bytes at a time, 64 KiB by default.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Returns the next line, or `None` once the whole content was returned.
+    pub async fn next_line(&mut self) -> Result<Option<Bytes>, Error> {
+        loop {
+            let found = self.pending[self.scanned..]
+                .iter()
+                .position(|byte| *byte == self.delimiter);
+            if let Some(offset) = found {
+                let len = self.scanned + offset;
+                self.check_length(len)?;
+                let mut line = self.pending.split_to(len + 1);
+                line.truncate(len);
+                self.scanned = 0;
+                return Ok(Some(line.freeze()));
+            }
+            self.scanned = self.pending.len();
+            self.check_length(self.scanned)?;
+
+            let size = match self.size {
+                Some(size) => size,
+                None => *self.size.insert(self.reader.size().await?),
+            };
+            if self.pos >= size {
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+                self.scanned = 0;
+                return Ok(Some(self.pending.split().freeze()));
+            }
+
+            let mut block = std::mem::take(&mut self.block);
+            block.resize((self.block_size as u64).min(size - self.pos) as usize, 0);
+            let (result, block) = self.reader.read_exact_at(block, self.pos).await;
+            self.block = block;
+            result?;
+            self.pos += self.block.len() as u64;
+            self.pending.extend_from_slice(&self.block);
+        }
+    }
+
+    /// Returns the lines as a stream, see [`LineReader::next_line`].
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes, Error>> {
+        futures_util::stream::unfold(Some(self), |reader| async move {
+            let mut reader = reader?;
+            match reader.next_line().await {
+                Ok(Some(line)) => Some((Ok(line), Some(reader))),
+                Ok(None) => None,
+                // the position is unknown after a failed read, the stream ends with the error
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    fn check_length(&self, len: usize) -> Result<(), Error> {
+        match self.max_line_length {
+            Some(max) if len > max => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line longer than {} bytes", max),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn lines_across_block_boundaries() {
+        use std::pin::pin;
+
+        use futures_util::StreamExt;
+        use tempfile::tempfile;
+        use tokio::fs::File;
+
+        use crate::{io::LineReader, Write};
+
+        let content = b"first\n\nsecond line\nthe third and longest line\nlast";
+        let mut file = File::from_std(tempfile().unwrap());
+        let (result, _) = file.write_all(&content[..]).await;
+        result.unwrap();
+        file.flush().await.unwrap();
+
+        for block_size in [1, 3, 4, 7, 64] {
+            let mut lines = pin!(LineReader::new(&mut file)
+                .block_size(block_size)
+                .into_stream());
+            let mut read = vec![];
+            while let Some(line) = lines.next().await {
+                read.push(line.unwrap());
+            }
+            assert_eq!(
+                read,
+                [
+                    &b"first"[..],
+                    b"",
+                    b"second line",
+                    b"the third and longest line",
+                    b"last"
+                ]
+            );
+        }
+
+        let mut words = LineReader::new(&mut file).delimiter(b' ').block_size(5);
+        assert_eq!(words.next_line().await.unwrap().unwrap(), "first\n\nsecond");
+        assert_eq!(words.next_line().await.unwrap().unwrap(), "line\nthe");
+
+        let mut lines = LineReader::new(&mut file).max_line_length(10).block_size(4);
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "first");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "");
+        assert!(lines.next_line().await.is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! Adapters over [`Read`](crate::Read) and [`Write`](crate::Write).
+
+mod chain;
+mod cursor;
+#[cfg(feature = "bytes")]
+mod lines;
+mod shared;
+mod take;
+
+pub use chain::ChainReader;
+#[cfg(feature = "bytes")]
+pub use lines::LineReader;
+pub use shared::SharedReader;
+pub use take::Take;
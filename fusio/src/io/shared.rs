@@ -0,0 +1,90 @@
+use futures_util::lock::Mutex;
+
+use crate::{Error, IoBufMut, Read};
+
+/// A [`Read`] shared by many tasks, e.g. behind the `Arc` of
+/// [`Fs::open_shared`](crate::fs::Fs::open_shared), so that the readers of a hot file don't each
+/// hold a handle of their own. Reads are positioned and don't depend on each other, they take
+/// turns on the single handle underneath. [`Read`] is implemented for `&SharedReader`.
+pub struct SharedReader<R> {
+    reader: Mutex<R>,
+}
+
+impl<R: Read> SharedReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Mutex::new(reader),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+
+    pub async fn read_exact_at<B: IoBufMut>(&self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        self.reader.lock().await.read_exact_at(buf, pos).await
+    }
+
+    pub async fn read_to_end_at(&self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        self.reader.lock().await.read_to_end_at(buf, pos).await
+    }
+
+    pub async fn size(&self) -> Result<u64, Error> {
+        self.reader.lock().await.size().await
+    }
+}
+
+impl<R: Read> Read for &SharedReader<R> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        SharedReader::read_exact_at(*self, buf, pos).await
+    }
+
+    async fn read_to_end_at(&mut self, buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        SharedReader::read_to_end_at(*self, buf, pos).await
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        SharedReader::size(*self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "fs"))]
+    #[tokio::test]
+    async fn concurrent_reads_share_a_handle() {
+        use futures_util::future::join_all;
+        use tempfile::TempDir;
+
+        use crate::{disk::TokioFs, fs::Fs, path::Path, Read};
+
+        let tmp_dir = TempDir::new().unwrap();
+        let path = Path::from_filesystem_path(tmp_dir.path())
+            .unwrap()
+            .child("file");
+        let content = (0..4096u32).map(|i| i as u8).collect::<Vec<_>>();
+        TokioFs.write_file(&path, content.clone()).await.0.unwrap();
+
+        let reader = TokioFs.open_shared(&path).await.unwrap();
+        let reads = (0..64u64).map(|i| {
+            let reader = reader.clone();
+            tokio::spawn(async move {
+                let pos = i * 61 % 4000;
+                let (result, buf) = reader.read_exact_at(vec![0; 64], pos).await;
+                result.unwrap();
+                (pos as usize, buf)
+            })
+        });
+        for read in join_all(reads).await {
+            let (pos, buf) = read.unwrap();
+            assert_eq!(buf, &content[pos..pos + 64]);
+        }
+
+        // as a `Read` of its own
+        let mut shared = &*reader;
+        let (result, buf) = Read::read_to_end_at(&mut shared, Vec::new(), 4000).await;
+        result.unwrap();
+        assert_eq!(buf, &content[4000..]);
+        assert_eq!(reader.size().await.unwrap(), 4096);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{Error, IoBufMut, Read};
+
+/// A [`Read`] over the first `limit` bytes of another, e.g. a fixed-length record at the start
+/// of a file. Reads past the limit fail, or return nothing for [`Read::read_to_end_at`], without
+/// reaching the underlying reader, which is never asked for the bytes after the limit.
+pub struct Take<R> {
+    reader: R,
+    limit: u64,
+}
+
+impl<R: Read> Take<R> {
+    pub fn new(reader: R, limit: u64) -> Self {
+        Self { reader, limit }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for Take<R> {
+    async fn read_exact_at<B: IoBufMut>(&mut self, buf: B, pos: u64) -> (Result<(), Error>, B) {
+        let len = buf.as_slice().len() as u64;
+        if pos.saturating_add(len) > self.limit {
+            return (
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "read of {} bytes at {} past the limit of {}",
+                        len, pos, self.limit
+                    ),
+                )
+                .into()),
+                buf,
+            );
+        }
+        self.reader.read_exact_at(buf, pos).await
+    }
+
+    async fn read_to_end_at(&mut self, mut buf: Vec<u8>, pos: u64) -> (Result<(), Error>, Vec<u8>) {
+        let size = match self.size().await {
+            Ok(size) => size,
+            Err(e) => return (Err(e), buf),
+        };
+        buf.resize(size.saturating_sub(pos) as usize, 0);
+        if buf.is_empty() {
+            return (Ok(()), buf);
+        }
+        self.reader.read_exact_at(buf, pos).await
+    }
+
+    async fn size(&self) -> Result<u64, Error> {
+        Ok(self.reader.size().await?.min(self.limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "tokio", feature = "aws"))]
+    #[tokio::test]
+    async fn reads_stop_at_limit() {
+        use std::io;
+
+        use http::{Method, StatusCode};
+
+        use crate::{fs::Fs, io::Take, path::Path, remotes::aws::mock::MockS3, Error, Read};
+
+        let mock = MockS3::new();
+        mock.put_object("record", "0123456789");
+        let file = mock.fs().open(&Path::from("record")).await.unwrap();
+        let mut take = Take::new(file, 4);
+
+        assert_eq!(take.size().await.unwrap(), 4);
+        let (result, buf) = take.read_to_end_at(vec![], 0).await;
+        result.unwrap();
+        assert_eq!(buf, b"0123");
+        let (result, buf) = take.read_to_end_at(vec![], 1).await;
+        result.unwrap();
+        assert_eq!(buf, b"123");
+        let (result, buf) = take.read_exact_at(vec![0; 2], 2).await;
+        result.unwrap();
+        assert_eq!(buf, b"23");
+
+        let (result, _) = take.read_exact_at(vec![0; 2], 3).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+        let (result, buf) = take.read_to_end_at(vec![1], 4).await;
+        result.unwrap();
+        assert!(buf.is_empty());
+
+        // every read was a range within the limit
+        assert_eq!(mock.requests(Method::GET), [StatusCode::PARTIAL_CONTENT; 3]);
+    }
+}
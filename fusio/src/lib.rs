@@ -5,6 +5,7 @@ mod error;
 #[cfg(feature = "fs")]
 pub mod fs;
 pub mod impls;
+pub mod io;
 pub mod path;
 
 use std::future::Future;
@@ -56,6 +57,32 @@ pub trait Write: MaybeSend {
     fn flush(&mut self) -> impl Future<Output = Result<(), Error>> + MaybeSend;
 
     fn close(&mut self) -> impl Future<Output = Result<(), Error>> + MaybeSend;
+
+    /// Closes the file like [`Write::close`], returning the metadata of what was written, e.g.
+    /// the size and ETag of an object of S3, so that callers can record it without asking the
+    /// file system again. Files that don't know their path, like local ones, fail with
+    /// [`Error::Unsupported`] and stay open.
+    #[cfg(feature = "fs")]
+    fn close_with_meta(&mut self) -> impl Future<Output = Result<fs::FileMeta, Error>> + MaybeSend {
+        async {
+            Err(Error::Unsupported {
+                message: "can't tell the metadata of this file on close".into(),
+            })
+        }
+    }
+
+    /// Reserves space for the first `len` bytes of the file without changing its size, so that
+    /// writing them neither fragments the file nor runs out of space midway. Local files reserve it
+    /// with `fallocate` on Linux and ignore it elsewhere, others fail with
+    /// [`Error::Unsupported`].
+    fn allocate(&mut self, len: u64) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        let _ = len;
+        async {
+            Err(Error::Unsupported {
+                message: "can't reserve space for this file".into(),
+            })
+        }
+    }
 }
 
 pub trait Read: MaybeSend + MaybeSync {
@@ -111,6 +138,15 @@ impl<W: Write> Write for &mut W {
     fn close(&mut self) -> impl Future<Output = Result<(), Error>> + MaybeSend {
         W::close(self)
     }
+
+    #[cfg(feature = "fs")]
+    fn close_with_meta(&mut self) -> impl Future<Output = Result<fs::FileMeta, Error>> + MaybeSend {
+        W::close_with_meta(self)
+    }
+
+    fn allocate(&mut self, len: u64) -> impl Future<Output = Result<(), Error>> + MaybeSend {
+        W::allocate(self, len)
+    }
 }
 
 #[cfg(test)]
@@ -234,7 +234,8 @@ pub(crate) fn absolute_path_to_url(path: impl AsRef<std::path::Path>) -> Result<
     })
 }
 
-pub fn path_to_local(location: &Path) -> Result<PathBuf, Error> {
+/// Returns the `file://` URL of `location` in the local file system, its parts percent-encoded.
+pub(crate) fn path_to_url(location: &Path) -> Url {
     let mut url = Url::parse("file:///").unwrap();
     url.path_segments_mut()
         .expect("url path")
@@ -242,6 +243,11 @@ pub fn path_to_local(location: &Path) -> Result<PathBuf, Error> {
         // but avoids creating paths with "//" which look odd in error messages.
         .pop_if_empty()
         .extend(location.parts());
+    url
+}
+
+pub fn path_to_local(location: &Path) -> Result<PathBuf, Error> {
+    let url = path_to_url(location);
 
     let path = url.to_file_path().map_err(|_| Error::InvalidUrl { url })?;
 